@@ -7,7 +7,10 @@ fn main() {
 
     let out_dir = env::var("OUT_DIR").unwrap();
 
-    compile_dx12_shaders(&out_dir);
+    // dxc is only needed for the dx12 backend, which only exists on windows
+    if env::var("CARGO_CFG_TARGET_OS").as_deref() == Ok("windows") {
+        compile_dx12_shaders(&out_dir);
+    }
 }
 
 #[derive(Clone, Copy)]
@@ -34,6 +37,60 @@ fn compile_dx12_shaders(out_dir: &str) {
         "rect_ps",
         out_dir,
     );
+
+    compile_dx12_shader(
+        "vs_main",
+        Kind::Vertex,
+        "composite_shader",
+        RESOURCE_DIR,
+        "composite_vs",
+        out_dir,
+    );
+
+    compile_dx12_shader(
+        "ps_main",
+        Kind::Pixel,
+        "composite_shader",
+        RESOURCE_DIR,
+        "composite_ps",
+        out_dir,
+    );
+
+    compile_dx12_shader(
+        "vs_main",
+        Kind::Vertex,
+        "blur_shader",
+        RESOURCE_DIR,
+        "blur_vs",
+        out_dir,
+    );
+
+    compile_dx12_shader(
+        "ps_main",
+        Kind::Pixel,
+        "blur_shader",
+        RESOURCE_DIR,
+        "blur_ps",
+        out_dir,
+    );
+
+    compile_dx12_shader(
+        "vs_main",
+        Kind::Vertex,
+        "output_shader",
+        RESOURCE_DIR,
+        "output_vs",
+        out_dir,
+    );
+
+    compile_dx12_shader(
+        "ps_main",
+        Kind::Pixel,
+        "output_shader",
+        RESOURCE_DIR,
+        "output_ps",
+        out_dir,
+    );
 }
 
 fn compile_dx12_shader(main: &str, kind: Kind, src: &str, src_dir: &str, out: &str, out_dir: &str) {
@@ -0,0 +1,73 @@
+use plinth::{
+    graphics::{Canvas, Color, Font, FrameInfo, GraphicsConfig, TextLayout, TextStyle},
+    system::{Window, WindowAttributes},
+    AppContext, Application, Config, EventHandler,
+};
+
+const FONT: &[u8] = include_bytes!("../tests/fonts/latin.ttf");
+
+fn main() {
+    let config = Config {
+        graphics: GraphicsConfig {
+            debug_mode: true,
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+
+    let font = Font::from_bytes(FONT, 0).unwrap();
+    let layout = TextLayout::new(
+        "Hello, world! The quick brown fox jumps over the lazy dog.",
+        &TextStyle::new(&font, 24.0),
+        Some(300.0),
+    );
+
+    Application::new(&config)
+        .unwrap()
+        .run(App { layout })
+        .unwrap();
+}
+
+pub struct AppWindow {}
+
+pub struct App {
+    layout: TextLayout,
+}
+
+impl EventHandler<AppWindow> for App {
+    fn start(&mut self, app: &mut AppContext<AppWindow>) {
+        app.create_window(WindowAttributes::default(), |_| AppWindow {})
+            .unwrap();
+    }
+
+    fn stop(&mut self) {
+        // no-op
+    }
+
+    fn wake_requested(
+        &mut self,
+        _app: &mut AppContext<AppWindow>,
+        _window: &mut Window<AppWindow>,
+    ) {
+        // no-op
+    }
+
+    fn destroyed(&mut self, _app: &mut AppContext<AppWindow>, _window_data: AppWindow) {
+        // no-op
+    }
+
+    fn repaint(
+        &mut self,
+        app: &mut AppContext<AppWindow>,
+        _window: &mut Window<AppWindow>,
+        canvas: &mut Canvas,
+        _frame: &FrameInfo,
+    ) {
+        // Glyphs that are already cached are skipped, so this is cheap after
+        // the first frame.
+        app.prepare_text(&self.layout).unwrap();
+
+        canvas.clear(Color::WHITE);
+        canvas.draw_text(&self.layout, (50.0, 50.0), Color::BLACK);
+    }
+}
@@ -0,0 +1,90 @@
+#define RS "RootConstants(num32BitConstants=21, b0),"              \
+           "DescriptorTable(SRV(t0, numDescriptors=unbounded))"
+
+cbuffer properties : register(b0, space0)
+{
+    // the descriptor of the texture to blur
+    uint source_index;
+    // the descriptor of a copy of the target, which the blur is drawn over
+    uint backdrop_index;
+    float sigma;
+    // 0 to blur rows, 1 to blur columns and draw the result
+    uint direction;
+    float4 rect_xywh;
+    float4 rect_radii;
+    // 1 if the blur is clipped to the rounded rect below
+    uint has_round_clip;
+    float4 clip_xywh;
+    float4 clip_radii;
+};
+
+Texture2D<float4> textures[] : register(t0);
+
+// A triangle that covers the whole target. Blurring is limited to the rect
+// with the scissor rect.
+[RootSignature(RS)] float4 vs_main(uint vertex : SV_VERTEXID) : SV_POSITION
+{
+    float2 uv = float2((vertex << 1) & 2, vertex & 2);
+    return float4(uv * float2(2.0, -2.0) + float2(-1.0, 1.0), 0.0, 1.0);
+}
+
+// Must be kept in sync with `round_rect_sdf` in `rect_shader.hlsl`.
+float round_rect_sdf(float2 p, float2 half_extent, float4 radii)
+{
+    // radii are ordered top-left, top-right, bottom-right, bottom-left
+    float2 side = p.x < 0.0 ? radii.xw : radii.yz;
+    float r = p.y < 0.0 ? side.x : side.y;
+
+    float2 q = abs(p) - half_extent + r;
+    return min(max(q.x, q.y), 0.0) + length(max(q, 0.0)) - r;
+}
+
+float round_rect_coverage(float2 p, float4 xywh, float4 radii)
+{
+    float2 half_extent = xywh.zw * 0.5;
+    return saturate(0.5 - round_rect_sdf(p - xywh.xy - half_extent, half_extent, radii));
+}
+
+// One pass of a separable Gaussian blur. Texels past the edges of the target
+// repeat the edge.
+//
+// Must be kept in sync with `RenderTarget::backdrop_blur` in `software.rs`.
+float4 ps_main(float4 position : SV_POSITION) : SV_TARGET
+{
+    int2 texel = int2(position.xy);
+
+    uint width;
+    uint height;
+    textures[source_index].GetDimensions(width, height);
+
+    int2 step = direction == 0 ? int2(1, 0) : int2(0, 1);
+    int radius = int(ceil(3.0 * sigma));
+
+    float4 sum = 0.0;
+    float total = 0.0;
+
+    for (int k = -radius; k <= radius; k++)
+    {
+        float weight = exp(-float(k * k) / (2.0 * sigma * sigma));
+        int2 t = clamp(texel + step * k, int2(0, 0), int2(width, height) - 1);
+
+        sum += textures[source_index].Load(int3(t, 0)) * weight;
+        total += weight;
+    }
+
+    float4 blurred = sum / total;
+
+    if (direction == 0)
+    {
+        return blurred;
+    }
+
+    float coverage = round_rect_coverage(position.xy, rect_xywh, rect_radii);
+    if (has_round_clip)
+    {
+        coverage *= round_rect_coverage(position.xy, clip_xywh, clip_radii);
+    }
+
+    float4 dst = textures[backdrop_index].Load(int3(texel, 0));
+    return lerp(dst, blurred, coverage);
+}
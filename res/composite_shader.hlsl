@@ -0,0 +1,139 @@
+#define RS "RootConstants(num32BitConstants=12, b0),"              \
+           "DescriptorTable(SRV(t0, numDescriptors=unbounded))"
+
+cbuffer properties : register(b0, space0)
+{
+    // the descriptor of the layer; the pixels beneath it follow
+    uint layer_index;
+    // one of `BlendMode` in `draw_list.rs`
+    uint blend_mode;
+    float opacity;
+    // 1 if the layer is clipped to the rounded rect below
+    uint has_round_clip;
+    float4 clip_xywh;
+    float4 clip_radii;
+};
+
+Texture2D<float4> textures[] : register(t0);
+
+// A triangle that covers the whole target. Compositing is limited to the
+// layer's clip with the scissor rect.
+[RootSignature(RS)] float4 vs_main(uint vertex : SV_VERTEXID) : SV_POSITION
+{
+    float2 uv = float2((vertex << 1) & 2, vertex & 2);
+    return float4(uv * float2(2.0, -2.0) + float2(-1.0, 1.0), 0.0, 1.0);
+}
+
+// Must be kept in sync with `round_rect_sdf` in `rect_shader.hlsl`.
+float round_rect_sdf(float2 p, float2 half_extent, float4 radii)
+{
+    // radii are ordered top-left, top-right, bottom-right, bottom-left
+    float2 side = p.x < 0.0 ? radii.xw : radii.yz;
+    float r = p.y < 0.0 ? side.x : side.y;
+
+    float2 q = abs(p) - half_extent + r;
+    return min(max(q.x, q.y), 0.0) + length(max(q, 0.0)) - r;
+}
+
+float screen(float b, float s)
+{
+    return b + s - b * s;
+}
+
+float hard_light(float b, float s)
+{
+    return s <= 0.5 ? b * 2.0 * s : screen(b, 2.0 * s - 1.0);
+}
+
+// Blends a channel of unpremultiplied colors.
+float blend(float b, float s, uint mode)
+{
+    switch (mode)
+    {
+    case 13: // Multiply
+        return b * s;
+    case 14: // Screen
+        return screen(b, s);
+    case 15: // Overlay
+        return hard_light(s, b);
+    case 16: // Darken
+        return min(b, s);
+    case 17: // Lighten
+        return max(b, s);
+    case 18: // ColorDodge
+        return b <= 0.0 ? 0.0 : (s >= 1.0 ? 1.0 : min(b / (1.0 - s), 1.0));
+    case 19: // ColorBurn
+        return b >= 1.0 ? 1.0 : (s <= 0.0 ? 0.0 : 1.0 - min((1.0 - b) / s, 1.0));
+    case 20: // HardLight
+        return hard_light(b, s);
+    case 21: // SoftLight
+    {
+        float d = b <= 0.25 ? ((16.0 * b - 12.0) * b + 4.0) * b : sqrt(b);
+        return s <= 0.5 ? b - (1.0 - 2.0 * s) * b * (1.0 - b) : b + (2.0 * s - 1.0) * (d - b);
+    }
+    case 22: // Difference
+        return abs(b - s);
+    default: // Exclusion
+        return b + s - 2.0 * b * s;
+    }
+}
+
+// Combines premultiplied colors with a blend mode.
+//
+// Must be kept in sync with `composite` in `software.rs`.
+float4 composite(float4 src, float4 dst, uint mode)
+{
+    float sa = src.a;
+    float da = dst.a;
+
+    // Porter-Duff operators, as the fractions of the source and destination
+    // that are kept
+    float2 f;
+    switch (mode)
+    {
+    case 0: f = float2(0.0, 0.0); break;                // Clear
+    case 1: f = float2(1.0, 0.0); break;                // Source
+    case 2: f = float2(0.0, 1.0); break;                // Destination
+    case 3: f = float2(1.0, 1.0 - sa); break;           // SourceOver
+    case 4: f = float2(1.0 - da, 1.0); break;           // DestinationOver
+    case 5: f = float2(da, 0.0); break;                 // SourceIn
+    case 6: f = float2(0.0, sa); break;                 // DestinationIn
+    case 7: f = float2(1.0 - da, 0.0); break;           // SourceOut
+    case 8: f = float2(0.0, 1.0 - sa); break;           // DestinationOut
+    case 9: f = float2(da, 1.0 - sa); break;            // SourceAtop
+    case 10: f = float2(1.0 - da, sa); break;           // DestinationAtop
+    case 11: f = float2(1.0 - da, 1.0 - sa); break;     // Xor
+    case 12: return min(src + dst, 1.0);                // Plus
+    default:
+    {
+        // The blend functions take unpremultiplied colors, and the result is
+        // drawn over the destination.
+        float3 b = dst.rgb / max(da, 1e-9);
+        float3 s = src.rgb / max(sa, 1e-9);
+        float3 blended = float3(blend(b.r, s.r, mode), blend(b.g, s.g, mode), blend(b.b, s.b, mode));
+        float3 rgb = src.rgb * (1.0 - da) + dst.rgb * (1.0 - sa) + sa * da * blended;
+        return float4(rgb, sa + da - sa * da);
+    }
+    }
+
+    return src * f.x + dst * f.y;
+}
+
+// Must be kept in sync with `RenderTarget::composite` in `software.rs`.
+float4 ps_main(float4 position : SV_POSITION) : SV_TARGET
+{
+    int3 texel = int3(position.xy, 0);
+
+    float4 src = textures[layer_index].Load(texel) * opacity;
+    float4 dst = textures[layer_index + 1].Load(texel);
+
+    float coverage = 1.0;
+    if (has_round_clip)
+    {
+        float2 half_extent = clip_xywh.zw * 0.5;
+        float distance = round_rect_sdf(position.xy - clip_xywh.xy - half_extent, half_extent, clip_radii);
+        coverage = saturate(0.5 - distance);
+    }
+
+    return lerp(dst, composite(src, dst, blend_mode), coverage);
+}
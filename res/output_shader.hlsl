@@ -0,0 +1,90 @@
+#define RS "RootConstants(num32BitConstants=6, b0),"               \
+           "DescriptorTable(SRV(t0, numDescriptors=unbounded))"
+
+cbuffer properties : register(b0, space0)
+{
+    // the descriptor of a copy of the target
+    uint source_index;
+    // one of `ColorSpace` in `hdr.rs`
+    uint color_space;
+    // 0 to clip, 1 for extended Reinhard
+    uint tone_mapping;
+    // the brightness that Reinhard maps to the peak, in nits
+    float white_point;
+    float sdr_white_level;
+    float max_luminance;
+};
+
+Texture2D<float4> textures[] : register(t0);
+
+// A triangle that covers the whole target.
+[RootSignature(RS)] float4 vs_main(uint vertex : SV_VERTEXID) : SV_POSITION
+{
+    float2 uv = float2((vertex << 1) & 2, vertex & 2);
+    return float4(uv * float2(2.0, -2.0) + float2(-1.0, 1.0), 0.0, 1.0);
+}
+
+static const float SCRGB_WHITE_LEVEL = 80.0;
+static const float PQ_MAX_LUMINANCE = 10000.0;
+
+// Rec. 709 to Rec. 2020 primaries, see ITU-R BT.2087.
+static const float3x3 REC709_TO_REC2020 = {
+    0.6274039, 0.329283, 0.04331307,
+    0.06909729, 0.9195404, 0.01136232,
+    0.01639144, 0.08801331, 0.8955953,
+};
+
+// SMPTE ST 2084.
+float3 pq_encode(float3 nits)
+{
+    const float m1 = 2610.0 / 16384.0;
+    const float m2 = 2523.0 / 4096.0 * 128.0;
+    const float c1 = 3424.0 / 4096.0;
+    const float c2 = 2413.0 / 4096.0 * 32.0;
+    const float c3 = 2392.0 / 4096.0 * 32.0;
+
+    float3 y = pow(saturate(nits / PQ_MAX_LUMINANCE), m1);
+    return pow((c1 + c2 * y) / (1.0 + c3 * y), m2);
+}
+
+// Must be kept in sync with `encode_output` in `hdr.rs`.
+float4 ps_main(float4 position : SV_POSITION) : SV_TARGET
+{
+    float4 color = textures[source_index].Load(int3(position.xy, 0));
+    float a = color.a;
+
+    if (a <= 0.0)
+    {
+        return 0.0;
+    }
+
+    // brightness is relative to SDR white until the color is encoded
+    float white = sdr_white_level;
+    float peak = color_space == 0 ? 1.0 : max_luminance / white;
+
+    float m = max(max(color.r, color.g), color.b) / a;
+    float scale = 1.0;
+    if (tone_mapping == 1 && m > 0.0 && white_point / white > peak)
+    {
+        float x = m / peak;
+        float w = white_point / white / peak;
+        scale = (x * (1.0 + x / (w * w)) / (1.0 + x)) * peak / m;
+    }
+
+    float3 rgb = min(color.rgb * scale, peak * a);
+
+    if (color_space == 0) // Srgb
+    {
+        rgb = max(rgb, 0.0);
+    }
+    else if (color_space == 1) // ScRgb
+    {
+        rgb = rgb * white / SCRGB_WHITE_LEVEL;
+    }
+    else // Pq
+    {
+        rgb = pq_encode(mul(REC709_TO_REC2020, rgb) / a * white) * a;
+    }
+
+    return float4(rgb, a);
+}
@@ -1,37 +1,73 @@
 #define RS "RootFlags (ALLOW_INPUT_ASSEMBLER_INPUT_LAYOUT),"       \
-           "RootConstants(num32BitConstants=3, b0),"               \
+           "RootConstants(num32BitConstants=12, b0),"              \
            "SRV(t0),"                                              \
            "DescriptorTable(SRV(t1, numDescriptors=unbounded)),"   \
-           "StaticSampler(s0, filter = FILTER_MIN_MAG_MIP_POINT)," \
-           "StaticSampler(s1, filter = FILTER_MIN_MAG_MIP_LINEAR)"
+           "SRV(t0, space=1)"
 
 struct Rect
 {
     float4 xywh;
     float4 uvwh;
+    // where one copy of the texture is drawn, in the rect's coordinates
+    float4 image_xywh;
     float4 color;
+    float4 radii;
+    float4 border_color;
     int texture_id;
+    // byte 0: the filter; 0 for point, 1 for linear, 2 for mipmapped
+    // byte 1: 1 if the texture is a signed distance field, 2 if it has mipmaps
+    // byte 2: the horizontal wrap mode; 0 for clamp, 1 for repeat, 2 for mirror
+    // byte 3: the vertical wrap mode
     uint flags;
-    float2 padding;
+    float border_inset;
+    float border_outset;
+    uint gradient_id;
+    float blur_sigma;
+    // columns of the transform from the rect's coordinates to the target's
+    float2 transform[3];
+};
+
+static const uint NO_GRADIENT = 0xFFFFFFFF;
+static const uint MAX_GRADIENT_STOPS = 8;
+
+// Must be kept in sync with `SDF_RANGE` in `sdf.rs`.
+static const float SDF_RANGE = 4.0;
+
+struct Gradient
+{
+    // linear: start.xy, end.xy
+    // radial: center.xy, radius
+    // conic: center.xy, start angle
+    float4 params;
+    uint kind;
+    uint num_stops;
+    // columns of the transform from the primitive's coordinates to the
+    // gradient's
+    float2 transform[3];
+    float offsets[MAX_GRADIENT_STOPS];
+    float4 colors[MAX_GRADIENT_STOPS];
 };
 
 cbuffer properties : register(b0, space0)
 {
     float2 viewport_scale;
     float viewport_height;
+    // 1 if drawing is clipped to the rounded rect below
+    uint has_round_clip;
+    float4 clip_xywh;
+    float4 clip_radii;
 };
 
 StructuredBuffer<Rect> rects : register(t0);
 Texture2D<float4> textures[] : register(t1);
-
-SamplerState point_sampler : register(s0);
-SamplerState linear_sampler : register(s1);
+StructuredBuffer<Gradient> gradients : register(t0, space1);
 
 struct VS_OUT
 {
     // all values in clip space
     float4 xy : SV_POSITION;
-    float2 uv : TEXCOORD0;
+    // the position in the rect's coordinates
+    float2 local : TEXCOORD0;
     uint instance : SV_INSTANCEID;
 };
 
@@ -52,6 +88,14 @@ float2 scale_to_viewport(float2 value)
     return value * viewport_scale * 2.0 - 1.0;
 }
 
+// The number of pixels per unit of the rect's coordinates, used to keep
+// anti-aliased edges a pixel wide.
+float transform_scale(Rect rect)
+{
+    float2x2 m = float2x2(rect.transform[0], rect.transform[1]);
+    return sqrt(abs(determinant(m)));
+}
+
 static const int2 positions[4] = {
     int2(0.0, 0.0),
     int2(0.0, 1.0),
@@ -61,29 +105,357 @@ static const int2 positions[4] = {
 [RootSignature(RS)] VS_OUT vs_main(uint vertex : SV_VERTEXID, uint instance : SV_INSTANCEID)
 {
     VS_OUT output;
+    Rect rect = rects[instance];
 
-    float2 xy = rects[instance].xywh.xy + rects[instance].xywh.zw * positions[vertex];
-    float2 uv = rects[instance].uvwh.xy + rects[instance].uvwh.zw * positions[vertex];
+    // Expand the quad by a pixel on each side so that partially covered
+    // pixels along the edges are shaded.
+    // Blurred rects extend up to three standard deviations past their edges.
+    // Degenerate transforms collapse the quad, so nothing is drawn.
+    float margin = rect.border_outset + 3.0 * rect.blur_sigma + 1.0 / max(transform_scale(rect), 1e-6);
+    float2 xy = rect.xywh.xy - margin + (rect.xywh.zw + 2.0 * margin) * positions[vertex];
 
-    output.xy = point_to_clip_space(xy);
-    output.uv = uv;
+    output.xy = point_to_clip_space(rect.transform[0] * xy.x + rect.transform[1] * xy.y + rect.transform[2]);
+    output.local = xy;
     output.instance = instance;
 
     return output;
 }
 
-float4 ps_main(VS_OUT input) : SV_TARGET
+// The signed distance from a point to the edge of a rounded rect centered on
+// the origin. Negative values are inside the rect.
+//
+// Must be kept in sync with `round_rect_sdf` in `software.rs`.
+float round_rect_sdf(float2 p, float2 half_extent, float4 radii)
+{
+    // radii are ordered top-left, top-right, bottom-right, bottom-left
+    float2 side = p.x < 0.0 ? radii.xw : radii.yz;
+    float r = p.y < 0.0 ? side.x : side.y;
+
+    float2 q = abs(p) - half_extent + r;
+    return min(max(q.x, q.y), 0.0) + length(max(q, 0.0)) - r;
+}
+
+// The fraction of the pixel centered on `p` that is covered by a rounded rect
+// after growing it by `amount` on every side (or shrinking it, if negative).
+// Square corners stay square when growing. `scale` is the number of pixels per
+// unit of `p`'s coordinate space.
+//
+// Must be kept in sync with `offset_round_rect` and `round_rect_coverage` in
+// `software.rs`.
+float round_rect_coverage(float2 p, float4 xywh, float4 radii, float amount, float scale)
+{
+    float2 half_extent = xywh.zw * 0.5 + amount;
+
+    if (half_extent.x <= 0.0 || half_extent.y <= 0.0)
+    {
+        return 0.0;
+    }
+
+    float4 offset_radii = max(radii + amount, 0.0) * (float4)(radii > 0.0);
+    float distance = round_rect_sdf(p - xywh.xy - xywh.zw * 0.5, half_extent, offset_radii);
+
+    return saturate(0.5 - distance * scale);
+}
+
+float gaussian(float x, float sigma)
+{
+    return exp(-(x * x) / (2.0 * sigma * sigma)) / (2.50662827463 * sigma);
+}
+
+// An approximation of the error function with a maximum error of about 5e-4.
+float2 erf_approx(float2 x)
+{
+    float2 s = sign(x);
+    float2 a = abs(x);
+    float2 t = 1.0 + (0.278393 + (0.230389 + 0.078108 * (a * a)) * a) * a;
+    t *= t;
+    return s - s / (t * t);
+}
+
+// The blurred coverage of a single row of a rounded rect.
+float shadow_row(float x, float y, float sigma, float corner, float2 half_extent)
+{
+    float delta = min(half_extent.y - corner - abs(y), 0.0);
+    float curved = half_extent.x - corner + sqrt(max(0.0, corner * corner - delta * delta));
+    float2 integral = 0.5 + 0.5 * erf_approx((x + float2(-curved, curved)) * (0.70710678118 / sigma));
+    return integral.y - integral.x;
+}
+
+// Approximates the coverage of a rounded rect convolved with a Gaussian, as
+// described in <https://madebyevan.com/shaders/fast-rounded-rectangle-shadows/>.
+//
+// Must be kept in sync with `round_rect_shadow` in `software.rs`.
+float round_rect_shadow(float2 p, float4 xywh, float4 radii, float sigma)
+{
+    float2 half_extent = xywh.zw * 0.5;
+    p = p - xywh.xy - half_extent;
+
+    // radii are ordered top-left, top-right, bottom-right, bottom-left
+    float2 side = p.x < 0.0 ? radii.xw : radii.yz;
+    float corner = p.y < 0.0 ? side.x : side.y;
+
+    float low = p.y - half_extent.y;
+    float high = p.y + half_extent.y;
+    float start = clamp(-3.0 * sigma, low, high);
+    float end = clamp(3.0 * sigma, low, high);
+
+    float interval = (end - start) / 4.0;
+    float y = start + interval * 0.5;
+    float value = 0.0;
+
+    for (int i = 0; i < 4; i++)
+    {
+        value += shadow_row(p.x, p.y - y, sigma, corner, half_extent) * gaussian(y, sigma) * interval;
+        y += interval;
+    }
+
+    return value;
+}
+
+// Must be kept in sync with `PackedGradient::color_at` in `gradient.rs`.
+float4 gradient_color(Gradient gradient, float2 p)
 {
-    Rect rect = rects[input.instance];
+    p = gradient.transform[0] * p.x + gradient.transform[1] * p.y + gradient.transform[2];
 
-    if (rect.flags & 1)
+    float t;
+    if (gradient.kind == 0)
+    {
+        float2 d = gradient.params.zw - gradient.params.xy;
+        float len2 = dot(d, d);
+        t = len2 > 0.0 ? dot(p - gradient.params.xy, d) / len2 : 0.0;
+    }
+    else if (gradient.kind == 1)
     {
-        // linear filtering
-        return rect.color * textures[rect.texture_id].Sample(linear_sampler, input.uv);
+        float radius = gradient.params.z;
+        t = radius > 0.0 ? length(p - gradient.params.xy) / radius : 1.0;
     }
     else
     {
-        // point filtering
-        return rect.color * textures[rect.texture_id].Sample(point_sampler, input.uv);
+        float2 d = p - gradient.params.xy;
+        t = frac((atan2(d.y, d.x) - gradient.params.z) / 6.28318530718);
+    }
+
+    if (gradient.num_stops == 0)
+    {
+        return float4(0.0, 0.0, 0.0, 0.0);
+    }
+
+    t = saturate(t);
+
+    if (t <= gradient.offsets[0])
+    {
+        return gradient.colors[0];
+    }
+
+    for (uint i = 1; i < gradient.num_stops; i++)
+    {
+        if (t <= gradient.offsets[i])
+        {
+            float start = gradient.offsets[i - 1];
+            float end = gradient.offsets[i];
+            float f = end > start ? (t - start) / (end - start) : 1.0;
+
+            return lerp(gradient.colors[i - 1], gradient.colors[i], f);
+        }
+    }
+
+    return gradient.colors[gradient.num_stops - 1];
+}
+
+// Maps a texel coordinate into [0, size).
+//
+// Must be kept in sync with `WrapMode::apply` in `draw_list.rs`.
+int wrap_texel(int i, int size, uint mode)
+{
+    if (mode == 1)
+    {
+        return ((i % size) + size) % size;
+    }
+
+    if (mode == 2)
+    {
+        int period = 2 * size;
+        int j = ((i % period) + period) % period;
+        return j < size ? j : period - 1 - j;
+    }
+
+    return clamp(i, 0, size - 1);
+}
+
+// Reads a texel of the rect's image, wrapping it into the image's region of
+// the texture. `region` is the region's origin and size in texels.
+float4 load_texel(Rect rect, int4 region, int2 texel)
+{
+    int x = wrap_texel(texel.x, region.z, (rect.flags >> 16) & 0xFF);
+    int y = wrap_texel(texel.y, region.w, (rect.flags >> 24) & 0xFF);
+
+    return textures[rect.texture_id].Load(int3(region.xy + int2(x, y), 0));
+}
+
+// Bilinearly filters the texels of `region` at `position`, in texels.
+float4 sample_bilinear(Rect rect, int4 region, float2 position)
+{
+    position -= 0.5;
+    int2 texel = int2(floor(position));
+    float2 weight = position - floor(position);
+
+    float4 top = lerp(load_texel(rect, region, texel), load_texel(rect, region, texel + int2(1, 0)), weight.x);
+    float4 bottom = lerp(load_texel(rect, region, texel + int2(0, 1)), load_texel(rect, region, texel + int2(1, 1)), weight.x);
+
+    return lerp(top, bottom, weight.y);
+}
+
+// Must be kept in sync with `mip_levels` in `mipmap.rs`.
+uint mip_levels(int2 size)
+{
+    return firstbithigh((uint)max(max(size.x, size.y), 1)) + 1;
+}
+
+// The origin and size of a level of a mip chain, given those of the full-size
+// level.
+//
+// Must be kept in sync with `mip_level_region` in `mipmap.rs`.
+int4 mip_level_region(int4 region, uint level)
+{
+    if (level == 0)
+    {
+        return region;
+    }
+
+    int offset = 0;
+    for (uint l = 1; l < level; l++)
+    {
+        offset += max(region.w >> l, 1);
+    }
+
+    return int4(region.x + region.z, region.y + offset, max(region.zw >> level, 1));
+}
+
+// The mip level to sample an image from, where each whole level halves the
+// number of texels per pixel.
+//
+// Must be kept in sync with `mip_lod` in `software.rs`.
+float mip_lod(Rect rect, int4 region, float scale)
+{
+    float2 texels_per_pixel = float2(region.zw) / (rect.image_xywh.zw * scale);
+    return max(log2(max(texels_per_pixel.x, texels_per_pixel.y)), 0.0);
+}
+
+// Samples the rect's image at `t`, where (0, 0) and (1, 1) are the corners of
+// the image. Images may share a texture with others, so texels are addressed
+// and filtered by hand to keep wrapping within the image.
+//
+// Must be kept in sync with `Texture::sample` in `software.rs`.
+float4 sample_texture(Rect rect, float2 t, float scale)
+{
+    uint width;
+    uint height;
+    textures[rect.texture_id].GetDimensions(width, height);
+
+    int4 region = int4(round(rect.uvwh * float2(width, height).xyxy));
+    uint filter = rect.flags & 0xFF;
+
+    if (filter == 0)
+    {
+        return load_texel(rect, region, int2(floor(t * float2(region.zw))));
+    }
+
+    if (filter == 2 && ((rect.flags >> 8) & 0xFF) == 2)
+    {
+        uint levels = mip_levels(region.zw);
+        float lod = clamp(mip_lod(rect, region, scale), 0.0, float(levels - 1));
+        uint level = uint(floor(lod));
+
+        int4 a_region = mip_level_region(region, level);
+        float4 a = sample_bilinear(rect, a_region, t * float2(a_region.zw));
+
+        if (level + 1 == levels)
+        {
+            return a;
+        }
+
+        int4 b_region = mip_level_region(region, level + 1);
+        float4 b = sample_bilinear(rect, b_region, t * float2(b_region.zw));
+
+        return lerp(a, b, lod - float(level));
+    }
+
+    return sample_bilinear(rect, region, t * float2(region.zw));
+}
+
+float median(float3 v)
+{
+    return max(min(v.r, v.g), min(max(v.r, v.g), v.b));
+}
+
+// The fraction of a pixel covered by the shape in a vector image, given the
+// texel sampled for it. The distance is scaled from texels to pixels so that
+// the edge stays a pixel wide at any scale.
+//
+// Must be kept in sync with `vector_coverage` in `software.rs`.
+float vector_coverage(float4 texel, Rect rect, float scale)
+{
+    float distance = (median(texel.rgb) - 0.5) * 2.0 * SDF_RANGE;
+
+    uint width;
+    uint height;
+    textures[rect.texture_id].GetDimensions(width, height);
+
+    float2 texels = round(rect.uvwh.zw * float2(width, height));
+    float2 pixels_per_texel = rect.image_xywh.zw / texels * scale;
+
+    return saturate(distance * 0.5 * (pixels_per_texel.x + pixels_per_texel.y) + 0.5);
+}
+
+// `p` is in the rect's coordinates.
+float4 shade_rect(Rect rect, float2 p)
+{
+    if (rect.blur_sigma > 0.0)
+    {
+        return rect.color * round_rect_shadow(p, rect.xywh, rect.radii, rect.blur_sigma);
     }
+
+    float scale = transform_scale(rect);
+
+    // The fill is drawn inside the border, and the border is drawn between
+    // the inner and outer shapes.
+    float outer_coverage = round_rect_coverage(p, rect.xywh, rect.radii, rect.border_outset, scale);
+
+    if (outer_coverage <= 0.0)
+    {
+        discard;
+    }
+
+    float inner_coverage = round_rect_coverage(p, rect.xywh, rect.radii, -rect.border_inset, scale);
+
+    float4 texel = sample_texture(rect, (p - rect.image_xywh.xy) / rect.image_xywh.zw, scale);
+
+    if (((rect.flags >> 8) & 0xFF) == 1)
+    {
+        texel = vector_coverage(texel, rect, scale);
+    }
+
+    float4 fill = rect.color;
+    if (rect.gradient_id != NO_GRADIENT)
+    {
+        fill = gradient_color(gradients[rect.gradient_id], p) * rect.color;
+    }
+
+    return fill * texel * inner_coverage + rect.border_color * (outer_coverage - inner_coverage);
+}
+
+float4 ps_main(VS_OUT input) : SV_TARGET
+{
+    // SV_POSITION is the pixel center in render target coordinates
+    float2 p = input.xy.xy;
+
+    float4 color = shade_rect(rects[input.instance], input.local);
+
+    // Rect clips are applied with the scissor rect.
+    if (has_round_clip)
+    {
+        color *= round_rect_coverage(p, clip_xywh, clip_radii, 0.0, 1.0);
+    }
+
+    return color;
 }
@@ -1,9 +1,15 @@
-use std::{collections::HashMap, marker::PhantomData};
+use std::{collections::HashMap, marker::PhantomData, path::Path};
+
+use windows::Win32::Foundation::HWND;
 
 use crate::{
     core::PassthroughBuildHasher,
-    geometry::{Extent, Pixel, Point, Scale, Wixel},
-    graphics::{Canvas, DrawList, FrameInfo, Graphics, GraphicsConfig, Image, Swapchain},
+    geometry::{Extent, Pixel, Point, Scale, Texel, Wixel},
+    graphics::{
+        image::codec::{self, FileFormat},
+        Canvas, ColorOutput, ColorSpace, DrawList, Format, FrameInfo, Graphics, GraphicsConfig,
+        Image, ImageError, Layout, Raster, Swapchain, TextLayout,
+    },
     limits::{self, GFX_IMAGE_COUNT},
     resource::{Error as ResourceError, Resource, StaticResource},
     string::HashedStr,
@@ -20,6 +26,16 @@ pub enum Error {
     EventLoop(#[from] EventLoopError),
 }
 
+#[derive(Debug, thiserror::Error)]
+pub enum RedrawError {
+    #[error("The window has not presented a frame that can be drawn again.")]
+    NoFrame,
+    #[error("The frame could not be drawn.")]
+    Image(#[from] ImageError),
+    #[error("The frame could not be saved.")]
+    Save(#[from] codec::Error),
+}
+
 #[derive(Debug, Default)]
 pub struct Config {
     pub resources: &'static [StaticResource],
@@ -44,7 +60,7 @@ impl Application {
     pub fn new(config: &Config) -> Result<Self, Error> {
         limits::GFX_IMAGE_COUNT.check(config.resources.len());
 
-        let mut graphics = Graphics::new(&config.graphics);
+        let graphics = Graphics::new(&config.graphics);
 
         let mut resources =
             HashMap::with_capacity_and_hasher(GFX_IMAGE_COUNT.get(), PassthroughBuildHasher::new());
@@ -102,6 +118,8 @@ pub struct AppContext<'a, UserWindowData> {
     graphics: &'a Graphics,
     resources: &'a mut HashMap<u64, Resource, PassthroughBuildHasher>,
     event_loop: &'a ActiveEventLoop<(WindowState<'a>, UserWindowData)>,
+    /// The last frame of the window that the current event is for.
+    frame: Option<PresentedFrame<'a>>,
 }
 
 impl<'a, UserWindowData> AppContext<'a, UserWindowData> {
@@ -114,9 +132,20 @@ impl<'a, UserWindowData> AppContext<'a, UserWindowData> {
             graphics,
             resources,
             event_loop,
+            frame: None,
         }
     }
 
+    fn with_window(mut self, meta: &'a WindowState, window: &Window<UserWindowData>) -> Self {
+        self.frame = meta.presented.map(|(extent, output)| PresentedFrame {
+            hwnd: window.hwnd(),
+            draw_list: &meta.draw_list,
+            extent,
+            output,
+        });
+        self
+    }
+
     /// Loads an image from a path.
     ///
     /// If the image is already loaded, this will return a reference to the
@@ -139,13 +168,48 @@ impl<'a, UserWindowData> AppContext<'a, UserWindowData> {
                 #[allow(unreachable_patterns)]
                 _ => Err(ResourceError::NotAnImage),
             },
-            std::collections::hash_map::Entry::Vacant(_entry) => {
-                // 3 parts: load header, create image, upload pixels
-                todo!()
+            std::collections::hash_map::Entry::Vacant(entry) => {
+                let bytes = std::fs::read(path.string).map_err(|e| match e.kind() {
+                    std::io::ErrorKind::NotFound => ResourceError::NotFound,
+                    _ => ResourceError::Io(e),
+                })?;
+
+                // TGA files have no signature, so fall back to the extension.
+                let format = FileFormat::from_signature(&bytes)
+                    .or_else(|| {
+                        Path::new(path.string)
+                            .extension()
+                            .and_then(|extension| extension.to_str())
+                            .and_then(FileFormat::from_extension)
+                    })
+                    .ok_or(ResourceError::NotAnImage)?;
+
+                let raster = codec::decode_as(format, &bytes)?;
+
+                let image = self.graphics.create_raster_image(raster.info())?;
+                self.graphics.upload_raster_image(image, &raster.as_buf())?;
+                self.graphics.flush_upload_buffer();
+
+                entry.insert(Resource::Image(image));
+                Ok(image)
             }
         }
     }
 
+    /// Rasterizes the glyphs in a text layout so that it can be drawn with
+    /// [`Canvas::draw_text`]. Glyphs that have already been prepared are
+    /// skipped, so a layout may be prepared every time it is drawn.
+    ///
+    /// # Errors
+    ///
+    /// This function returns [`ImageError::MaxCount`] if the texture cache
+    /// has no room for more glyphs.
+    pub fn prepare_text(&self, layout: &TextLayout) -> Result<(), ImageError> {
+        self.graphics.prepare_text(layout)?;
+        self.graphics.flush_upload_buffer();
+        Ok(())
+    }
+
     /// Loads a resource from a path.
     ///
     /// If the resource is already loaded, this will return a reference to the
@@ -166,6 +230,61 @@ impl<'a, UserWindowData> AppContext<'a, UserWindowData> {
         todo!()
     }
 
+    /// Draws the last frame that was presented to a window again, into an
+    /// offscreen image in sRGB.
+    ///
+    /// This does not read back the window's swapchain. The frame's draw list
+    /// is replayed with the window's tone mapping, so colors brighter than SDR
+    /// white are tone mapped as the window's are and then clipped, and a
+    /// window that presents in PQ or scRGB is re-encoded rather than copied.
+    /// Like any draw list, the frame should not be drawn again once an image
+    /// that it uses has been deleted.
+    ///
+    /// # Errors
+    ///
+    /// This function returns [`RedrawError::NoFrame`] if the window has not
+    /// presented a frame yet, or if it is called while the window is being
+    /// repainted or for a window other than the one that the current event
+    /// is for. It returns [`RedrawError::Image`] if the window is larger than
+    /// [`GFX_IMAGE_EXTENT`](crate::limits::GFX_IMAGE_EXTENT).
+    pub fn redraw_window(&self, window: &Window<UserWindowData>) -> Result<Raster, RedrawError> {
+        let frame = self
+            .frame
+            .filter(|frame| frame.hwnd == window.hwnd())
+            .ok_or(RedrawError::NoFrame)?;
+
+        let mut target =
+            self.graphics
+                .create_render_target(frame.extent, Layout::Rgba8, Format::Srgb)?;
+
+        target.set_color_output(ColorOutput {
+            color_space: ColorSpace::Srgb,
+            ..frame.output
+        });
+
+        self.graphics.draw(frame.draw_list, &mut target);
+        Ok(self.graphics.read_pixels(&target))
+    }
+
+    /// Draws the last frame that was presented to a window again and writes
+    /// it to an image file, choosing the file format from the path's
+    /// extension. See [`redraw_window`](Self::redraw_window) for details.
+    ///
+    /// # Errors
+    ///
+    /// This function returns an error if the frame could not be drawn, if the
+    /// extension is not that of a file format that can be encoded, or if the
+    /// file could not be written.
+    pub fn save_window_redraw(
+        &self,
+        window: &Window<UserWindowData>,
+        path: impl AsRef<Path>,
+    ) -> Result<(), RedrawError> {
+        let raster = self.redraw_window(window)?;
+        codec::save(path, &raster.as_buf())?;
+        Ok(())
+    }
+
     /// Creates a new window.
     ///
     /// # Errors
@@ -181,8 +300,12 @@ impl<'a, UserWindowData> AppContext<'a, UserWindowData> {
         attributes: WindowAttributes,
         constructor: impl FnOnce(Window<()>) -> UserWindowData,
     ) -> Result<(), WindowError> {
+        let (color_space, tone_mapping) = (attributes.color_space, attributes.tone_mapping);
+
         self.event_loop.create_window(attributes, |window| {
-            let swapchain = self.graphics.create_swapchain(window.hwnd());
+            let mut swapchain = self.graphics.create_swapchain(window.hwnd());
+            swapchain.set_color_output(color_space, tone_mapping);
+
             let user_data = constructor(window);
 
             (
@@ -191,6 +314,7 @@ impl<'a, UserWindowData> AppContext<'a, UserWindowData> {
                     draw_list: DrawList::new(),
                     dpi_scale: Scale::default(),
                     to_resize: Extent::default(),
+                    presented: None,
                 },
                 user_data,
             )
@@ -288,6 +412,15 @@ pub trait EventHandler<WindowData> {
     ) {
     }
 
+    /// The display settings have changed, such as the resolution or whether
+    /// HDR is enabled.
+    fn display_changed(
+        &mut self,
+        app: &mut AppContext<WindowData>,
+        window: &mut Window<WindowData>,
+    ) {
+    }
+
     fn wake_requested(&mut self, app: &mut AppContext<WindowData>, window: &mut Window<WindowData>);
 
     fn repaint(
@@ -362,6 +495,19 @@ struct WindowState<'a> {
     draw_list: DrawList,
     dpi_scale: Scale<Wixel, Pixel>,
     to_resize: Extent<Wixel>,
+    /// The extent and color output of the frame in `draw_list`, once it has
+    /// been presented.
+    presented: Option<(Extent<Texel>, ColorOutput)>,
+}
+
+/// The frame that was last presented to a window, which can be drawn again
+/// offscreen.
+#[derive(Clone, Copy)]
+struct PresentedFrame<'a> {
+    hwnd: HWND,
+    draw_list: &'a DrawList,
+    extent: Extent<Texel>,
+    output: ColorOutput,
 }
 
 struct ApplicationEventHandler<'a, UserData, Client: EventHandler<UserData>> {
@@ -431,8 +577,9 @@ impl<UserData, Outer: EventHandler<UserData>> SysEventHandler<(WindowState<'_>,
         event_loop: &ActiveEventLoop<(WindowState, UserData)>,
         window: Window<(WindowState, UserData)>,
     ) {
-        let mut cx = AppContext::new(self.graphics, self.resources, event_loop);
-        let (_, mut wn) = window.split();
+        let (meta, mut wn) = window.split();
+        let mut cx =
+            AppContext::new(self.graphics, self.resources, event_loop).with_window(meta, &wn);
         self.client.activated(&mut cx, &mut wn);
     }
 
@@ -441,8 +588,9 @@ impl<UserData, Outer: EventHandler<UserData>> SysEventHandler<(WindowState<'_>,
         event_loop: &ActiveEventLoop<(WindowState, UserData)>,
         window: Window<(WindowState, UserData)>,
     ) {
-        let mut cx = AppContext::new(self.graphics, self.resources, event_loop);
-        let (_, mut wn) = window.split();
+        let (meta, mut wn) = window.split();
+        let mut cx =
+            AppContext::new(self.graphics, self.resources, event_loop).with_window(meta, &wn);
         self.client.deactivated(&mut cx, &mut wn);
     }
 
@@ -451,8 +599,9 @@ impl<UserData, Outer: EventHandler<UserData>> SysEventHandler<(WindowState<'_>,
         event_loop: &ActiveEventLoop<(WindowState, UserData)>,
         window: Window<(WindowState, UserData)>,
     ) {
-        let mut cx = AppContext::new(self.graphics, self.resources, event_loop);
-        let (_, mut wn) = window.split();
+        let (meta, mut wn) = window.split();
+        let mut cx =
+            AppContext::new(self.graphics, self.resources, event_loop).with_window(meta, &wn);
         self.client.drag_resize_started(&mut cx, &mut wn);
     }
 
@@ -461,8 +610,9 @@ impl<UserData, Outer: EventHandler<UserData>> SysEventHandler<(WindowState<'_>,
         event_loop: &ActiveEventLoop<(WindowState, UserData)>,
         window: Window<(WindowState, UserData)>,
     ) {
-        let mut cx = AppContext::new(self.graphics, self.resources, event_loop);
-        let (_, mut wn) = window.split();
+        let (meta, mut wn) = window.split();
+        let mut cx =
+            AppContext::new(self.graphics, self.resources, event_loop).with_window(meta, &wn);
         self.client.drag_resize_ended(&mut cx, &mut wn);
     }
 
@@ -472,10 +622,11 @@ impl<UserData, Outer: EventHandler<UserData>> SysEventHandler<(WindowState<'_>,
         window: Window<(WindowState, UserData)>,
         size: Extent<Wixel>,
     ) {
-        let mut cx = AppContext::new(self.graphics, self.resources, event_loop);
         let (meta, mut wn) = window.split();
-
         meta.to_resize = size;
+
+        let mut cx =
+            AppContext::new(self.graphics, self.resources, event_loop).with_window(meta, &wn);
         self.client.resized(&mut cx, &mut wn, size);
     }
 
@@ -486,10 +637,11 @@ impl<UserData, Outer: EventHandler<UserData>> SysEventHandler<(WindowState<'_>,
         dpi: Scale<Wixel, Pixel>,
         size: Extent<Wixel>,
     ) {
-        let mut cx = AppContext::new(self.graphics, self.resources, event_loop);
         let (meta, mut wn) = window.split();
-
         meta.dpi_scale = dpi;
+
+        let mut cx =
+            AppContext::new(self.graphics, self.resources, event_loop).with_window(meta, &wn);
         self.client.dpi_changed(&mut cx, &mut wn, dpi, size);
     }
 
@@ -498,8 +650,9 @@ impl<UserData, Outer: EventHandler<UserData>> SysEventHandler<(WindowState<'_>,
         event_loop: &ActiveEventLoop<(WindowState, UserData)>,
         window: Window<(WindowState, UserData)>,
     ) {
-        let mut cx = AppContext::new(self.graphics, self.resources, event_loop);
-        let (_, mut wn) = window.split();
+        let (meta, mut wn) = window.split();
+        let mut cx =
+            AppContext::new(self.graphics, self.resources, event_loop).with_window(meta, &wn);
         self.client.close_requested(&mut cx, &mut wn);
     }
 
@@ -508,8 +661,9 @@ impl<UserData, Outer: EventHandler<UserData>> SysEventHandler<(WindowState<'_>,
         event_loop: &ActiveEventLoop<(WindowState, UserData)>,
         window: Window<(WindowState, UserData)>,
     ) {
-        let mut cx = AppContext::new(self.graphics, self.resources, event_loop);
-        let (_, mut wn) = window.split();
+        let (meta, mut wn) = window.split();
+        let mut cx =
+            AppContext::new(self.graphics, self.resources, event_loop).with_window(meta, &wn);
         self.client.shown(&mut cx, &mut wn);
     }
 
@@ -518,8 +672,9 @@ impl<UserData, Outer: EventHandler<UserData>> SysEventHandler<(WindowState<'_>,
         event_loop: &ActiveEventLoop<(WindowState, UserData)>,
         window: Window<(WindowState, UserData)>,
     ) {
-        let mut cx = AppContext::new(self.graphics, self.resources, event_loop);
-        let (_, mut wn) = window.split();
+        let (meta, mut wn) = window.split();
+        let mut cx =
+            AppContext::new(self.graphics, self.resources, event_loop).with_window(meta, &wn);
         self.client.hidden(&mut cx, &mut wn);
     }
 
@@ -528,8 +683,9 @@ impl<UserData, Outer: EventHandler<UserData>> SysEventHandler<(WindowState<'_>,
         event_loop: &ActiveEventLoop<(WindowState, UserData)>,
         window: Window<(WindowState, UserData)>,
     ) {
-        let mut cx = AppContext::new(self.graphics, self.resources, event_loop);
-        let (_, mut wn) = window.split();
+        let (meta, mut wn) = window.split();
+        let mut cx =
+            AppContext::new(self.graphics, self.resources, event_loop).with_window(meta, &wn);
         self.client.maximized(&mut cx, &mut wn);
     }
 
@@ -538,8 +694,9 @@ impl<UserData, Outer: EventHandler<UserData>> SysEventHandler<(WindowState<'_>,
         event_loop: &ActiveEventLoop<(WindowState, UserData)>,
         window: Window<(WindowState, UserData)>,
     ) {
-        let mut cx = AppContext::new(self.graphics, self.resources, event_loop);
-        let (_, mut wn) = window.split();
+        let (meta, mut wn) = window.split();
+        let mut cx =
+            AppContext::new(self.graphics, self.resources, event_loop).with_window(meta, &wn);
         self.client.minimized(&mut cx, &mut wn);
     }
 
@@ -548,8 +705,9 @@ impl<UserData, Outer: EventHandler<UserData>> SysEventHandler<(WindowState<'_>,
         event_loop: &ActiveEventLoop<(WindowState, UserData)>,
         window: Window<(WindowState, UserData)>,
     ) {
-        let mut cx = AppContext::new(self.graphics, self.resources, event_loop);
-        let (_, mut wn) = window.split();
+        let (meta, mut wn) = window.split();
+        let mut cx =
+            AppContext::new(self.graphics, self.resources, event_loop).with_window(meta, &wn);
         self.client.restored(&mut cx, &mut wn);
     }
 
@@ -559,18 +717,35 @@ impl<UserData, Outer: EventHandler<UserData>> SysEventHandler<(WindowState<'_>,
         window: Window<(WindowState, UserData)>,
         position: Point<Wixel>,
     ) {
-        let mut cx = AppContext::new(self.graphics, self.resources, event_loop);
-        let (_, mut wn) = window.split();
+        let (meta, mut wn) = window.split();
+        meta.swapchain.window_moved();
+
+        let mut cx =
+            AppContext::new(self.graphics, self.resources, event_loop).with_window(meta, &wn);
         self.client.moved(&mut cx, &mut wn, position);
     }
 
+    fn display_changed(
+        &mut self,
+        event_loop: &ActiveEventLoop<(WindowState, UserData)>,
+        window: Window<(WindowState, UserData)>,
+    ) {
+        let (meta, mut wn) = window.split();
+        meta.swapchain.display_changed();
+
+        let mut cx =
+            AppContext::new(self.graphics, self.resources, event_loop).with_window(meta, &wn);
+        self.client.display_changed(&mut cx, &mut wn);
+    }
+
     fn wake_requested(
         &mut self,
         event_loop: &ActiveEventLoop<(WindowState, UserData)>,
         window: Window<(WindowState, UserData)>,
     ) {
-        let mut cx = AppContext::new(self.graphics, self.resources, event_loop);
-        let (_, mut wn) = window.split();
+        let (meta, mut wn) = window.split();
+        let mut cx =
+            AppContext::new(self.graphics, self.resources, event_loop).with_window(meta, &wn);
         self.client.wake_requested(&mut cx, &mut wn);
     }
 
@@ -590,6 +765,7 @@ impl<UserData, Outer: EventHandler<UserData>> SysEventHandler<(WindowState<'_>,
         let mut image = meta.swapchain.next_image();
 
         meta.draw_list.clear();
+        meta.presented = None;
 
         // hacky
         let scale = Scale::new(meta.dpi_scale.factor);
@@ -605,6 +781,7 @@ impl<UserData, Outer: EventHandler<UserData>> SysEventHandler<(WindowState<'_>,
         canvas.finish();
 
         self.graphics.draw(&meta.draw_list, &mut image);
+        meta.presented = Some((image.extent(), image.color_output()));
 
         image.present();
     }
@@ -627,8 +804,9 @@ impl<UserData, Outer: EventHandler<UserData>> SysEventHandler<(WindowState<'_>,
         state: ButtonState,
         modifiers: ModifierKeys,
     ) {
-        let mut cx = AppContext::new(self.graphics, self.resources, event_loop);
-        let (_, mut wn) = window.split();
+        let (meta, mut wn) = window.split();
+        let mut cx =
+            AppContext::new(self.graphics, self.resources, event_loop).with_window(meta, &wn);
         self.client.key(&mut cx, &mut wn, code, state, modifiers);
     }
 
@@ -642,8 +820,9 @@ impl<UserData, Outer: EventHandler<UserData>> SysEventHandler<(WindowState<'_>,
         position: Point<Wixel>,
         modifiers: ModifierKeys,
     ) {
-        let mut cx = AppContext::new(self.graphics, self.resources, event_loop);
-        let (_, mut wn) = window.split();
+        let (meta, mut wn) = window.split();
+        let mut cx =
+            AppContext::new(self.graphics, self.resources, event_loop).with_window(meta, &wn);
         self.client
             .mouse_button(&mut cx, &mut wn, button, state, position, modifiers);
     }
@@ -656,8 +835,9 @@ impl<UserData, Outer: EventHandler<UserData>> SysEventHandler<(WindowState<'_>,
         axis: ScrollAxis,
         modifiers: ModifierKeys,
     ) {
-        let mut cx = AppContext::new(self.graphics, self.resources, event_loop);
-        let (_, mut wn) = window.split();
+        let (meta, mut wn) = window.split();
+        let mut cx =
+            AppContext::new(self.graphics, self.resources, event_loop).with_window(meta, &wn);
         self.client
             .mouse_scrolled(&mut cx, &mut wn, delta, axis, modifiers);
     }
@@ -668,8 +848,9 @@ impl<UserData, Outer: EventHandler<UserData>> SysEventHandler<(WindowState<'_>,
         window: Window<(WindowState, UserData)>,
         position: Point<Wixel>,
     ) {
-        let mut cx = AppContext::new(self.graphics, self.resources, event_loop);
-        let (_, mut wn) = window.split();
+        let (meta, mut wn) = window.split();
+        let mut cx =
+            AppContext::new(self.graphics, self.resources, event_loop).with_window(meta, &wn);
         self.client.pointer_moved(&mut cx, &mut wn, position);
     }
 
@@ -679,8 +860,9 @@ impl<UserData, Outer: EventHandler<UserData>> SysEventHandler<(WindowState<'_>,
         window: Window<(WindowState, UserData)>,
         position: Point<Wixel>,
     ) {
-        let mut cx = AppContext::new(self.graphics, self.resources, event_loop);
-        let (_, mut wn) = window.split();
+        let (meta, mut wn) = window.split();
+        let mut cx =
+            AppContext::new(self.graphics, self.resources, event_loop).with_window(meta, &wn);
         self.client.pointer_entered(&mut cx, &mut wn, position);
     }
 
@@ -689,8 +871,9 @@ impl<UserData, Outer: EventHandler<UserData>> SysEventHandler<(WindowState<'_>,
         event_loop: &ActiveEventLoop<(WindowState, UserData)>,
         window: Window<(WindowState, UserData)>,
     ) {
-        let mut cx = AppContext::new(self.graphics, self.resources, event_loop);
-        let (_, mut wn) = window.split();
+        let (meta, mut wn) = window.split();
+        let mut cx =
+            AppContext::new(self.graphics, self.resources, event_loop).with_window(meta, &wn);
         self.client.pointer_left(&mut cx, &mut wn);
     }
 }
@@ -163,7 +163,7 @@ impl<const CAPACITY: usize, V, K: Key> SlotMap<CAPACITY, V, K> {
             slot.epoch = epoch;
             slot.next = self.next_free;
             self.next_free = key.index();
-            self.num_free.checked_add(1).unwrap();
+            self.num_free = self.num_free.checked_add(1).unwrap();
         } else {
             // no-op: epoch saturation, retire the slot
         }
@@ -200,5 +200,11 @@ mod tests {
 
         assert_eq!(map.remove(key), Some(u32::MAX));
         assert_eq!(map.remove(key), None);
+
+        assert!(map.has_capacity(1));
+        let key2 = map.insert(2).unwrap();
+        assert_eq!(key2.index(), key.index());
+        assert_ne!(key2.epoch(), key.epoch());
+        assert!(map.get(key).is_none());
     }
 }
@@ -226,6 +226,24 @@ impl<T: Num> Rect<T> {
         let extent = self.extent;
         [origin.x, origin.y, extent.width, extent.height]
     }
+
+    /// The area covered by both rects. If they do not overlap, the result has
+    /// an empty extent.
+    #[must_use]
+    pub fn intersect(&self, other: &Self) -> Self
+    where
+        T: Add<Output = T> + Sub<Output = T>,
+    {
+        let left = self.origin.x.max(other.origin.x);
+        let top = self.origin.y.max(other.origin.y);
+        let right = (self.origin.x + self.extent.width).min(other.origin.x + other.extent.width);
+        let bottom = (self.origin.y + self.extent.height).min(other.origin.y + other.extent.height);
+
+        Self::new(
+            (left, top),
+            ((right - left).max(T::ZERO), (bottom - top).max(T::ZERO)),
+        )
+    }
 }
 
 impl<T: Num, I: Into<T>> From<(I, I, I, I)> for Rect<T> {
@@ -301,6 +319,154 @@ impl<T: Num, U: Num> Default for Scale<T, U> {
     }
 }
 
+/// A 2D affine transform that maps coordinates in `Src` to coordinates in
+/// `Dst`.
+///
+/// The matrix is stored by column as `[a, b, c, d, e, f]`, so that a point is
+/// mapped to `(a * x + c * y + e, b * x + d * y + f)`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Transform<Src: Num, Dst: Num> {
+    pub m: [f32; 6],
+    _phantom: std::marker::PhantomData<(Src, Dst)>,
+}
+
+/// A transform within the pixel space of a canvas.
+pub type Affine = Transform<Pixel, Pixel>;
+
+impl<Src: Num, Dst: Num> Transform<Src, Dst> {
+    pub const IDENTITY: Self = Self::new([1.0, 0.0, 0.0, 1.0, 0.0, 0.0]);
+
+    #[must_use]
+    pub const fn new(m: [f32; 6]) -> Self {
+        Self {
+            m,
+            _phantom: std::marker::PhantomData,
+        }
+    }
+
+    #[must_use]
+    pub const fn translation(x: f32, y: f32) -> Self {
+        Self::new([1.0, 0.0, 0.0, 1.0, x, y])
+    }
+
+    #[must_use]
+    pub const fn scale(x: f32, y: f32) -> Self {
+        Self::new([x, 0.0, 0.0, y, 0.0, 0.0])
+    }
+
+    /// A clockwise rotation about the origin, in radians. Clockwise because the
+    /// y axis points down.
+    #[must_use]
+    pub fn rotation(angle: f32) -> Self {
+        let (sin, cos) = angle.sin_cos();
+        Self::new([cos, sin, -sin, cos, 0.0, 0.0])
+    }
+
+    /// Shears x by the tangent of `x_angle` per unit of y, and y by the
+    /// tangent of `y_angle` per unit of x. Angles are in radians.
+    #[must_use]
+    pub fn skew(x_angle: f32, y_angle: f32) -> Self {
+        Self::new([1.0, y_angle.tan(), x_angle.tan(), 1.0, 0.0, 0.0])
+    }
+
+    /// Applies `self`, then `next`.
+    #[must_use]
+    pub fn then<Next: Num>(&self, next: &Transform<Dst, Next>) -> Transform<Src, Next> {
+        let [a, b, c, d, e, f] = self.m;
+        let [na, nb, nc, nd, ne, nf] = next.m;
+
+        Transform::new([
+            na * a + nc * b,
+            nb * a + nd * b,
+            na * c + nc * d,
+            nb * c + nd * d,
+            na * e + nc * f + ne,
+            nb * e + nd * f + nf,
+        ])
+    }
+
+    /// The transform that undoes this one, or `None` if it collapses the plane
+    /// onto a line or point.
+    #[must_use]
+    pub fn inverse(&self) -> Option<Transform<Dst, Src>> {
+        let [a, b, c, d, e, f] = self.m;
+        let det = a * d - b * c;
+
+        if det == 0.0 || !det.is_finite() {
+            return None;
+        }
+
+        let inv = 1.0 / det;
+        Some(Transform::new([
+            d * inv,
+            -b * inv,
+            -c * inv,
+            a * inv,
+            (c * f - d * e) * inv,
+            (b * e - a * f) * inv,
+        ]))
+    }
+
+    /// The factor by which the transform scales areas.
+    #[must_use]
+    pub fn determinant(&self) -> f32 {
+        let [a, b, c, d, ..] = self.m;
+        a * d - b * c
+    }
+
+    /// Whether the transform maps axis-aligned rects to axis-aligned rects
+    /// without flipping them.
+    #[must_use]
+    pub fn is_axis_aligned(&self) -> bool {
+        let [a, b, c, d, ..] = self.m;
+        b == 0.0 && c == 0.0 && a > 0.0 && d > 0.0
+    }
+
+    #[must_use]
+    pub fn transform_point(&self, point: Point<Src>) -> Point<Dst>
+    where
+        Src: Into<f32>,
+        Dst: From<f32>,
+    {
+        let (x, y) = self.apply(point.x.into(), point.y.into());
+        Point::new(x, y)
+    }
+
+    /// The smallest axis-aligned rect that contains the transformed rect.
+    #[must_use]
+    pub fn transform_rect(&self, rect: Rect<Src>) -> Rect<Dst>
+    where
+        Src: Into<f32>,
+        Dst: From<f32>,
+    {
+        let [x, y, w, h] = rect.to_xywh().map(Into::into);
+
+        let corners =
+            [(x, y), (x + w, y), (x, y + h), (x + w, y + h)].map(|(x, y)| self.apply(x, y));
+
+        let (mut min, mut max) = (corners[0], corners[0]);
+        for (x, y) in corners {
+            min = (min.0.min(x), min.1.min(y));
+            max = (max.0.max(x), max.1.max(y));
+        }
+
+        Rect::new(min, (max.0 - min.0, max.1 - min.1))
+    }
+
+    /// Transforms a point given as raw coordinates.
+    #[must_use]
+    pub fn apply(&self, x: f32, y: f32) -> (f32, f32) {
+        let [a, b, c, d, e, f] = self.m;
+        (a * x + c * y + e, b * x + d * y + f)
+    }
+}
+
+impl<Src: Num, Dst: Num> Default for Transform<Src, Dst> {
+    fn default() -> Self {
+        Self::IDENTITY
+    }
+}
+
 pub trait Num: Copy + Default + Add + Sub + Mul + Div + PartialOrd + PartialEq {
     const ZERO: Self;
     const ONE: Self;
@@ -640,3 +806,34 @@ impl Num for f64 {
         self.max(other)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn transform() {
+        let t = Affine::scale(2.0, 2.0).then(&Affine::translation(10.0, 0.0));
+        assert_eq!(
+            t.transform_point(Point::new(1.0, 1.0)),
+            Point::new(12.0, 2.0)
+        );
+
+        let r = Affine::rotation(std::f32::consts::FRAC_PI_2);
+        let p = r.transform_point(Point::new(1.0, 0.0));
+        assert!((p.x.0 - 0.0).abs() < 1e-6 && (p.y.0 - 1.0).abs() < 1e-6);
+
+        let inverse = t.inverse().unwrap();
+        assert_eq!(
+            inverse.transform_point(Point::new(12.0, 2.0)),
+            Point::new(1.0, 1.0)
+        );
+
+        assert!(Affine::scale(0.0, 1.0).inverse().is_none());
+
+        let bounds = r.transform_rect(Rect::new((0.0, 0.0), (2.0, 1.0)));
+        assert!((bounds.origin.x.0 + 1.0).abs() < 1e-6);
+        assert!((bounds.extent.width.0 - 1.0).abs() < 1e-6);
+        assert!((bounds.extent.height.0 - 2.0).abs() < 1e-6);
+    }
+}
@@ -17,24 +17,35 @@ use windows::{
                 ID3D12InfoQueue1, ID3D12Resource, D3D12_COMMAND_LIST_TYPE,
                 D3D12_COMMAND_LIST_TYPE_DIRECT, D3D12_COMMAND_QUEUE_DESC,
                 D3D12_COMMAND_QUEUE_FLAG_NONE, D3D12_CPU_DESCRIPTOR_HANDLE,
-                D3D12_CPU_PAGE_PROPERTY_UNKNOWN, D3D12_DESCRIPTOR_HEAP_DESC,
-                D3D12_DESCRIPTOR_HEAP_FLAGS, D3D12_DESCRIPTOR_HEAP_FLAG_SHADER_VISIBLE,
+                D3D12_CPU_PAGE_PROPERTY_UNKNOWN, D3D12_DEFAULT_SHADER_4_COMPONENT_MAPPING,
+                D3D12_DESCRIPTOR_HEAP_DESC, D3D12_DESCRIPTOR_HEAP_FLAGS,
+                D3D12_DESCRIPTOR_HEAP_FLAG_NONE, D3D12_DESCRIPTOR_HEAP_FLAG_SHADER_VISIBLE,
                 D3D12_DESCRIPTOR_HEAP_TYPE, D3D12_DESCRIPTOR_HEAP_TYPE_CBV_SRV_UAV,
-                D3D12_FENCE_FLAG_NONE, D3D12_GPU_DESCRIPTOR_HANDLE, D3D12_HEAP_FLAG_NONE,
-                D3D12_HEAP_PROPERTIES, D3D12_HEAP_TYPE_DEFAULT, D3D12_HEAP_TYPE_UPLOAD,
+                D3D12_DESCRIPTOR_HEAP_TYPE_RTV, D3D12_FENCE_FLAG_NONE, D3D12_GPU_DESCRIPTOR_HANDLE,
+                D3D12_HEAP_FLAG_NONE, D3D12_HEAP_PROPERTIES, D3D12_HEAP_TYPE,
+                D3D12_HEAP_TYPE_DEFAULT, D3D12_HEAP_TYPE_READBACK, D3D12_HEAP_TYPE_UPLOAD,
                 D3D12_MEMORY_POOL_UNKNOWN, D3D12_MESSAGE_CALLBACK_FLAG_NONE,
                 D3D12_MESSAGE_CATEGORY, D3D12_MESSAGE_ID, D3D12_MESSAGE_SEVERITY,
                 D3D12_MESSAGE_SEVERITY_CORRUPTION, D3D12_MESSAGE_SEVERITY_ERROR,
                 D3D12_MESSAGE_SEVERITY_INFO, D3D12_MESSAGE_SEVERITY_MESSAGE,
-                D3D12_MESSAGE_SEVERITY_WARNING, D3D12_RESOURCE_DESC,
-                D3D12_RESOURCE_DIMENSION_BUFFER, D3D12_RESOURCE_DIMENSION_TEXTURE2D,
-                D3D12_RESOURCE_FLAG_NONE, D3D12_RESOURCE_STATE_GENERIC_READ,
+                D3D12_MESSAGE_SEVERITY_WARNING, D3D12_PLACED_SUBRESOURCE_FOOTPRINT, D3D12_RANGE,
+                D3D12_RESOURCE_DESC, D3D12_RESOURCE_DIMENSION_BUFFER,
+                D3D12_RESOURCE_DIMENSION_TEXTURE2D, D3D12_RESOURCE_FLAG_ALLOW_RENDER_TARGET,
+                D3D12_RESOURCE_FLAG_NONE, D3D12_RESOURCE_STATES, D3D12_RESOURCE_STATE_COPY_DEST,
+                D3D12_RESOURCE_STATE_COPY_SOURCE, D3D12_RESOURCE_STATE_GENERIC_READ,
                 D3D12_RESOURCE_STATE_PIXEL_SHADER_RESOURCE, D3D12_RESOURCE_STATE_RENDER_TARGET,
+                D3D12_SHADER_COMPONENT_MAPPING_ALWAYS_SET_BIT_AVOIDING_ZEROMEM_MISTAKES,
+                D3D12_SHADER_COMPONENT_MAPPING_FROM_MEMORY_COMPONENT_0,
+                D3D12_SHADER_RESOURCE_VIEW_DESC, D3D12_SHADER_RESOURCE_VIEW_DESC_0,
+                D3D12_SRV_DIMENSION_TEXTURE2D, D3D12_SUBRESOURCE_FOOTPRINT, D3D12_TEX2D_SRV,
+                D3D12_TEXTURE_COPY_LOCATION, D3D12_TEXTURE_COPY_LOCATION_0,
+                D3D12_TEXTURE_COPY_TYPE_PLACED_FOOTPRINT,
+                D3D12_TEXTURE_COPY_TYPE_SUBRESOURCE_INDEX, D3D12_TEXTURE_DATA_PITCH_ALIGNMENT,
                 D3D12_TEXTURE_LAYOUT_ROW_MAJOR, D3D12_TEXTURE_LAYOUT_UNKNOWN, D3D12_VIEWPORT,
             },
             DirectComposition::{DCompositionCreateDevice2, IDCompositionDevice},
             Dxgi::{
-                Common::{DXGI_FORMAT_UNKNOWN, DXGI_SAMPLE_DESC},
+                Common::{DXGI_FORMAT_R16G16B16A16_FLOAT, DXGI_FORMAT_UNKNOWN, DXGI_SAMPLE_DESC},
                 CreateDXGIFactory2, IDXGIFactory2, DXGI_CREATE_FACTORY_DEBUG,
             },
         },
@@ -46,12 +57,20 @@ use crate::{
     geometry::{Extent, Point, Texel},
     graphics::{
         backend::{dx12::image_barrier, SubmitId, TextureId},
-        draw_list::Command,
-        DrawList, Format, GraphicsConfig, Layout, RasterBuf,
+        draw_list::{BackdropBlur, Clip, Command, Layer, RRect},
+        hdr::{encode_output, ColorOutput},
+        image::f16_to_f32,
+        DrawList, Format, GraphicsConfig, Layout, Raster, RasterBuf,
     },
+    limits::GFX_LAYER_DEPTH,
 };
 
-use super::{shaders::RectShader, to_dxgi_format, uploader::Uploader, RenderTarget, Swapchain};
+use super::{
+    shaders::{BlurShader, CompositeShader, OutputShader, RectShader},
+    to_dxgi_format,
+    uploader::Uploader,
+    RenderTarget, Swapchain,
+};
 
 const DEFAULT_DRAW_BUFFER_SIZE: u64 = 64 * 1024;
 
@@ -60,6 +79,9 @@ pub struct Device {
     pub handle: ID3D12Device,
     pub queue: Queue,
     pub rect_shader: RectShader,
+    pub composite_shader: CompositeShader,
+    pub blur_shader: BlurShader,
+    pub output_shader: OutputShader,
     pub compositor: IDCompositionDevice,
 
     uploader: RefCell<Uploader>,
@@ -69,6 +91,9 @@ pub struct Device {
 
     textures: RefCell<Box<SlotMap<1024, ID3D12Resource, TextureId>>>,
     pub texture_descriptors: DescriptorHeap,
+
+    /// The targets that nested layers are drawn into, by depth.
+    layers: RefCell<Vec<LayerTarget>>,
 }
 
 impl Device {
@@ -125,6 +150,9 @@ impl Device {
         let queue = Queue::new(&device, D3D12_COMMAND_LIST_TYPE_DIRECT);
 
         let rect_shader = RectShader::new(&device);
+        let composite_shader = CompositeShader::new(&device);
+        let blur_shader = BlurShader::new(&device);
+        let output_shader = OutputShader::new(&device);
 
         let compositor = unsafe { DCompositionCreateDevice2(None) }.unwrap();
 
@@ -133,7 +161,7 @@ impl Device {
         let texture_descriptors = DescriptorHeap::new(
             &device,
             D3D12_DESCRIPTOR_HEAP_TYPE_CBV_SRV_UAV,
-            1024,
+            LAYER_DESCRIPTOR_BASE + 2 * GFX_LAYER_DEPTH.get() as u32,
             D3D12_DESCRIPTOR_HEAP_FLAG_SHADER_VISIBLE,
         );
 
@@ -158,12 +186,16 @@ impl Device {
             handle: device,
             queue,
             rect_shader,
+            composite_shader,
+            blur_shader,
+            output_shader,
             compositor,
             command_list,
             frames: RefCell::new(frames),
             uploader: RefCell::new(uploader),
             textures: RefCell::new(Box::new(textures)),
             texture_descriptors,
+            layers: RefCell::new(Vec::new()),
         }
     }
 
@@ -189,6 +221,29 @@ impl Device {
     ) -> TextureId {
         let format = to_dxgi_format(layout, format);
 
+        // Single-channel textures hold coverage, so every channel reads the
+        // stored value. This makes them act as premultiplied white.
+        let component_mapping = match layout {
+            Layout::Alpha8 | Layout::Alpha8Vector => REPLICATE_RED_MAPPING,
+            Layout::Rgba8 | Layout::Rgba8Vector | Layout::Bgra8 | Layout::Rgba16Float => {
+                D3D12_DEFAULT_SHADER_4_COMPONENT_MAPPING
+            }
+        };
+
+        let view_desc = D3D12_SHADER_RESOURCE_VIEW_DESC {
+            Format: format,
+            ViewDimension: D3D12_SRV_DIMENSION_TEXTURE2D,
+            Shader4ComponentMapping: component_mapping,
+            Anonymous: D3D12_SHADER_RESOURCE_VIEW_DESC_0 {
+                Texture2D: D3D12_TEX2D_SRV {
+                    MostDetailedMip: 0,
+                    MipLevels: 1,
+                    PlaneSlice: 0,
+                    ResourceMinLODClamp: 0.0,
+                },
+            },
+        };
+
         let heap_desc = D3D12_HEAP_PROPERTIES {
             Type: D3D12_HEAP_TYPE_DEFAULT,
             CPUPageProperty: D3D12_CPU_PAGE_PROPERTY_UNKNOWN,
@@ -234,7 +289,10 @@ impl Device {
                 let image = image.unwrap();
                 let view = self.texture_descriptors.cpu(key.index());
 
-                unsafe { self.handle.CreateShaderResourceView(&image, None, view) };
+                unsafe {
+                    self.handle
+                        .CreateShaderResourceView(&image, Some(&view_desc), view);
+                };
 
                 image
             })
@@ -243,6 +301,212 @@ impl Device {
         id
     }
 
+    /// Releases a texture. The caller must ensure that no pending submission
+    /// reads from it.
+    pub fn destroy_texture(&self, texture: TextureId) {
+        self.textures
+            .borrow_mut()
+            .remove(texture)
+            .expect("Texture has already been destroyed");
+    }
+
+    pub fn create_render_target(
+        &self,
+        extent: Extent<Texel>,
+        layout: Layout,
+        format: Format,
+    ) -> RenderTarget {
+        let heap_desc = D3D12_HEAP_PROPERTIES {
+            Type: D3D12_HEAP_TYPE_DEFAULT,
+            CPUPageProperty: D3D12_CPU_PAGE_PROPERTY_UNKNOWN,
+            MemoryPoolPreference: D3D12_MEMORY_POOL_UNKNOWN,
+            CreationNodeMask: 0,
+            VisibleNodeMask: 0,
+        };
+
+        // Offscreen targets use the same format as the swapchain so that they
+        // are compatible with the rect shader's pipeline state.
+        let image_desc = D3D12_RESOURCE_DESC {
+            Dimension: D3D12_RESOURCE_DIMENSION_TEXTURE2D,
+            Alignment: 0,
+            Width: extent.width.0 as u64,
+            Height: extent.height.0 as u32,
+            DepthOrArraySize: 1,
+            MipLevels: 1,
+            Format: DXGI_FORMAT_R16G16B16A16_FLOAT,
+            SampleDesc: DXGI_SAMPLE_DESC {
+                Count: 1,
+                Quality: 0,
+            },
+            Layout: D3D12_TEXTURE_LAYOUT_UNKNOWN,
+            Flags: D3D12_RESOURCE_FLAG_ALLOW_RENDER_TARGET,
+        };
+
+        let mut image: Option<ID3D12Resource> = None;
+
+        unsafe {
+            self.handle.CreateCommittedResource(
+                &heap_desc,
+                D3D12_HEAP_FLAG_NONE,
+                &image_desc,
+                D3D12_RESOURCE_STATE_PIXEL_SHADER_RESOURCE,
+                None,
+                &mut image,
+            )
+        }
+        .unwrap();
+
+        let image = image.unwrap();
+
+        let rtv_heap: ID3D12DescriptorHeap = unsafe {
+            self.handle
+                .CreateDescriptorHeap(&D3D12_DESCRIPTOR_HEAP_DESC {
+                    Type: D3D12_DESCRIPTOR_HEAP_TYPE_RTV,
+                    NumDescriptors: 1,
+                    Flags: D3D12_DESCRIPTOR_HEAP_FLAG_NONE,
+                    NodeMask: 0,
+                })
+        }
+        .unwrap();
+
+        let descriptor = unsafe { rtv_heap.GetCPUDescriptorHandleForHeapStart() };
+
+        unsafe {
+            self.handle.CreateRenderTargetView(&image, None, descriptor);
+        }
+
+        RenderTarget {
+            draw: None,
+            size: extent,
+            state: D3D12_RESOURCE_STATE_PIXEL_SHADER_RESOURCE,
+            resource: image,
+            descriptor,
+            rtv_heap: Some(rtv_heap),
+            layout,
+            format,
+            output: ColorOutput::default(),
+            encode_on_gpu: false,
+            encode_target: None,
+        }
+    }
+
+    /// Copies the contents of a render target into system memory.
+    ///
+    /// This blocks until the copy has completed.
+    pub fn read_pixels(&self, target: &RenderTarget) -> Raster {
+        if let Some(draw) = target.draw {
+            self.queue.wait(draw);
+        }
+
+        let width = usize::try_from(target.size.width.0).unwrap();
+        let height = usize::try_from(target.size.height.0).unwrap();
+
+        // 4 channels of 16-bit floats
+        let row_size = width * 8;
+        let row_pitch = row_size.next_multiple_of(D3D12_TEXTURE_DATA_PITCH_ALIGNMENT as usize);
+
+        let buffer = alloc_readback_buffer(&self.handle, (row_pitch * height) as u64);
+
+        let command_allocator: ID3D12CommandAllocator = unsafe {
+            self.handle
+                .CreateCommandAllocator(D3D12_COMMAND_LIST_TYPE_DIRECT)
+        }
+        .unwrap();
+
+        let command_list: ID3D12GraphicsCommandList = unsafe {
+            self.handle.CreateCommandList(
+                0,
+                D3D12_COMMAND_LIST_TYPE_DIRECT,
+                &command_allocator,
+                None,
+            )
+        }
+        .unwrap();
+
+        image_barrier(
+            &command_list,
+            &target.resource,
+            target.state,
+            D3D12_RESOURCE_STATE_COPY_SOURCE,
+        );
+
+        let src = D3D12_TEXTURE_COPY_LOCATION {
+            pResource: unsafe { std::mem::transmute_copy(&target.resource) },
+            Type: D3D12_TEXTURE_COPY_TYPE_SUBRESOURCE_INDEX,
+            Anonymous: D3D12_TEXTURE_COPY_LOCATION_0 {
+                SubresourceIndex: 0,
+            },
+        };
+
+        let dst = D3D12_TEXTURE_COPY_LOCATION {
+            pResource: unsafe { std::mem::transmute_copy(&buffer) },
+            Type: D3D12_TEXTURE_COPY_TYPE_PLACED_FOOTPRINT,
+            Anonymous: D3D12_TEXTURE_COPY_LOCATION_0 {
+                PlacedFootprint: D3D12_PLACED_SUBRESOURCE_FOOTPRINT {
+                    Offset: 0,
+                    Footprint: D3D12_SUBRESOURCE_FOOTPRINT {
+                        Format: DXGI_FORMAT_R16G16B16A16_FLOAT,
+                        Width: width as u32,
+                        Height: height as u32,
+                        Depth: 1,
+                        RowPitch: row_pitch as u32,
+                    },
+                },
+            },
+        };
+
+        unsafe { command_list.CopyTextureRegion(&dst, 0, 0, 0, &src, None) };
+
+        image_barrier(
+            &command_list,
+            &target.resource,
+            D3D12_RESOURCE_STATE_COPY_SOURCE,
+            target.state,
+        );
+
+        unsafe { command_list.Close() }.unwrap();
+
+        let sync = self.queue.submit(&command_list.cast().unwrap());
+        self.queue.wait(sync);
+
+        let mut pixels = Vec::with_capacity(width * height);
+
+        unsafe {
+            let mut map = std::ptr::null_mut();
+            buffer
+                .Map(
+                    0,
+                    Some(&D3D12_RANGE {
+                        Begin: 0,
+                        End: row_pitch * height,
+                    }),
+                    Some(&mut map),
+                )
+                .unwrap();
+
+            let base = map.cast::<u8>();
+
+            for y in 0..height {
+                let row =
+                    std::slice::from_raw_parts(base.add(y * row_pitch).cast::<u16>(), width * 4);
+
+                pixels.extend(row.chunks_exact(4).map(|texel| {
+                    let pixel = std::array::from_fn(|i| f16_to_f32(texel[i]));
+
+                    if target.encode_on_gpu {
+                        pixel
+                    } else {
+                        encode_output(pixel, &target.output)
+                    }
+                }));
+            }
+
+            buffer.Unmap(0, Some(&D3D12_RANGE { Begin: 0, End: 0 }));
+        }
+
+        Raster::from_linear_rgba(target.info(), &pixels)
+    }
+
     pub fn copy_raster_to_texture(
         &self,
         target: TextureId,
@@ -259,7 +523,7 @@ impl Device {
         self.uploader.borrow_mut().flush_upload_buffer(&self.queue);
     }
 
-    pub fn draw(&self, draw_list: &DrawList, target: &mut RenderTarget) {
+    pub fn draw(&self, draw_list: &DrawList, target: &mut RenderTarget) -> SubmitId {
         self.flush_upload_buffer();
 
         let mut frames = self.frames.borrow_mut();
@@ -281,6 +545,8 @@ impl Device {
 
         frame.reset(&self.queue, &self.command_list);
 
+        let layers = self.prepare_layers(draw_list, target);
+
         image_barrier(
             &self.command_list,
             &target.resource,
@@ -293,7 +559,11 @@ impl Device {
             &self.command_list,
             draw_list,
             &self.rect_shader,
+            &self.composite_shader,
+            &self.blur_shader,
+            &self.output_shader,
             target,
+            &layers,
             &self.texture_descriptors,
         );
 
@@ -309,10 +579,144 @@ impl Device {
         frame.sync = self.queue.submit(&self.command_list.cast().unwrap());
         target.draw = Some(frame.sync);
 
+        let sync = frame.sync;
         frames.push_back(frame);
+        sync
+    }
+}
+
+impl Device {
+    /// Makes sure that there is a layer target the size of the render target
+    /// for each level of nesting in the draw list. Backdrop blurs use the
+    /// layer target one level deeper than the one they are drawn into, and
+    /// encoding output on the GPU uses the first.
+    fn prepare_layers(
+        &self,
+        draw_list: &DrawList,
+        target: &RenderTarget,
+    ) -> Ref<'_, [LayerTarget]> {
+        let extent = target.extent();
+        let depth = draw_list
+            .iter()
+            .scan(0, |depth, command| {
+                match command {
+                    Command::PushLayer(_) => *depth += 1,
+                    Command::PopLayer => *depth -= 1,
+                    Command::BackdropBlur(_) => return Some(*depth + 1),
+                    _ => {}
+                }
+                Some(*depth)
+            })
+            .max()
+            .unwrap_or(0)
+            .max(usize::from(target.encode_on_gpu));
+
+        {
+            let mut layers = self.layers.borrow_mut();
+
+            let valid = layers
+                .iter()
+                .take_while(|layer| layer.target.size == extent)
+                .count();
+
+            if valid < layers.len().min(depth) {
+                // Earlier draws may still be using the layers.
+                self.queue.wait_idle();
+                layers.truncate(valid);
+            }
+
+            for index in layers.len()..depth {
+                layers.push(self.create_layer_target(index, extent));
+            }
+        }
+
+        Ref::map(self.layers.borrow(), |layers| &layers[..depth])
+    }
+
+    /// Creates the target for a layer at the given depth, along with the
+    /// texture that the pixels beneath the layer are copied into when it is
+    /// composited.
+    fn create_layer_target(&self, depth: usize, extent: Extent<Texel>) -> LayerTarget {
+        let target = self.create_render_target(extent, Layout::Rgba8, Format::Linear);
+
+        let heap_desc = D3D12_HEAP_PROPERTIES {
+            Type: D3D12_HEAP_TYPE_DEFAULT,
+            CPUPageProperty: D3D12_CPU_PAGE_PROPERTY_UNKNOWN,
+            MemoryPoolPreference: D3D12_MEMORY_POOL_UNKNOWN,
+            CreationNodeMask: 0,
+            VisibleNodeMask: 0,
+        };
+
+        let mut backdrop: Option<ID3D12Resource> = None;
+
+        unsafe {
+            self.handle.CreateCommittedResource(
+                &heap_desc,
+                D3D12_HEAP_FLAG_NONE,
+                &D3D12_RESOURCE_DESC {
+                    Flags: D3D12_RESOURCE_FLAG_NONE,
+                    ..target.resource.GetDesc()
+                },
+                D3D12_RESOURCE_STATE_PIXEL_SHADER_RESOURCE,
+                None,
+                &mut backdrop,
+            )
+        }
+        .unwrap();
+
+        let backdrop = backdrop.unwrap();
+
+        let view_desc = D3D12_SHADER_RESOURCE_VIEW_DESC {
+            Format: DXGI_FORMAT_R16G16B16A16_FLOAT,
+            ViewDimension: D3D12_SRV_DIMENSION_TEXTURE2D,
+            Shader4ComponentMapping: D3D12_DEFAULT_SHADER_4_COMPONENT_MAPPING,
+            Anonymous: D3D12_SHADER_RESOURCE_VIEW_DESC_0 {
+                Texture2D: D3D12_TEX2D_SRV {
+                    MostDetailedMip: 0,
+                    MipLevels: 1,
+                    PlaneSlice: 0,
+                    ResourceMinLODClamp: 0.0,
+                },
+            },
+        };
+
+        let index = layer_descriptor(depth);
+
+        unsafe {
+            self.handle.CreateShaderResourceView(
+                &target.resource,
+                Some(&view_desc),
+                self.texture_descriptors.cpu(index),
+            );
+            self.handle.CreateShaderResourceView(
+                &backdrop,
+                Some(&view_desc),
+                self.texture_descriptors.cpu(index + 1),
+            );
+        }
+
+        LayerTarget { target, backdrop }
     }
 }
 
+/// Layers use the descriptors that follow those of textures, two per layer.
+const LAYER_DESCRIPTOR_BASE: u32 = 1024;
+
+/// The descriptor of the layer at the given depth. The descriptor of its
+/// backdrop follows it.
+#[allow(clippy::cast_possible_truncation)]
+fn layer_descriptor(depth: usize) -> u32 {
+    LAYER_DESCRIPTOR_BASE + 2 * depth as u32
+}
+
+/// The target that a layer is drawn into.
+pub struct LayerTarget {
+    target: RenderTarget,
+    /// A copy of the pixels beneath the layer, which blend modes read from
+    /// when the layer is composited.
+    backdrop: ID3D12Resource,
+}
+
 /// A queue of GPU commands.
 ///
 /// Based on the implementation described here: <https://alextardif.com/D3D11To12P1.html>
@@ -341,7 +745,9 @@ impl Queue {
         Self {
             handle: queue,
             fence,
-            num_submitted: AtomicU64::new(0),
+            // The fence starts at 0, so the first submission must signal 1 or
+            // it would be considered done before it has executed.
+            num_submitted: AtomicU64::new(1),
             num_completed: AtomicU64::new(0),
         }
     }
@@ -492,8 +898,42 @@ impl DescriptorHeap {
 }
 
 pub fn alloc_upload_buffer(device: &ID3D12Device, size: u64) -> ID3D12Resource {
+    alloc_buffer(
+        device,
+        size,
+        D3D12_HEAP_TYPE_UPLOAD,
+        D3D12_RESOURCE_STATE_GENERIC_READ,
+    )
+}
+
+pub fn alloc_readback_buffer(device: &ID3D12Device, size: u64) -> ID3D12Resource {
+    alloc_buffer(
+        device,
+        size,
+        D3D12_HEAP_TYPE_READBACK,
+        D3D12_RESOURCE_STATE_COPY_DEST,
+    )
+}
+
+/// Reads the first component of a texel into every channel, as
+/// `D3D12_ENCODE_SHADER_4_COMPONENT_MAPPING` would.
+#[allow(clippy::cast_sign_loss)]
+const REPLICATE_RED_MAPPING: u32 = {
+    let red = D3D12_SHADER_COMPONENT_MAPPING_FROM_MEMORY_COMPONENT_0.0 as u32;
+    red | red << 3
+        | red << 6
+        | red << 9
+        | D3D12_SHADER_COMPONENT_MAPPING_ALWAYS_SET_BIT_AVOIDING_ZEROMEM_MISTAKES
+};
+
+fn alloc_buffer(
+    device: &ID3D12Device,
+    size: u64,
+    heap: D3D12_HEAP_TYPE,
+    state: D3D12_RESOURCE_STATES,
+) -> ID3D12Resource {
     let heap_desc = D3D12_HEAP_PROPERTIES {
-        Type: D3D12_HEAP_TYPE_UPLOAD,
+        Type: heap,
         CPUPageProperty: D3D12_CPU_PAGE_PROPERTY_UNKNOWN,
         MemoryPoolPreference: D3D12_MEMORY_POOL_UNKNOWN,
         CreationNodeMask: 0,
@@ -523,7 +963,7 @@ pub fn alloc_upload_buffer(device: &ID3D12Device, size: u64) -> ID3D12Resource {
             &heap_desc,
             D3D12_HEAP_FLAG_NONE,
             &buffer_desc,
-            D3D12_RESOURCE_STATE_GENERIC_READ,
+            state,
             None,
             &mut buffer,
         )
@@ -533,6 +973,50 @@ pub fn alloc_upload_buffer(device: &ID3D12Device, size: u64) -> ID3D12Resource {
     buffer.unwrap()
 }
 
+/// The scissor rect for a clip's bounds. Pixels are inside the scissor rect if
+/// their centers are inside the bounds.
+///
+/// Must be kept in sync with `RenderTarget::pixel_range` in `software.rs`.
+#[allow(clippy::cast_possible_truncation)]
+fn scissor_rect(clip: &Clip, target: &RenderTarget) -> RECT {
+    let [x, y, w, h] = clip.bounds.to_xywh().map(|v| v.0);
+
+    let width = f32::from(target.extent().width);
+    let height = f32::from(target.extent().height);
+
+    let left = (x - 0.5).ceil().clamp(0.0, width);
+    let top = (y - 0.5).ceil().clamp(0.0, height);
+    let right = (x + w - 0.5).ceil().clamp(left, width);
+    let bottom = (y + h - 0.5).ceil().clamp(top, height);
+
+    RECT {
+        left: left as i32,
+        top: top as i32,
+        right: right as i32,
+        bottom: bottom as i32,
+    }
+}
+
+/// The pixels that a backdrop blur draws to: those touched by its rect,
+/// within the scissor rect of its clip.
+///
+/// Must be kept in sync with `RenderTarget::backdrop_blur` in `software.rs`.
+#[allow(clippy::cast_possible_truncation)]
+fn blur_scissor_rect(blur: &BackdropBlur, target: &RenderTarget) -> RECT {
+    let clip = scissor_rect(&blur.clip, target);
+    let [x, y, w, h] = blur.rect.to_xywh().map(|v| v.0);
+
+    let left = (x.floor() as i32).clamp(clip.left, clip.right);
+    let top = (y.floor() as i32).clamp(clip.top, clip.bottom);
+
+    RECT {
+        left,
+        top,
+        right: ((x + w).ceil() as i32).clamp(left, clip.right),
+        bottom: ((y + h).ceil() as i32).clamp(top, clip.bottom),
+    }
+}
+
 pub struct Frame {
     buffer: Option<ID3D12Resource>,
     base: *mut u8,
@@ -572,16 +1056,27 @@ impl Frame {
         }
     }
 
+    #[allow(clippy::too_many_arguments)]
     pub fn write(
         &mut self,
         device: &ID3D12Device,
         command_list: &ID3D12GraphicsCommandList,
         draw_list: &DrawList,
         shader: &RectShader,
+        composite_shader: &CompositeShader,
+        blur_shader: &BlurShader,
+        output_shader: &OutputShader,
         target: &RenderTarget,
+        layers: &[LayerTarget],
         textures: &DescriptorHeap,
     ) {
-        let content_size = std::mem::size_of_val(draw_list.prims.as_slice());
+        let prims_size = std::mem::size_of_val(draw_list.prims.as_slice());
+        let gradients_size = std::mem::size_of_val(draw_list.gradients.as_slice());
+
+        // The gradient table follows the rects, aligned so that it can be
+        // bound as a separate structured buffer.
+        let gradients_offset = prims_size.next_multiple_of(256);
+        let content_size = gradients_offset + gradients_size;
 
         if self.size < content_size {
             self.buffer = None;
@@ -600,9 +1095,23 @@ impl Frame {
 
         unsafe {
             self.base
-                .copy_from_nonoverlapping(draw_list.prims.as_ptr().cast(), content_size);
+                .copy_from_nonoverlapping(draw_list.prims.as_ptr().cast(), prims_size);
+
+            self.base
+                .add(gradients_offset)
+                .copy_from_nonoverlapping(draw_list.gradients.as_ptr().cast(), gradients_size);
         }
 
+        let buffer_address = unsafe { self.buffer.as_ref().unwrap().GetGPUVirtualAddress() };
+
+        // Nothing reads from the gradient table if it is empty, but it must
+        // still be bound to a valid address.
+        let gradients_address = if gradients_size == 0 {
+            buffer_address
+        } else {
+            buffer_address + gradients_offset as u64
+        };
+
         let viewport_scale = [
             1.0 / f32::from(target.extent().width),
             1.0 / f32::from(target.extent().height),
@@ -636,12 +1145,34 @@ impl Frame {
 
         shader.bind(
             command_list,
-            self.buffer.as_ref().unwrap(),
+            buffer_address,
+            gradients_address,
             textures.gpu_base,
             viewport_scale,
             f32::from(target.extent().height),
         );
 
+        // The scissor rect and rounded clip, which are restored after each
+        // layer is composited.
+        let mut scissor = RECT {
+            left: 0,
+            top: 0,
+            right: i32::from(target.extent().width.0),
+            bottom: i32::from(target.extent().height.0),
+        };
+        let mut round_clip = None;
+
+        // The layers that are being drawn. Everything is drawn into the
+        // innermost one.
+        let mut layer_stack: Vec<Layer> = Vec::new();
+        let current = |depth: usize| {
+            if depth == 0 {
+                target
+            } else {
+                &layers[depth - 1].target
+            }
+        };
+
         let mut rect_start = 0;
         for command in it.by_ref() {
             match command {
@@ -649,16 +1180,247 @@ impl Frame {
                 Command::Close => break,
                 Command::Clear(color) => unsafe {
                     command_list.ClearRenderTargetView(
-                        target.descriptor,
+                        current(layer_stack.len()).descriptor,
                         &color.to_array_f32(),
                         None,
                     );
                 },
+                Command::Clip(clip) => {
+                    scissor = scissor_rect(&clip, target);
+                    round_clip = clip
+                        .rounded
+                        .map(|(rect, radii)| (rect.to_xywh().map(|v| v.0), radii));
+
+                    unsafe { command_list.RSSetScissorRects(&[scissor]) };
+                    shader.set_round_clip(command_list, round_clip);
+                }
                 Command::Rects(count) => {
-                    unsafe { command_list.DrawInstanced(4, count, 0, rect_start) };
+                    let rects_address =
+                        buffer_address + u64::from(rect_start) * size_of::<RRect>() as u64;
+
+                    shader.set_rects(command_list, rects_address);
+                    unsafe { command_list.DrawInstanced(4, count, 0, 0) };
                     rect_start += count;
                 }
+                Command::PushLayer(layer) => {
+                    let next = &layers[layer_stack.len()].target;
+                    layer_stack.push(layer);
+
+                    image_barrier(
+                        command_list,
+                        &next.resource,
+                        D3D12_RESOURCE_STATE_PIXEL_SHADER_RESOURCE,
+                        D3D12_RESOURCE_STATE_RENDER_TARGET,
+                    );
+
+                    unsafe {
+                        command_list.OMSetRenderTargets(1, Some(&next.descriptor), false, None);
+                        command_list.ClearRenderTargetView(next.descriptor, &[0.0; 4], None);
+                    }
+                }
+                Command::PopLayer => {
+                    let layer = layer_stack.pop().unwrap();
+                    let depth = layer_stack.len();
+                    let top = &layers[depth];
+                    let below = current(depth);
+
+                    image_barrier(
+                        command_list,
+                        &top.target.resource,
+                        D3D12_RESOURCE_STATE_RENDER_TARGET,
+                        D3D12_RESOURCE_STATE_PIXEL_SHADER_RESOURCE,
+                    );
+
+                    // The composite shader can't read from the target that it
+                    // draws to, so it reads a copy instead.
+                    copy_backdrop(command_list, below, &top.backdrop);
+
+                    unsafe {
+                        command_list.OMSetRenderTargets(1, Some(&below.descriptor), false, None);
+                        command_list.RSSetScissorRects(&[scissor_rect(&layer.clip, target)]);
+                    }
+
+                    composite_shader.draw(
+                        command_list,
+                        textures.gpu_base,
+                        layer_descriptor(depth),
+                        &layer,
+                    );
+
+                    shader.bind(
+                        command_list,
+                        buffer_address,
+                        gradients_address,
+                        textures.gpu_base,
+                        viewport_scale,
+                        f32::from(target.extent().height),
+                    );
+
+                    unsafe { command_list.RSSetScissorRects(&[scissor]) };
+                    shader.set_round_clip(command_list, round_clip);
+                }
+                Command::BackdropBlur(blur) => {
+                    // The blur is drawn in two passes, using the next layer's
+                    // target to hold the result of the first.
+                    let depth = layer_stack.len();
+                    let scratch = &layers[depth];
+                    let below = current(depth);
+
+                    let blurred = blur_scissor_rect(&blur, target);
+                    if blurred.left == blurred.right || blurred.top == blurred.bottom {
+                        continue;
+                    }
+
+                    copy_backdrop(command_list, below, &scratch.backdrop);
+
+                    // The second pass reads rows above and below the rect.
+                    #[allow(clippy::cast_possible_truncation)]
+                    let radius = (3.0 * blur.sigma).ceil() as i32;
+                    let rows = RECT {
+                        top: (blurred.top - radius).max(0),
+                        bottom: (blurred.bottom + radius).min(i32::from(target.extent().height.0)),
+                        ..blurred
+                    };
+
+                    image_barrier(
+                        command_list,
+                        &scratch.target.resource,
+                        D3D12_RESOURCE_STATE_PIXEL_SHADER_RESOURCE,
+                        D3D12_RESOURCE_STATE_RENDER_TARGET,
+                    );
+
+                    unsafe {
+                        command_list.OMSetRenderTargets(
+                            1,
+                            Some(&scratch.target.descriptor),
+                            false,
+                            None,
+                        );
+                        command_list.RSSetScissorRects(&[rows]);
+                    }
+
+                    blur_shader.draw(
+                        command_list,
+                        textures.gpu_base,
+                        layer_descriptor(depth) + 1,
+                        layer_descriptor(depth) + 1,
+                        false,
+                        &blur,
+                    );
+
+                    image_barrier(
+                        command_list,
+                        &scratch.target.resource,
+                        D3D12_RESOURCE_STATE_RENDER_TARGET,
+                        D3D12_RESOURCE_STATE_PIXEL_SHADER_RESOURCE,
+                    );
+
+                    unsafe {
+                        command_list.OMSetRenderTargets(1, Some(&below.descriptor), false, None);
+                        command_list.RSSetScissorRects(&[blurred]);
+                    }
+
+                    blur_shader.draw(
+                        command_list,
+                        textures.gpu_base,
+                        layer_descriptor(depth),
+                        layer_descriptor(depth) + 1,
+                        true,
+                        &blur,
+                    );
+
+                    shader.bind(
+                        command_list,
+                        buffer_address,
+                        gradients_address,
+                        textures.gpu_base,
+                        viewport_scale,
+                        f32::from(target.extent().height),
+                    );
+
+                    unsafe { command_list.RSSetScissorRects(&[scissor]) };
+                    shader.set_round_clip(command_list, round_clip);
+                }
+            }
+        }
+
+        if target.encode_on_gpu {
+            // Like blurs, the output shader reads from a copy of the target.
+            copy_backdrop(command_list, target, &layers[0].backdrop);
+
+            let descriptor = if let Some(encode) = &target.encode_target {
+                image_barrier(
+                    command_list,
+                    &encode.resource,
+                    encode.state,
+                    D3D12_RESOURCE_STATE_RENDER_TARGET,
+                );
+                encode.descriptor
+            } else {
+                target.descriptor
+            };
+
+            unsafe {
+                command_list.OMSetRenderTargets(1, Some(&descriptor), false, None);
+                command_list.RSSetScissorRects(&[RECT {
+                    left: 0,
+                    top: 0,
+                    right: i32::from(target.extent().width.0),
+                    bottom: i32::from(target.extent().height.0),
+                }]);
+            }
+
+            output_shader.draw(
+                command_list,
+                textures.gpu_base,
+                layer_descriptor(0) + 1,
+                &target.output,
+            );
+
+            if let Some(encode) = &target.encode_target {
+                image_barrier(
+                    command_list,
+                    &encode.resource,
+                    D3D12_RESOURCE_STATE_RENDER_TARGET,
+                    encode.state,
+                );
             }
         }
     }
 }
+
+/// Copies a render target into a texture that shaders can read from while
+/// drawing to the target.
+fn copy_backdrop(
+    command_list: &ID3D12GraphicsCommandList,
+    target: &RenderTarget,
+    backdrop: &ID3D12Resource,
+) {
+    image_barrier(
+        command_list,
+        &target.resource,
+        D3D12_RESOURCE_STATE_RENDER_TARGET,
+        D3D12_RESOURCE_STATE_COPY_SOURCE,
+    );
+    image_barrier(
+        command_list,
+        backdrop,
+        D3D12_RESOURCE_STATE_PIXEL_SHADER_RESOURCE,
+        D3D12_RESOURCE_STATE_COPY_DEST,
+    );
+
+    unsafe { command_list.CopyResource(backdrop, &target.resource) };
+
+    image_barrier(
+        command_list,
+        backdrop,
+        D3D12_RESOURCE_STATE_COPY_DEST,
+        D3D12_RESOURCE_STATE_PIXEL_SHADER_RESOURCE,
+    );
+    image_barrier(
+        command_list,
+        &target.resource,
+        D3D12_RESOURCE_STATE_COPY_SOURCE,
+        D3D12_RESOURCE_STATE_RENDER_TARGET,
+    );
+}
@@ -7,20 +7,25 @@ use std::mem::ManuallyDrop;
 
 use windows::Win32::Graphics::{
     Direct3D12::{
-        ID3D12GraphicsCommandList, ID3D12Resource, D3D12_CPU_DESCRIPTOR_HANDLE,
-        D3D12_RESOURCE_BARRIER, D3D12_RESOURCE_BARRIER_0, D3D12_RESOURCE_BARRIER_ALL_SUBRESOURCES,
-        D3D12_RESOURCE_BARRIER_FLAG_NONE, D3D12_RESOURCE_BARRIER_TYPE_TRANSITION,
-        D3D12_RESOURCE_STATES, D3D12_RESOURCE_TRANSITION_BARRIER,
+        ID3D12DescriptorHeap, ID3D12GraphicsCommandList, ID3D12Resource,
+        D3D12_CPU_DESCRIPTOR_HANDLE, D3D12_RESOURCE_BARRIER, D3D12_RESOURCE_BARRIER_0,
+        D3D12_RESOURCE_BARRIER_ALL_SUBRESOURCES, D3D12_RESOURCE_BARRIER_FLAG_NONE,
+        D3D12_RESOURCE_BARRIER_TYPE_TRANSITION, D3D12_RESOURCE_STATES,
+        D3D12_RESOURCE_TRANSITION_BARRIER,
     },
     Dxgi::Common::{
         DXGI_FORMAT, DXGI_FORMAT_B8G8R8A8_UNORM, DXGI_FORMAT_B8G8R8A8_UNORM_SRGB,
-        DXGI_FORMAT_R8G8B8A8_UNORM, DXGI_FORMAT_R8G8B8A8_UNORM_SRGB, DXGI_FORMAT_R8_UNORM,
+        DXGI_FORMAT_R16G16B16A16_FLOAT, DXGI_FORMAT_R8G8B8A8_UNORM,
+        DXGI_FORMAT_R8G8B8A8_UNORM_SRGB, DXGI_FORMAT_R8_UNORM,
     },
 };
 
 use crate::{
     geometry::{Extent, Texel},
-    graphics::{Format, Layout},
+    graphics::{
+        hdr::{output_format, ColorOutput},
+        Format, ImageInfo, Layout,
+    },
 };
 
 pub use self::device::Device;
@@ -34,12 +39,41 @@ pub struct RenderTarget {
     pub state: D3D12_RESOURCE_STATES,
     pub resource: ID3D12Resource,
     pub descriptor: D3D12_CPU_DESCRIPTOR_HANDLE,
+    /// Owns the descriptor for offscreen targets. Swapchain images borrow
+    /// theirs from the swapchain.
+    pub rtv_heap: Option<ID3D12DescriptorHeap>,
+    /// The layout and format that pixels are converted to on readback.
+    pub layout: Layout,
+    pub format: Format,
+    pub output: ColorOutput,
+    /// Pixels are encoded for output by a final pass on the GPU, rather than
+    /// on readback. This is needed for swapchain images, which are shown
+    /// without being read back.
+    pub encode_on_gpu: bool,
+    /// The image that the output pass writes to, if not the target itself.
+    /// PQ output is encoded into a 10-bit image that cannot be drawn to
+    /// directly.
+    pub encode_target: Option<EncodeTarget>,
+}
+
+pub struct EncodeTarget {
+    pub resource: ID3D12Resource,
+    pub descriptor: D3D12_CPU_DESCRIPTOR_HANDLE,
+    pub state: D3D12_RESOURCE_STATES,
 }
 
 impl RenderTarget {
     pub fn extent(&self) -> Extent<Texel> {
         self.size
     }
+
+    pub fn info(&self) -> ImageInfo {
+        ImageInfo {
+            extent: self.size,
+            layout: self.layout,
+            format: output_format(self.format, &self.output),
+        }
+    }
 }
 
 pub fn image_barrier(
@@ -72,7 +106,9 @@ fn to_dxgi_format(layout: Layout, format: Format) -> DXGI_FORMAT {
         (_, Format::Unkown) => panic!("Unknown format"),
         (Layout::Rgba8, Format::Srgb) => DXGI_FORMAT_R8G8B8A8_UNORM_SRGB,
         (Layout::Rgba8, Format::Linear) => DXGI_FORMAT_R8G8B8A8_UNORM,
-        (Layout::Rgba8Vector, Format::Srgb) => DXGI_FORMAT_R8G8B8A8_UNORM_SRGB,
+        (Layout::Rgba8Vector, Format::Srgb) => {
+            panic!("Rgba8Vector is not supported in SRGB format")
+        }
         (Layout::Rgba8Vector, Format::Linear) => DXGI_FORMAT_R8G8B8A8_UNORM,
         (Layout::Bgra8, Format::Srgb) => DXGI_FORMAT_B8G8R8A8_UNORM_SRGB,
         (Layout::Bgra8, Format::Linear) => DXGI_FORMAT_B8G8R8A8_UNORM,
@@ -82,5 +118,9 @@ fn to_dxgi_format(layout: Layout, format: Format) -> DXGI_FORMAT {
             panic!("Alpha8Vector is not supported in SRGB format")
         }
         (Layout::Alpha8Vector, Format::Linear) => DXGI_FORMAT_R8_UNORM,
+        (Layout::Rgba16Float, Format::Srgb) => {
+            panic!("Rgba16Float is not supported in SRGB format")
+        }
+        (Layout::Rgba16Float, Format::Linear) => DXGI_FORMAT_R16G16B16A16_FLOAT,
     }
 }
@@ -1,20 +1,29 @@
 use windows::Win32::{
     Foundation::{FALSE, TRUE},
     Graphics::{
-        Direct3D::D3D_PRIMITIVE_TOPOLOGY_TRIANGLESTRIP,
+        Direct3D::{D3D_PRIMITIVE_TOPOLOGY_TRIANGLELIST, D3D_PRIMITIVE_TOPOLOGY_TRIANGLESTRIP},
         Direct3D12::{
-            ID3D12Device, ID3D12GraphicsCommandList, ID3D12PipelineState, ID3D12Resource,
-            ID3D12RootSignature, D3D12_BLEND_DESC, D3D12_BLEND_INV_SRC_ALPHA, D3D12_BLEND_ONE,
-            D3D12_BLEND_OP_ADD, D3D12_COLOR_WRITE_ENABLE_ALL, D3D12_CULL_MODE_BACK,
-            D3D12_DEPTH_STENCIL_DESC, D3D12_FILL_MODE_SOLID, D3D12_GPU_DESCRIPTOR_HANDLE,
-            D3D12_GRAPHICS_PIPELINE_STATE_DESC, D3D12_INPUT_ELEMENT_DESC, D3D12_INPUT_LAYOUT_DESC,
-            D3D12_LOGIC_OP_NOOP, D3D12_PRIMITIVE_TOPOLOGY_TYPE_TRIANGLE, D3D12_RASTERIZER_DESC,
+            ID3D12Device, ID3D12GraphicsCommandList, ID3D12PipelineState, ID3D12RootSignature,
+            D3D12_BLEND_DESC, D3D12_BLEND_INV_SRC_ALPHA, D3D12_BLEND_ONE, D3D12_BLEND_OP_ADD,
+            D3D12_BLEND_ZERO, D3D12_COLOR_WRITE_ENABLE_ALL, D3D12_CULL_MODE_BACK,
+            D3D12_CULL_MODE_NONE, D3D12_DEPTH_STENCIL_DESC, D3D12_FILL_MODE_SOLID,
+            D3D12_GPU_DESCRIPTOR_HANDLE, D3D12_GRAPHICS_PIPELINE_STATE_DESC,
+            D3D12_INPUT_ELEMENT_DESC, D3D12_INPUT_LAYOUT_DESC, D3D12_LOGIC_OP_NOOP,
+            D3D12_PRIMITIVE_TOPOLOGY_TYPE_TRIANGLE, D3D12_RASTERIZER_DESC,
             D3D12_RENDER_TARGET_BLEND_DESC, D3D12_SHADER_BYTECODE,
         },
-        Dxgi::Common::{DXGI_FORMAT_R16G16B16A16_FLOAT, DXGI_FORMAT_UNKNOWN, DXGI_SAMPLE_DESC},
+        Dxgi::Common::{
+            DXGI_FORMAT, DXGI_FORMAT_R10G10B10A2_UNORM, DXGI_FORMAT_R16G16B16A16_FLOAT,
+            DXGI_FORMAT_UNKNOWN, DXGI_SAMPLE_DESC,
+        },
     },
 };
 
+use crate::graphics::{
+    draw_list::{BackdropBlur, Layer},
+    hdr::{ColorOutput, ColorSpace, ToneMapping},
+};
+
 pub struct RectShader {
     pub root_signature: ID3D12RootSignature,
     pub pipeline_state: ID3D12PipelineState,
@@ -39,7 +48,7 @@ impl RectShader {
                 DestBlend: D3D12_BLEND_INV_SRC_ALPHA,
                 BlendOp: D3D12_BLEND_OP_ADD,
                 SrcBlendAlpha: D3D12_BLEND_ONE,
-                DestBlendAlpha: D3D12_BLEND_ONE,
+                DestBlendAlpha: D3D12_BLEND_INV_SRC_ALPHA,
                 BlendOpAlpha: D3D12_BLEND_OP_ADD,
                 LogicOp: D3D12_LOGIC_OP_NOOP,
                 RenderTargetWriteMask: u8::try_from(D3D12_COLOR_WRITE_ENABLE_ALL.0).unwrap(),
@@ -102,7 +111,8 @@ impl RectShader {
     pub fn bind(
         &self,
         command_list: &ID3D12GraphicsCommandList,
-        rects: &ID3D12Resource,
+        rects: u64,
+        gradients: u64,
         texture_descriptors: D3D12_GPU_DESCRIPTOR_HANDLE,
         viewport_scale: [f32; 2],
         viewport_height: f32,
@@ -118,9 +128,354 @@ impl RectShader {
                     .cast(),
                 0,
             );
-            command_list.SetGraphicsRootShaderResourceView(1, rects.GetGPUVirtualAddress());
             command_list.SetGraphicsRootDescriptorTable(2, texture_descriptors);
+            command_list.SetGraphicsRootShaderResourceView(3, gradients);
             command_list.IASetPrimitiveTopology(D3D_PRIMITIVE_TOPOLOGY_TRIANGLESTRIP);
         }
+
+        self.set_rects(command_list, rects);
+        self.set_round_clip(command_list, None);
+    }
+
+    /// Binds the rects to draw, starting at `address`.
+    ///
+    /// `SV_InstanceID` does not include the start instance of a draw, so each
+    /// batch rebinds the rect buffer at its first rect instead.
+    pub fn set_rects(&self, command_list: &ID3D12GraphicsCommandList, address: u64) {
+        unsafe { command_list.SetGraphicsRootShaderResourceView(1, address) };
+    }
+
+    /// Sets the rounded rect that drawing is clipped to, if any.
+    pub fn set_round_clip(
+        &self,
+        command_list: &ID3D12GraphicsCommandList,
+        clip: Option<([f32; 4], [f32; 4])>,
+    ) {
+        let (has_clip, [x, y, w, h], [tl, tr, br, bl]) = match clip {
+            Some((xywh, radii)) => (1u32, xywh, radii),
+            None => (0, [0.0; 4], [0.0; 4]),
+        };
+
+        let constants = [
+            has_clip,
+            x.to_bits(),
+            y.to_bits(),
+            w.to_bits(),
+            h.to_bits(),
+            tl.to_bits(),
+            tr.to_bits(),
+            br.to_bits(),
+            bl.to_bits(),
+        ];
+
+        unsafe {
+            command_list.SetGraphicsRoot32BitConstants(
+                0,
+                u32::try_from(constants.len()).unwrap(),
+                constants.as_ptr().cast(),
+                3,
+            );
+        }
+    }
+}
+
+/// Composites a layer onto the target beneath it. See `composite_shader.hlsl`.
+pub struct CompositeShader {
+    pub root_signature: ID3D12RootSignature,
+    pub pipeline_state: ID3D12PipelineState,
+}
+
+impl CompositeShader {
+    pub fn new(device: &ID3D12Device) -> Self {
+        const VS: &[u8] = include_bytes!(concat!(env!("OUT_DIR"), "/composite_vs.cso"));
+        const PS: &[u8] = include_bytes!(concat!(env!("OUT_DIR"), "/composite_ps.cso"));
+
+        let (root_signature, pipeline_state) = create_fullscreen_pipeline(device, VS, PS);
+
+        Self {
+            root_signature,
+            pipeline_state,
+        }
+    }
+
+    /// Composites the layer whose descriptor is `layer_index` onto the bound
+    /// render target. The descriptor after it must be a copy of the target.
+    pub fn draw(
+        &self,
+        command_list: &ID3D12GraphicsCommandList,
+        texture_descriptors: D3D12_GPU_DESCRIPTOR_HANDLE,
+        layer_index: u32,
+        layer: &Layer,
+    ) {
+        let (has_clip, [x, y, w, h], [tl, tr, br, bl]) = match layer.clip.rounded {
+            Some((rect, radii)) => (1u32, rect.to_xywh().map(|v| v.0), radii),
+            None => (0, [0.0; 4], [0.0; 4]),
+        };
+
+        let constants = [
+            layer_index,
+            layer.blend_mode as u32,
+            layer.opacity.to_bits(),
+            has_clip,
+            x.to_bits(),
+            y.to_bits(),
+            w.to_bits(),
+            h.to_bits(),
+            tl.to_bits(),
+            tr.to_bits(),
+            br.to_bits(),
+            bl.to_bits(),
+        ];
+
+        unsafe {
+            command_list.SetPipelineState(&self.pipeline_state);
+            command_list.SetGraphicsRootSignature(&self.root_signature);
+            command_list.SetGraphicsRoot32BitConstants(
+                0,
+                u32::try_from(constants.len()).unwrap(),
+                constants.as_ptr().cast(),
+                0,
+            );
+            command_list.SetGraphicsRootDescriptorTable(1, texture_descriptors);
+            command_list.IASetPrimitiveTopology(D3D_PRIMITIVE_TOPOLOGY_TRIANGLELIST);
+            command_list.DrawInstanced(3, 1, 0, 0);
+        }
+    }
+}
+
+/// Creates the pipeline for a shader that draws a single triangle over the
+/// whole target, with blending disabled. Such shaders blend with the pixels
+/// beneath them themselves, by reading a copy of the target.
+fn create_fullscreen_pipeline(
+    device: &ID3D12Device,
+    vs: &[u8],
+    ps: &[u8],
+) -> (ID3D12RootSignature, ID3D12PipelineState) {
+    let root_signature: ID3D12RootSignature = unsafe { device.CreateRootSignature(0, vs) }.unwrap();
+    let pipeline_state = create_fullscreen_pipeline_state(
+        device,
+        &root_signature,
+        vs,
+        ps,
+        DXGI_FORMAT_R16G16B16A16_FLOAT,
+    );
+
+    (root_signature, pipeline_state)
+}
+
+fn create_fullscreen_pipeline_state(
+    device: &ID3D12Device,
+    root_signature: &ID3D12RootSignature,
+    vs: &[u8],
+    ps: &[u8],
+    format: DXGI_FORMAT,
+) -> ID3D12PipelineState {
+    let mut blend_targets: [_; 8] = Default::default();
+    blend_targets[0] = D3D12_RENDER_TARGET_BLEND_DESC {
+        BlendEnable: FALSE,
+        LogicOpEnable: FALSE,
+        SrcBlend: D3D12_BLEND_ONE,
+        DestBlend: D3D12_BLEND_ZERO,
+        BlendOp: D3D12_BLEND_OP_ADD,
+        SrcBlendAlpha: D3D12_BLEND_ONE,
+        DestBlendAlpha: D3D12_BLEND_ZERO,
+        BlendOpAlpha: D3D12_BLEND_OP_ADD,
+        LogicOp: D3D12_LOGIC_OP_NOOP,
+        RenderTargetWriteMask: u8::try_from(D3D12_COLOR_WRITE_ENABLE_ALL.0).unwrap(),
+    };
+
+    let mut rtv_formats = [DXGI_FORMAT_UNKNOWN; 8];
+    rtv_formats[0] = format;
+
+    let desc = D3D12_GRAPHICS_PIPELINE_STATE_DESC {
+        pRootSignature: unsafe { std::mem::transmute_copy(root_signature) },
+        VS: D3D12_SHADER_BYTECODE {
+            pShaderBytecode: vs.as_ptr().cast(),
+            BytecodeLength: vs.len(),
+        },
+        PS: D3D12_SHADER_BYTECODE {
+            pShaderBytecode: ps.as_ptr().cast(),
+            BytecodeLength: ps.len(),
+        },
+        BlendState: D3D12_BLEND_DESC {
+            AlphaToCoverageEnable: FALSE,
+            IndependentBlendEnable: FALSE,
+            RenderTarget: blend_targets,
+        },
+        SampleMask: u32::MAX,
+        RasterizerState: D3D12_RASTERIZER_DESC {
+            FillMode: D3D12_FILL_MODE_SOLID,
+            CullMode: D3D12_CULL_MODE_NONE,
+            ..Default::default()
+        },
+        DepthStencilState: D3D12_DEPTH_STENCIL_DESC {
+            DepthEnable: FALSE,
+            StencilEnable: FALSE,
+            ..Default::default()
+        },
+        PrimitiveTopologyType: D3D12_PRIMITIVE_TOPOLOGY_TYPE_TRIANGLE,
+        NumRenderTargets: 1,
+        RTVFormats: rtv_formats,
+        DSVFormat: DXGI_FORMAT_UNKNOWN,
+        SampleDesc: DXGI_SAMPLE_DESC {
+            Count: 1,
+            Quality: 0,
+        },
+        ..Default::default()
+    };
+
+    unsafe { device.CreateGraphicsPipelineState(&desc) }.unwrap()
+}
+
+/// Blurs what has been drawn beneath a rounded rect. See `blur_shader.hlsl`.
+pub struct BlurShader {
+    pub root_signature: ID3D12RootSignature,
+    pub pipeline_state: ID3D12PipelineState,
+}
+
+impl BlurShader {
+    pub fn new(device: &ID3D12Device) -> Self {
+        const VS: &[u8] = include_bytes!(concat!(env!("OUT_DIR"), "/blur_vs.cso"));
+        const PS: &[u8] = include_bytes!(concat!(env!("OUT_DIR"), "/blur_ps.cso"));
+
+        let (root_signature, pipeline_state) = create_fullscreen_pipeline(device, VS, PS);
+
+        Self {
+            root_signature,
+            pipeline_state,
+        }
+    }
+
+    /// Blurs the texture whose descriptor is `source_index` into the bound
+    /// render target, along rows if `vertical` is false. The vertical pass
+    /// draws the blur over the texture whose descriptor is `backdrop_index`.
+    pub fn draw(
+        &self,
+        command_list: &ID3D12GraphicsCommandList,
+        texture_descriptors: D3D12_GPU_DESCRIPTOR_HANDLE,
+        source_index: u32,
+        backdrop_index: u32,
+        vertical: bool,
+        blur: &BackdropBlur,
+    ) {
+        let [x, y, w, h] = blur.rect.to_xywh().map(|v| v.0);
+        let [r0, r1, r2, r3] = blur.radii;
+        let (has_clip, [cx, cy, cw, ch], [c0, c1, c2, c3]) = match blur.clip.rounded {
+            Some((rect, radii)) => (1u32, rect.to_xywh().map(|v| v.0), radii),
+            None => (0, [0.0; 4], [0.0; 4]),
+        };
+
+        let constants = [
+            source_index,
+            backdrop_index,
+            blur.sigma.to_bits(),
+            u32::from(vertical),
+            x.to_bits(),
+            y.to_bits(),
+            w.to_bits(),
+            h.to_bits(),
+            r0.to_bits(),
+            r1.to_bits(),
+            r2.to_bits(),
+            r3.to_bits(),
+            has_clip,
+            cx.to_bits(),
+            cy.to_bits(),
+            cw.to_bits(),
+            ch.to_bits(),
+            c0.to_bits(),
+            c1.to_bits(),
+            c2.to_bits(),
+            c3.to_bits(),
+        ];
+
+        unsafe {
+            command_list.SetPipelineState(&self.pipeline_state);
+            command_list.SetGraphicsRootSignature(&self.root_signature);
+            command_list.SetGraphicsRoot32BitConstants(
+                0,
+                u32::try_from(constants.len()).unwrap(),
+                constants.as_ptr().cast(),
+                0,
+            );
+            command_list.SetGraphicsRootDescriptorTable(1, texture_descriptors);
+            command_list.IASetPrimitiveTopology(D3D_PRIMITIVE_TOPOLOGY_TRIANGLELIST);
+            command_list.DrawInstanced(3, 1, 0, 0);
+        }
+    }
+}
+
+/// Encodes the pixels of a swapchain image for output. See
+/// `output_shader.hlsl`.
+pub struct OutputShader {
+    pub root_signature: ID3D12RootSignature,
+    pub pipeline_state: ID3D12PipelineState,
+    /// Writes PQ output to a 10-bit target.
+    pub pq_pipeline_state: ID3D12PipelineState,
+}
+
+impl OutputShader {
+    pub fn new(device: &ID3D12Device) -> Self {
+        const VS: &[u8] = include_bytes!(concat!(env!("OUT_DIR"), "/output_vs.cso"));
+        const PS: &[u8] = include_bytes!(concat!(env!("OUT_DIR"), "/output_ps.cso"));
+
+        let (root_signature, pipeline_state) = create_fullscreen_pipeline(device, VS, PS);
+        let pq_pipeline_state = create_fullscreen_pipeline_state(
+            device,
+            &root_signature,
+            VS,
+            PS,
+            DXGI_FORMAT_R10G10B10A2_UNORM,
+        );
+
+        Self {
+            root_signature,
+            pipeline_state,
+            pq_pipeline_state,
+        }
+    }
+
+    /// Encodes the texture whose descriptor is `source_index` into the bound
+    /// render target, which must be 10-bit for PQ output and 16-bit float
+    /// otherwise.
+    pub fn draw(
+        &self,
+        command_list: &ID3D12GraphicsCommandList,
+        texture_descriptors: D3D12_GPU_DESCRIPTOR_HANDLE,
+        source_index: u32,
+        output: &ColorOutput,
+    ) {
+        let (tone_mapping, white_point) = match output.tone_mapping {
+            ToneMapping::Clip => (0u32, 0.0f32),
+            ToneMapping::Reinhard { white_point } => (1, white_point),
+        };
+
+        let constants = [
+            source_index,
+            output.color_space as u32,
+            tone_mapping,
+            white_point.to_bits(),
+            output.sdr_white_level.to_bits(),
+            output.max_luminance.to_bits(),
+        ];
+
+        let pipeline_state = match output.color_space {
+            ColorSpace::Srgb | ColorSpace::ScRgb => &self.pipeline_state,
+            ColorSpace::Pq => &self.pq_pipeline_state,
+        };
+
+        unsafe {
+            command_list.SetPipelineState(pipeline_state);
+            command_list.SetGraphicsRootSignature(&self.root_signature);
+            command_list.SetGraphicsRoot32BitConstants(
+                0,
+                u32::try_from(constants.len()).unwrap(),
+                constants.as_ptr().cast(),
+                0,
+            );
+            command_list.SetGraphicsRootDescriptorTable(1, texture_descriptors);
+            command_list.IASetPrimitiveTopology(D3D_PRIMITIVE_TOPOLOGY_TRIANGLELIST);
+            command_list.DrawInstanced(3, 1, 0, 0);
+        }
     }
 }
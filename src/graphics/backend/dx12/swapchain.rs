@@ -1,6 +1,13 @@
 use windows::{
     core::Interface,
     Win32::{
+        Devices::Display::{
+            DisplayConfigGetDeviceInfo, GetDisplayConfigBufferSizes, QueryDisplayConfig,
+            DISPLAYCONFIG_DEVICE_INFO_GET_SDR_WHITE_LEVEL,
+            DISPLAYCONFIG_DEVICE_INFO_GET_SOURCE_NAME, DISPLAYCONFIG_DEVICE_INFO_HEADER,
+            DISPLAYCONFIG_MODE_INFO, DISPLAYCONFIG_PATH_INFO, DISPLAYCONFIG_SDR_WHITE_LEVEL,
+            DISPLAYCONFIG_SOURCE_DEVICE_NAME, QDC_ONLY_ACTIVE_PATHS,
+        },
         Foundation::{HANDLE, HWND, RECT},
         Graphics::{
             Direct3D12::{
@@ -11,13 +18,18 @@ use windows::{
             DirectComposition::{IDCompositionTarget, IDCompositionVisual},
             Dxgi::{
                 Common::{
-                    DXGI_ALPHA_MODE_IGNORE, DXGI_FORMAT_R16G16B16A16_FLOAT, DXGI_FORMAT_UNKNOWN,
-                    DXGI_SAMPLE_DESC,
+                    DXGI_ALPHA_MODE_IGNORE, DXGI_COLOR_SPACE_RGB_FULL_G10_NONE_P709,
+                    DXGI_COLOR_SPACE_RGB_FULL_G2084_NONE_P2020, DXGI_FORMAT,
+                    DXGI_FORMAT_R10G10B10A2_UNORM, DXGI_FORMAT_R16G16B16A16_FLOAT,
+                    DXGI_FORMAT_UNKNOWN, DXGI_SAMPLE_DESC,
                 },
-                IDXGISwapChain3, DXGI_FRAME_STATISTICS, DXGI_RGBA, DXGI_SCALING_STRETCH,
-                DXGI_SWAP_CHAIN_DESC1, DXGI_SWAP_CHAIN_FLAG_FRAME_LATENCY_WAITABLE_OBJECT,
+                CreateDXGIFactory1, IDXGIFactory1, IDXGIOutput6, IDXGISwapChain3,
+                DXGI_FRAME_STATISTICS, DXGI_OUTPUT_DESC1, DXGI_RGBA, DXGI_SCALING_STRETCH,
+                DXGI_SWAP_CHAIN_COLOR_SPACE_SUPPORT_FLAG_PRESENT, DXGI_SWAP_CHAIN_DESC1,
+                DXGI_SWAP_CHAIN_FLAG_FRAME_LATENCY_WAITABLE_OBJECT,
                 DXGI_SWAP_EFFECT_FLIP_SEQUENTIAL, DXGI_USAGE_RENDER_TARGET_OUTPUT,
             },
+            Gdi::{MonitorFromWindow, HMONITOR, MONITOR_DEFAULTTONEAREST},
         },
         System::Threading::WaitForSingleObjectEx,
         UI::WindowsAndMessaging::GetClientRect,
@@ -26,12 +38,15 @@ use windows::{
 
 use crate::{
     geometry::{Extent, Wixel},
-    graphics::{FrameInfo, RenderTarget as BRenderTarget},
+    graphics::{
+        hdr::SCRGB_WHITE_LEVEL, ColorOutput, ColorSpace, DisplayInfo, Format, FrameInfo, Layout,
+        RenderTarget as BRenderTarget, ToneMapping,
+    },
     limits,
     time::{FramesPerSecond, PresentPeriod, PresentTime},
 };
 
-use super::{device::Device, RenderTarget};
+use super::{device::Device, EncodeTarget, RenderTarget};
 
 pub struct SwapchainImage<'a, 'b> {
     swapchain: &'a mut Swapchain<'b>,
@@ -68,6 +83,7 @@ impl SwapchainImage<'_, '_> {
 pub struct Swapchain<'a> {
     device: &'a Device,
 
+    hwnd: HWND,
     handle: IDXGISwapChain3,
     #[allow(dead_code)]
     target: IDCompositionTarget,
@@ -81,6 +97,14 @@ pub struct Swapchain<'a> {
 
     size: Extent<Wixel>,
 
+    color_space: ColorSpace,
+    tone_mapping: ToneMapping,
+    /// Drawn to in place of the swapchain's images for PQ output. The output
+    /// pass encodes it into the 10-bit images.
+    pq_target: Option<RenderTarget>,
+    /// The display that the window was last shown on, and its capabilities.
+    display: Option<(HMONITOR, DisplayInfo)>,
+
     composition_rate: FramesPerSecond,
     target_frame_rate: Option<FramesPerSecond>,
 
@@ -148,6 +172,8 @@ impl<'device> Swapchain<'device> {
                     .unwrap();
             }
 
+            set_dxgi_color_space(&swapchain, ColorSpace::default());
+
             unsafe { visual.SetContent(&swapchain) }.unwrap();
             unsafe { device.compositor.Commit() }.unwrap();
 
@@ -174,11 +200,16 @@ impl<'device> Swapchain<'device> {
 
         Self {
             device,
+            hwnd,
             handle: swapchain,
             target,
             visual,
             waitable_object: latency_event,
             size: Extent::default(),
+            color_space: ColorSpace::default(),
+            tone_mapping: ToneMapping::default(),
+            pq_target: None,
+            display: None,
             rtv_heap: swapchain_rtv_heap,
             rtv: swapchain_rtv,
             target_frame_rate: None,
@@ -196,7 +227,76 @@ impl<'device> Swapchain<'device> {
         self.size = size;
     }
 
-    pub fn frame_info(&self) -> FrameInfo {
+    /// Sets how images are encoded for display. sRGB images are shown at the
+    /// display's SDR white level.
+    ///
+    /// sRGB and scRGB output is presented from 16-bit float images. PQ
+    /// output is drawn to a 16-bit float target, and then encoded into
+    /// 10-bit images.
+    pub fn set_color_output(&mut self, color_space: ColorSpace, tone_mapping: ToneMapping) {
+        let format = swapchain_format(color_space);
+
+        if format != swapchain_format(self.color_space) {
+            let mut desc = DXGI_SWAP_CHAIN_DESC1::default();
+            unsafe { self.handle.GetDesc1(&mut desc) }.unwrap();
+
+            self.device.idle();
+            self.pq_target = None;
+
+            unsafe {
+                self.handle.ResizeBuffers(
+                    0,
+                    desc.Width,
+                    desc.Height,
+                    format,
+                    DXGI_SWAP_CHAIN_FLAG_FRAME_LATENCY_WAITABLE_OBJECT.0 as _,
+                )
+            }
+            .unwrap();
+        }
+
+        set_dxgi_color_space(&self.handle, color_space);
+
+        self.color_space = color_space;
+        self.tone_mapping = tone_mapping;
+    }
+
+    /// Checks whether the window has moved to a different display, whose
+    /// capabilities must be queried again.
+    pub fn window_moved(&mut self) {
+        let monitor = unsafe { MonitorFromWindow(self.hwnd, MONITOR_DEFAULTTONEAREST) };
+
+        if self.display.is_some_and(|(current, _)| current != monitor) {
+            self.display = None;
+        }
+    }
+
+    /// Queries the display's capabilities again before the next image, after
+    /// the display settings have changed.
+    pub fn display_changed(&mut self) {
+        self.display = None;
+    }
+
+    /// The capabilities of the display that the window is shown on.
+    ///
+    /// These are slow to query, so they are kept until the window moves to a
+    /// different display or the display settings change.
+    pub fn display_info(&mut self) -> DisplayInfo {
+        if let Some((_, info)) = self.display {
+            return info;
+        }
+
+        let monitor = unsafe { MonitorFromWindow(self.hwnd, MONITOR_DEFAULTTONEAREST) };
+        let Some(desc) = output_desc(monitor) else {
+            return DisplayInfo::default();
+        };
+
+        let info = display_info(&desc);
+        self.display = Some((monitor, info));
+        info
+    }
+
+    pub fn frame_info(&mut self) -> FrameInfo {
         let prev_present_time = {
             let mut stats = DXGI_FRAME_STATISTICS::default();
             unsafe { self.handle.GetFrameStatistics(&mut stats) }
@@ -225,6 +325,7 @@ impl<'device> Swapchain<'device> {
             next_present_time,
             prev_present_time,
             prev_target_present_time: PresentTime::default(),
+            display: self.display_info(),
         }
     }
 
@@ -242,15 +343,68 @@ impl<'device> Swapchain<'device> {
                 .CreateRenderTargetView(&image, None, self.rtv);
         };
 
-        let rt = RenderTarget {
-            draw: None,
-            size: self.size.cast(),
-            state: D3D12_RESOURCE_STATE_PRESENT,
-            resource: image,
-            descriptor: self.rtv,
+        let fi = self.frame_info();
+
+        let output = ColorOutput::for_display(ColorSpace::ScRgb, self.tone_mapping, &fi.display);
+        let output = match self.color_space {
+            ColorSpace::Srgb => ColorOutput {
+                max_luminance: output.sdr_white_level,
+                ..output
+            },
+            ColorSpace::ScRgb => output,
+            ColorSpace::Pq => ColorOutput {
+                color_space: ColorSpace::Pq,
+                ..output
+            },
         };
 
-        let fi = self.frame_info();
+        let size = self.size.cast();
+
+        let rt = if self.color_space == ColorSpace::Pq {
+            // The swapchain is idle whenever its size changes, so the old
+            // target can be dropped.
+            if !matches!(&self.pq_target, Some(target) if target.size == size) {
+                self.pq_target = Some(self.device.create_render_target(
+                    size,
+                    Layout::Rgba8,
+                    Format::Linear,
+                ));
+            }
+
+            let target = self.pq_target.as_ref().unwrap();
+
+            RenderTarget {
+                draw: None,
+                size,
+                state: target.state,
+                resource: target.resource.clone(),
+                descriptor: target.descriptor,
+                rtv_heap: None,
+                layout: Layout::Rgba8,
+                format: Format::Srgb,
+                output,
+                encode_on_gpu: true,
+                encode_target: Some(EncodeTarget {
+                    resource: image,
+                    descriptor: self.rtv,
+                    state: D3D12_RESOURCE_STATE_PRESENT,
+                }),
+            }
+        } else {
+            RenderTarget {
+                draw: None,
+                size,
+                state: D3D12_RESOURCE_STATE_PRESENT,
+                resource: image,
+                descriptor: self.rtv,
+                rtv_heap: None,
+                layout: Layout::Rgba8,
+                format: Format::Srgb,
+                output,
+                encode_on_gpu: true,
+                encode_target: None,
+            }
+        };
 
         SwapchainImage {
             swapchain: self,
@@ -264,6 +418,131 @@ impl<'device> Swapchain<'device> {
     }
 }
 
+/// The format of the swapchain's images. sRGB output is presented in scRGB,
+/// which can show both SDR and HDR colors, and is the only HDR color space
+/// that 16-bit float swapchains support. PQ needs 10-bit images.
+fn swapchain_format(color_space: ColorSpace) -> DXGI_FORMAT {
+    match color_space {
+        ColorSpace::Srgb | ColorSpace::ScRgb => DXGI_FORMAT_R16G16B16A16_FLOAT,
+        ColorSpace::Pq => DXGI_FORMAT_R10G10B10A2_UNORM,
+    }
+}
+
+/// Tells the compositor how the swapchain's images are encoded.
+fn set_dxgi_color_space(swapchain: &IDXGISwapChain3, color_space: ColorSpace) {
+    let dxgi_color_space = match color_space {
+        ColorSpace::Srgb | ColorSpace::ScRgb => DXGI_COLOR_SPACE_RGB_FULL_G10_NONE_P709,
+        ColorSpace::Pq => DXGI_COLOR_SPACE_RGB_FULL_G2084_NONE_P2020,
+    };
+
+    let support = unsafe { swapchain.CheckColorSpaceSupport(dxgi_color_space) }.unwrap_or(0);
+
+    if support & DXGI_SWAP_CHAIN_COLOR_SPACE_SUPPORT_FLAG_PRESENT.0 as u32 != 0 {
+        unsafe { swapchain.SetColorSpace1(dxgi_color_space) }.unwrap();
+    }
+}
+
+/// Describes the output that shows a monitor.
+///
+/// A new factory is created for each query, since a factory keeps describing
+/// outputs as they were when it was created.
+fn output_desc(monitor: HMONITOR) -> Option<DXGI_OUTPUT_DESC1> {
+    let factory: IDXGIFactory1 = unsafe { CreateDXGIFactory1() }.ok()?;
+
+    (0..)
+        .map_while(|index| unsafe { factory.EnumAdapters1(index) }.ok())
+        .flat_map(|adapter| {
+            (0..).map_while(move |index| unsafe { adapter.EnumOutputs(index) }.ok())
+        })
+        .find_map(|output| {
+            let output = output.cast::<IDXGIOutput6>().ok()?;
+
+            let mut desc = DXGI_OUTPUT_DESC1::default();
+            unsafe { output.GetDesc1(&mut desc) }.ok()?;
+            (desc.Monitor == monitor).then_some(desc)
+        })
+}
+
+fn display_info(desc: &DXGI_OUTPUT_DESC1) -> DisplayInfo {
+    let hdr_enabled = desc.ColorSpace == DXGI_COLOR_SPACE_RGB_FULL_G2084_NONE_P2020;
+
+    // SDR displays show scRGB's 1.0 as white.
+    let sdr_white_level = if hdr_enabled {
+        sdr_white_level(&desc.DeviceName).unwrap_or(SCRGB_WHITE_LEVEL)
+    } else {
+        SCRGB_WHITE_LEVEL
+    };
+
+    DisplayInfo {
+        hdr_enabled,
+        sdr_white_level,
+        min_luminance: desc.MinLuminance,
+        max_luminance: desc.MaxLuminance,
+        max_full_frame_luminance: desc.MaxFullFrameLuminance,
+    }
+}
+
+/// Finds the brightness that the user has chosen for SDR content on the
+/// display with the given GDI device name, in nits.
+fn sdr_white_level(device_name: &[u16; 32]) -> Option<f32> {
+    let (mut num_paths, mut num_modes) = (0, 0);
+    unsafe { GetDisplayConfigBufferSizes(QDC_ONLY_ACTIVE_PATHS, &mut num_paths, &mut num_modes) }
+        .ok()?;
+
+    let mut paths = vec![DISPLAYCONFIG_PATH_INFO::default(); num_paths as usize];
+    let mut modes = vec![DISPLAYCONFIG_MODE_INFO::default(); num_modes as usize];
+
+    unsafe {
+        QueryDisplayConfig(
+            QDC_ONLY_ACTIVE_PATHS,
+            &mut num_paths,
+            paths.as_mut_ptr(),
+            &mut num_modes,
+            modes.as_mut_ptr(),
+            None,
+        )
+    }
+    .ok()?;
+
+    paths.truncate(num_paths as usize);
+
+    paths.iter().find_map(|path| {
+        let mut source = DISPLAYCONFIG_SOURCE_DEVICE_NAME {
+            header: DISPLAYCONFIG_DEVICE_INFO_HEADER {
+                r#type: DISPLAYCONFIG_DEVICE_INFO_GET_SOURCE_NAME,
+                size: size_of::<DISPLAYCONFIG_SOURCE_DEVICE_NAME>() as u32,
+                adapterId: path.sourceInfo.adapterId,
+                id: path.sourceInfo.id,
+            },
+            ..Default::default()
+        };
+
+        if unsafe { DisplayConfigGetDeviceInfo(&mut source.header) } != 0
+            || &source.viewGdiDeviceName != device_name
+        {
+            return None;
+        }
+
+        let mut white_level = DISPLAYCONFIG_SDR_WHITE_LEVEL {
+            header: DISPLAYCONFIG_DEVICE_INFO_HEADER {
+                r#type: DISPLAYCONFIG_DEVICE_INFO_GET_SDR_WHITE_LEVEL,
+                size: size_of::<DISPLAYCONFIG_SDR_WHITE_LEVEL>() as u32,
+                adapterId: path.targetInfo.adapterId,
+                id: path.targetInfo.id,
+            },
+            ..Default::default()
+        };
+
+        if unsafe { DisplayConfigGetDeviceInfo(&mut white_level.header) } != 0 {
+            return None;
+        }
+
+        // The level is given in thousandths of scRGB's white level.
+        #[allow(clippy::cast_precision_loss)]
+        Some(white_level.SDRWhiteLevel as f32 / 1000.0 * SCRGB_WHITE_LEVEL)
+    })
+}
+
 impl Drop for Swapchain<'_> {
     fn drop(&mut self) {
         // technically, wait for the most recent present to complete
@@ -1,14 +1,24 @@
+#[cfg(target_os = "windows")]
 use std::ops::{Deref, DerefMut};
 
+#[cfg(target_os = "windows")]
+use crate::geometry::Wixel;
 use crate::{
     core::static_slot_map::new_key_type,
-    geometry::{Extent, Point, Texel, Wixel},
+    geometry::{Extent, Point, Texel},
 };
 
-use super::{Backend, DrawList, Format, FrameInfo, GraphicsConfig, Layout, RasterBuf};
+#[cfg(target_os = "windows")]
+use super::FrameInfo;
+use super::{
+    Backend, ColorOutput, DrawList, Format, GraphicsConfig, ImageInfo, Layout, Raster, RasterBuf,
+};
+#[cfg(target_os = "windows")]
+use super::{ColorSpace, ToneMapping};
 
 #[cfg(target_os = "windows")]
 pub mod dx12;
+pub mod software;
 
 #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
 pub struct SubmitId(pub(crate) u64);
@@ -18,6 +28,8 @@ new_key_type!(TextureId);
 #[allow(clippy::large_enum_variant)]
 pub enum Device {
     Null,
+    Software(software::Device),
+    #[cfg(target_os = "windows")]
     Dx12(dx12::Device),
 }
 
@@ -25,8 +37,11 @@ impl Device {
     pub fn new(config: &GraphicsConfig) -> Self {
         match config.backend {
             Backend::Null => Device::Null,
+            Backend::Software => Device::Software(software::Device::new()),
             #[cfg(target_os = "windows")]
             Backend::Auto | Backend::Dx12 => Device::Dx12(dx12::Device::new(config)),
+            #[cfg(not(target_os = "windows"))]
+            Backend::Auto => Device::Software(software::Device::new()),
         }
     }
 
@@ -34,10 +49,33 @@ impl Device {
     pub fn create_swapchain(&self, hwnd: windows::Win32::Foundation::HWND) -> Swapchain {
         match self {
             Self::Null => Swapchain::Null,
+            Self::Software(device) => Swapchain::Software(device.create_swapchain()),
             Self::Dx12(device) => Swapchain::Dx12(device.create_swapchain(hwnd)),
         }
     }
 
+    pub fn create_render_target(
+        &self,
+        extent: Extent<Texel>,
+        layout: Layout,
+        format: Format,
+    ) -> RenderTarget {
+        match self {
+            Self::Null => RenderTarget::Null(ImageInfo {
+                extent,
+                layout,
+                format,
+            }),
+            Self::Software(device) => {
+                RenderTarget::Software(device.create_render_target(extent, layout, format))
+            }
+            #[cfg(target_os = "windows")]
+            Self::Dx12(device) => {
+                RenderTarget::Dx12(device.create_render_target(extent, layout, format))
+            }
+        }
+    }
+
     pub fn create_texture(
         &self,
         extent: Extent<Texel>,
@@ -46,10 +84,21 @@ impl Device {
     ) -> TextureId {
         match self {
             Self::Null => TextureId::new(0, 0),
+            Self::Software(device) => device.create_texture(extent, layout, format),
+            #[cfg(target_os = "windows")]
             Self::Dx12(device) => device.create_texture(extent, layout, format),
         }
     }
 
+    pub fn destroy_texture(&self, texture: TextureId) {
+        match self {
+            Self::Null => {}
+            Self::Software(device) => device.destroy_texture(texture),
+            #[cfg(target_os = "windows")]
+            Self::Dx12(device) => device.destroy_texture(texture),
+        }
+    }
+
     pub fn copy_raster_to_texture(
         &self,
         target: TextureId,
@@ -58,90 +107,166 @@ impl Device {
     ) {
         match self {
             Self::Null => {}
+            Self::Software(device) => device.copy_raster_to_texture(target, pixels, origin),
+            #[cfg(target_os = "windows")]
             Self::Dx12(device) => device.copy_raster_to_texture(target, pixels, origin),
         }
     }
 
     pub fn flush_upload_buffer(&self) {
         match self {
-            Self::Null => {}
+            Self::Null | Self::Software(_) => {}
+            #[cfg(target_os = "windows")]
             Self::Dx12(device) => device.flush_upload_buffer(),
         }
     }
 
-    pub fn draw(&self, draw_list: &DrawList, target: &mut RenderTarget) {
+    pub fn read_pixels(&self, target: &RenderTarget) -> Raster {
+        match (self, target) {
+            (Self::Null, RenderTarget::Null(info)) => Raster::new(
+                *info,
+                vec![0; info.row_size() * usize::try_from(info.extent.height.0).unwrap()],
+            ),
+            (Self::Software(device), RenderTarget::Software(target)) => device.read_pixels(target),
+            #[cfg(target_os = "windows")]
+            (Self::Dx12(device), RenderTarget::Dx12(target)) => device.read_pixels(target),
+            _ => panic!("Mismatched device and render target backends"),
+        }
+    }
+
+    /// Returns `true` if the submission has finished executing.
+    pub fn is_done(&self, submit: SubmitId) -> bool {
+        #[cfg(not(target_os = "windows"))]
+        let _ = submit;
+
+        match self {
+            Self::Null | Self::Software(_) => true,
+            #[cfg(target_os = "windows")]
+            Self::Dx12(device) => device.queue.is_done(submit),
+        }
+    }
+
+    pub fn draw(&self, draw_list: &DrawList, target: &mut RenderTarget) -> SubmitId {
         match (self, target) {
-            (Self::Null, _) => {}
+            (Self::Null, _) => SubmitId(0),
+            (Self::Software(device), RenderTarget::Software(target)) => {
+                device.draw(draw_list, target)
+            }
+            #[cfg(target_os = "windows")]
             (Self::Dx12(device), RenderTarget::Dx12(target)) => device.draw(draw_list, target),
             _ => panic!("Mismatched device and render target backends"),
         }
     }
 }
 
+#[cfg(target_os = "windows")]
 pub enum Swapchain<'device> {
     Null,
+    Software(software::Swapchain),
     Dx12(dx12::Swapchain<'device>),
 }
 
+#[cfg(target_os = "windows")]
 impl<'device> Swapchain<'device> {
     pub fn resize(&mut self, extent: Extent<Wixel>) {
         match self {
             Self::Null => {}
+            Self::Software(context) => context.resize(extent),
             Self::Dx12(context) => context.resize(extent),
         }
     }
 
+    pub fn set_color_output(&mut self, color_space: ColorSpace, tone_mapping: ToneMapping) {
+        match self {
+            Self::Null => {}
+            Self::Software(context) => context.set_color_output(color_space, tone_mapping),
+            Self::Dx12(context) => context.set_color_output(color_space, tone_mapping),
+        }
+    }
+
+    /// Checks whether the window has moved to a different display, whose
+    /// capabilities must be queried again.
+    pub fn window_moved(&mut self) {
+        match self {
+            Self::Null | Self::Software(_) => {}
+            Self::Dx12(context) => context.window_moved(),
+        }
+    }
+
+    /// Queries the display's capabilities again before the next image, after
+    /// the display settings have changed.
+    pub fn display_changed(&mut self) {
+        match self {
+            Self::Null | Self::Software(_) => {}
+            Self::Dx12(context) => context.display_changed(),
+        }
+    }
+
     pub fn next_image<'this>(&'this mut self) -> SwapchainImage<'this, 'device> {
         match self {
-            Self::Null => SwapchainImage::Null(RenderTarget::Null),
+            Self::Null => SwapchainImage::Null(RenderTarget::Null(ImageInfo {
+                extent: Extent::ZERO,
+                layout: Layout::Rgba8,
+                format: Format::Srgb,
+            })),
+            Self::Software(context) => SwapchainImage::Software(context.next_image()),
             Self::Dx12(context) => SwapchainImage::Dx12(context.next_image()),
         }
     }
 }
 
+#[cfg(target_os = "windows")]
 pub enum SwapchainImage<'a, 'b> {
     Null(RenderTarget),
+    Software(&'a mut RenderTarget),
     Dx12(dx12::SwapchainImage<'a, 'b>),
 }
 
+#[cfg(target_os = "windows")]
 impl SwapchainImage<'_, '_> {
     pub fn frame_info(&self) -> FrameInfo {
         match self {
-            Self::Null(_) => FrameInfo::default(),
+            Self::Null(_) | Self::Software(_) => FrameInfo::default(),
             Self::Dx12(image) => image.frame_info(),
         }
     }
 
     pub fn present(self) {
         match self {
-            Self::Null(_) => {}
+            Self::Null(_) | Self::Software(_) => {}
             Self::Dx12(image) => image.present(),
         }
     }
 }
 
+#[cfg(target_os = "windows")]
 impl Deref for SwapchainImage<'_, '_> {
     type Target = RenderTarget;
 
     fn deref(&self) -> &Self::Target {
         match self {
             Self::Null(target) => target,
+            Self::Software(target) => target,
             Self::Dx12(image) => image.render_target(),
         }
     }
 }
 
+#[cfg(target_os = "windows")]
 impl DerefMut for SwapchainImage<'_, '_> {
     fn deref_mut(&mut self) -> &mut Self::Target {
         match self {
             Self::Null(target) => target,
+            Self::Software(target) => target,
             Self::Dx12(image) => image.render_target_mut(),
         }
     }
 }
 
 pub enum RenderTarget {
-    Null,
+    Null(ImageInfo),
+    Software(software::RenderTarget),
+    #[cfg(target_os = "windows")]
     Dx12(dx12::RenderTarget),
 }
 
@@ -149,8 +274,32 @@ impl RenderTarget {
     #[must_use]
     pub fn extent(&self) -> Extent<Texel> {
         match self {
-            Self::Null => Extent::new(0, 0),
+            Self::Null(info) => info.extent,
+            Self::Software(target) => target.extent(),
+            #[cfg(target_os = "windows")]
             Self::Dx12(target) => target.extent(),
         }
     }
+
+    /// How the pixels of the render target are encoded for display.
+    #[must_use]
+    pub fn color_output(&self) -> ColorOutput {
+        match self {
+            Self::Null(_) => ColorOutput::default(),
+            Self::Software(target) => target.color_output(),
+            #[cfg(target_os = "windows")]
+            Self::Dx12(target) => target.output,
+        }
+    }
+
+    /// Sets how the pixels of the render target are encoded when they are
+    /// read back.
+    pub fn set_color_output(&mut self, output: ColorOutput) {
+        match self {
+            Self::Null(_) => {}
+            Self::Software(target) => target.set_color_output(output),
+            #[cfg(target_os = "windows")]
+            Self::Dx12(target) => target.output = output,
+        }
+    }
 }
@@ -0,0 +1,1072 @@
+//! A CPU rasterizer that executes draw lists without a GPU.
+//!
+//! The output is intended to match the dx12 backend: render targets hold
+//! linear RGBA floats, rects are rasterized at pixel centers, and colors are
+//! blended the same way as the rect shader's pipeline state.
+
+use std::cell::RefCell;
+
+#[cfg(target_os = "windows")]
+use crate::{
+    geometry::Wixel,
+    graphics::{ColorSpace, DisplayInfo, RenderTarget as BRenderTarget, ToneMapping},
+};
+use crate::{
+    geometry::{Affine, Extent, Point, Rect, Texel},
+    graphics::{
+        backend::{SubmitId, TextureId},
+        color::srgb_to_linear,
+        draw_list::{
+            BackdropBlur, BlendMode, Clip, Command, ImageKind, Layer, RRect, Sampler,
+            TextureFilter, NO_GRADIENT,
+        },
+        gradient::PackedGradient,
+        hdr::{encode_output, output_format, ColorOutput},
+        image::f16_to_f32,
+        mipmap::{mip_level_region, mip_levels},
+        sdf::{median, SDF_RANGE},
+        DrawList, Format, ImageInfo, Layout, Raster, RasterBuf,
+    },
+    limits::GFX_TEXTURE_COUNT,
+};
+
+pub struct Device {
+    textures: RefCell<Vec<Texture>>,
+    /// The indices of destroyed textures, which are reused by new textures.
+    free_textures: RefCell<Vec<u32>>,
+}
+
+impl Device {
+    pub fn new() -> Self {
+        Self {
+            textures: RefCell::new(Vec::new()),
+            free_textures: RefCell::new(Vec::new()),
+        }
+    }
+
+    #[cfg(target_os = "windows")]
+    pub fn create_swapchain(&self) -> Swapchain {
+        Swapchain::new()
+    }
+
+    pub fn create_render_target(
+        &self,
+        extent: Extent<Texel>,
+        layout: Layout,
+        format: Format,
+    ) -> RenderTarget {
+        RenderTarget::new(extent, layout, format)
+    }
+
+    pub fn create_texture(
+        &self,
+        extent: Extent<Texel>,
+        layout: Layout,
+        format: Format,
+    ) -> TextureId {
+        assert!(format != Format::Unkown, "Unknown format");
+
+        let mut textures = self.textures.borrow_mut();
+        let mut free_textures = self.free_textures.borrow_mut();
+        GFX_TEXTURE_COUNT.check(textures.len() - free_textures.len() + 1);
+
+        let size = texel_count(extent) * layout.bytes_per_pixel();
+
+        let texture = Texture {
+            extent,
+            layout,
+            format,
+            data: vec![0; size],
+        };
+
+        let index = if let Some(index) = free_textures.pop() {
+            textures[index as usize] = texture;
+            index
+        } else {
+            textures.push(texture);
+            u32::try_from(textures.len() - 1).unwrap()
+        };
+
+        TextureId::new(index, 0)
+    }
+
+    pub fn destroy_texture(&self, texture: TextureId) {
+        let mut textures = self.textures.borrow_mut();
+        let texture_ref = &mut textures[texture.index() as usize];
+
+        // Release the memory now; the slot is reused by the next texture.
+        texture_ref.data = Vec::new();
+        texture_ref.extent = Extent::ZERO;
+
+        self.free_textures.borrow_mut().push(texture.index());
+    }
+
+    pub fn copy_raster_to_texture(
+        &self,
+        target: TextureId,
+        pixels: &RasterBuf,
+        origin: Point<Texel>,
+    ) {
+        let mut textures = self.textures.borrow_mut();
+        let texture = &mut textures[target.index() as usize];
+
+        assert_eq!(
+            pixels.info().layout,
+            texture.layout,
+            "Pixel layout does not match the texture."
+        );
+
+        let row_size = pixels.row_size();
+        let texture_row_size = texture.row_size();
+
+        let x = usize::try_from(origin.x.0).unwrap();
+        let y = usize::try_from(origin.y.0).unwrap();
+        let offset = y * texture_row_size + x * texture.layout.bytes_per_pixel();
+
+        for (i, row) in pixels.data().chunks_exact(row_size).enumerate() {
+            let start = offset + i * texture_row_size;
+            texture.data[start..start + row_size].copy_from_slice(row);
+        }
+    }
+
+    pub fn read_pixels(&self, target: &RenderTarget) -> Raster {
+        let pixels: Vec<_> = target
+            .pixels
+            .iter()
+            .map(|&pixel| encode_output(pixel, &target.output))
+            .collect();
+
+        Raster::from_linear_rgba(target.info(), &pixels)
+    }
+
+    /// Drawing completes before this returns, so the returned submission is
+    /// always done.
+    pub fn draw(&self, draw_list: &DrawList, target: &mut RenderTarget) -> SubmitId {
+        let textures = self.textures.borrow();
+
+        let mut it = draw_list.iter();
+
+        assert_eq!(it.next(), Some(Command::Begin(draw_list.areas[0])));
+
+        let mut clip = Clip {
+            bounds: draw_list.areas[0],
+            rounded: None,
+        };
+
+        // The pixels beneath each layer that is being drawn, which the layer
+        // is composited onto when it is popped.
+        let mut layers = Vec::new();
+
+        let mut rect_start = 0;
+        for command in it.by_ref() {
+            match command {
+                Command::Begin(_) => unreachable!(),
+                Command::Close => break,
+                Command::Clear(color) => target.clear(color.to_array_f32()),
+                Command::Clip(new_clip) => clip = new_clip,
+                Command::PushLayer(layer) => {
+                    let transparent = vec![[0.0; 4]; target.pixels.len()];
+                    layers.push((layer, std::mem::replace(&mut target.pixels, transparent)));
+                }
+                Command::PopLayer => {
+                    let (layer, below) = layers.pop().unwrap();
+                    let pixels = std::mem::replace(&mut target.pixels, below);
+                    target.composite(&pixels, &layer);
+                }
+                Command::BackdropBlur(blur) => target.backdrop_blur(&blur),
+                Command::Rects(count) => {
+                    let count = count as usize;
+                    for rect in &draw_list.prims[rect_start..rect_start + count] {
+                        target.draw_rect(rect, &clip, &textures, &draw_list.gradients);
+                    }
+                    rect_start += count;
+                }
+            }
+        }
+
+        SubmitId(0)
+    }
+}
+
+/// A render target backed by system memory.
+///
+/// Pixels are stored as linear, premultiplied RGBA in the same way as the
+/// `R16G16B16A16_FLOAT` targets used by the dx12 backend. The layout, format
+/// and color output only apply when the pixels are read back.
+pub struct RenderTarget {
+    size: Extent<Texel>,
+    layout: Layout,
+    format: Format,
+    output: ColorOutput,
+    pixels: Vec<[f32; 4]>,
+}
+
+impl RenderTarget {
+    pub fn new(size: Extent<Texel>, layout: Layout, format: Format) -> Self {
+        Self {
+            size,
+            layout,
+            format,
+            output: ColorOutput::default(),
+            pixels: vec![[0.0; 4]; texel_count(size)],
+        }
+    }
+
+    pub fn extent(&self) -> Extent<Texel> {
+        self.size
+    }
+
+    pub fn info(&self) -> ImageInfo {
+        ImageInfo {
+            extent: self.size,
+            layout: self.layout,
+            format: output_format(self.format, &self.output),
+        }
+    }
+
+    pub fn color_output(&self) -> ColorOutput {
+        self.output
+    }
+
+    pub fn set_color_output(&mut self, output: ColorOutput) {
+        self.output = output;
+    }
+
+    pub fn pixels(&self) -> &[[f32; 4]] {
+        &self.pixels
+    }
+
+    fn clear(&mut self, color: [f32; 4]) {
+        self.pixels.fill(color);
+    }
+
+    fn draw_rect(
+        &mut self,
+        rect: &RRect,
+        clip: &Clip,
+        textures: &[Texture],
+        gradients: &[PackedGradient],
+    ) {
+        let [_, _, w, h] = rect.xywh;
+
+        if w <= 0.0 || h <= 0.0 {
+            return;
+        }
+
+        let transform = Affine::new(rect.transform);
+        let Some(inverse) = transform.inverse() else {
+            return;
+        };
+
+        // The number of pixels per unit of the rect's coordinates, used to
+        // keep anti-aliased edges a pixel wide.
+        let scale = transform.determinant().abs().sqrt();
+
+        if rect.blur_sigma > 0.0 {
+            self.draw_blurred_rect(rect, clip, &transform, scale);
+            return;
+        }
+
+        let width = usize::try_from(self.size.width.0).unwrap();
+
+        // The fill is drawn inside the border, and the border is drawn
+        // between the inner and outer shapes.
+        let (outer, outer_radii) = offset_round_rect(rect.xywh, rect.radii, rect.border_outset);
+        let (inner, inner_radii) = offset_round_rect(rect.xywh, rect.radii, -rect.border_inset);
+
+        // Include the pixels whose centers are within half a pixel of the
+        // edge, since they are partially covered.
+        let (xs, ys) = self.pixel_range(&transform, outer, 1.0 / scale, clip);
+
+        let texture = &textures[rect.texture_id as usize];
+        let region = texture.region(rect.uvwh);
+        let [ix, iy, iw, ih] = rect.image_xywh;
+        let lod = mip_lod(rect, region, scale);
+
+        let gradient =
+            (rect.gradient_id != NO_GRADIENT).then(|| &gradients[rect.gradient_id as usize]);
+
+        for py in ys {
+            let row = py * width;
+
+            for px in xs.clone() {
+                let (cx, cy) = (px as f32 + 0.5, py as f32 + 0.5);
+                let (lx, ly) = inverse.apply(cx, cy);
+
+                let clip_coverage = clip_coverage(clip, [cx, cy]);
+                let outer_coverage = round_rect_coverage([lx, ly], outer, outer_radii, scale);
+
+                if outer_coverage == 0.0 || clip_coverage == 0.0 {
+                    continue;
+                }
+
+                let inner_coverage = round_rect_coverage([lx, ly], inner, inner_radii, scale);
+                let border_coverage = outer_coverage - inner_coverage;
+
+                let t = [(lx - ix) / iw, (ly - iy) / ih];
+                let texel = texture.sample(region, t, lod, rect.sampler);
+                let texel = if rect.sampler.kind == ImageKind::Vector {
+                    [vector_coverage(texel, rect, region, scale); 4]
+                } else {
+                    texel
+                };
+
+                let fill = gradient.map_or(rect.color, |gradient| {
+                    let color = gradient.color_at(lx, ly);
+                    std::array::from_fn(|i| color[i] * rect.color[i])
+                });
+
+                let src = std::array::from_fn(|i| {
+                    (fill[i] * texel[i] * inner_coverage + rect.border_color[i] * border_coverage)
+                        * clip_coverage
+                });
+
+                blend(&mut self.pixels[row + px], src);
+            }
+        }
+    }
+
+    /// Composites the pixels of a layer onto the target within the layer's
+    /// clip.
+    ///
+    /// Must be kept in sync with `ps_main` in `composite_shader.hlsl`.
+    #[allow(clippy::cast_precision_loss)]
+    fn composite(&mut self, layer_pixels: &[[f32; 4]], layer: &Layer) {
+        let width = usize::try_from(self.size.width.0).unwrap();
+        let bounds = layer.clip.bounds.to_xywh().map(|v| v.0);
+        let (xs, ys) = self.pixel_range(&Affine::IDENTITY, bounds, 0.0, &layer.clip);
+
+        for py in ys {
+            for px in xs.clone() {
+                let i = py * width + px;
+                let coverage = clip_coverage(&layer.clip, [px as f32 + 0.5, py as f32 + 0.5]);
+
+                let src = layer_pixels[i].map(|c| c * layer.opacity);
+                let dst = self.pixels[i];
+                let out = composite(src, dst, layer.blend_mode);
+
+                self.pixels[i] = std::array::from_fn(|c| dst[c] + (out[c] - dst[c]) * coverage);
+            }
+        }
+    }
+
+    /// Blurs the pixels beneath a rounded rect with a separable Gaussian.
+    /// Pixels past the edges of the target repeat the edge.
+    ///
+    /// Must be kept in sync with `ps_main` in `blur_shader.hlsl`.
+    #[allow(
+        clippy::cast_possible_truncation,
+        clippy::cast_possible_wrap,
+        clippy::cast_precision_loss,
+        clippy::cast_sign_loss
+    )]
+    fn backdrop_blur(&mut self, blur: &BackdropBlur) {
+        let width = usize::try_from(self.size.width.0).unwrap();
+        let height = usize::try_from(self.size.height.0).unwrap();
+
+        let xywh = blur.rect.to_xywh().map(|v| v.0);
+        let (xs, ys) = self.pixel_range(&Affine::IDENTITY, xywh, 0.0, &blur.clip);
+
+        if xs.is_empty() || ys.is_empty() {
+            return;
+        }
+
+        let radius = (3.0 * blur.sigma).ceil() as usize;
+        let weights: Vec<f32> = (0..=2 * radius)
+            .map(|i| {
+                let d = i as f32 - radius as f32;
+                (-(d * d) / (2.0 * blur.sigma * blur.sigma)).exp()
+            })
+            .collect();
+        let total: f32 = weights.iter().sum();
+
+        let blur_along = |load: &dyn Fn(usize) -> [f32; 4], i: usize, len: usize| {
+            let mut sum = [0.0; 4];
+            for (k, w) in weights.iter().enumerate() {
+                let j = (i + k).saturating_sub(radius).min(len - 1);
+                let texel = load(j);
+                for c in 0..4 {
+                    sum[c] += texel[c] * w;
+                }
+            }
+            sum.map(|c| c / total)
+        };
+
+        // The rows that the vertical pass reads from.
+        let rows = ys.start.saturating_sub(radius)..(ys.end + radius).min(height);
+
+        let horizontal: Vec<[f32; 4]> = rows
+            .clone()
+            .flat_map(|y| xs.clone().map(move |x| (x, y)))
+            .map(|(x, y)| blur_along(&|j| self.pixels[y * width + j], x, width))
+            .collect();
+
+        let columns = xs.len();
+        for y in ys {
+            for x in xs.clone() {
+                let load = |j: usize| horizontal[(j - rows.start) * columns + x - xs.start];
+                let blurred = blur_along(&load, y, height);
+
+                let p = [x as f32 + 0.5, y as f32 + 0.5];
+                let coverage =
+                    round_rect_coverage(p, xywh, blur.radii, 1.0) * clip_coverage(&blur.clip, p);
+
+                let dst = &mut self.pixels[y * width + x];
+                *dst = std::array::from_fn(|c| dst[c] + (blurred[c] - dst[c]) * coverage);
+            }
+        }
+    }
+
+    fn draw_blurred_rect(&mut self, rect: &RRect, clip: &Clip, transform: &Affine, scale: f32) {
+        let width = usize::try_from(self.size.width.0).unwrap();
+        let inverse = transform.inverse().unwrap();
+
+        // The blur is negligible beyond three standard deviations.
+        let margin = 3.0 * rect.blur_sigma + 1.0 / scale;
+        let (xs, ys) = self.pixel_range(transform, rect.xywh, margin, clip);
+
+        for py in ys {
+            let row = py * width;
+
+            for px in xs.clone() {
+                let (cx, cy) = (px as f32 + 0.5, py as f32 + 0.5);
+                let (lx, ly) = inverse.apply(cx, cy);
+
+                let coverage = round_rect_shadow([lx, ly], rect.xywh, rect.radii, rect.blur_sigma)
+                    * clip_coverage(clip, [cx, cy]);
+
+                if coverage <= 0.0 {
+                    continue;
+                }
+
+                let src = rect.color.map(|c| c * coverage);
+                blend(&mut self.pixels[row + px], src);
+            }
+        }
+    }
+
+    /// The pixels that may be touched by drawing within `xywh` grown by
+    /// `margin`, after transforming it to the target. These are limited to
+    /// the clip's bounds and the target.
+    ///
+    /// A pixel is inside the clip's bounds if its center is, matching the
+    /// scissor rect used by the dx12 backend.
+    #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+    fn pixel_range(
+        &self,
+        transform: &Affine,
+        xywh: [f32; 4],
+        margin: f32,
+        clip: &Clip,
+    ) -> (std::ops::Range<usize>, std::ops::Range<usize>) {
+        let [x, y, w, h] = xywh;
+        let bounds = transform.transform_rect(Rect::new(
+            (x - margin, y - margin),
+            (w + 2.0 * margin, h + 2.0 * margin),
+        ));
+
+        let [x, y, w, h] = bounds.to_xywh().map(|v| v.0);
+        let [cx, cy, cw, ch] = clip.bounds.to_xywh().map(|v| v.0);
+
+        let range = |min: f32, max: f32, clip_min: f32, clip_max: f32, limit: i16| {
+            let limit = f32::from(limit);
+            let start = min.floor().max((clip_min - 0.5).ceil()).clamp(0.0, limit);
+            let end = max.ceil().min((clip_max - 0.5).ceil()).clamp(0.0, limit);
+            start as usize..end.max(start) as usize
+        };
+
+        (
+            range(x, x + w, cx, cx + cw, self.size.width.0),
+            range(y, y + h, cy, cy + ch, self.size.height.0),
+        )
+    }
+}
+
+/// A swapchain that renders into system memory.
+///
+/// Nothing is presented to the window, but draws are executed in full.
+#[cfg(target_os = "windows")]
+pub struct Swapchain {
+    target: BRenderTarget,
+    output: ColorOutput,
+}
+
+#[cfg(target_os = "windows")]
+impl Swapchain {
+    fn new() -> Self {
+        Self {
+            target: BRenderTarget::Software(RenderTarget::new(
+                Extent::<Wixel>::default().cast(),
+                Layout::Rgba8,
+                Format::Srgb,
+            )),
+            output: ColorOutput::default(),
+        }
+    }
+
+    pub fn resize(&mut self, extent: Extent<Wixel>) {
+        self.target = BRenderTarget::Software(RenderTarget::new(
+            extent.cast(),
+            Layout::Rgba8,
+            Format::Srgb,
+        ));
+        self.target.set_color_output(self.output);
+    }
+
+    /// Sets how images are encoded for display. There is no display, so
+    /// colors are encoded as if for an SDR one.
+    pub fn set_color_output(&mut self, color_space: ColorSpace, tone_mapping: ToneMapping) {
+        self.output = ColorOutput::for_display(color_space, tone_mapping, &DisplayInfo::default());
+        self.target.set_color_output(self.output);
+    }
+
+    pub fn next_image(&mut self) -> &mut BRenderTarget {
+        &mut self.target
+    }
+}
+
+struct Texture {
+    extent: Extent<Texel>,
+    layout: Layout,
+    format: Format,
+    data: Vec<u8>,
+}
+
+impl Texture {
+    fn row_size(&self) -> usize {
+        usize::try_from(self.extent.width.0).unwrap() * self.layout.bytes_per_pixel()
+    }
+
+    /// The texels covered by `uvwh`, as `[x, y, width, height]`.
+    #[allow(clippy::cast_possible_truncation)]
+    fn region(&self, uvwh: [f32; 4]) -> [i32; 4] {
+        let width = f32::from(self.extent.width);
+        let height = f32::from(self.extent.height);
+
+        [
+            (uvwh[0] * width).round() as i32,
+            (uvwh[1] * height).round() as i32,
+            (uvwh[2] * width).round() as i32,
+            (uvwh[3] * height).round() as i32,
+        ]
+    }
+
+    /// Samples the texels in `region` at `t`, where `[0, 0]` and `[1, 1]` are
+    /// the corners of the region. Coordinates are wrapped within the region.
+    ///
+    /// Mipmapped images are sampled from the levels nearest to `lod`.
+    ///
+    /// Must be kept in sync with `sample_texture` in `rect_shader.hlsl`.
+    #[allow(
+        clippy::cast_possible_truncation,
+        clippy::cast_precision_loss,
+        clippy::cast_sign_loss
+    )]
+    fn sample(&self, region: [i32; 4], t: [f32; 2], lod: f32, sampler: Sampler) -> [f32; 4] {
+        match sampler.filter {
+            TextureFilter::Point => {
+                let [_, _, width, height] = region;
+                let x = (t[0] * width as f32).floor() as i32;
+                let y = (t[1] * height as f32).floor() as i32;
+                self.load_wrapped(region, x, y, sampler)
+            }
+            TextureFilter::Mipmapped if sampler.kind == ImageKind::Mipmapped => {
+                let levels = mip_levels(region[2], region[3]);
+                let lod = lod.clamp(0.0, (levels - 1) as f32);
+                let level = lod.floor() as u32;
+
+                let a = self.sample_bilinear(mip_level_region(region, level), t, sampler);
+                if level + 1 == levels {
+                    return a;
+                }
+
+                let b = self.sample_bilinear(mip_level_region(region, level + 1), t, sampler);
+                let f = lod - level as f32;
+                std::array::from_fn(|i| a[i] + (b[i] - a[i]) * f)
+            }
+            TextureFilter::Linear | TextureFilter::Mipmapped => {
+                self.sample_bilinear(region, t, sampler)
+            }
+        }
+    }
+
+    #[allow(clippy::cast_possible_truncation, clippy::cast_precision_loss)]
+    fn sample_bilinear(&self, region: [i32; 4], t: [f32; 2], sampler: Sampler) -> [f32; 4] {
+        let [_, _, width, height] = region;
+        let x = t[0] * width as f32 - 0.5;
+        let y = t[1] * height as f32 - 0.5;
+
+        let x0 = x.floor();
+        let y0 = y.floor();
+        let fx = x - x0;
+        let fy = y - y0;
+
+        let (x0, y0) = (x0 as i32, y0 as i32);
+
+        let a = self.load_wrapped(region, x0, y0, sampler);
+        let b = self.load_wrapped(region, x0 + 1, y0, sampler);
+        let c = self.load_wrapped(region, x0, y0 + 1, sampler);
+        let d = self.load_wrapped(region, x0 + 1, y0 + 1, sampler);
+
+        std::array::from_fn(|i| {
+            let top = a[i] + (b[i] - a[i]) * fx;
+            let bottom = c[i] + (d[i] - c[i]) * fx;
+            top + (bottom - top) * fy
+        })
+    }
+
+    /// Reads a texel of `region`, wrapping its coordinates within the region.
+    fn load_wrapped(&self, region: [i32; 4], x: i32, y: i32, sampler: Sampler) -> [f32; 4] {
+        let [rx, ry, width, height] = region;
+        self.load(
+            rx + sampler.wrap_x.apply(x, width),
+            ry + sampler.wrap_y.apply(y, height),
+        )
+    }
+
+    /// Reads a single texel, converting it to linear RGBA.
+    fn load(&self, x: i32, y: i32) -> [f32; 4] {
+        let x = usize::try_from(x).unwrap();
+        let y = usize::try_from(y).unwrap();
+
+        let bpp = self.layout.bytes_per_pixel();
+        let offset = y * self.row_size() + x * bpp;
+        let bytes = &self.data[offset..offset + bpp];
+
+        let unorm = |b: u8| f32::from(b) / 255.0;
+        let color = |b: u8| match self.format {
+            Format::Srgb => srgb_to_linear(unorm(b)),
+            Format::Linear | Format::Unkown => unorm(b),
+        };
+
+        // Single-channel textures hold coverage, and read as premultiplied
+        // white. Must be kept in sync with the views made in `dx12::Device`.
+        match self.layout {
+            Layout::Rgba8 | Layout::Rgba8Vector => [
+                color(bytes[0]),
+                color(bytes[1]),
+                color(bytes[2]),
+                unorm(bytes[3]),
+            ],
+            Layout::Bgra8 => [
+                color(bytes[2]),
+                color(bytes[1]),
+                color(bytes[0]),
+                unorm(bytes[3]),
+            ],
+            Layout::Alpha8 | Layout::Alpha8Vector => [unorm(bytes[0]); 4],
+            Layout::Rgba16Float => std::array::from_fn(|i| {
+                f16_to_f32(u16::from_le_bytes([bytes[2 * i], bytes[2 * i + 1]]))
+            }),
+        }
+    }
+}
+
+/// Blends with `ONE, INV_SRC_ALPHA`, as configured in `RectShader`.
+fn blend(dst: &mut [f32; 4], src: [f32; 4]) {
+    let inv_src_alpha = 1.0 - src[3];
+    dst[0] = src[0] + dst[0] * inv_src_alpha;
+    dst[1] = src[1] + dst[1] * inv_src_alpha;
+    dst[2] = src[2] + dst[2] * inv_src_alpha;
+    dst[3] = src[3] + dst[3] * inv_src_alpha;
+}
+
+/// Combines premultiplied colors with a blend mode.
+///
+/// Must be kept in sync with `composite` in `composite_shader.hlsl`.
+fn composite(src: [f32; 4], dst: [f32; 4], mode: BlendMode) -> [f32; 4] {
+    let (sa, da) = (src[3], dst[3]);
+
+    let porter_duff = |fa: f32, fb: f32| std::array::from_fn(|i| src[i] * fa + dst[i] * fb);
+
+    let blend: fn(f32, f32) -> f32 = match mode {
+        BlendMode::Clear => return [0.0; 4],
+        BlendMode::Source => return src,
+        BlendMode::Destination => return dst,
+        BlendMode::SourceOver => return porter_duff(1.0, 1.0 - sa),
+        BlendMode::DestinationOver => return porter_duff(1.0 - da, 1.0),
+        BlendMode::SourceIn => return porter_duff(da, 0.0),
+        BlendMode::DestinationIn => return porter_duff(0.0, sa),
+        BlendMode::SourceOut => return porter_duff(1.0 - da, 0.0),
+        BlendMode::DestinationOut => return porter_duff(0.0, 1.0 - sa),
+        BlendMode::SourceAtop => return porter_duff(da, 1.0 - sa),
+        BlendMode::DestinationAtop => return porter_duff(1.0 - da, sa),
+        BlendMode::Xor => return porter_duff(1.0 - da, 1.0 - sa),
+        BlendMode::Plus => return porter_duff(1.0, 1.0).map(|c| c.min(1.0)),
+        BlendMode::Multiply => |b, s| b * s,
+        BlendMode::Screen => screen,
+        BlendMode::Overlay => |b, s| hard_light(s, b),
+        BlendMode::Darken => f32::min,
+        BlendMode::Lighten => f32::max,
+        BlendMode::ColorDodge => |b, s| {
+            if b <= 0.0 {
+                0.0
+            } else if s >= 1.0 {
+                1.0
+            } else {
+                (b / (1.0 - s)).min(1.0)
+            }
+        },
+        BlendMode::ColorBurn => |b, s| {
+            if b >= 1.0 {
+                1.0
+            } else if s <= 0.0 {
+                0.0
+            } else {
+                1.0 - ((1.0 - b) / s).min(1.0)
+            }
+        },
+        BlendMode::HardLight => hard_light,
+        BlendMode::SoftLight => |b, s| {
+            if s <= 0.5 {
+                b - (1.0 - 2.0 * s) * b * (1.0 - b)
+            } else {
+                let d = if b <= 0.25 {
+                    ((16.0 * b - 12.0) * b + 4.0) * b
+                } else {
+                    b.sqrt()
+                };
+                b + (2.0 * s - 1.0) * (d - b)
+            }
+        },
+        BlendMode::Difference => |b, s| (b - s).abs(),
+        BlendMode::Exclusion => |b, s| b + s - 2.0 * b * s,
+    };
+
+    // The blend functions take unpremultiplied colors, and the result is
+    // drawn over the destination.
+    let unpremultiply = |c: f32, a: f32| if a > 0.0 { c / a } else { 0.0 };
+
+    let mut out: [f32; 4] = std::array::from_fn(|i| {
+        let blended = blend(unpremultiply(dst[i], da), unpremultiply(src[i], sa));
+        src[i] * (1.0 - da) + dst[i] * (1.0 - sa) + sa * da * blended
+    });
+    out[3] = sa + da - sa * da;
+    out
+}
+
+fn screen(b: f32, s: f32) -> f32 {
+    b + s - b * s
+}
+
+fn hard_light(b: f32, s: f32) -> f32 {
+    if s <= 0.5 {
+        b * 2.0 * s
+    } else {
+        screen(b, 2.0 * s - 1.0)
+    }
+}
+
+/// The coverage of the clip's rounded rect at `p`, or 1 if it has none.
+fn clip_coverage(clip: &Clip, p: [f32; 2]) -> f32 {
+    clip.rounded.map_or(1.0, |(rect, radii)| {
+        round_rect_coverage(p, rect.to_xywh().map(|v| v.0), radii, 1.0)
+    })
+}
+
+/// Grows a rounded rect by `amount` on every side, or shrinks it if `amount` is
+/// negative.
+///
+/// Square corners stay square when growing.
+fn offset_round_rect(xywh: [f32; 4], radii: [f32; 4], amount: f32) -> ([f32; 4], [f32; 4]) {
+    let [x, y, w, h] = xywh;
+
+    let radii = radii.map(|r| if r > 0.0 { (r + amount).max(0.0) } else { 0.0 });
+
+    (
+        [x - amount, y - amount, w + 2.0 * amount, h + 2.0 * amount],
+        radii,
+    )
+}
+
+/// The fraction of the pixel centered on `p` that is covered by a rounded rect.
+///
+/// `scale` is the number of pixels per unit of `p`'s coordinate space.
+fn round_rect_coverage(p: [f32; 2], xywh: [f32; 4], radii: [f32; 4], scale: f32) -> f32 {
+    let [x, y, w, h] = xywh;
+
+    if w <= 0.0 || h <= 0.0 {
+        return 0.0;
+    }
+
+    let half = [w / 2.0, h / 2.0];
+    let distance = round_rect_sdf([p[0] - x - half[0], p[1] - y - half[1]], half, radii);
+
+    (0.5 - distance * scale).clamp(0.0, 1.0)
+}
+
+/// The fraction of a pixel covered by the shape in a vector image, given the
+/// texel sampled for it. The distance is scaled from texels to pixels so that
+/// the edge stays a pixel wide at any scale.
+///
+/// Must be kept in sync with `vector_coverage` in `rect_shader.hlsl`.
+#[allow(clippy::cast_precision_loss)]
+fn vector_coverage(texel: [f32; 4], rect: &RRect, region: [i32; 4], scale: f32) -> f32 {
+    let distance = (median([texel[0], texel[1], texel[2]]) - 0.5) * 2.0 * SDF_RANGE;
+
+    let [_, _, width, height] = rect.image_xywh;
+    let pixels_per_texel = (width / region[2] as f32 + height / region[3] as f32) * 0.5 * scale;
+
+    (distance * pixels_per_texel + 0.5).clamp(0.0, 1.0)
+}
+
+/// The mip level to sample an image from, where each whole level halves the
+/// number of texels per pixel.
+///
+/// Must be kept in sync with `mip_lod` in `rect_shader.hlsl`.
+#[allow(clippy::cast_precision_loss)]
+fn mip_lod(rect: &RRect, region: [i32; 4], scale: f32) -> f32 {
+    let [_, _, width, height] = rect.image_xywh;
+    let texels_per_pixel =
+        (region[2] as f32 / (width * scale)).max(region[3] as f32 / (height * scale));
+
+    texels_per_pixel.log2().max(0.0)
+}
+
+/// Approximates the coverage of a rounded rect convolved with a Gaussian, as
+/// described in <https://madebyevan.com/shaders/fast-rounded-rectangle-shadows/>.
+///
+/// The blur is exact along x, and sampled along y.
+///
+/// Must be kept in sync with `round_rect_shadow` in `rect_shader.hlsl`.
+fn round_rect_shadow(p: [f32; 2], xywh: [f32; 4], radii: [f32; 4], sigma: f32) -> f32 {
+    const SAMPLES: usize = 4;
+
+    let [x, y, w, h] = xywh;
+    let half = [w / 2.0, h / 2.0];
+    let p = [p[0] - x - half[0], p[1] - y - half[1]];
+
+    let [tl, tr, br, bl] = radii;
+    let corner = match (p[0] < 0.0, p[1] < 0.0) {
+        (true, true) => tl,
+        (false, true) => tr,
+        (false, false) => br,
+        (true, false) => bl,
+    };
+
+    let low = p[1] - half[1];
+    let high = p[1] + half[1];
+    let start = (-3.0 * sigma).clamp(low, high);
+    let end = (3.0 * sigma).clamp(low, high);
+
+    let interval = (end - start) / SAMPLES as f32;
+    let mut sample_y = start + interval * 0.5;
+    let mut value = 0.0;
+
+    for _ in 0..SAMPLES {
+        value += shadow_row(p[0], p[1] - sample_y, sigma, corner, half)
+            * gaussian(sample_y, sigma)
+            * interval;
+        sample_y += interval;
+    }
+
+    value
+}
+
+/// The blurred coverage of a single row of a rounded rect.
+fn shadow_row(x: f32, y: f32, sigma: f32, corner: f32, half: [f32; 2]) -> f32 {
+    let delta = (half[1] - corner - y.abs()).min(0.0);
+    let curved = half[0] - corner + (corner * corner - delta * delta).max(0.0).sqrt();
+
+    let scale = std::f32::consts::FRAC_1_SQRT_2 / sigma;
+    let low = 0.5 + 0.5 * erf((x - curved) * scale);
+    let high = 0.5 + 0.5 * erf((x + curved) * scale);
+
+    high - low
+}
+
+fn gaussian(x: f32, sigma: f32) -> f32 {
+    const SQRT_TAU: f32 = 2.506_628_3;
+    (-(x * x) / (2.0 * sigma * sigma)).exp() / (SQRT_TAU * sigma)
+}
+
+/// An approximation of the error function with a maximum error of about
+/// 5e-4.
+fn erf(x: f32) -> f32 {
+    let a = x.abs();
+    let t = 1.0 + (0.278_393 + (0.230_389 + 0.078_108 * (a * a)) * a) * a;
+    let t2 = t * t;
+    x.signum() * (1.0 - 1.0 / (t2 * t2))
+}
+
+/// The signed distance from a point to the edge of a rounded rect centered on
+/// the origin. Negative values are inside the rect.
+///
+/// Must be kept in sync with `round_rect_sdf` in `rect_shader.hlsl`.
+fn round_rect_sdf(p: [f32; 2], half: [f32; 2], radii: [f32; 4]) -> f32 {
+    let [tl, tr, br, bl] = radii;
+
+    let r = match (p[0] < 0.0, p[1] < 0.0) {
+        (true, true) => tl,
+        (false, true) => tr,
+        (false, false) => br,
+        (true, false) => bl,
+    };
+
+    let qx = p[0].abs() - half[0] + r;
+    let qy = p[1].abs() - half[1] + r;
+
+    qx.max(qy).min(0.0) + qx.max(0.0).hypot(qy.max(0.0)) - r
+}
+
+fn texel_count(extent: Extent<Texel>) -> usize {
+    usize::try_from(extent.width.0).unwrap() * usize::try_from(extent.height.0).unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::graphics::{
+        draw_list::Canvas, text::GlyphCache, texture_atlas::TextureCache, Color, Image, RoundRect,
+    };
+
+    use super::*;
+
+    fn setup() -> (Device, RefCell<TextureCache>) {
+        let device = Device::new();
+
+        let textures = TextureCache::new(
+            Extent::new(1, 1),
+            Layout::Rgba8,
+            Format::Linear,
+            |extent, layout, format| device.create_texture(extent, layout, format),
+        );
+
+        let (_, white_pixel) = textures.default();
+        device.copy_raster_to_texture(
+            white_pixel,
+            &RasterBuf::new(
+                ImageInfo {
+                    extent: Extent::new(1, 1),
+                    layout: Layout::Rgba8,
+                    format: Format::Linear,
+                },
+                &[0xFF, 0xFF, 0xFF, 0xFF],
+            ),
+            Point::new(0, 0),
+        );
+
+        (device, RefCell::new(textures))
+    }
+
+    fn pixel(target: &RenderTarget, x: usize, y: usize) -> [f32; 4] {
+        target.pixels()[y * target.extent().width.0 as usize + x]
+    }
+
+    #[test]
+    fn clear_and_fill() {
+        let (device, textures) = setup();
+        let glyphs = RefCell::new(GlyphCache::default());
+        let mut target = RenderTarget::new(Extent::new(8, 8), Layout::Rgba8, Format::Linear);
+        let mut draw_list = DrawList::new();
+
+        {
+            let mut canvas = Canvas::new(
+                &textures,
+                &glyphs,
+                &mut draw_list,
+                Rect::new(Point::ZERO, Extent::new(8.0, 8.0)),
+            );
+            canvas.clear(Color::BLACK);
+            canvas.draw_rect(&RoundRect::new((2.0, 2.0, 4.0, 4.0)));
+            canvas.draw_rect(&RoundRect::new((4.0, 4.0, 4.0, 4.0)).with_color(Color {
+                r: 0.0,
+                g: 0.0,
+                b: 0.5,
+                a: 0.5,
+            }));
+            canvas.finish();
+        }
+
+        device.draw(&draw_list, &mut target);
+
+        assert_eq!(pixel(&target, 0, 0), [0.0, 0.0, 0.0, 1.0]);
+        assert_eq!(pixel(&target, 2, 2), [1.0, 1.0, 1.0, 1.0]);
+        assert_eq!(pixel(&target, 5, 5), [0.5, 0.5, 1.0, 1.0]);
+        assert_eq!(pixel(&target, 7, 7), [0.0, 0.0, 0.5, 1.0]);
+        assert_eq!(pixel(&target, 6, 1), [0.0, 0.0, 0.0, 1.0]);
+    }
+
+    #[test]
+    fn sample_image() {
+        let (device, mut textures) = setup();
+        let glyphs = RefCell::new(GlyphCache::default());
+        let mut target = RenderTarget::new(Extent::new(4, 1), Layout::Rgba8, Format::Linear);
+        let mut draw_list = DrawList::new();
+
+        let info = ImageInfo {
+            extent: Extent::new(2, 1),
+            layout: Layout::Bgra8,
+            format: Format::Linear,
+        };
+
+        let (texture, cached) = textures
+            .get_mut()
+            .insert_rect(info.extent, info.layout, info.format, |e, l, f| {
+                device.create_texture(e, l, f)
+            })
+            .unwrap();
+
+        let (_, rect) = textures.get_mut().get_rect(cached).unwrap();
+        device.copy_raster_to_texture(
+            texture,
+            &RasterBuf::new(info, &[255, 0, 0, 255, 0, 255, 0, 255]),
+            rect.origin,
+        );
+
+        let image = Image {
+            info: info.pack(),
+            key: crate::graphics::image::PackedKey::new()
+                .with_index(cached.index())
+                .with_epoch(cached.epoch()),
+            offset: crate::graphics::image::PackedOffset::new(),
+        };
+
+        {
+            let mut canvas = Canvas::new(
+                &textures,
+                &glyphs,
+                &mut draw_list,
+                Rect::new(Point::ZERO, Extent::new(4.0, 1.0)),
+            );
+            canvas.draw_rect(&RoundRect::new((0.0, 0.0, 4.0, 1.0)).with_image(image));
+            canvas.finish();
+        }
+
+        device.draw(&draw_list, &mut target);
+
+        assert_eq!(pixel(&target, 0, 0), [0.0, 0.0, 1.0, 1.0]);
+        assert_eq!(pixel(&target, 1, 0), [0.0, 0.0, 1.0, 1.0]);
+        assert_eq!(pixel(&target, 2, 0), [0.0, 1.0, 0.0, 1.0]);
+        assert_eq!(pixel(&target, 3, 0), [0.0, 1.0, 0.0, 1.0]);
+    }
+
+    #[test]
+    fn layer_opacity() {
+        let (device, textures) = setup();
+        let glyphs = RefCell::new(GlyphCache::default());
+        let mut target = RenderTarget::new(Extent::new(4, 1), Layout::Rgba8, Format::Linear);
+        let mut draw_list = DrawList::new();
+
+        {
+            let mut canvas = Canvas::new(
+                &textures,
+                &glyphs,
+                &mut draw_list,
+                Rect::new(Point::ZERO, Extent::new(4.0, 1.0)),
+            );
+            canvas.clear(Color::BLACK);
+            canvas.push_layer(0.5, BlendMode::SourceOver);
+            canvas.draw_rect(&RoundRect::new((0.0, 0.0, 3.0, 1.0)).with_color(Color::RED));
+            canvas.draw_rect(&RoundRect::new((1.0, 0.0, 2.0, 1.0)).with_color(Color::BLUE));
+            canvas.pop_layer();
+            canvas.finish();
+        }
+
+        device.draw(&draw_list, &mut target);
+
+        assert_eq!(pixel(&target, 0, 0), [0.5, 0.0, 0.0, 1.0]);
+        // the red beneath the blue rect is hidden before the layer is faded
+        assert_eq!(pixel(&target, 1, 0), [0.0, 0.0, 0.5, 1.0]);
+        assert_eq!(pixel(&target, 3, 0), [0.0, 0.0, 0.0, 1.0]);
+    }
+}
@@ -1,3 +1,20 @@
+use std::str::FromStr;
+
+#[derive(Debug, thiserror::Error, PartialEq, Eq)]
+pub enum Error {
+    #[error("The color is not a hex color of the form #rgb, #rgba, #rrggbb or #rrggbbaa.")]
+    InvalidHex,
+    #[error("The color is not a hex color or a CSS color name.")]
+    UnknownName,
+}
+
+/// A color in linear light with premultiplied alpha, which is how the canvas
+/// blends colors.
+///
+/// Colors are usually specified in sRGB with straight alpha, as they are in
+/// CSS and most design tools. Convert them with [`from_srgb`](Self::from_srgb),
+/// [`from_rgba8`](Self::from_rgba8), or one of the other constructors rather
+/// than writing their values into the fields directly.
 #[derive(Clone, Copy, Debug, PartialEq)]
 pub struct Color {
     pub r: f32,
@@ -42,9 +59,552 @@ impl Color {
         a: 1.0,
     };
 
+    pub const TRANSPARENT: Self = Self {
+        r: 0.0,
+        g: 0.0,
+        b: 0.0,
+        a: 0.0,
+    };
+
+    /// Creates a color from linear, premultiplied components.
+    #[must_use]
+    pub const fn new(r: f32, g: f32, b: f32, a: f32) -> Self {
+        Self { r, g, b, a }
+    }
+
     #[inline]
     #[must_use]
     pub fn to_array_f32(&self) -> [f32; 4] {
         [self.r, self.g, self.b, self.a]
     }
+
+    /// Multiplies the color components by alpha.
+    #[must_use]
+    pub fn premultiply(&self) -> Self {
+        Self::new(self.r * self.a, self.g * self.a, self.b * self.a, self.a)
+    }
+
+    /// Divides the color components by alpha. Fully transparent colors become
+    /// transparent black.
+    #[must_use]
+    pub fn unpremultiply(&self) -> Self {
+        if self.a > 0.0 {
+            Self::new(self.r / self.a, self.g / self.a, self.b / self.a, self.a)
+        } else {
+            Self::TRANSPARENT
+        }
+    }
+
+    /// Creates a color from sRGB-encoded components with straight alpha.
+    #[must_use]
+    pub fn from_srgb(r: f32, g: f32, b: f32, a: f32) -> Self {
+        Self::new(srgb_to_linear(r), srgb_to_linear(g), srgb_to_linear(b), a).premultiply()
+    }
+
+    /// The color as sRGB-encoded components with straight alpha.
+    #[must_use]
+    pub fn to_srgb(&self) -> [f32; 4] {
+        let Self { r, g, b, a } = self.unpremultiply();
+        [linear_to_srgb(r), linear_to_srgb(g), linear_to_srgb(b), a]
+    }
+
+    /// Creates a color from 8-bit sRGB components with straight alpha, packed
+    /// as `0xRRGGBBAA`.
+    #[must_use]
+    pub fn from_rgba8(rgba: u32) -> Self {
+        let [r, g, b, a] = rgba.to_be_bytes().map(|c| f32::from(c) / 255.0);
+        Self::from_srgb(r, g, b, a)
+    }
+
+    /// The color as 8-bit sRGB components with straight alpha, packed as
+    /// `0xRRGGBBAA`. Components are clamped to the range 0 to 1.
+    #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+    #[must_use]
+    pub fn to_rgba8(&self) -> u32 {
+        let bytes = self
+            .to_srgb()
+            .map(|c| (c.clamp(0.0, 1.0) * 255.0).round() as u8);
+        u32::from_be_bytes(bytes)
+    }
+
+    /// Creates a color from hue (in degrees), saturation, and lightness in
+    /// sRGB, with straight alpha.
+    #[must_use]
+    pub fn from_hsl(hue: f32, saturation: f32, lightness: f32, a: f32) -> Self {
+        let chroma = (1.0 - (2.0 * lightness - 1.0).abs()) * saturation;
+        let [r, g, b] = hue_to_rgb(hue, chroma, lightness - chroma / 2.0);
+        Self::from_srgb(r, g, b, a)
+    }
+
+    /// The color as hue (in degrees, from 0 to 360), saturation, lightness,
+    /// and straight alpha. Grays have a hue of 0.
+    #[must_use]
+    pub fn to_hsl(&self) -> [f32; 4] {
+        let [r, g, b, a] = self.to_srgb();
+        let (hue, max, chroma) = rgb_to_hue(r, g, b);
+
+        let lightness = max - chroma / 2.0;
+        let saturation = if lightness <= 0.0 || lightness >= 1.0 {
+            0.0
+        } else {
+            chroma / (1.0 - (2.0 * lightness - 1.0).abs())
+        };
+
+        [hue, saturation, lightness, a]
+    }
+
+    /// Creates a color from hue (in degrees), saturation, and value in sRGB,
+    /// with straight alpha.
+    #[must_use]
+    pub fn from_hsv(hue: f32, saturation: f32, value: f32, a: f32) -> Self {
+        let chroma = value * saturation;
+        let [r, g, b] = hue_to_rgb(hue, chroma, value - chroma);
+        Self::from_srgb(r, g, b, a)
+    }
+
+    /// The color as hue (in degrees, from 0 to 360), saturation, value, and
+    /// straight alpha. Grays have a hue of 0.
+    #[must_use]
+    pub fn to_hsv(&self) -> [f32; 4] {
+        let [r, g, b, a] = self.to_srgb();
+        let (hue, max, chroma) = rgb_to_hue(r, g, b);
+        let saturation = if max > 0.0 { chroma / max } else { 0.0 };
+        [hue, saturation, max, a]
+    }
+
+    /// Creates a color from perceptual lightness (from 0 to 1), chroma, and
+    /// hue (in degrees) in the Oklab color space, with straight alpha. Colors
+    /// outside of the sRGB gamut are clipped to it.
+    #[must_use]
+    pub fn from_oklch(lightness: f32, chroma: f32, hue: f32, a: f32) -> Self {
+        let (sin, cos) = hue.to_radians().sin_cos();
+        let [r, g, b] = oklab_to_linear([lightness, chroma * cos, chroma * sin]);
+        Self::new(r.clamp(0.0, 1.0), g.clamp(0.0, 1.0), b.clamp(0.0, 1.0), a).premultiply()
+    }
+
+    /// The color as Oklab lightness, chroma, hue (in degrees, from 0 to
+    /// 360), and straight alpha. Grays have a hue of 0.
+    #[must_use]
+    pub fn to_oklch(&self) -> [f32; 4] {
+        let Self { r, g, b, a } = self.unpremultiply();
+        let [lightness, ok_a, ok_b] = linear_to_oklab([r, g, b]);
+
+        let chroma = ok_a.hypot(ok_b);
+        let hue = if chroma > 1e-6 {
+            ok_b.atan2(ok_a).to_degrees().rem_euclid(360.0)
+        } else {
+            0.0
+        };
+
+        [lightness, chroma, hue, a]
+    }
+
+    /// Parses a hex color of the form `#rgb`, `#rgba`, `#rrggbb`, or
+    /// `#rrggbbaa` in sRGB. The `#` is optional.
+    pub fn from_hex(hex: &str) -> Result<Self, Error> {
+        let digits = hex.strip_prefix('#').unwrap_or(hex);
+
+        if !digits.bytes().all(|c| c.is_ascii_hexdigit()) {
+            return Err(Error::InvalidHex);
+        }
+
+        let value = u32::from_str_radix(digits, 16).map_err(|_| Error::InvalidHex)?;
+
+        // Short forms repeat each digit, so that #f80 is #ff8800.
+        let widen = |v: u32| {
+            (0..4)
+                .map(|i| (((v >> (4 * i)) & 0xF) * 0x11) << (8 * i))
+                .sum::<u32>()
+        };
+
+        let rgba = match digits.len() {
+            3 => widen(value << 4 | 0xF),
+            4 => widen(value),
+            6 => value << 8 | 0xFF,
+            8 => value,
+            _ => return Err(Error::InvalidHex),
+        };
+
+        Ok(Self::from_rgba8(rgba))
+    }
+
+    /// Looks up one of the named colors of CSS, such as `"rebeccapurple"`.
+    /// Names are not case-sensitive.
+    #[must_use]
+    pub fn from_css_name(name: &str) -> Option<Self> {
+        let name = name.to_ascii_lowercase();
+
+        if name == "transparent" {
+            return Some(Self::TRANSPARENT);
+        }
+
+        CSS_NAMES
+            .binary_search_by(|(n, _)| n.cmp(&name.as_str()))
+            .ok()
+            .map(|i| Self::from_rgba8(CSS_NAMES[i].1 << 8 | 0xFF))
+    }
+
+    /// Interpolates between two colors in the Oklab color space, which gives
+    /// an even change in perceived lightness and hue. `t` is clamped to the
+    /// range 0 to 1.
+    ///
+    /// As in CSS, colors are premultiplied while they are interpolated, so
+    /// that the color of a transparent end does not bleed into the result.
+    #[must_use]
+    pub fn lerp_oklab(&self, other: &Self, t: f32) -> Self {
+        let t = t.clamp(0.0, 1.0);
+
+        let premultiplied_oklab = |color: &Self| {
+            let Self { r, g, b, a } = color.unpremultiply();
+            linear_to_oklab([r, g, b]).map(|c| c * a)
+        };
+
+        let from = premultiplied_oklab(self);
+        let to = premultiplied_oklab(other);
+        let a = self.a + (other.a - self.a) * t;
+
+        if a <= 0.0 {
+            return Self::TRANSPARENT;
+        }
+
+        let lab = std::array::from_fn(|i| (from[i] + (to[i] - from[i]) * t) / a);
+        let [r, g, b] = oklab_to_linear(lab);
+        Self::new(r.max(0.0), g.max(0.0), b.max(0.0), a).premultiply()
+    }
+}
+
+impl FromStr for Color {
+    type Err = Error;
+
+    /// Parses a hex color or a CSS color name.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+
+        if s.starts_with('#') {
+            Self::from_hex(s)
+        } else {
+            Self::from_css_name(s).ok_or(Error::UnknownName)
+        }
+    }
+}
+
+/// Converts an sRGB-encoded channel value to linear.
+pub(crate) fn srgb_to_linear(value: f32) -> f32 {
+    if value <= 0.040_45 {
+        value / 12.92
+    } else {
+        ((value + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+/// Converts a linear channel value to sRGB encoding.
+pub(crate) fn linear_to_srgb(value: f32) -> f32 {
+    if value <= 0.003_130_8 {
+        value * 12.92
+    } else {
+        1.055 * value.powf(1.0 / 2.4) - 0.055
+    }
+}
+
+/// The red, green, and blue components of a color with the given hue (in
+/// degrees) and chroma, with `min` added to each.
+fn hue_to_rgb(hue: f32, chroma: f32, min: f32) -> [f32; 3] {
+    let h = hue.rem_euclid(360.0) / 60.0;
+    let x = chroma * (1.0 - (h % 2.0 - 1.0).abs());
+
+    let [r, g, b] = match h {
+        h if h < 1.0 => [chroma, x, 0.0],
+        h if h < 2.0 => [x, chroma, 0.0],
+        h if h < 3.0 => [0.0, chroma, x],
+        h if h < 4.0 => [0.0, x, chroma],
+        h if h < 5.0 => [x, 0.0, chroma],
+        _ => [chroma, 0.0, x],
+    };
+
+    [r + min, g + min, b + min]
+}
+
+/// The hue (in degrees), largest component, and chroma of a color.
+#[allow(clippy::float_cmp)]
+fn rgb_to_hue(r: f32, g: f32, b: f32) -> (f32, f32, f32) {
+    let max = r.max(g).max(b);
+    let chroma = max - r.min(g).min(b);
+
+    let hue = if chroma <= 0.0 {
+        0.0
+    } else if max == r {
+        60.0 * ((g - b) / chroma).rem_euclid(6.0)
+    } else if max == g {
+        60.0 * ((b - r) / chroma + 2.0)
+    } else {
+        60.0 * ((r - g) / chroma + 4.0)
+    };
+
+    (hue, max, chroma)
+}
+
+/// Converts linear sRGB to Oklab. See <https://bottosson.github.io/posts/oklab/>.
+fn linear_to_oklab([r, g, b]: [f32; 3]) -> [f32; 3] {
+    let l = (0.412_221_46 * r + 0.536_332_55 * g + 0.051_445_995 * b).cbrt();
+    let m = (0.211_903_5 * r + 0.680_699_5 * g + 0.107_396_96 * b).cbrt();
+    let s = (0.088_302_46 * r + 0.281_718_85 * g + 0.629_978_7 * b).cbrt();
+
+    [
+        0.210_454_26 * l + 0.793_617_8 * m - 0.004_072_047 * s,
+        1.977_998_5 * l - 2.428_592_2 * m + 0.450_593_7 * s,
+        0.025_904_037 * l + 0.782_771_77 * m - 0.808_675_77 * s,
+    ]
+}
+
+/// Converts Oklab to linear sRGB. Components may fall outside of the range 0
+/// to 1 for colors outside of the sRGB gamut.
+fn oklab_to_linear([lightness, a, b]: [f32; 3]) -> [f32; 3] {
+    let l = (lightness + 0.396_337_78 * a + 0.215_803_76 * b).powi(3);
+    let m = (lightness - 0.105_561_346 * a - 0.063_854_17 * b).powi(3);
+    let s = (lightness - 0.089_484_18 * a - 1.291_485_5 * b).powi(3);
+
+    [
+        4.076_741_7 * l - 3.307_711_6 * m + 0.230_969_94 * s,
+        -1.268_438 * l + 2.609_757_4 * m - 0.341_319_38 * s,
+        -0.004_196_086_3 * l - 0.703_418_6 * m + 1.707_614_7 * s,
+    ]
+}
+
+/// The named colors of CSS, sorted by name, as `0xRRGGBB` in sRGB.
+const CSS_NAMES: [(&str, u32); 148] = [
+    ("aliceblue", 0xF0F8FF),
+    ("antiquewhite", 0xFAEBD7),
+    ("aqua", 0x00FFFF),
+    ("aquamarine", 0x7FFFD4),
+    ("azure", 0xF0FFFF),
+    ("beige", 0xF5F5DC),
+    ("bisque", 0xFFE4C4),
+    ("black", 0x000000),
+    ("blanchedalmond", 0xFFEBCD),
+    ("blue", 0x0000FF),
+    ("blueviolet", 0x8A2BE2),
+    ("brown", 0xA52A2A),
+    ("burlywood", 0xDEB887),
+    ("cadetblue", 0x5F9EA0),
+    ("chartreuse", 0x7FFF00),
+    ("chocolate", 0xD2691E),
+    ("coral", 0xFF7F50),
+    ("cornflowerblue", 0x6495ED),
+    ("cornsilk", 0xFFF8DC),
+    ("crimson", 0xDC143C),
+    ("cyan", 0x00FFFF),
+    ("darkblue", 0x00008B),
+    ("darkcyan", 0x008B8B),
+    ("darkgoldenrod", 0xB8860B),
+    ("darkgray", 0xA9A9A9),
+    ("darkgreen", 0x006400),
+    ("darkgrey", 0xA9A9A9),
+    ("darkkhaki", 0xBDB76B),
+    ("darkmagenta", 0x8B008B),
+    ("darkolivegreen", 0x556B2F),
+    ("darkorange", 0xFF8C00),
+    ("darkorchid", 0x9932CC),
+    ("darkred", 0x8B0000),
+    ("darksalmon", 0xE9967A),
+    ("darkseagreen", 0x8FBC8F),
+    ("darkslateblue", 0x483D8B),
+    ("darkslategray", 0x2F4F4F),
+    ("darkslategrey", 0x2F4F4F),
+    ("darkturquoise", 0x00CED1),
+    ("darkviolet", 0x9400D3),
+    ("deeppink", 0xFF1493),
+    ("deepskyblue", 0x00BFFF),
+    ("dimgray", 0x696969),
+    ("dimgrey", 0x696969),
+    ("dodgerblue", 0x1E90FF),
+    ("firebrick", 0xB22222),
+    ("floralwhite", 0xFFFAF0),
+    ("forestgreen", 0x228B22),
+    ("fuchsia", 0xFF00FF),
+    ("gainsboro", 0xDCDCDC),
+    ("ghostwhite", 0xF8F8FF),
+    ("gold", 0xFFD700),
+    ("goldenrod", 0xDAA520),
+    ("gray", 0x808080),
+    ("green", 0x008000),
+    ("greenyellow", 0xADFF2F),
+    ("grey", 0x808080),
+    ("honeydew", 0xF0FFF0),
+    ("hotpink", 0xFF69B4),
+    ("indianred", 0xCD5C5C),
+    ("indigo", 0x4B0082),
+    ("ivory", 0xFFFFF0),
+    ("khaki", 0xF0E68C),
+    ("lavender", 0xE6E6FA),
+    ("lavenderblush", 0xFFF0F5),
+    ("lawngreen", 0x7CFC00),
+    ("lemonchiffon", 0xFFFACD),
+    ("lightblue", 0xADD8E6),
+    ("lightcoral", 0xF08080),
+    ("lightcyan", 0xE0FFFF),
+    ("lightgoldenrodyellow", 0xFAFAD2),
+    ("lightgray", 0xD3D3D3),
+    ("lightgreen", 0x90EE90),
+    ("lightgrey", 0xD3D3D3),
+    ("lightpink", 0xFFB6C1),
+    ("lightsalmon", 0xFFA07A),
+    ("lightseagreen", 0x20B2AA),
+    ("lightskyblue", 0x87CEFA),
+    ("lightslategray", 0x778899),
+    ("lightslategrey", 0x778899),
+    ("lightsteelblue", 0xB0C4DE),
+    ("lightyellow", 0xFFFFE0),
+    ("lime", 0x00FF00),
+    ("limegreen", 0x32CD32),
+    ("linen", 0xFAF0E6),
+    ("magenta", 0xFF00FF),
+    ("maroon", 0x800000),
+    ("mediumaquamarine", 0x66CDAA),
+    ("mediumblue", 0x0000CD),
+    ("mediumorchid", 0xBA55D3),
+    ("mediumpurple", 0x9370DB),
+    ("mediumseagreen", 0x3CB371),
+    ("mediumslateblue", 0x7B68EE),
+    ("mediumspringgreen", 0x00FA9A),
+    ("mediumturquoise", 0x48D1CC),
+    ("mediumvioletred", 0xC71585),
+    ("midnightblue", 0x191970),
+    ("mintcream", 0xF5FFFA),
+    ("mistyrose", 0xFFE4E1),
+    ("moccasin", 0xFFE4B5),
+    ("navajowhite", 0xFFDEAD),
+    ("navy", 0x000080),
+    ("oldlace", 0xFDF5E6),
+    ("olive", 0x808000),
+    ("olivedrab", 0x6B8E23),
+    ("orange", 0xFFA500),
+    ("orangered", 0xFF4500),
+    ("orchid", 0xDA70D6),
+    ("palegoldenrod", 0xEEE8AA),
+    ("palegreen", 0x98FB98),
+    ("paleturquoise", 0xAFEEEE),
+    ("palevioletred", 0xDB7093),
+    ("papayawhip", 0xFFEFD5),
+    ("peachpuff", 0xFFDAB9),
+    ("peru", 0xCD853F),
+    ("pink", 0xFFC0CB),
+    ("plum", 0xDDA0DD),
+    ("powderblue", 0xB0E0E6),
+    ("purple", 0x800080),
+    ("rebeccapurple", 0x663399),
+    ("red", 0xFF0000),
+    ("rosybrown", 0xBC8F8F),
+    ("royalblue", 0x4169E1),
+    ("saddlebrown", 0x8B4513),
+    ("salmon", 0xFA8072),
+    ("sandybrown", 0xF4A460),
+    ("seagreen", 0x2E8B57),
+    ("seashell", 0xFFF5EE),
+    ("sienna", 0xA0522D),
+    ("silver", 0xC0C0C0),
+    ("skyblue", 0x87CEEB),
+    ("slateblue", 0x6A5ACD),
+    ("slategray", 0x708090),
+    ("slategrey", 0x708090),
+    ("snow", 0xFFFAFA),
+    ("springgreen", 0x00FF7F),
+    ("steelblue", 0x4682B4),
+    ("tan", 0xD2B48C),
+    ("teal", 0x008080),
+    ("thistle", 0xD8BFD8),
+    ("tomato", 0xFF6347),
+    ("turquoise", 0x40E0D0),
+    ("violet", 0xEE82EE),
+    ("wheat", 0xF5DEB3),
+    ("white", 0xFFFFFF),
+    ("whitesmoke", 0xF5F5F5),
+    ("yellow", 0xFFFF00),
+    ("yellowgreen", 0x9ACD32),
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(a: [f32; 4], b: [f32; 4]) {
+        assert!(
+            a.iter().zip(&b).all(|(x, y)| (x - y).abs() < 1e-3),
+            "{a:?} != {b:?}"
+        );
+    }
+
+    #[test]
+    fn conversions() {
+        let color = Color::from_srgb(1.0, 0.5, 0.0, 0.5);
+        assert_close(color.to_array_f32(), [0.5, 0.107, 0.0, 0.5]);
+        assert_close(color.to_srgb(), [1.0, 0.5, 0.0, 0.5]);
+        assert_eq!(color.to_rgba8(), 0xFF80_0080);
+
+        assert_close(
+            Color::from_hsl(30.0, 1.0, 0.5, 0.5).to_array_f32(),
+            [0.5, 0.107, 0.0, 0.5],
+        );
+        assert_close(color.to_hsl(), [30.0, 1.0, 0.5, 0.5]);
+        assert_close(color.to_hsv(), [30.0, 1.0, 1.0, 0.5]);
+        assert_close(
+            Color::from_hsv(30.0, 1.0, 1.0, 0.5).to_array_f32(),
+            [0.5, 0.107, 0.0, 0.5],
+        );
+
+        // reference values from https://oklch.com
+        assert_close(Color::WHITE.to_oklch(), [1.0, 0.0, 0.0, 1.0]);
+        assert_close(Color::RED.to_oklch(), [0.628, 0.2577, 29.234, 1.0]);
+        assert_close(
+            Color::from_oklch(0.628, 0.2577, 29.234, 1.0).to_array_f32(),
+            Color::RED.to_array_f32(),
+        );
+    }
+
+    #[test]
+    fn parse() {
+        assert_eq!(
+            Color::from_hex("#ff8000"),
+            Ok(Color::from_rgba8(0xFF80_00FF))
+        );
+        assert_eq!(
+            Color::from_hex("ff800080"),
+            Ok(Color::from_rgba8(0xFF80_0080))
+        );
+        assert_eq!(Color::from_hex("#f80"), Ok(Color::from_rgba8(0xFF88_00FF)));
+        assert_eq!(Color::from_hex("#f808"), Ok(Color::from_rgba8(0xFF88_0088)));
+        assert_eq!(Color::from_hex("#ff80"), Ok(Color::from_rgba8(0xFFFF_8800)));
+        assert_eq!(Color::from_hex("#ff80g0"), Err(Error::InvalidHex));
+        assert_eq!(Color::from_hex("#+f8000"), Err(Error::InvalidHex));
+        assert_eq!(Color::from_hex("#ff800"), Err(Error::InvalidHex));
+
+        assert_eq!("RebeccaPurple".parse(), Ok(Color::from_rgba8(0x6633_99FF)));
+        assert_eq!(" #ffffff ".parse(), Ok(Color::WHITE));
+        assert_eq!("transparent".parse(), Ok(Color::TRANSPARENT));
+        assert_eq!("blurple".parse::<Color>(), Err(Error::UnknownName));
+    }
+
+    #[test]
+    fn lerp() {
+        let red = Color::RED;
+        let blue = Color::BLUE;
+
+        assert_close(
+            red.lerp_oklab(&blue, 0.0).to_array_f32(),
+            red.to_array_f32(),
+        );
+        assert_close(
+            red.lerp_oklab(&blue, 1.0).to_array_f32(),
+            blue.to_array_f32(),
+        );
+
+        // halfway is halfway in perceived lightness
+        let [l0, ..] = red.to_oklch();
+        let [l1, ..] = blue.to_oklch();
+        let [l, ..] = red.lerp_oklab(&blue, 0.5).to_oklch();
+        assert!((l - (l0 + l1) / 2.0).abs() < 1e-3);
+
+        // the transparent end doesn't darken the result
+        let faded = red.lerp_oklab(&Color::TRANSPARENT, 0.5);
+        assert_close(faded.unpremultiply().to_array_f32(), [1.0, 0.0, 0.0, 0.5]);
+    }
 }
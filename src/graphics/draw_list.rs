@@ -1,24 +1,204 @@
 use core::panic;
+use std::cell::RefCell;
 
 use crate::{
-    geometry::{Pixel, Rect, UV},
-    graphics::{color::Color, primitives::RoundRect, texture_atlas::CachedTextureId},
-    limits::GFX_DRAW_PRIM_COUNT,
+    geometry::{Affine, Pixel, Point, Rect, UV},
+    graphics::{
+        color::Color,
+        gradient::{Fill, Gradient, PackedGradient},
+        image::{Image, Layout},
+        path::{stroke_polyline, FillRule, Path, Stroke},
+        primitives::{BorderAlignment, Insets, RoundRect, SliceMode},
+        rasterizer::{rasterize, Edge},
+        text::{GlyphCache, TextLayout},
+    },
+    limits::{GFX_DRAW_PRIM_COUNT, GFX_LAYER_DEPTH},
 };
 
 use super::texture_atlas::TextureCache;
 
-#[repr(u32)]
-#[derive(Clone, Copy, Debug, Default, PartialEq)]
+/// How far flattened curves may stray from the true curve, in pixels.
+pub(super) const PATH_TOLERANCE: f32 = 0.1;
+
+/// How texels are combined when an image is drawn at a different size.
+#[repr(u8)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
 pub enum TextureFilter {
+    /// The nearest texel is used.
     #[default]
     Point,
+    /// The four nearest texels are blended.
     Linear,
+    /// The four nearest texels are blended in the two nearest mip levels.
+    /// Images without mipmaps are filtered like `Linear`.
+    Mipmapped,
+}
+
+/// How an image is extended beyond its edges.
+#[repr(u8)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum WrapMode {
+    /// The texels along the edge are repeated.
+    #[default]
+    Clamp,
+    /// The image is repeated.
+    Repeat,
+    /// The image is repeated, and every other copy is flipped.
+    Mirror,
+}
+
+impl WrapMode {
+    /// Maps a texel coordinate into `0..size`.
+    ///
+    /// Must be kept in sync with `wrap_texel` in `rect_shader.hlsl`.
+    pub(crate) fn apply(self, i: i32, size: i32) -> i32 {
+        match self {
+            Self::Clamp => i.clamp(0, size - 1),
+            Self::Repeat => i.rem_euclid(size),
+            Self::Mirror => {
+                let j = i.rem_euclid(2 * size);
+                if j < size {
+                    j
+                } else {
+                    2 * size - 1 - j
+                }
+            }
+        }
+    }
+}
+
+/// What the texels of an image hold, which changes how they are sampled.
+#[repr(u8)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub(crate) enum ImageKind {
+    #[default]
+    Raster,
+    /// Signed distances, drawn as a mask. See [`sdf`](super::sdf).
+    Vector,
+    /// Colors with a mip chain beside them. See [`mipmap`](super::mipmap).
+    Mipmapped,
 }
 
-#[derive(Clone, Copy, Debug, Default, PartialEq)]
+/// How an image is read when it is drawn.
+///
+/// Wrapping is done within the image, so images that share a texture with
+/// others never sample their neighbors.
+///
+/// This is packed into the `flags` of a rect in `rect_shader.hlsl`, a byte per
+/// field.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
 pub struct Sampler {
     pub filter: TextureFilter,
+    /// This is set from the image when drawing.
+    pub(crate) kind: ImageKind,
+    pub wrap_x: WrapMode,
+    pub wrap_y: WrapMode,
+}
+
+impl Sampler {
+    /// A sampler that wraps the same way along both axes.
+    #[must_use]
+    pub fn new(filter: TextureFilter, wrap: WrapMode) -> Self {
+        Self {
+            filter,
+            kind: ImageKind::Raster,
+            wrap_x: wrap,
+            wrap_y: wrap,
+        }
+    }
+
+    /// This sampler, adjusted for drawing the given image.
+    fn for_image(mut self, image: &Image) -> Self {
+        self.kind = if matches!(image.layout(), Layout::Rgba8Vector | Layout::Alpha8Vector) {
+            ImageKind::Vector
+        } else if image.is_mipmapped() {
+            ImageKind::Mipmapped
+        } else {
+            ImageKind::Raster
+        };
+
+        // Distances are interpolated so that edges between texels are smooth.
+        if self.kind == ImageKind::Vector && self.filter == TextureFilter::Point {
+            self.filter = TextureFilter::Linear;
+        }
+
+        self
+    }
+}
+
+/// How a layer is combined with what is drawn beneath it.
+///
+/// Colors are blended as described in the W3C's [Compositing and Blending
+/// Level 1](https://www.w3.org/TR/compositing-1/). The Porter-Duff operators
+/// name the layer the source and what is beneath it the destination. The
+/// blend modes from `Multiply` on composite the blended color with
+/// `SourceOver`.
+///
+/// Must be kept in sync with `composite` in `composite_shader.hlsl`.
+#[repr(u32)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum BlendMode {
+    /// Nothing is kept.
+    Clear,
+    /// Only the layer is kept.
+    Source,
+    /// Only the destination is kept.
+    Destination,
+    /// The layer is drawn over the destination, as rects are.
+    #[default]
+    SourceOver,
+    /// The layer is drawn beneath the destination.
+    DestinationOver,
+    /// The layer is kept where the destination is.
+    SourceIn,
+    /// The destination is kept where the layer is.
+    DestinationIn,
+    /// The layer is kept where the destination is not.
+    SourceOut,
+    /// The destination is kept where the layer is not.
+    DestinationOut,
+    /// The layer is drawn over the destination, but only where the
+    /// destination is.
+    SourceAtop,
+    /// The destination is drawn over the layer, but only where the layer is.
+    DestinationAtop,
+    /// The layer and the destination are kept where they do not overlap.
+    Xor,
+    /// The layer and the destination are added together.
+    Plus,
+    Multiply,
+    Screen,
+    Overlay,
+    Darken,
+    Lighten,
+    ColorDodge,
+    ColorBurn,
+    HardLight,
+    SoftLight,
+    Difference,
+    Exclusion,
+}
+
+/// A group of draws that are composited together.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Layer {
+    pub opacity: f32,
+    pub blend_mode: BlendMode,
+    /// The clip when the layer was pushed. Only the area inside it is
+    /// composited.
+    pub clip: Clip,
+}
+
+/// A blur of everything that has been drawn beneath a rounded rect.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct BackdropBlur {
+    /// The rect in the render target's coordinates.
+    pub rect: Rect<Pixel>,
+    pub radii: [f32; 4],
+    /// The standard deviation of the blur, in pixels.
+    pub sigma: f32,
+    pub clip: Clip,
 }
 
 #[repr(C)]
@@ -27,29 +207,88 @@ pub struct Sampler {
 pub struct RRect {
     pub xywh: [f32; 4],
     pub uvwh: [f32; 4],
+    /// Where one copy of the texture is drawn, in the rect's coordinates.
+    pub image_xywh: [f32; 4],
+    /// The fill color, or the color that the gradient is multiplied by if
+    /// there is one.
     pub color: [f32; 4],
+    pub radii: [f32; 4],
+    pub border_color: [f32; 4],
     pub texture_id: u32,
     pub sampler: Sampler,
+    /// How far the inner edge of the border is inside the rect.
+    pub border_inset: f32,
+    /// How far the outer edge of the border is outside the rect.
+    pub border_outset: f32,
+    /// An index into the draw list's gradient table, or [`NO_GRADIENT`].
+    pub gradient_id: u32,
+    /// The standard deviation of the blur applied to the rect, or 0 for a
+    /// sharp rect. Blurred rects have no border.
+    pub blur_sigma: f32,
+    /// Maps the rect's coordinates to the render target's, stored by column as
+    /// in [`Transform`](crate::geometry::Transform).
+    pub transform: [f32; 6],
 }
 
+pub const NO_GRADIENT: u32 = u32::MAX;
+
 impl RRect {
-    pub fn new(rect: &RoundRect, uvwh: &Rect<UV>, texture_id: u32, sampler: Sampler) -> Self {
+    pub fn new(
+        rect: &RoundRect,
+        uvwh: &Rect<UV>,
+        texture_id: u32,
+        sampler: Sampler,
+        gradient_id: u32,
+    ) -> Self {
+        let xywh = rect.rect.to_xywh().map(|x| x.0);
+        let max_radius = xywh[2].min(xywh[3]).max(0.0) / 2.0;
+
+        let width = rect.border.width.max(0.0);
+        let (border_inset, border_outset) = match rect.border.alignment {
+            BorderAlignment::Inside => (width, 0.0),
+            BorderAlignment::Center => (width / 2.0, width / 2.0),
+            BorderAlignment::Outside => (0.0, width),
+        };
+
         Self {
-            xywh: rect.rect.to_xywh().map(|x| x.0),
+            xywh,
             uvwh: uvwh.to_xywh().map(|x| x.0),
+            image_xywh: rect.image_rect.map_or(xywh, |r| r.to_xywh().map(|x| x.0)),
             color: rect.color.to_array_f32(),
+            radii: rect.radii.map(|r| r.clamp(0.0, max_radius)),
+            border_color: rect.border.color.to_array_f32(),
             texture_id,
             sampler,
+            border_inset,
+            border_outset,
+            gradient_id,
+            blur_sigma: 0.0,
+            transform: Affine::IDENTITY.m,
         }
     }
 }
 
+/// Limits drawing to an area of the canvas.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Clip {
+    /// Nothing outside of this rect is drawn. Edges are snapped to the pixel
+    /// centers that they contain.
+    pub bounds: Rect<Pixel>,
+    /// A rounded rect and its corner radii that further limit drawing. Its
+    /// edges are anti-aliased.
+    pub rounded: Option<(Rect<Pixel>, [f32; 4])>,
+}
+
 #[derive(Clone, Copy, Debug, PartialEq)]
 pub enum Command {
     Begin(Rect<Pixel>),
     Close,
     Clear(Color),
+    Clip(Clip),
     Rects(u32),
+    PushLayer(Layer),
+    PopLayer,
+    BackdropBlur(BackdropBlur),
 }
 
 #[derive(Copy, Clone, Debug, PartialEq, Eq)]
@@ -57,7 +296,11 @@ pub enum DrawCommand {
     Begin,
     Close,
     Clear,
+    Clip,
     Rects,
+    PushLayer,
+    PopLayer,
+    BackdropBlur,
 }
 
 #[derive(Debug, Default)]
@@ -66,6 +309,10 @@ pub struct DrawList {
     pub(super) prims: Vec<RRect>,
     pub(super) areas: Vec<Rect<Pixel>>,
     pub(super) colors: Vec<Color>,
+    pub(super) gradients: Vec<PackedGradient>,
+    pub(super) clips: Vec<Clip>,
+    pub(super) layers: Vec<Layer>,
+    pub(super) blurs: Vec<BackdropBlur>,
     pub(super) commands: Vec<(DrawCommand, u32)>,
 }
 
@@ -76,6 +323,10 @@ impl DrawList {
             prims: Vec::new(),
             areas: Vec::new(),
             colors: Vec::new(),
+            gradients: Vec::new(),
+            clips: Vec::new(),
+            layers: Vec::new(),
+            blurs: Vec::new(),
             commands: Vec::new(),
         }
     }
@@ -84,6 +335,10 @@ impl DrawList {
         self.prims.clear();
         self.areas.clear();
         self.colors.clear();
+        self.gradients.clear();
+        self.clips.clear();
+        self.layers.clear();
+        self.blurs.clear();
         self.commands.clear();
     }
 
@@ -91,6 +346,9 @@ impl DrawList {
         DrawIter {
             areas: &self.areas,
             colors: &self.colors,
+            clips: &self.clips,
+            layers: &self.layers,
+            blurs: &self.blurs,
             commands: &self.commands,
             index: 0,
             draws: 0,
@@ -110,6 +368,9 @@ impl<'a> IntoIterator for &'a DrawList {
 pub struct DrawIter<'a> {
     areas: &'a [Rect<Pixel>],
     colors: &'a [Color],
+    clips: &'a [Clip],
+    layers: &'a [Layer],
+    blurs: &'a [BackdropBlur],
     commands: &'a [(DrawCommand, u32)],
     index: usize,
     draws: usize,
@@ -130,10 +391,16 @@ impl Iterator for DrawIter<'_> {
             }
             (DrawCommand::Close, _) => Command::Close,
             (DrawCommand::Clear, color_i) => Command::Clear(self.colors[color_i as usize]),
+            (DrawCommand::Clip, clip_i) => Command::Clip(self.clips[clip_i as usize]),
             (DrawCommand::Rects, count) => {
                 self.draws += 1;
                 Command::Rects(count)
             }
+            (DrawCommand::PushLayer, layer_i) => Command::PushLayer(self.layers[layer_i as usize]),
+            (DrawCommand::PopLayer, _) => Command::PopLayer,
+            (DrawCommand::BackdropBlur, blur_i) => {
+                Command::BackdropBlur(self.blurs[blur_i as usize])
+            }
         };
 
         self.index += 1;
@@ -141,10 +408,79 @@ impl Iterator for DrawIter<'_> {
     }
 }
 
+/// A range of a slice along one axis, as `(start, length)`.
+type Interval = (f32, f32);
+
+/// Splits an axis of a nine-slice image and its destination into three
+/// slices, as pairs of the source range in texels and the destination range.
+fn slice_edges(
+    size: f32,
+    start_inset: f32,
+    end_inset: f32,
+    dst: f32,
+    dst_size: f32,
+) -> [(Interval, Interval); 3] {
+    let start_inset = start_inset.clamp(0.0, size);
+    let end_inset = end_inset.clamp(0.0, size - start_inset);
+
+    // The corners shrink to fit if there isn't room for them.
+    let fit = (dst_size / (start_inset + end_inset)).min(1.0);
+    let (dst_start, dst_end) = (start_inset * fit, end_inset * fit);
+
+    [
+        ((0.0, start_inset), (dst, dst_start)),
+        (
+            (start_inset, size - start_inset - end_inset),
+            (dst + dst_start, dst_size - dst_start - dst_end),
+        ),
+        (
+            (size - end_inset, end_inset),
+            (dst + dst_size - dst_end, dst_end),
+        ),
+    ]
+}
+
+/// Repeats the source range across the destination range if `tile` is set,
+/// cutting the last repetition short. Empty slices produce nothing.
+fn tiles(
+    (src, src_size): Interval,
+    (dst, dst_size): Interval,
+    tile: bool,
+) -> Vec<(Interval, Interval)> {
+    if src_size <= 0.0 || dst_size <= 0.0 {
+        return Vec::new();
+    }
+
+    if !tile {
+        return vec![((src, src_size), (dst, dst_size))];
+    }
+
+    let mut tiles = Vec::new();
+    let mut offset = 0.0;
+
+    while offset < dst_size {
+        let size = src_size.min(dst_size - offset);
+        tiles.push(((src, size), (dst + offset, size)));
+        offset += src_size;
+    }
+
+    tiles
+}
+
+/// The largest factor by which a transform scales lengths, approximately.
+fn max_scale(transform: &Affine) -> f32 {
+    let [a, b, c, d, ..] = transform.m;
+    a.hypot(b).max(c.hypot(d)).max(f32::EPSILON)
+}
+
 pub struct Canvas<'a> {
-    textures: &'a TextureCache,
+    textures: &'a RefCell<TextureCache>,
+    glyphs: &'a RefCell<GlyphCache>,
     draw_list: &'a mut DrawList,
     region: Rect<Pixel>,
+    clip_stack: Vec<Clip>,
+    transform_stack: Vec<Affine>,
+    layer_depth: usize,
     rect_batch_start: usize,
     rect_batch_count: usize,
     state: DrawCommand,
@@ -152,7 +488,8 @@ pub struct Canvas<'a> {
 
 impl<'a> Canvas<'a> {
     pub(super) fn new(
-        textures: &'a TextureCache,
+        textures: &'a RefCell<TextureCache>,
+        glyphs: &'a RefCell<GlyphCache>,
         draw_list: &'a mut DrawList,
         region: Rect<Pixel>,
     ) -> Self {
@@ -162,8 +499,12 @@ impl<'a> Canvas<'a> {
 
         Self {
             textures,
+            glyphs,
             draw_list,
             region,
+            clip_stack: Vec::new(),
+            transform_stack: Vec::new(),
+            layer_depth: 0,
             rect_batch_start: 0,
             rect_batch_count: 0,
             state: DrawCommand::Begin,
@@ -175,9 +516,15 @@ impl<'a> Canvas<'a> {
         self.region
     }
 
+    /// Fills the whole canvas with a color. This ignores the clip.
     pub fn clear(&mut self, color: Color) {
         match self.state {
-            DrawCommand::Begin | DrawCommand::Clear => {}
+            DrawCommand::Begin
+            | DrawCommand::Clear
+            | DrawCommand::Clip
+            | DrawCommand::PushLayer
+            | DrawCommand::PopLayer
+            | DrawCommand::BackdropBlur => {}
             DrawCommand::Rects => self.submit_batch(),
             DrawCommand::Close => panic!("Canvas state Close -> Clear is a bug."),
         }
@@ -192,14 +539,546 @@ impl<'a> Canvas<'a> {
         self.state = DrawCommand::Clear;
     }
 
+    /// Applies `transform` to everything drawn until the matching
+    /// [`pop_transform`](Self::pop_transform).
+    ///
+    /// The transform is applied before any transforms that were pushed before
+    /// it, so nested transforms work in the coordinate space of their parent.
+    pub fn push_transform(&mut self, transform: &Affine) {
+        let transform = transform.then(&self.current_transform());
+        self.transform_stack.push(transform);
+    }
+
+    /// Restores the transform from before the last
+    /// [`push_transform`](Self::push_transform).
+    ///
+    /// # Panics
+    ///
+    /// Panics if there is no transform to pop.
+    pub fn pop_transform(&mut self) {
+        self.transform_stack
+            .pop()
+            .expect("pop_transform called without a matching push_transform");
+    }
+
+    /// The transform that is applied to everything drawn.
+    #[must_use]
+    pub fn current_transform(&self) -> Affine {
+        self.transform_stack
+            .last()
+            .copied()
+            .unwrap_or(Affine::IDENTITY)
+    }
+
+    /// Limits drawing to the intersection of `rect` and the current clip
+    /// until the matching [`pop_clip`](Self::pop_clip).
+    ///
+    /// The rect is transformed by the current transform. Clips stay
+    /// axis-aligned, so a rotated or skewed clip covers the bounding box of the
+    /// transformed rect.
+    pub fn push_clip(&mut self, rect: impl Into<Rect<Pixel>>) {
+        let rect = self.current_transform().transform_rect(rect.into());
+        let current = self.current_clip();
+
+        self.push_clip_inner(Clip {
+            bounds: current.bounds.intersect(&rect),
+            rounded: current.rounded,
+        });
+    }
+
+    /// Limits drawing to the intersection of a rounded rect and the current
+    /// clip until the matching [`pop_clip`](Self::pop_clip).
+    ///
+    /// Only the innermost rounded clip has rounded corners. Any rounded clips
+    /// that it is nested in clip to their bounding rects. Transforms are
+    /// applied as in [`push_clip`](Self::push_clip), with the radii scaled by
+    /// the transform's average scale factor.
+    pub fn push_round_clip(&mut self, rect: impl Into<Rect<Pixel>>, radii: [f32; 4]) {
+        let transform = self.current_transform();
+        let rect = transform.transform_rect(rect.into());
+        let radii = radii.map(|r| r * transform.determinant().abs().sqrt());
+        let current = self.current_clip();
+
+        let max_radius = rect.extent.width.0.min(rect.extent.height.0).max(0.0) / 2.0;
+
+        self.push_clip_inner(Clip {
+            bounds: current.bounds.intersect(&rect),
+            rounded: Some((rect, radii.map(|r| r.clamp(0.0, max_radius)))),
+        });
+    }
+
+    /// Restores the clip from before the last [`push_clip`](Self::push_clip)
+    /// or [`push_round_clip`](Self::push_round_clip).
+    ///
+    /// # Panics
+    ///
+    /// Panics if there is no clip to pop.
+    pub fn pop_clip(&mut self) {
+        self.clip_stack
+            .pop()
+            .expect("pop_clip called without a matching push_clip");
+
+        let clip = self.current_clip();
+        self.record_clip(clip);
+    }
+
+    /// Draws everything until the matching [`pop_layer`](Self::pop_layer)
+    /// into a separate, transparent layer. The layer is then composited onto
+    /// the canvas with `opacity` and `blend_mode`.
+    ///
+    /// Only the area inside the current clip is composited, including for
+    /// modes that change what is beneath the layer where the layer is empty,
+    /// like [`BlendMode::SourceIn`]. [`clear`](Self::clear) clears the layer.
+    ///
+    /// # Panics
+    ///
+    /// Panics if layers are nested more than
+    /// [`GFX_LAYER_DEPTH`](crate::limits::GFX_LAYER_DEPTH) deep.
+    pub fn push_layer(&mut self, opacity: f32, blend_mode: BlendMode) {
+        self.end_batch(DrawCommand::PushLayer);
+
+        GFX_LAYER_DEPTH.check(self.layer_depth + 1);
+        GFX_DRAW_PRIM_COUNT.check(self.draw_list.layers.len());
+
+        self.draw_list
+            .commands
+            .push((DrawCommand::PushLayer, self.draw_list.layers.len() as u32));
+
+        self.draw_list.layers.push(Layer {
+            opacity: opacity.clamp(0.0, 1.0),
+            blend_mode,
+            clip: self.current_clip(),
+        });
+
+        self.layer_depth += 1;
+        self.state = DrawCommand::PushLayer;
+    }
+
+    /// Composites the layer from the last [`push_layer`](Self::push_layer)
+    /// onto what was drawn before it.
+    ///
+    /// # Panics
+    ///
+    /// Panics if there is no layer to pop.
+    pub fn pop_layer(&mut self) {
+        assert!(
+            self.layer_depth > 0,
+            "pop_layer called without a matching push_layer"
+        );
+
+        self.end_batch(DrawCommand::PopLayer);
+
+        self.draw_list.commands.push((DrawCommand::PopLayer, 0));
+        self.layer_depth -= 1;
+        self.state = DrawCommand::PopLayer;
+    }
+
     pub fn draw_rect(&mut self, rect: &RoundRect) {
+        // Expired images are drawn without a texture rather than sampling
+        // whatever has since taken their place in the atlas.
+        let (texture_id, uvwh) = self
+            .textures
+            .borrow()
+            .get_uv_region(rect.image.cache_id(), rect.image.region())
+            .or_else(|_| {
+                self.textures
+                    .borrow()
+                    .get_uv_rect(self.textures.borrow().default().0)
+            })
+            .unwrap();
+
+        let gradient_id = rect.gradient.map_or(NO_GRADIENT, |gradient| {
+            self.push_gradient(&gradient, &Affine::IDENTITY)
+        });
+
+        let mut prim = RRect::new(
+            rect,
+            &uvwh,
+            texture_id.index(),
+            rect.sampler.for_image(&rect.image),
+            gradient_id,
+        );
+
+        // A gradient replaces the rect's color rather than tinting it.
+        if rect.gradient.is_some() {
+            prim.color = Color::WHITE.to_array_f32();
+        }
+
+        self.push_rect(prim);
+    }
+
+    /// Draws an image scaled to fill `rect`, keeping its corners at their
+    /// native size. The corners are the parts of the image outside of
+    /// `insets`, and the edges and center between them are stretched or tiled
+    /// according to `mode`.
+    ///
+    /// If `rect` is too small for the corners, they are scaled down to fit.
+    #[allow(clippy::cast_precision_loss)]
+    pub fn draw_nine_slice(
+        &mut self,
+        image: Image,
+        insets: Insets,
+        rect: impl Into<Rect<Pixel>>,
+        mode: SliceMode,
+    ) {
+        let rect = rect.into();
+        let Ok((texture_id, uvwh)) = self
+            .textures
+            .borrow()
+            .get_uv_region(image.cache_id(), image.region())
+        else {
+            // Expired images are drawn without a texture, like `draw_rect`.
+            self.draw_rect(&RoundRect::new(rect).with_image(image));
+            return;
+        };
+
+        let [x, y, width, height] = rect.to_xywh().map(|v| v.0);
+        let [u, v, uv_width, uv_height] = uvwh.to_xywh().map(|v| v.0);
+        let extent = image.extent();
+        let (image_width, image_height) = (f32::from(extent.width), f32::from(extent.height));
+
+        let columns = slice_edges(image_width, insets.left, insets.right, x, width);
+        let rows = slice_edges(image_height, insets.top, insets.bottom, y, height);
+        let sampler = Sampler::default().for_image(&image);
+
+        for (row, &(src_y, dst_y)) in rows.iter().enumerate() {
+            for (column, &(src_x, dst_x)) in columns.iter().enumerate() {
+                // Corners are never tiled.
+                let tile_x = mode == SliceMode::Tile && column == 1;
+                let tile_y = mode == SliceMode::Tile && row == 1;
+
+                for (src_x, dst_x) in tiles(src_x, dst_x, tile_x) {
+                    for (src_y, dst_y) in tiles(src_y, dst_y, tile_y) {
+                        let slice = RoundRect::new((dst_x.0, dst_y.0, dst_x.1, dst_y.1));
+                        let slice_uv = Rect::new(
+                            (
+                                u + uv_width * src_x.0 / image_width,
+                                v + uv_height * src_y.0 / image_height,
+                            ),
+                            (
+                                uv_width * src_x.1 / image_width,
+                                uv_height * src_y.1 / image_height,
+                            ),
+                        );
+
+                        self.push_rect(RRect::new(
+                            &slice,
+                            &slice_uv,
+                            texture_id.index(),
+                            sampler,
+                            NO_GRADIENT,
+                        ));
+                    }
+                }
+            }
+        }
+    }
+
+    /// Draws a text layout with its top-left corner at `origin`, filled with
+    /// a color or a gradient.
+    ///
+    /// The origin is rounded to whole pixels so that glyphs cover the pixels
+    /// that they were rasterized for. Glyphs are rasterized without the
+    /// current transform, so scaled or rotated text is resampled.
+    ///
+    /// The layout must have been prepared with
+    /// [`Graphics::prepare_text`](super::Graphics::prepare_text), or with
+    /// `AppContext::prepare_text` in a window. Glyphs that have not been
+    /// prepared are not drawn.
+    pub fn draw_text(
+        &mut self,
+        layout: &TextLayout,
+        origin: impl Into<Point<Pixel>>,
+        fill: impl Into<Fill>,
+    ) {
+        let origin = origin.into();
+        let (x, y) = (origin.x.0.round(), origin.y.0.round());
+
+        // Gradients are positioned in the same coordinates as `origin`.
+        let (color, gradient_id) = match fill.into() {
+            Fill::Color(color) => (color, NO_GRADIENT),
+            Fill::Gradient(gradient) => (
+                Color::WHITE,
+                self.push_gradient(&gradient, &Affine::IDENTITY),
+            ),
+        };
+
+        for glyph in layout.glyphs() {
+            let (key, pixel) = layout.glyph_key(glyph);
+
+            let Some(cached) = self.glyphs.borrow().get(key).copied() else {
+                debug_assert!(
+                    self.glyphs.borrow().contains(key),
+                    "draw_text called with a layout that has not been prepared"
+                );
+                continue;
+            };
+
+            let Ok((texture_id, uvwh)) = self.textures.borrow().get_uv_rect(cached.image) else {
+                continue;
+            };
+
+            #[allow(clippy::cast_precision_loss)]
+            let rect = RoundRect::new((
+                x + (pixel + cached.offset[0]) as f32,
+                y + glyph.position.y.0.round() + cached.offset[1] as f32,
+                f32::from(cached.extent.width.0),
+                f32::from(cached.extent.height.0),
+            ))
+            .with_color(color);
+
+            self.push_rect(RRect::new(
+                &rect,
+                &uvwh,
+                texture_id.index(),
+                Sampler::default(),
+                gradient_id,
+            ));
+        }
+    }
+
+    /// Draws the shadow cast by a rounded rect.
+    ///
+    /// The shadow is the rect moved by `offset` and grown by `spread` on every
+    /// side, then blurred. Like CSS box shadows, `blur_radius` is twice the
+    /// standard deviation of the blur. The shadow is drawn underneath the
+    /// whole rect, so it should be drawn before the rect that casts it.
+    pub fn draw_shadow(
+        &mut self,
+        rect: impl Into<Rect<Pixel>>,
+        radii: [f32; 4],
+        blur_radius: f32,
+        spread: f32,
+        offset: impl Into<Point<Pixel>>,
+        color: Color,
+    ) {
+        let [x, y, w, h] = rect.into().to_xywh().map(|v| v.0);
+        let offset = offset.into();
+
+        let shadow = RoundRect::new((
+            x + offset.x.0 - spread,
+            y + offset.y.0 - spread,
+            w + 2.0 * spread,
+            h + 2.0 * spread,
+        ))
+        .with_radii(radii.map(|r| if r > 0.0 { (r + spread).max(0.0) } else { 0.0 }))
+        .with_color(color);
+
+        if shadow.rect.extent.width.0 <= 0.0 || shadow.rect.extent.height.0 <= 0.0 {
+            return;
+        }
+
+        let (texture_id, uvwh) = self
+            .textures
+            .borrow()
+            .get_uv_rect(self.textures.borrow().default().0)
+            .unwrap();
+
+        let mut prim = RRect::new(
+            &shadow,
+            &uvwh,
+            texture_id.index(),
+            Sampler::default(),
+            NO_GRADIENT,
+        );
+        prim.blur_sigma = blur_radius.max(0.0) / 2.0;
+
+        self.push_rect(prim);
+    }
+
+    /// Blurs everything that has already been drawn beneath a rounded rect,
+    /// like frosted glass. `sigma` is the standard deviation of the blur.
+    ///
+    /// The rect is transformed by the current transform in the same way as
+    /// [`push_round_clip`](Self::push_round_clip), and `sigma` is scaled with
+    /// it. Inside a layer, only what has been drawn into the layer is blurred.
+    pub fn draw_backdrop_blur(
+        &mut self,
+        rect: impl Into<Rect<Pixel>>,
+        radii: [f32; 4],
+        sigma: f32,
+    ) {
+        let transform = self.current_transform();
+        let scale = transform.determinant().abs().sqrt();
+        let rect = transform.transform_rect(rect.into());
+
+        if sigma <= 0.0 || rect.extent.width.0 <= 0.0 || rect.extent.height.0 <= 0.0 {
+            return;
+        }
+
+        self.end_batch(DrawCommand::BackdropBlur);
+
+        let max_radius = rect.extent.width.0.min(rect.extent.height.0) / 2.0;
+        GFX_DRAW_PRIM_COUNT.check(self.draw_list.blurs.len());
+
+        self.draw_list
+            .commands
+            .push((DrawCommand::BackdropBlur, self.draw_list.blurs.len() as u32));
+
+        self.draw_list.blurs.push(BackdropBlur {
+            rect,
+            radii: radii.map(|r| (r * scale).clamp(0.0, max_radius)),
+            sigma: sigma * scale,
+            clip: self.current_clip(),
+        });
+
+        self.state = DrawCommand::BackdropBlur;
+    }
+
+    /// Fills the area enclosed by a path with a color or a gradient. Open
+    /// subpaths are closed with a straight line. Gradients are positioned in
+    /// the same coordinates as the path.
+    pub fn fill_path(&mut self, path: &Path, rule: FillRule, fill: impl Into<Fill>) {
+        let transform = self.current_transform();
+        let polylines = path.flatten(PATH_TOLERANCE / max_scale(&transform));
+
+        let edges: Vec<Edge> = polylines
+            .iter()
+            .flat_map(|polyline| Edge::polygon(&polyline.points))
+            .collect();
+
+        self.fill_edges(&edges, rule, fill.into());
+    }
+
+    /// Draws the outline of a path with a color or a gradient.
+    ///
+    /// The stroke is computed before the path is transformed, so scaling a
+    /// path also scales its stroke. Gradients are positioned in the same
+    /// coordinates as the path.
+    pub fn stroke_path(&mut self, path: &Path, stroke: &Stroke, fill: impl Into<Fill>) {
+        let transform = self.current_transform();
+        let tolerance = PATH_TOLERANCE / max_scale(&transform);
+
+        let mut polygons = Vec::new();
+        for polyline in path.flatten(tolerance) {
+            stroke_polyline(&polyline, stroke, tolerance, &mut polygons);
+        }
+
+        let edges: Vec<Edge> = polygons
+            .iter()
+            .flat_map(|polygon| Edge::polygon(polygon))
+            .collect();
+
+        self.fill_edges(&edges, FillRule::NonZero, fill.into());
+    }
+
+    pub fn finish(&mut self) {
+        debug_assert!(
+            self.clip_stack.is_empty(),
+            "Canvas finished with unbalanced push_clip calls."
+        );
+        debug_assert!(
+            self.transform_stack.is_empty(),
+            "Canvas finished with unbalanced push_transform calls."
+        );
+        debug_assert!(
+            self.layer_depth == 0,
+            "Canvas finished with unbalanced push_layer calls."
+        );
+
+        match self.state {
+            DrawCommand::Begin
+            | DrawCommand::Clear
+            | DrawCommand::Clip
+            | DrawCommand::PushLayer
+            | DrawCommand::PopLayer
+            | DrawCommand::BackdropBlur => {}
+            DrawCommand::Rects => self.submit_batch(),
+            DrawCommand::Close => return,
+        }
+
+        self.draw_list.commands.push((DrawCommand::Close, 0));
+        self.state = DrawCommand::Close;
+    }
+
+    /// Transforms the edges of a path into the render target and draws them
+    /// as spans of pixels with the same coverage.
+    #[allow(clippy::cast_possible_truncation, clippy::cast_precision_loss)]
+    fn fill_edges(&mut self, edges: &[Edge], rule: FillRule, fill: Fill) {
+        let transform = self.current_transform();
+
+        // Spans are in the render target's coordinates, so gradients are
+        // mapped back to the path's.
+        let (color, gradient_id) = match fill {
+            Fill::Color(color) => (color, NO_GRADIENT),
+            Fill::Gradient(gradient) => {
+                let Some(inverse) = transform.inverse() else {
+                    return;
+                };
+                (Color::WHITE, self.push_gradient(&gradient, &inverse))
+            }
+        };
+
+        let apply = |[x, y]: [f32; 2]| {
+            let (x, y) = transform.apply(x, y);
+            [x, y]
+        };
+
+        let edges: Vec<Edge> = edges
+            .iter()
+            .map(|edge| Edge {
+                from: apply(edge.from),
+                to: apply(edge.to),
+            })
+            .collect();
+
+        let [x, y, w, h] = self.current_clip().bounds.to_xywh().map(|v| v.0);
+        let bounds = [
+            x.floor() as i32,
+            y.floor() as i32,
+            (x + w).ceil() as i32,
+            (y + h).ceil() as i32,
+        ];
+
+        let (texture_id, uvwh) = self
+            .textures
+            .borrow()
+            .get_uv_rect(self.textures.borrow().default().0)
+            .unwrap();
+
+        for span in rasterize(&edges, rule, bounds) {
+            let c = span.coverage;
+            let rect = RoundRect::new((
+                span.x as f32,
+                span.y as f32,
+                span.width as f32,
+                span.height as f32,
+            ))
+            .with_color(Color {
+                r: color.r * c,
+                g: color.g * c,
+                b: color.b * c,
+                a: color.a * c,
+            });
+
+            // Spans are already in the render target's coordinates.
+            self.push_prim(RRect::new(
+                &rect,
+                &uvwh,
+                texture_id.index(),
+                Sampler::default(),
+                gradient_id,
+            ));
+        }
+    }
+
+    fn push_rect(&mut self, mut prim: RRect) {
+        prim.transform = self.current_transform().m;
+        self.push_prim(prim);
+    }
+
+    fn push_prim(&mut self, prim: RRect) {
         match self.state {
             DrawCommand::Begin => {
                 debug_assert_eq!(self.rect_batch_start, 0);
                 debug_assert_eq!(self.rect_batch_count, 0);
                 debug_assert_eq!(self.draw_list.prims.len(), 0);
             }
-            DrawCommand::Clear => {
+            DrawCommand::Clear
+            | DrawCommand::Clip
+            | DrawCommand::PushLayer
+            | DrawCommand::PopLayer
+            | DrawCommand::BackdropBlur => {
                 debug_assert_eq!(self.rect_batch_count, 0);
                 self.rect_batch_start = self.draw_list.prims.len();
             }
@@ -207,15 +1086,7 @@ impl<'a> Canvas<'a> {
             DrawCommand::Close => panic!("Canvas state Close -> DrawRect is a bug."),
         }
 
-        let cache_id = CachedTextureId::new(rect.image.key.index(), rect.image.key.epoch());
-        let (texture_id, uvwh) = self.textures.get_uv_rect(cache_id);
-
-        self.draw_list.prims.push(RRect::new(
-            rect,
-            &uvwh,
-            texture_id.index(),
-            Sampler::default(),
-        ));
+        self.draw_list.prims.push(prim);
 
         GFX_DRAW_PRIM_COUNT.check(self.rect_batch_count);
 
@@ -224,15 +1095,68 @@ impl<'a> Canvas<'a> {
         self.state = DrawCommand::Rects;
     }
 
-    pub fn finish(&mut self) {
+    fn current_clip(&self) -> Clip {
+        self.clip_stack.last().copied().unwrap_or(Clip {
+            bounds: self.region,
+            rounded: None,
+        })
+    }
+
+    fn push_clip_inner(&mut self, clip: Clip) {
+        self.clip_stack.push(clip);
+        self.record_clip(clip);
+    }
+
+    fn record_clip(&mut self, clip: Clip) {
         match self.state {
-            DrawCommand::Begin | DrawCommand::Clear => {}
+            DrawCommand::Begin
+            | DrawCommand::Clear
+            | DrawCommand::Clip
+            | DrawCommand::PushLayer
+            | DrawCommand::PopLayer
+            | DrawCommand::BackdropBlur => {}
             DrawCommand::Rects => self.submit_batch(),
-            DrawCommand::Close => return,
+            DrawCommand::Close => panic!("Canvas state Close -> Clip is a bug."),
         }
 
-        self.draw_list.commands.push((DrawCommand::Close, 0));
-        self.state = DrawCommand::Close;
+        GFX_DRAW_PRIM_COUNT.check(self.draw_list.clips.len());
+
+        self.draw_list
+            .commands
+            .push((DrawCommand::Clip, self.draw_list.clips.len() as u32));
+
+        self.draw_list.clips.push(clip);
+        self.state = DrawCommand::Clip;
+    }
+
+    /// Adds a gradient to the gradient table, reusing the last entry if it is
+    /// the same gradient. `to_gradient` maps the coordinates of the
+    /// primitives that use it to the gradient's.
+    fn push_gradient(&mut self, gradient: &Gradient, to_gradient: &Affine) -> u32 {
+        let mut packed = gradient.pack();
+        packed.transform = to_gradient.m;
+
+        if self.draw_list.gradients.last() != Some(&packed) {
+            GFX_DRAW_PRIM_COUNT.check(self.draw_list.gradients.len());
+            self.draw_list.gradients.push(packed);
+        }
+
+        (self.draw_list.gradients.len() - 1) as u32
+    }
+
+    /// Submits the rects that have been drawn so far, if any, before a
+    /// command that is not a draw.
+    fn end_batch(&mut self, next: DrawCommand) {
+        match self.state {
+            DrawCommand::Begin
+            | DrawCommand::Clear
+            | DrawCommand::Clip
+            | DrawCommand::PushLayer
+            | DrawCommand::PopLayer
+            | DrawCommand::BackdropBlur => {}
+            DrawCommand::Rects => self.submit_batch(),
+            DrawCommand::Close => panic!("Canvas state Close -> {next:?} is a bug."),
+        }
     }
 
     fn submit_batch(&mut self) {
@@ -0,0 +1,289 @@
+use crate::{
+    geometry::{Affine, Pixel, Point},
+    limits::GFX_GRADIENT_STOP_COUNT,
+};
+
+use super::color::Color;
+
+const MAX_STOPS: usize = GFX_GRADIENT_STOP_COUNT.get();
+
+/// The shape of a gradient. Positions are in the same coordinate space as the
+/// primitive that the gradient fills.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum GradientKind {
+    /// Colors vary along the line from `start` to `end`, and are constant
+    /// perpendicular to it.
+    Linear {
+        start: Point<Pixel>,
+        end: Point<Pixel>,
+    },
+    /// Colors vary with distance from `center`, reaching the last stop at
+    /// `radius`.
+    Radial { center: Point<Pixel>, radius: f32 },
+    /// Colors vary with the angle around `center`, starting at `angle`
+    /// (in radians, clockwise from the positive x axis) and sweeping one full
+    /// turn.
+    Conic { center: Point<Pixel>, angle: f32 },
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ColorStop {
+    /// The position of the stop along the gradient, from 0 to 1.
+    pub offset: f32,
+    pub color: Color,
+}
+
+/// A gradient fill with up to
+/// [`GFX_GRADIENT_STOP_COUNT`](crate::limits::GFX_GRADIENT_STOP_COUNT) color
+/// stops.
+///
+/// Colors are interpolated in linear light as premultiplied values. Points
+/// before the first stop or after the last take the color of the nearest
+/// stop.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Gradient {
+    pub kind: GradientKind,
+    stops: [ColorStop; MAX_STOPS],
+    num_stops: usize,
+}
+
+impl Gradient {
+    #[must_use]
+    pub fn new(kind: GradientKind) -> Self {
+        Self {
+            kind,
+            stops: [ColorStop {
+                offset: 0.0,
+                color: Color::BLACK,
+            }; MAX_STOPS],
+            num_stops: 0,
+        }
+    }
+
+    #[must_use]
+    pub fn linear(start: impl Into<Point<Pixel>>, end: impl Into<Point<Pixel>>) -> Self {
+        Self::new(GradientKind::Linear {
+            start: start.into(),
+            end: end.into(),
+        })
+    }
+
+    #[must_use]
+    pub fn radial(center: impl Into<Point<Pixel>>, radius: f32) -> Self {
+        Self::new(GradientKind::Radial {
+            center: center.into(),
+            radius,
+        })
+    }
+
+    #[must_use]
+    pub fn conic(center: impl Into<Point<Pixel>>, angle: f32) -> Self {
+        Self::new(GradientKind::Conic {
+            center: center.into(),
+            angle,
+        })
+    }
+
+    /// Adds a color stop. Stops may be added in any order; stops with the same
+    /// offset produce a hard edge.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the gradient already has
+    /// [`GFX_GRADIENT_STOP_COUNT`](crate::limits::GFX_GRADIENT_STOP_COUNT)
+    /// stops.
+    #[must_use]
+    pub fn with_stop(mut self, offset: f32, color: Color) -> Self {
+        GFX_GRADIENT_STOP_COUNT.check(self.num_stops + 1);
+
+        let offset = offset.clamp(0.0, 1.0);
+        let index = self.stops().partition_point(|stop| stop.offset <= offset);
+
+        self.stops.copy_within(index..self.num_stops, index + 1);
+        self.stops[index] = ColorStop { offset, color };
+        self.num_stops += 1;
+
+        self
+    }
+
+    #[must_use]
+    pub fn stops(&self) -> &[ColorStop] {
+        &self.stops[..self.num_stops]
+    }
+
+    pub(crate) fn pack(&self) -> PackedGradient {
+        let (kind, params) = match self.kind {
+            GradientKind::Linear { start, end } => (0, [start.x.0, start.y.0, end.x.0, end.y.0]),
+            GradientKind::Radial { center, radius } => (1, [center.x.0, center.y.0, radius, 0.0]),
+            GradientKind::Conic { center, angle } => (2, [center.x.0, center.y.0, angle, 0.0]),
+        };
+
+        let mut packed = PackedGradient {
+            params,
+            kind,
+            num_stops: u32::try_from(self.num_stops).unwrap(),
+            transform: Affine::IDENTITY.m,
+            offsets: [0.0; MAX_STOPS],
+            colors: [[0.0; 4]; MAX_STOPS],
+        };
+
+        for (i, stop) in self.stops().iter().enumerate() {
+            packed.offsets[i] = stop.offset;
+            packed.colors[i] = stop.color.to_array_f32();
+        }
+
+        packed
+    }
+}
+
+/// How a shape is filled, either with a solid color or a gradient.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Fill {
+    Color(Color),
+    Gradient(Gradient),
+}
+
+impl From<Color> for Fill {
+    fn from(color: Color) -> Self {
+        Self::Color(color)
+    }
+}
+
+impl From<Gradient> for Fill {
+    fn from(gradient: Gradient) -> Self {
+        Self::Gradient(gradient)
+    }
+}
+
+/// The representation of a gradient in a draw list's gradient table.
+///
+/// Must be kept in sync with `Gradient` in `rect_shader.hlsl`.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) struct PackedGradient {
+    /// Linear: start x, start y, end x, end y.
+    /// Radial: center x, center y, radius.
+    /// Conic: center x, center y, start angle.
+    pub params: [f32; 4],
+    pub kind: u32,
+    pub num_stops: u32,
+    /// Maps the coordinates of the primitives that use the gradient to the
+    /// gradient's, stored by column as in
+    /// [`Transform`](crate::geometry::Transform).
+    pub transform: [f32; 6],
+    pub offsets: [f32; MAX_STOPS],
+    pub colors: [[f32; 4]; MAX_STOPS],
+}
+
+impl PackedGradient {
+    /// Computes the color of the gradient at a point.
+    ///
+    /// Must be kept in sync with `gradient_color` in `rect_shader.hlsl`.
+    pub fn color_at(&self, x: f32, y: f32) -> [f32; 4] {
+        let (x, y) = Affine::new(self.transform).apply(x, y);
+        let [a, b, c, d] = self.params;
+
+        let t = match self.kind {
+            0 => {
+                let (dx, dy) = (c - a, d - b);
+                let len2 = dx * dx + dy * dy;
+                if len2 > 0.0 {
+                    ((x - a) * dx + (y - b) * dy) / len2
+                } else {
+                    0.0
+                }
+            }
+            1 => {
+                if c > 0.0 {
+                    (x - a).hypot(y - b) / c
+                } else {
+                    1.0
+                }
+            }
+            _ => {
+                let turn = ((y - b).atan2(x - a) - c) / std::f32::consts::TAU;
+                turn - turn.floor()
+            }
+        };
+
+        let stops = self.num_stops as usize;
+        if stops == 0 {
+            return [0.0; 4];
+        }
+
+        let t = t.clamp(0.0, 1.0);
+
+        if t <= self.offsets[0] {
+            return self.colors[0];
+        }
+
+        for i in 1..stops {
+            if t <= self.offsets[i] {
+                let (start, end) = (self.offsets[i - 1], self.offsets[i]);
+                let f = if end > start {
+                    (t - start) / (end - start)
+                } else {
+                    1.0
+                };
+
+                let (from, to) = (self.colors[i - 1], self.colors[i]);
+                return std::array::from_fn(|c| from[c] + (to[c] - from[c]) * f);
+            }
+        }
+
+        self.colors[stops - 1]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn gray(v: f32) -> Color {
+        Color {
+            r: v,
+            g: v,
+            b: v,
+            a: 1.0,
+        }
+    }
+
+    #[test]
+    fn stops_are_sorted() {
+        let gradient = Gradient::linear((0.0, 0.0), (1.0, 0.0))
+            .with_stop(1.0, gray(1.0))
+            .with_stop(0.0, gray(0.0))
+            .with_stop(0.5, gray(0.5));
+
+        let offsets: Vec<_> = gradient.stops().iter().map(|s| s.offset).collect();
+        assert_eq!(offsets, [0.0, 0.5, 1.0]);
+    }
+
+    #[test]
+    fn evaluate() {
+        let linear = Gradient::linear((10.0, 0.0), (20.0, 0.0))
+            .with_stop(0.0, gray(0.0))
+            .with_stop(1.0, gray(1.0))
+            .pack();
+
+        assert_eq!(linear.color_at(0.0, 5.0), [0.0, 0.0, 0.0, 1.0]);
+        assert_eq!(linear.color_at(15.0, 5.0), [0.5, 0.5, 0.5, 1.0]);
+        assert_eq!(linear.color_at(30.0, 5.0), [1.0, 1.0, 1.0, 1.0]);
+
+        let radial = Gradient::radial((0.0, 0.0), 10.0)
+            .with_stop(0.0, gray(1.0))
+            .with_stop(0.5, gray(0.0))
+            .pack();
+
+        assert_eq!(radial.color_at(3.0, 4.0), [0.0, 0.0, 0.0, 1.0]);
+        assert_eq!(radial.color_at(0.0, 2.5), [0.5, 0.5, 0.5, 1.0]);
+
+        // a quarter turn clockwise from the start angle
+        let conic = Gradient::conic((0.0, 0.0), 0.0)
+            .with_stop(0.0, gray(0.0))
+            .with_stop(1.0, gray(1.0))
+            .pack();
+
+        assert_eq!(conic.color_at(0.0, 1.0), [0.25, 0.25, 0.25, 1.0]);
+    }
+}
@@ -0,0 +1,305 @@
+//! Output of HDR and wide-gamut color.
+//!
+//! Colors are drawn in linear light with sRGB primaries, where 1.0 is SDR
+//! white. Colors brighter than white and colors outside of the sRGB gamut
+//! (with negative components) are kept while drawing, and are only brought
+//! into the display's range when pixels are encoded for output, as described
+//! by a [`ColorOutput`].
+
+use super::Format;
+
+/// The brightness of 1.0 in scRGB, in nits.
+pub(crate) const SCRGB_WHITE_LEVEL: f32 = 80.0;
+
+/// The brightness of 1.0 in PQ, in nits.
+const PQ_MAX_LUMINANCE: f32 = 10_000.0;
+
+/// How the pixels of a render target are encoded for display.
+#[repr(u32)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum ColorSpace {
+    /// sRGB, limited to SDR white. The sRGB transfer function is applied
+    /// according to the target's [`Format`].
+    #[default]
+    Srgb,
+    /// Linear light with sRGB primaries, where 1.0 is 80 nits. Components
+    /// may be greater than 1 for HDR, or negative for colors outside of the
+    /// sRGB gamut.
+    ScRgb,
+    /// Rec. 2100: Rec. 2020 primaries with the PQ transfer function, where
+    /// 1.0 is 10,000 nits.
+    Pq,
+}
+
+/// How colors that are brighter than the display can show are brought into
+/// its range.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum ToneMapping {
+    /// Components are clipped to the display's peak brightness. Colors
+    /// within the display's range are unchanged.
+    #[default]
+    Clip,
+    /// Colors are compressed with the extended Reinhard operator, so that
+    /// `white_point` (in nits) maps to the display's peak brightness. This
+    /// darkens every color somewhat, and keeps the hue of bright colors.
+    Reinhard { white_point: f32 },
+}
+
+/// The capabilities of the display that a window is shown on.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct DisplayInfo {
+    /// The display is in HDR mode, and can show colors brighter than SDR
+    /// white.
+    pub hdr_enabled: bool,
+    /// The brightness of SDR white, in nits.
+    pub sdr_white_level: f32,
+    /// The darkest the display can be, in nits.
+    pub min_luminance: f32,
+    /// The brightest the display can be in a small area, in nits.
+    pub max_luminance: f32,
+    /// The brightest the display can be over its whole area, in nits.
+    pub max_full_frame_luminance: f32,
+}
+
+impl Default for DisplayInfo {
+    /// An SDR display.
+    fn default() -> Self {
+        Self {
+            hdr_enabled: false,
+            sdr_white_level: SCRGB_WHITE_LEVEL,
+            min_luminance: 0.0,
+            max_luminance: SCRGB_WHITE_LEVEL,
+            max_full_frame_luminance: SCRGB_WHITE_LEVEL,
+        }
+    }
+}
+
+/// How the pixels of a render target are encoded for display.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ColorOutput {
+    pub color_space: ColorSpace,
+    pub tone_mapping: ToneMapping,
+    /// The brightness that SDR white (1.0) is shown at, in nits.
+    pub sdr_white_level: f32,
+    /// The brightest the display can be, in nits. Brighter colors are tone
+    /// mapped. This is only used by HDR color spaces; sRGB output is limited
+    /// to SDR white.
+    pub max_luminance: f32,
+}
+
+impl ColorOutput {
+    /// Output in a color space, with brightness levels to suit a display.
+    #[must_use]
+    pub fn for_display(
+        color_space: ColorSpace,
+        tone_mapping: ToneMapping,
+        display: &DisplayInfo,
+    ) -> Self {
+        Self {
+            color_space,
+            tone_mapping,
+            sdr_white_level: display.sdr_white_level,
+            max_luminance: if display.hdr_enabled {
+                display.max_luminance
+            } else {
+                display.sdr_white_level
+            },
+        }
+    }
+}
+
+impl Default for ColorOutput {
+    /// SDR output in sRGB.
+    fn default() -> Self {
+        Self::for_display(
+            ColorSpace::default(),
+            ToneMapping::default(),
+            &DisplayInfo::default(),
+        )
+    }
+}
+
+/// The format of pixels that are read back from a render target. HDR color
+/// spaces have their own transfer functions, so the sRGB transfer function is
+/// not applied to them.
+pub(crate) fn output_format(format: Format, output: &ColorOutput) -> Format {
+    match output.color_space {
+        ColorSpace::Srgb => format,
+        ColorSpace::ScRgb | ColorSpace::Pq => Format::Linear,
+    }
+}
+
+/// Encodes a linear, premultiplied color for output.
+///
+/// Must be kept in sync with `output_shader.hlsl`.
+pub(crate) fn encode_output(color: [f32; 4], output: &ColorOutput) -> [f32; 4] {
+    let [r, g, b, a] = color;
+
+    if a <= 0.0 {
+        return [0.0; 4];
+    }
+
+    // Brightness is relative to SDR white until the color is encoded.
+    let white = output.sdr_white_level;
+    let peak = match output.color_space {
+        ColorSpace::Srgb => 1.0,
+        ColorSpace::ScRgb | ColorSpace::Pq => output.max_luminance / white,
+    };
+
+    let rgb = tone_map([r, g, b], a, output.tone_mapping, white, peak);
+
+    let [r, g, b] = match output.color_space {
+        ColorSpace::Srgb => rgb.map(|c| c.max(0.0)),
+        ColorSpace::ScRgb => rgb.map(|c| c * white / SCRGB_WHITE_LEVEL),
+        ColorSpace::Pq => rec709_to_rec2020(rgb).map(|c| pq_encode(c / a * white) * a),
+    };
+
+    [r, g, b, a]
+}
+
+/// Brings a premultiplied color within the peak brightness (relative to SDR
+/// white). The color is scaled by its largest component so that its hue does
+/// not change.
+fn tone_map(rgb: [f32; 3], a: f32, tone_mapping: ToneMapping, white: f32, peak: f32) -> [f32; 3] {
+    let max = rgb[0].max(rgb[1]).max(rgb[2]) / a;
+
+    let scale = match tone_mapping {
+        ToneMapping::Reinhard { white_point } if max > 0.0 && white_point / white > peak => {
+            let x = max / peak;
+            let w = white_point / white / peak;
+            (x * (1.0 + x / (w * w)) / (1.0 + x)) * peak / max
+        }
+        _ => 1.0,
+    };
+
+    rgb.map(|c| (c * scale).min(peak * a))
+}
+
+/// Converts linear light from Rec. 709 (sRGB) primaries to Rec. 2020
+/// primaries. See ITU-R BT.2087.
+fn rec709_to_rec2020([r, g, b]: [f32; 3]) -> [f32; 3] {
+    [
+        0.627_403_9 * r + 0.329_283 * g + 0.043_313_07 * b,
+        0.069_097_29 * r + 0.919_540_4 * g + 0.011_362_32 * b,
+        0.016_391_44 * r + 0.088_013_31 * g + 0.895_595_3 * b,
+    ]
+}
+
+/// Encodes a brightness in nits with the PQ transfer function (SMPTE ST
+/// 2084).
+fn pq_encode(nits: f32) -> f32 {
+    const M1: f32 = 2610.0 / 16384.0;
+    const M2: f32 = 2523.0 / 4096.0 * 128.0;
+    const C1: f32 = 3424.0 / 4096.0;
+    const C2: f32 = 2413.0 / 4096.0 * 32.0;
+    const C3: f32 = 2392.0 / 4096.0 * 32.0;
+
+    let y = (nits / PQ_MAX_LUMINANCE).clamp(0.0, 1.0).powf(M1);
+    ((C1 + C2 * y) / (1.0 + C3 * y)).powf(M2)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::{
+        geometry::{Extent, Scale},
+        graphics::{Backend, Color, DrawList, Graphics, GraphicsConfig, Layout, RoundRect},
+    };
+
+    fn assert_close(a: [f32; 4], b: [f32; 4]) {
+        assert!(
+            a.iter().zip(&b).all(|(x, y)| (x - y).abs() < 1e-3),
+            "{a:?} != {b:?}"
+        );
+    }
+
+    #[test]
+    fn encode() {
+        let sdr = ColorOutput::default();
+        assert_close(
+            encode_output([0.25, 0.5, 2.0, 1.0], &sdr),
+            [0.25, 0.5, 1.0, 1.0],
+        );
+        assert_close(
+            encode_output([0.25, 0.5, 0.5, 0.5], &sdr),
+            [0.25, 0.5, 0.5, 0.5],
+        );
+
+        let hdr = DisplayInfo {
+            hdr_enabled: true,
+            sdr_white_level: 200.0,
+            min_luminance: 0.0,
+            max_luminance: 400.0,
+            max_full_frame_luminance: 400.0,
+        };
+
+        let scrgb = ColorOutput::for_display(ColorSpace::ScRgb, ToneMapping::Clip, &hdr);
+        assert_close(
+            encode_output([1.0, 4.0, -0.1, 1.0], &scrgb),
+            [2.5, 5.0, -0.25, 1.0],
+        );
+
+        // the white point reaches the peak, and the hue is kept
+        let reinhard = ColorOutput {
+            tone_mapping: ToneMapping::Reinhard { white_point: 800.0 },
+            ..scrgb
+        };
+        assert_close(
+            encode_output([4.0, 2.0, 0.0, 1.0], &reinhard),
+            [5.0, 2.5, 0.0, 1.0],
+        );
+
+        // 100 and 1000 nits, from the tables in ITU-R BT.2100
+        let pq = ColorOutput {
+            sdr_white_level: 100.0,
+            max_luminance: 1000.0,
+            ..ColorOutput::for_display(ColorSpace::Pq, ToneMapping::Clip, &hdr)
+        };
+        assert_close(
+            encode_output([1.0, 1.0, 1.0, 1.0], &pq),
+            [0.508, 0.508, 0.508, 1.0],
+        );
+        assert_close(
+            encode_output([10.0, 10.0, 10.0, 1.0], &pq),
+            [0.752, 0.752, 0.752, 1.0],
+        );
+    }
+
+    #[test]
+    fn software_readback() {
+        let graphics = Graphics::new(&GraphicsConfig {
+            backend: Backend::Software,
+            debug_mode: false,
+            ..Default::default()
+        });
+
+        let mut target = graphics
+            .create_render_target(Extent::new(2, 1), Layout::Rgba16Float, Format::Linear)
+            .unwrap();
+
+        target.set_color_output(ColorOutput {
+            max_luminance: 400.0,
+            ..ColorOutput::for_display(
+                ColorSpace::ScRgb,
+                ToneMapping::Clip,
+                &DisplayInfo::default(),
+            )
+        });
+
+        let mut draw_list = DrawList::new();
+        {
+            let mut canvas = graphics.create_canvas(&target, &mut draw_list, Scale::default());
+            let bright = Color::new(2.0, 8.0, 0.5, 1.0);
+            canvas.draw_rect(&RoundRect::new((0.0, 0.0, 1.0, 1.0)).with_color(bright));
+            canvas.draw_rect(&RoundRect::new((1.0, 0.0, 1.0, 1.0)).with_color(Color::WHITE));
+            canvas.finish();
+        }
+        graphics.draw(&draw_list, &mut target);
+
+        // colors beyond SDR white are kept, up to the display's peak
+        let pixels = graphics.read_pixels(&target).as_buf().to_linear_rgba();
+        assert_close(pixels[0], [2.0, 5.0, 0.5, 1.0]);
+        assert_close(pixels[1], [1.0; 4]);
+    }
+}
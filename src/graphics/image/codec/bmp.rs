@@ -0,0 +1,184 @@
+//! Windows bitmaps, with 1 to 32 bits per pixel. Run-length encoded and
+//! embedded JPEG or PNG bitmaps are not supported.
+
+use crate::graphics::Format;
+
+use super::{finish, image_extent, Error, Raster, Reader};
+
+const BI_RGB: u32 = 0;
+const BI_BITFIELDS: u32 = 3;
+const BI_ALPHABITFIELDS: u32 = 6;
+
+/// The size of `BITMAPCOREHEADER`, which uses 16-bit dimensions.
+const CORE_HEADER_SIZE: u32 = 12;
+
+/// The size of `BITMAPINFOHEADER`. Later headers extend it, starting with
+/// the channel masks.
+const INFO_HEADER_SIZE: u32 = 40;
+
+/// The bits of a pixel that hold a channel.
+#[derive(Clone, Copy)]
+struct Mask {
+    mask: u32,
+    shift: u32,
+    max: u32,
+}
+
+impl Mask {
+    fn new(mask: u32) -> Self {
+        let shift = if mask == 0 { 0 } else { mask.trailing_zeros() };
+        Self {
+            mask,
+            shift,
+            max: mask >> shift,
+        }
+    }
+
+    /// Reads the channel from a pixel, scaled to 8 bits.
+    #[allow(clippy::cast_possible_truncation)]
+    fn read(self, pixel: u32) -> u8 {
+        if self.max == 0 {
+            return 0;
+        }
+
+        let value = u64::from((pixel & self.mask) >> self.shift);
+        ((value * 255 + u64::from(self.max) / 2) / u64::from(self.max)) as u8
+    }
+}
+
+pub(super) fn decode(bytes: &[u8]) -> Result<Raster, Error> {
+    let mut reader = Reader::new(bytes);
+
+    if reader.array()? != *b"BM" {
+        return Err(Error::Malformed("missing BMP signature"));
+    }
+
+    reader.skip(8)?;
+    let data_offset = reader.u32_le()? as usize;
+
+    let header_size = reader.u32_le()?;
+    let (width, height, bits) = if header_size == CORE_HEADER_SIZE {
+        let width = i32::from(reader.u16_le()?);
+        let height = i32::from(reader.u16_le()?);
+        reader.skip(2)?;
+        (width, height, reader.u16_le()?)
+    } else if header_size >= INFO_HEADER_SIZE {
+        let width = reader.i32_le()?;
+        let height = reader.i32_le()?;
+        reader.skip(2)?;
+        (width, height, reader.u16_le()?)
+    } else {
+        return Err(Error::Malformed("invalid BMP header size"));
+    };
+
+    // Rows are stored from the bottom up unless the height is negative.
+    let top_down = height < 0;
+    let extent = image_extent(width.unsigned_abs(), height.unsigned_abs())?;
+
+    let (compression, colors_used) = if header_size >= INFO_HEADER_SIZE {
+        let compression = reader.u32_le()?;
+        reader.skip(12)?;
+        let colors_used = reader.u32_le()?;
+        reader.skip(4)?;
+        (compression, colors_used)
+    } else {
+        (BI_RGB, 0)
+    };
+
+    // Channel masks follow the fixed part of the header, either as part of a
+    // later version of the header or on their own.
+    let mut masks = match bits {
+        16 => [0x7C00, 0x03E0, 0x001F, 0],
+        _ => [0x00FF_0000, 0x0000_FF00, 0x0000_00FF, 0],
+    };
+
+    match compression {
+        BI_RGB => {}
+        BI_BITFIELDS | BI_ALPHABITFIELDS if matches!(bits, 16 | 32) => {
+            let count = if header_size >= INFO_HEADER_SIZE + 16 || compression == BI_ALPHABITFIELDS
+            {
+                4
+            } else {
+                3
+            };
+
+            for mask in &mut masks[..count] {
+                *mask = reader.u32_le()?;
+            }
+        }
+        _ => return Err(Error::Unsupported("compressed bitmaps")),
+    }
+
+    // Skip whatever remains of a later version of the header.
+    let read = bytes.len() - reader.remaining().len();
+    let header_end = (14 + header_size as usize).max(read);
+    let mut reader = Reader::new(bytes);
+    reader.skip(header_end)?;
+
+    let palette = if bits <= 8 {
+        let entry_size = if header_size == CORE_HEADER_SIZE {
+            3
+        } else {
+            4
+        };
+        let count = if colors_used == 0 {
+            1 << bits
+        } else {
+            colors_used.min(256) as usize
+        };
+
+        let entries = reader.take(count * entry_size)?;
+        entries
+            .chunks_exact(entry_size)
+            .map(|entry| [entry[2], entry[1], entry[0], 255])
+            .collect()
+    } else {
+        Vec::new()
+    };
+
+    let [r, g, b, a] = masks.map(Mask::new);
+
+    #[allow(clippy::cast_sign_loss)]
+    let (width, height) = (extent.width.0 as usize, extent.height.0 as usize);
+    let row_size = (width * usize::from(bits)).div_ceil(32) * 4;
+
+    let mut reader = Reader::new(bytes);
+    reader.skip(data_offset)?;
+    let data = reader.take(row_size * height)?;
+
+    let mut rgba = Vec::with_capacity(width * height * 4);
+
+    for y in 0..height {
+        let row_index = if top_down { y } else { height - 1 - y };
+        let row = &data[row_index * row_size..][..row_size];
+
+        for x in 0..width {
+            let pixel = match bits {
+                1 | 2 | 4 | 8 => {
+                    let bits = usize::from(bits);
+                    let bit = x * bits;
+                    let index = (row[bit / 8] >> (8 - bits - bit % 8)) & ((1 << bits) - 1) as u8;
+                    *palette
+                        .get(usize::from(index))
+                        .ok_or(Error::Malformed("palette index out of range"))?
+                }
+                16 | 32 => {
+                    let value = if bits == 16 {
+                        u32::from(u16::from_le_bytes([row[x * 2], row[x * 2 + 1]]))
+                    } else {
+                        u32::from_le_bytes(row[x * 4..][..4].try_into().unwrap())
+                    };
+
+                    let alpha = if a.mask == 0 { 255 } else { a.read(value) };
+                    [r.read(value), g.read(value), b.read(value), alpha]
+                }
+                24 => [row[x * 3 + 2], row[x * 3 + 1], row[x * 3], 255],
+                _ => return Err(Error::Unsupported("bitmaps with this many bits per pixel")),
+            };
+
+            rgba.extend_from_slice(&pixel);
+        }
+    }
+
+    Ok(finish(extent, Format::Srgb, rgba))
+}
@@ -0,0 +1,44 @@
+//! JPEG images, decoded with the `jpeg-decoder` crate. Embedded color
+//! profiles are ignored, and colors are assumed to be sRGB.
+
+use jpeg_decoder::{Decoder, PixelFormat};
+
+use crate::graphics::Format;
+
+use super::{finish, image_extent, Error, Raster};
+
+pub(super) fn decode(bytes: &[u8]) -> Result<Raster, Error> {
+    let mut decoder = Decoder::new(bytes);
+    decoder.read_info()?;
+
+    let info = decoder
+        .info()
+        .ok_or(Error::Malformed("missing JPEG frame header"))?;
+    let extent = image_extent(u32::from(info.width), u32::from(info.height))?;
+
+    if info.pixel_format == PixelFormat::L16 {
+        return Err(Error::Unsupported("16-bit grayscale JPEG"));
+    }
+
+    let pixels = decoder.decode()?;
+
+    let rgba = match info.pixel_format {
+        PixelFormat::L8 => pixels.iter().flat_map(|&l| [l, l, l, 255]).collect(),
+        PixelFormat::RGB24 => pixels
+            .chunks_exact(3)
+            .flat_map(|rgb| [rgb[0], rgb[1], rgb[2], 255])
+            .collect(),
+        PixelFormat::CMYK32 => pixels
+            .chunks_exact(4)
+            .flat_map(|cmyk| {
+                #[allow(clippy::cast_possible_truncation)]
+                let channel =
+                    |c: u8| ((255 - u16::from(c)) * (255 - u16::from(cmyk[3])) / 255) as u8;
+                [channel(cmyk[0]), channel(cmyk[1]), channel(cmyk[2]), 255]
+            })
+            .collect(),
+        PixelFormat::L16 => unreachable!(),
+    };
+
+    Ok(finish(extent, Format::Srgb, rgba))
+}
@@ -0,0 +1,566 @@
+//! Decoding and encoding of image files.
+//!
+//! Each file format is behind a cargo feature of the same name (`png`,
+//! `jpeg`, `qoi`, `bmp` and `tga`), all of which are enabled by default.
+//! Using a format whose feature is disabled returns [`Error::Disabled`].
+//!
+//! Images are decoded to [`Layout::Rgba8`] with premultiplied alpha, in the
+//! color space that the file declares: [`Format::Srgb`] unless the file says
+//! that its colors are linear.
+//!
+//! Only PNG and QOI images can be encoded. Files are written with straight
+//! alpha, as the formats require.
+
+// The helpers below are shared by the decoders, so builds without some of them
+// leave parts unused.
+#![cfg_attr(
+    not(all(
+        feature = "png",
+        feature = "jpeg",
+        feature = "qoi",
+        feature = "bmp",
+        feature = "tga"
+    )),
+    allow(dead_code)
+)]
+
+#[cfg(feature = "bmp")]
+mod bmp;
+#[cfg(feature = "jpeg")]
+mod jpeg;
+#[cfg(feature = "png")]
+mod png;
+#[cfg(feature = "qoi")]
+mod qoi;
+#[cfg(feature = "tga")]
+mod tga;
+
+use std::path::Path;
+
+use crate::{
+    geometry::{Extent, Texel},
+    graphics::color::{linear_to_srgb, srgb_to_linear},
+    limits::GFX_IMAGE_EXTENT,
+};
+
+use super::{Format, Info, Layout, Raster, RasterBuf};
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error("The file is not in a recognized image format.")]
+    UnknownFormat,
+    #[error("Decoding {0:?} images is not enabled. Enable its cargo feature to do so.")]
+    Disabled(FileFormat),
+    #[error("The image is malformed: {0}.")]
+    Malformed(&'static str),
+    #[error("The image uses a feature that is not supported: {0}.")]
+    Unsupported(&'static str),
+    #[error("The image size exceeds the max image size.")]
+    SizeLimit,
+    #[error("Encoding {0:?} images is not supported.")]
+    NoEncoder(FileFormat),
+    #[error("The image file could not be written.")]
+    Io(#[from] std::io::Error),
+    #[cfg(feature = "png")]
+    #[error("The PNG image could not be decoded.")]
+    Png(#[from] ::png::DecodingError),
+    #[cfg(feature = "png")]
+    #[error("The PNG image could not be encoded.")]
+    PngEncode(#[from] ::png::EncodingError),
+    #[cfg(feature = "jpeg")]
+    #[error("The JPEG image could not be decoded.")]
+    Jpeg(#[from] jpeg_decoder::Error),
+}
+
+/// The file formats that images can be decoded from or encoded to.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum FileFormat {
+    Png,
+    Jpeg,
+    Qoi,
+    Bmp,
+    Tga,
+}
+
+impl FileFormat {
+    /// Identifies the format of a file from the signature at its start.
+    ///
+    /// TGA files have no signature, so they can only be identified by their
+    /// extension.
+    #[must_use]
+    pub fn from_signature(bytes: &[u8]) -> Option<Self> {
+        if bytes.starts_with(b"\x89PNG\r\n\x1a\n") {
+            Some(Self::Png)
+        } else if bytes.starts_with(&[0xFF, 0xD8, 0xFF]) {
+            Some(Self::Jpeg)
+        } else if bytes.starts_with(b"qoif") {
+            Some(Self::Qoi)
+        } else if bytes.starts_with(b"BM") {
+            Some(Self::Bmp)
+        } else {
+            None
+        }
+    }
+
+    /// Identifies the format of a file from its extension, ignoring case.
+    #[must_use]
+    pub fn from_extension(extension: &str) -> Option<Self> {
+        [
+            ("png", Self::Png),
+            ("jpg", Self::Jpeg),
+            ("jpeg", Self::Jpeg),
+            ("qoi", Self::Qoi),
+            ("bmp", Self::Bmp),
+            ("dib", Self::Bmp),
+            ("tga", Self::Tga),
+        ]
+        .into_iter()
+        .find_map(|(name, format)| extension.eq_ignore_ascii_case(name).then_some(format))
+    }
+
+    /// Whether support for the format has been compiled in.
+    #[must_use]
+    pub const fn is_enabled(self) -> bool {
+        match self {
+            Self::Png => cfg!(feature = "png"),
+            Self::Jpeg => cfg!(feature = "jpeg"),
+            Self::Qoi => cfg!(feature = "qoi"),
+            Self::Bmp => cfg!(feature = "bmp"),
+            Self::Tga => cfg!(feature = "tga"),
+        }
+    }
+}
+
+/// Decodes an image file, identifying its format from its signature.
+///
+/// # Errors
+///
+/// This function returns [`Error::UnknownFormat`] if the file has no known
+/// signature, which includes every TGA file; use [`decode_as`] for those.
+/// See [`decode_as`] for other errors.
+pub fn decode(bytes: &[u8]) -> Result<Raster, Error> {
+    let format = FileFormat::from_signature(bytes).ok_or(Error::UnknownFormat)?;
+    decode_as(format, bytes)
+}
+
+/// Decodes an image file of a known format.
+///
+/// # Errors
+///
+/// This function returns an error if support for the format is disabled, if
+/// the file is malformed or uses features that are not supported, or if the
+/// image is larger than [`GFX_IMAGE_EXTENT`](crate::limits::GFX_IMAGE_EXTENT).
+#[allow(unreachable_code, unused_variables)]
+pub fn decode_as(format: FileFormat, bytes: &[u8]) -> Result<Raster, Error> {
+    match format {
+        #[cfg(feature = "png")]
+        FileFormat::Png => png::decode(bytes),
+        #[cfg(feature = "jpeg")]
+        FileFormat::Jpeg => jpeg::decode(bytes),
+        #[cfg(feature = "qoi")]
+        FileFormat::Qoi => qoi::decode(bytes),
+        #[cfg(feature = "bmp")]
+        FileFormat::Bmp => bmp::decode(bytes),
+        #[cfg(feature = "tga")]
+        FileFormat::Tga => tga::decode(bytes),
+        #[allow(unreachable_patterns)]
+        format => Err(Error::Disabled(format)),
+    }
+}
+
+/// Encodes an image in a file format.
+///
+/// Colors are written in sRGB, except for 8-bit linear layouts which are
+/// written as linear. [`Layout::Rgba16Float`] colors are clipped to SDR
+/// white, and signed distance fields are written as they are.
+///
+/// # Errors
+///
+/// This function returns [`Error::NoEncoder`] if the format cannot be
+/// encoded, or [`Error::Disabled`] if support for the format is disabled.
+#[allow(unreachable_code, unused_variables)]
+pub fn encode(format: FileFormat, pixels: &RasterBuf) -> Result<Vec<u8>, Error> {
+    let extent = pixels.info().extent;
+
+    match format {
+        #[cfg(feature = "png")]
+        FileFormat::Png => {
+            let (format, rgba) = straight_rgba(pixels);
+            png::encode(extent, format, &rgba)
+        }
+        #[cfg(feature = "qoi")]
+        FileFormat::Qoi => {
+            let (format, rgba) = straight_rgba(pixels);
+            Ok(qoi::encode(extent, format, &rgba))
+        }
+        FileFormat::Jpeg | FileFormat::Bmp | FileFormat::Tga => Err(Error::NoEncoder(format)),
+        #[allow(unreachable_patterns)]
+        format => Err(Error::Disabled(format)),
+    }
+}
+
+/// Encodes an image and writes it to a file, choosing the file format from
+/// the path's extension.
+///
+/// # Errors
+///
+/// This function returns [`Error::UnknownFormat`] if the extension is not
+/// that of a known file format, and [`Error::Io`] if the file could not be
+/// written. See [`encode`] for other errors.
+pub fn save(path: impl AsRef<Path>, pixels: &RasterBuf) -> Result<(), Error> {
+    let path = path.as_ref();
+    let format = path
+        .extension()
+        .and_then(|extension| extension.to_str())
+        .and_then(FileFormat::from_extension)
+        .ok_or(Error::UnknownFormat)?;
+
+    std::fs::write(path, encode(format, pixels)?)?;
+    Ok(())
+}
+
+/// Checks that an image's extent is within [`GFX_IMAGE_EXTENT`], so that the
+/// decoded raster can be made into an [`Image`](super::Image). Decoders call
+/// this before allocating space for the pixels.
+fn image_extent(width: u32, height: u32) -> Result<Extent<Texel>, Error> {
+    let (Ok(width), Ok(height)) = (i16::try_from(width), i16::try_from(height)) else {
+        return Err(Error::SizeLimit);
+    };
+
+    if width == 0 || height == 0 {
+        return Err(Error::Malformed("image has no pixels"));
+    }
+
+    let extent = Extent::new(width, height);
+    GFX_IMAGE_EXTENT.test(extent, Error::SizeLimit)?;
+    Ok(extent)
+}
+
+/// Makes a raster from pixels in RGBA order with straight alpha.
+///
+/// Colors are premultiplied in linear space, like those of
+/// [`Raster::from_linear_rgba`].
+fn finish(extent: Extent<Texel>, format: Format, mut rgba: Vec<u8>) -> Raster {
+    #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+    let unorm = |value: f32| (value * 255.0).round() as u8;
+
+    for pixel in rgba.chunks_exact_mut(4) {
+        if pixel[3] == 255 {
+            continue;
+        }
+
+        let alpha = f32::from(pixel[3]) / 255.0;
+
+        for c in &mut pixel[..3] {
+            let value = f32::from(*c) / 255.0;
+            *c = match format {
+                Format::Srgb => unorm(linear_to_srgb(srgb_to_linear(value) * alpha)),
+                Format::Linear | Format::Unkown => unorm(value * alpha),
+            };
+        }
+    }
+
+    Raster::new(
+        Info {
+            extent,
+            format,
+            layout: Layout::Rgba8,
+        },
+        rgba,
+    )
+}
+
+/// Converts pixels to RGBA with straight alpha, and returns the format that
+/// the colors are in. This is the inverse of [`finish`].
+fn straight_rgba(pixels: &RasterBuf) -> (Format, Vec<u8>) {
+    let info = pixels.info();
+
+    match info.layout {
+        Layout::Rgba8Vector => return (Format::Linear, pixels.data().to_vec()),
+        Layout::Alpha8Vector => {
+            let rgba = pixels.data().iter().flat_map(|&v| [v, v, v, 255]);
+            return (Format::Linear, rgba.collect());
+        }
+        _ => {}
+    }
+
+    let format = match (info.layout, info.format) {
+        (Layout::Rgba16Float, _) | (_, Format::Srgb) => Format::Srgb,
+        _ => Format::Linear,
+    };
+
+    #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+    let unorm = |value: f32| (value.clamp(0.0, 1.0) * 255.0).round() as u8;
+
+    let rgba = pixels
+        .to_linear_rgba()
+        .into_iter()
+        .flat_map(|[r, g, b, a]| {
+            if a <= 0.0 {
+                return [0; 4];
+            }
+
+            let color = |c: f32| match format {
+                Format::Srgb => unorm(linear_to_srgb((c / a).clamp(0.0, 1.0))),
+                Format::Linear | Format::Unkown => unorm(c / a),
+            };
+
+            [color(r), color(g), color(b), unorm(a)]
+        })
+        .collect();
+
+    (format, rgba)
+}
+
+/// Reads the fields of a file in order.
+struct Reader<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn new(bytes: &'a [u8]) -> Self {
+        Self { bytes }
+    }
+
+    fn remaining(&self) -> &'a [u8] {
+        self.bytes
+    }
+
+    fn take(&mut self, len: usize) -> Result<&'a [u8], Error> {
+        if len > self.bytes.len() {
+            return Err(Error::Malformed("the file ends unexpectedly"));
+        }
+
+        let (taken, rest) = self.bytes.split_at(len);
+        self.bytes = rest;
+        Ok(taken)
+    }
+
+    fn skip(&mut self, len: usize) -> Result<(), Error> {
+        self.take(len).map(|_| ())
+    }
+
+    fn array<const N: usize>(&mut self) -> Result<[u8; N], Error> {
+        Ok(self.take(N)?.try_into().unwrap())
+    }
+
+    fn u8(&mut self) -> Result<u8, Error> {
+        Ok(self.array::<1>()?[0])
+    }
+
+    fn u16_le(&mut self) -> Result<u16, Error> {
+        Ok(u16::from_le_bytes(self.array()?))
+    }
+
+    fn u32_le(&mut self) -> Result<u32, Error> {
+        Ok(u32::from_le_bytes(self.array()?))
+    }
+
+    fn i32_le(&mut self) -> Result<i32, Error> {
+        Ok(i32::from_le_bytes(self.array()?))
+    }
+
+    fn u32_be(&mut self) -> Result<u32, Error> {
+        Ok(u32::from_be_bytes(self.array()?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rgba(raster: &Raster) -> Vec<[u8; 4]> {
+        raster
+            .data()
+            .chunks_exact(4)
+            .map(|pixel| pixel.try_into().unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn signatures() {
+        assert_eq!(
+            FileFormat::from_signature(b"\x89PNG\r\n\x1a\n...."),
+            Some(FileFormat::Png)
+        );
+        assert_eq!(FileFormat::from_signature(b"qoif"), Some(FileFormat::Qoi));
+        assert_eq!(FileFormat::from_signature(b"\0\0\x02"), None);
+        assert_eq!(FileFormat::from_extension("JPG"), Some(FileFormat::Jpeg));
+        assert!(matches!(decode(b"GIF89a"), Err(Error::UnknownFormat)));
+    }
+
+    #[test]
+    #[cfg(feature = "qoi")]
+    fn qoi() {
+        #[rustfmt::skip]
+        let file = [
+            b'q', b'o', b'i', b'f', 0, 0, 0, 3, 0, 0, 0, 1, 4, 0,
+            0xFE, 255, 0, 0, // rgb
+            0xFF, 0, 0, 255, 128, // rgba
+            0b1100_0000, // run of 1
+            0, 0, 0, 0, 0, 0, 0, 1,
+        ];
+
+        let raster = decode(&file).unwrap();
+        assert_eq!(raster.info().extent, Extent::new(3, 1));
+        assert_eq!(raster.info().format, Format::Srgb);
+        // translucent colors are premultiplied in linear space
+        assert_eq!(
+            rgba(&raster),
+            [[255, 0, 0, 255], [0, 0, 188, 128], [0, 0, 188, 128]]
+        );
+
+        assert!(matches!(decode(&file[..20]), Err(Error::Malformed(_))));
+    }
+
+    #[test]
+    #[cfg(feature = "bmp")]
+    fn bmp() {
+        // 2x2, 24 bits per pixel, stored bottom-up with rows padded to 4 bytes
+        #[rustfmt::skip]
+        let mut file = vec![
+            b'B', b'M', 70, 0, 0, 0, 0, 0, 0, 0, 54, 0, 0, 0,
+            40, 0, 0, 0, 2, 0, 0, 0, 2, 0, 0, 0, 1, 0, 24, 0,
+            0, 0, 0, 0, 16, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
+            0, 0, 0, 0, 0, 0, 0, 0,
+        ];
+        file.extend_from_slice(&[255, 0, 0, 0, 255, 0, 0, 0]);
+        file.extend_from_slice(&[0, 0, 255, 255, 255, 255, 0, 0]);
+
+        let raster = decode(&file).unwrap();
+        assert_eq!(
+            rgba(&raster),
+            [
+                [255, 0, 0, 255],
+                [255, 255, 255, 255],
+                [0, 0, 255, 255],
+                [0, 255, 0, 255],
+            ]
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "tga")]
+    fn tga() {
+        // 3x1, run-length encoded, 32 bits per pixel, stored top-down
+        #[rustfmt::skip]
+        let file = [
+            0, 0, 10, 0, 0, 0, 0, 0, 0, 0, 0, 0, 3, 0, 1, 0, 32, 0x28,
+            0x81, 0, 255, 0, 255, // run of 2 green pixels
+            0x00, 255, 0, 0, 0, // 1 raw transparent pixel
+        ];
+
+        assert!(matches!(decode(&file), Err(Error::UnknownFormat)));
+
+        let raster = decode_as(FileFormat::Tga, &file).unwrap();
+        assert_eq!(
+            rgba(&raster),
+            [[0, 255, 0, 255], [0, 255, 0, 255], [0, 0, 0, 0]]
+        );
+
+        // a color map with 0 bits per entry
+        #[rustfmt::skip]
+        let file = [
+            0, 1, 1, 0, 0, 4, 0, 0, 0, 0, 0, 0, 1, 0, 1, 0, 8, 0,
+            0,
+        ];
+        assert!(matches!(
+            decode_as(FileFormat::Tga, &file),
+            Err(Error::Unsupported(_))
+        ));
+
+        // an image with no pixels
+        let mut file = file;
+        file[12] = 0;
+        assert!(matches!(
+            decode_as(FileFormat::Tga, &file),
+            Err(Error::Malformed(_))
+        ));
+    }
+
+    #[test]
+    #[cfg(all(feature = "png", feature = "qoi"))]
+    fn round_trip() {
+        // every 8-bit value survives, and translucent colors are premultiplied
+        // again after being written with straight alpha
+        let data: Vec<u8> = (0..=255)
+            .flat_map(|v| [v, 255 - v, v / 2, 255])
+            .chain([100, 50, 0, 128, 0, 0, 0, 0])
+            .collect();
+
+        for format in [Format::Srgb, Format::Linear] {
+            let info = Info {
+                extent: Extent::new(129, 2),
+                format,
+                layout: Layout::Rgba8,
+            };
+            let pixels = RasterBuf::new(info, &data);
+
+            for file_format in [FileFormat::Png, FileFormat::Qoi] {
+                let raster = decode(&encode(file_format, &pixels).unwrap()).unwrap();
+                assert_eq!(raster.info(), info);
+                assert!(raster
+                    .data()
+                    .iter()
+                    .zip(&data)
+                    .all(|(a, b)| a.abs_diff(*b) <= 1));
+            }
+        }
+
+        let info = Info {
+            extent: Extent::new(1, 1),
+            format: Format::Srgb,
+            layout: Layout::Rgba8,
+        };
+        assert!(matches!(
+            encode(FileFormat::Jpeg, &RasterBuf::new(info, &[0; 4])),
+            Err(Error::NoEncoder(FileFormat::Jpeg))
+        ));
+    }
+
+    #[test]
+    #[cfg(feature = "qoi")]
+    fn extent_limits() {
+        use crate::graphics::{Backend, Graphics, GraphicsConfig};
+
+        let info = Info {
+            extent: Extent::new(4096, 1),
+            format: Format::Srgb,
+            layout: Layout::Rgba8,
+        };
+        let file = encode(FileFormat::Qoi, &RasterBuf::new(info, &[255; 4096 * 4])).unwrap();
+        let raster = decode(&file).unwrap();
+        assert_eq!(raster.info(), info);
+
+        // as `AppContext::load_image` does
+        let graphics = Graphics::new(&GraphicsConfig {
+            backend: Backend::Software,
+            ..Default::default()
+        });
+        let image = graphics.create_raster_image(raster.info()).unwrap();
+        graphics
+            .upload_raster_image(image, &raster.as_buf())
+            .unwrap();
+        assert_eq!(image.extent(), info.extent);
+
+        // larger images are rejected before their pixels are read
+        let mut file = file;
+        file[4..8].copy_from_slice(&4097_u32.to_be_bytes());
+        assert!(matches!(decode(&file), Err(Error::SizeLimit)));
+    }
+
+    #[test]
+    #[cfg(feature = "png")]
+    fn png() {
+        let mut file = Vec::new();
+        {
+            let mut encoder = ::png::Encoder::new(&mut file, 2, 1);
+            encoder.set_color(::png::ColorType::GrayscaleAlpha);
+            let mut writer = encoder.write_header().unwrap();
+            writer.write_image_data(&[255, 255, 0, 64]).unwrap();
+        }
+
+        let raster = decode(&file).unwrap();
+        assert_eq!(rgba(&raster), [[255, 255, 255, 255], [0, 0, 0, 64]]);
+    }
+}
@@ -0,0 +1,71 @@
+//! PNG images, decoded and encoded with the `png` crate. 16-bit channels are
+//! reduced to 8 bits and the first frame of animated images is used.
+
+use ::png::{
+    BitDepth, ColorType, Decoder, Encoder, ScaledFloat, SrgbRenderingIntent, Transformations,
+};
+
+use crate::{
+    geometry::{Extent, Texel},
+    graphics::Format,
+};
+
+use super::{finish, image_extent, Error, Raster};
+
+/// A gamma of 1.0, as stored in the gAMA chunk.
+const LINEAR_GAMMA: u32 = 100_000;
+
+pub(super) fn decode(bytes: &[u8]) -> Result<Raster, Error> {
+    let mut decoder = Decoder::new(bytes);
+    decoder.set_transformations(Transformations::EXPAND | Transformations::STRIP_16);
+
+    let mut reader = decoder.read_info()?;
+    let info = reader.info();
+    let extent = image_extent(info.width, info.height)?;
+
+    // Images are assumed to be sRGB unless they say that they are linear.
+    let format = match (info.srgb, info.gama_chunk) {
+        (None, Some(gamma)) if gamma.into_scaled().abs_diff(LINEAR_GAMMA) < 1000 => Format::Linear,
+        _ => Format::Srgb,
+    };
+
+    let mut buffer = vec![0; reader.output_buffer_size()];
+    let frame = reader.next_frame(&mut buffer)?;
+    buffer.truncate(frame.buffer_size());
+
+    let rgba = match frame.color_type {
+        ColorType::Grayscale => buffer.iter().flat_map(|&l| [l, l, l, 255]).collect(),
+        ColorType::GrayscaleAlpha => buffer
+            .chunks_exact(2)
+            .flat_map(|la| [la[0], la[0], la[0], la[1]])
+            .collect(),
+        ColorType::Rgb => buffer
+            .chunks_exact(3)
+            .flat_map(|rgb| [rgb[0], rgb[1], rgb[2], 255])
+            .collect(),
+        ColorType::Rgba => buffer,
+        ColorType::Indexed => return Err(Error::Malformed("PNG palette could not be expanded")),
+    };
+
+    Ok(finish(extent, format, rgba))
+}
+
+pub(super) fn encode(extent: Extent<Texel>, format: Format, rgba: &[u8]) -> Result<Vec<u8>, Error> {
+    let mut file = Vec::new();
+
+    #[allow(clippy::cast_sign_loss)]
+    let mut encoder = Encoder::new(&mut file, extent.width.0 as u32, extent.height.0 as u32);
+    encoder.set_color(ColorType::Rgba);
+    encoder.set_depth(BitDepth::Eight);
+
+    match format {
+        Format::Srgb => encoder.set_source_srgb(SrgbRenderingIntent::Perceptual),
+        Format::Linear | Format::Unkown => encoder.set_source_gamma(ScaledFloat::new(1.0)),
+    }
+
+    let mut writer = encoder.write_header()?;
+    writer.write_image_data(rgba)?;
+    writer.finish()?;
+
+    Ok(file)
+}
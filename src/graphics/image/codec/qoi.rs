@@ -0,0 +1,161 @@
+//! The Quite OK Image format. See <https://qoiformat.org/qoi-specification.pdf>.
+
+use crate::{
+    geometry::{Extent, Texel},
+    graphics::Format,
+};
+
+use super::{finish, image_extent, Error, Raster, Reader};
+
+const OP_INDEX: u8 = 0b00;
+const OP_DIFF: u8 = 0b01;
+const OP_LUMA: u8 = 0b10;
+const OP_RUN: u8 = 0b11;
+const OP_RGB: u8 = 0xFE;
+const OP_RGBA: u8 = 0xFF;
+
+/// The index of a color in the table of recently seen colors.
+fn hash([r, g, b, a]: [u8; 4]) -> usize {
+    (usize::from(r) * 3 + usize::from(g) * 5 + usize::from(b) * 7 + usize::from(a) * 11) % 64
+}
+
+pub(super) fn decode(bytes: &[u8]) -> Result<Raster, Error> {
+    let mut reader = Reader::new(bytes);
+
+    if reader.array()? != *b"qoif" {
+        return Err(Error::Malformed("missing QOI signature"));
+    }
+
+    let extent = image_extent(reader.u32_be()?, reader.u32_be()?)?;
+    let _channels = reader.u8()?;
+
+    // Colors are either sRGB or linear. Alpha is always linear.
+    let format = match reader.u8()? {
+        0 => Format::Srgb,
+        1 => Format::Linear,
+        _ => return Err(Error::Malformed("invalid QOI color space")),
+    };
+
+    #[allow(clippy::cast_sign_loss)]
+    let count = extent.width.0 as usize * extent.height.0 as usize;
+    let mut rgba = Vec::with_capacity(count * 4);
+
+    let mut seen = [[0u8; 4]; 64];
+    let mut pixel = [0, 0, 0, 255];
+    let mut run = 0;
+
+    for _ in 0..count {
+        if run > 0 {
+            run -= 1;
+        } else {
+            let op = reader.u8()?;
+
+            match (op, op >> 6) {
+                (OP_RGB, _) => {
+                    let [r, g, b] = reader.array()?;
+                    pixel = [r, g, b, pixel[3]];
+                }
+                (OP_RGBA, _) => pixel = reader.array()?,
+                (_, OP_INDEX) => pixel = seen[usize::from(op)],
+                (_, OP_DIFF) => {
+                    let diff = |shift: u8| ((op >> shift) & 0b11).wrapping_sub(2);
+                    pixel[0] = pixel[0].wrapping_add(diff(4));
+                    pixel[1] = pixel[1].wrapping_add(diff(2));
+                    pixel[2] = pixel[2].wrapping_add(diff(0));
+                }
+                (_, OP_LUMA) => {
+                    let next = reader.u8()?;
+                    let dg = (op & 0b11_1111).wrapping_sub(32);
+                    let dr = dg.wrapping_add(next >> 4).wrapping_sub(8);
+                    let db = dg.wrapping_add(next & 0b1111).wrapping_sub(8);
+                    pixel[0] = pixel[0].wrapping_add(dr);
+                    pixel[1] = pixel[1].wrapping_add(dg);
+                    pixel[2] = pixel[2].wrapping_add(db);
+                }
+                (_, OP_RUN) => run = op & 0b11_1111,
+                _ => unreachable!(),
+            }
+
+            seen[hash(pixel)] = pixel;
+        }
+
+        rgba.extend_from_slice(&pixel);
+    }
+
+    Ok(finish(extent, format, rgba))
+}
+
+pub(super) fn encode(extent: Extent<Texel>, format: Format, rgba: &[u8]) -> Vec<u8> {
+    let mut file = Vec::with_capacity(14 + rgba.len() + 8);
+
+    #[allow(clippy::cast_sign_loss)]
+    let (width, height) = (extent.width.0 as u32, extent.height.0 as u32);
+    file.extend_from_slice(b"qoif");
+    file.extend_from_slice(&width.to_be_bytes());
+    file.extend_from_slice(&height.to_be_bytes());
+    file.push(4);
+    file.push(match format {
+        Format::Srgb => 0,
+        Format::Linear | Format::Unkown => 1,
+    });
+
+    let mut seen = [[0u8; 4]; 64];
+    let mut prev = [0, 0, 0, 255];
+    let mut run = 0;
+
+    let pixels = rgba.chunks_exact(4);
+    let count = pixels.len();
+
+    for (i, pixel) in pixels.enumerate() {
+        let pixel: [u8; 4] = pixel.try_into().unwrap();
+
+        if pixel == prev {
+            run += 1;
+            if run == 62 || i == count - 1 {
+                file.push((OP_RUN << 6) | (run - 1));
+                run = 0;
+            }
+            continue;
+        }
+
+        if run > 0 {
+            file.push((OP_RUN << 6) | (run - 1));
+            run = 0;
+        }
+
+        let index = hash(pixel);
+
+        if seen[index] == pixel {
+            #[allow(clippy::cast_possible_truncation)]
+            file.push((OP_INDEX << 6) | index as u8);
+        } else {
+            seen[index] = pixel;
+
+            if pixel[3] == prev[3] {
+                let dr = pixel[0].wrapping_sub(prev[0]).wrapping_add(2);
+                let dg = pixel[1].wrapping_sub(prev[1]).wrapping_add(2);
+                let db = pixel[2].wrapping_sub(prev[2]).wrapping_add(2);
+
+                // the luma differences are relative to the green difference
+                let dg_luma = dg.wrapping_add(30);
+                let dr_dg = dr.wrapping_sub(dg).wrapping_add(8);
+                let db_dg = db.wrapping_sub(dg).wrapping_add(8);
+
+                if dr < 4 && dg < 4 && db < 4 {
+                    file.push((OP_DIFF << 6) | (dr << 4) | (dg << 2) | db);
+                } else if dg_luma < 64 && dr_dg < 16 && db_dg < 16 {
+                    file.extend_from_slice(&[(OP_LUMA << 6) | dg_luma, (dr_dg << 4) | db_dg]);
+                } else {
+                    file.extend_from_slice(&[OP_RGB, pixel[0], pixel[1], pixel[2]]);
+                }
+            } else {
+                file.extend_from_slice(&[OP_RGBA, pixel[0], pixel[1], pixel[2], pixel[3]]);
+            }
+        }
+
+        prev = pixel;
+    }
+
+    file.extend_from_slice(&[0, 0, 0, 0, 0, 0, 0, 1]);
+    file
+}
@@ -0,0 +1,163 @@
+//! Truevision TGA images, with or without a color map or run-length encoding.
+
+use crate::graphics::Format;
+
+use super::{finish, image_extent, Error, Raster, Reader};
+
+const COLOR_MAPPED: u8 = 1;
+const TRUE_COLOR: u8 = 2;
+const GRAYSCALE: u8 = 3;
+const RLE: u8 = 8;
+
+/// Pixels are stored from the top down, rather than from the bottom up.
+const TOP_DOWN: u8 = 0b10_0000;
+/// Pixels are stored from right to left.
+const RIGHT_TO_LEFT: u8 = 0b1_0000;
+
+pub(super) fn decode(bytes: &[u8]) -> Result<Raster, Error> {
+    let mut reader = Reader::new(bytes);
+
+    let id_length = reader.u8()?;
+    let has_color_map = reader.u8()? == 1;
+    let image_type = reader.u8()?;
+    let map_first = reader.u16_le()?;
+    let map_length = reader.u16_le()?;
+    let map_bits = reader.u8()?;
+    reader.skip(4)?;
+    let extent = image_extent(u32::from(reader.u16_le()?), u32::from(reader.u16_le()?))?;
+    let bits = reader.u8()?;
+    let descriptor = reader.u8()?;
+
+    let kind = image_type & !RLE;
+    if !matches!(kind, COLOR_MAPPED | TRUE_COLOR | GRAYSCALE) {
+        return Err(Error::Unsupported("TGA images without pixel data"));
+    }
+
+    // The number of alpha bits is only a hint, but images that claim to have
+    // none often leave garbage in the alpha channel.
+    let has_alpha = descriptor & 0b1111 > 0;
+
+    reader.skip(usize::from(id_length))?;
+
+    let color_map = if has_color_map {
+        if !matches!(map_bits, 15 | 16 | 24 | 32) {
+            return Err(Error::Unsupported(
+                "TGA color maps with this many bits per entry",
+            ));
+        }
+
+        let size = usize::from(map_bits).div_ceil(8);
+        let entries = reader.take(usize::from(map_length) * size)?;
+        entries
+            .chunks_exact(size)
+            .map(|entry| color(entry, map_bits, false, has_alpha))
+            .collect::<Result<Vec<_>, _>>()?
+    } else if kind == COLOR_MAPPED {
+        return Err(Error::Malformed(
+            "color-mapped TGA image without a color map",
+        ));
+    } else {
+        Vec::new()
+    };
+
+    let pixel_size = usize::from(bits).div_ceil(8);
+    if pixel_size == 0 || pixel_size > 4 {
+        return Err(Error::Unsupported(
+            "TGA images with this many bits per pixel",
+        ));
+    }
+
+    let read_pixel = |bytes: &[u8]| -> Result<[u8; 4], Error> {
+        if kind == COLOR_MAPPED {
+            let index = bytes
+                .iter()
+                .rev()
+                .fold(0usize, |index, &byte| (index << 8) | usize::from(byte));
+
+            color_map
+                .get(index.wrapping_sub(usize::from(map_first)))
+                .copied()
+                .ok_or(Error::Malformed("color map index out of range"))
+        } else {
+            color(bytes, bits, kind == GRAYSCALE, has_alpha)
+        }
+    };
+
+    #[allow(clippy::cast_sign_loss)]
+    let (width, height) = (extent.width.0 as usize, extent.height.0 as usize);
+    let count = width * height;
+    let mut pixels = Vec::with_capacity(count);
+
+    if image_type & RLE == 0 {
+        let data = reader.take(count * pixel_size)?;
+        for bytes in data.chunks_exact(pixel_size) {
+            pixels.push(read_pixel(bytes)?);
+        }
+    } else {
+        while pixels.len() < count {
+            let header = reader.u8()?;
+            let length = usize::from(header & 0x7F) + 1;
+
+            if header & 0x80 == 0 {
+                for _ in 0..length {
+                    pixels.push(read_pixel(reader.take(pixel_size)?)?);
+                }
+            } else {
+                let pixel = read_pixel(reader.take(pixel_size)?)?;
+                pixels.extend(std::iter::repeat_n(pixel, length));
+            }
+        }
+
+        // Packets may cross the end of a row, but not the end of the image.
+        pixels.truncate(count);
+    }
+
+    let mut rgba = Vec::with_capacity(count * 4);
+
+    for y in 0..height {
+        let row_index = if descriptor & TOP_DOWN == 0 {
+            height - 1 - y
+        } else {
+            y
+        };
+        let row = &pixels[row_index * width..][..width];
+
+        if descriptor & RIGHT_TO_LEFT == 0 {
+            row.iter().for_each(|pixel| rgba.extend_from_slice(pixel));
+        } else {
+            row.iter()
+                .rev()
+                .for_each(|pixel| rgba.extend_from_slice(pixel));
+        }
+    }
+
+    Ok(finish(extent, Format::Srgb, rgba))
+}
+
+/// Reads a color that is stored in BGR(A) order, or as gray with optional
+/// alpha.
+fn color(bytes: &[u8], bits: u8, gray: bool, has_alpha: bool) -> Result<[u8; 4], Error> {
+    let alpha = |a: u8| if has_alpha { a } else { 255 };
+
+    Ok(match (gray, bits) {
+        (true, 8) => [bytes[0], bytes[0], bytes[0], 255],
+        (true, 16) => [bytes[0], bytes[0], bytes[0], alpha(bytes[1])],
+        (false, 15 | 16) => {
+            let value = u16::from_le_bytes([bytes[0], bytes[1]]);
+            #[allow(clippy::cast_possible_truncation)]
+            let channel = |shift: u16| {
+                let c = ((value >> shift) & 0x1F) as u8;
+                (c << 3) | (c >> 2)
+            };
+            let a = if value & 0x8000 == 0 { 0 } else { 255 };
+            [channel(10), channel(5), channel(0), alpha(a)]
+        }
+        (false, 24) => [bytes[2], bytes[1], bytes[0], 255],
+        (false, 32) => [bytes[2], bytes[1], bytes[0], alpha(bytes[3])],
+        _ => {
+            return Err(Error::Unsupported(
+                "TGA images with this many bits per pixel",
+            ))
+        }
+    })
+}
@@ -0,0 +1,695 @@
+pub mod codec;
+
+use std::{fmt::Debug, ptr::addr_of};
+
+use crate::{
+    geometry::{Extent, Rect, Texel},
+    graphics::{
+        color::{linear_to_srgb, srgb_to_linear},
+        texture_atlas::CachedTextureId,
+    },
+    limits::{GFX_IMAGE_COUNT, GFX_IMAGE_EXTENT, GFX_TEXTURE_COUNT},
+};
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error("The image size exceeds the max image size.")]
+    SizeLimit,
+    #[error("The image size does not agree with the number of bytes provided.")]
+    SizeError,
+    #[error("The pixel layout or format does not agree with the image.")]
+    FormatMismatch,
+    #[error("The image layout cannot be stored in the image format.")]
+    UnsupportedFormat,
+    #[error("The image could not be created because the image count limit ({}) or the texture count limit ({}) has been reached ", GFX_IMAGE_COUNT.get(), GFX_TEXTURE_COUNT.get())]
+    MaxCount,
+    #[error("The image handle has expired.")]
+    Expired,
+}
+
+/// The layout of pixel data in memory.
+#[repr(u8)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Layout {
+    Rgba8,
+    /// A multi-channel signed distance field. See
+    /// [`Raster::msdf_from_path`].
+    Rgba8Vector,
+    Bgra8,
+    Alpha8,
+    /// A single-channel signed distance field. See
+    /// [`Raster::sdf_from_alpha`].
+    Alpha8Vector,
+    /// Half-precision floating point RGBA, which can hold colors brighter
+    /// than white for HDR. The format must be [`Format::Linear`].
+    Rgba16Float,
+}
+
+impl Layout {
+    #[must_use]
+    pub const fn bytes_per_pixel(self) -> usize {
+        match self {
+            Self::Rgba8 | Self::Rgba8Vector | Self::Bgra8 => 4,
+            Self::Alpha8 | Self::Alpha8Vector => 1,
+            Self::Rgba16Float => 8,
+        }
+    }
+}
+
+impl From<u8> for Layout {
+    fn from(value: u8) -> Self {
+        match value {
+            0 => Self::Rgba8,
+            1 => Self::Rgba8Vector,
+            2 => Self::Bgra8,
+            3 => Self::Alpha8,
+            4 => Self::Alpha8Vector,
+            5 => Self::Rgba16Float,
+            _ => panic!("Invalid layout value: {value}"),
+        }
+    }
+}
+
+/// The color space that pixel data is encoded in.
+#[repr(u8)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Format {
+    Unkown,
+    Srgb,
+    Linear,
+}
+
+impl From<u8> for Format {
+    fn from(value: u8) -> Self {
+        match value {
+            0 => Self::Unkown,
+            1 => Self::Srgb,
+            2 => Self::Linear,
+            _ => panic!("Invalid format value: {value}"),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Info {
+    pub extent: Extent<Texel>,
+    pub format: Format,
+    pub layout: Layout,
+}
+
+impl Info {
+    #[must_use]
+    pub const fn row_size(&self) -> usize {
+        self.extent.width.0 as usize * self.layout.bytes_per_pixel()
+    }
+
+    /// Whether images and render targets can be created with this layout and
+    /// format. Only [`Layout::Rgba8`] and [`Layout::Bgra8`] can be stored as
+    /// sRGB, and no layout can be stored in an unknown format.
+    #[must_use]
+    pub const fn is_supported(&self) -> bool {
+        match self.format {
+            Format::Unkown => false,
+            Format::Srgb => matches!(self.layout, Layout::Rgba8 | Layout::Bgra8),
+            Format::Linear => true,
+        }
+    }
+}
+
+impl Info {
+    pub(crate) fn pack(self) -> PackedInfo {
+        PackedInfo::new()
+            .with_width(self.extent.width.0)
+            .with_height(self.extent.height.0)
+            .with_layout(self.layout as u8)
+            .with_format(self.format as u8)
+    }
+}
+
+/// A handle to an image.
+///
+/// Once created, images are immutable.
+///
+/// An image may also be a view of a region of another image, made with
+/// [`Image::sub_image`], which shares its texture. Its extent is the extent of
+/// the region.
+#[derive(Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Image {
+    pub(crate) info: PackedInfo,
+    pub(crate) key: PackedKey,
+    pub(crate) offset: PackedOffset,
+}
+
+impl Image {
+    /// A view of a region of this image. Drawing the view draws only that
+    /// region, and deleting it deletes the whole image.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the region is empty or not within the image.
+    #[must_use]
+    #[allow(clippy::cast_sign_loss)]
+    pub fn sub_image(&self, region: Rect<Texel>) -> Self {
+        let extent = self.extent();
+        let [x, y, width, height] = region.to_xywh().map(|v| v.0);
+
+        assert!(
+            x >= 0
+                && y >= 0
+                && width > 0
+                && height > 0
+                && x + width <= extent.width.0
+                && y + height <= extent.height.0,
+            "Sub-image region {region:?} is not within the image's extent {extent:?}."
+        );
+
+        Self {
+            info: self
+                .info
+                .with_width(width)
+                .with_height(height)
+                .with_mipmapped(false),
+            key: self.key,
+            offset: PackedOffset::new()
+                .with_x(self.offset.x() + x as u16)
+                .with_y(self.offset.y() + y as u16)
+                .with_is_view(true),
+        }
+    }
+
+    pub(crate) fn cache_id(&self) -> CachedTextureId {
+        CachedTextureId::new(self.key.index(), self.key.epoch())
+    }
+
+    /// Whether the image was created with mipmaps. Sub-images are drawn
+    /// without them.
+    #[must_use]
+    pub fn is_mipmapped(&self) -> bool {
+        self.info.mipmapped()
+    }
+
+    /// The region of its cached rect that the image refers to, or `None` for
+    /// the whole rect. This is only the full-size level of a mipmapped image.
+    #[allow(clippy::cast_possible_wrap)]
+    pub(crate) fn region(&self) -> Option<Rect<Texel>> {
+        if self.offset.is_view() {
+            Some(Rect::new(
+                (Texel(self.offset.x() as i16), Texel(self.offset.y() as i16)),
+                self.extent(),
+            ))
+        } else if self.is_mipmapped() {
+            Some(Rect::from_extent(self.extent()))
+        } else {
+            None
+        }
+    }
+
+    #[must_use]
+    pub fn extent(&self) -> Extent<Texel> {
+        Extent::new(Texel(self.info.width()), Texel(self.info.height()))
+    }
+
+    #[must_use]
+    pub fn layout(&self) -> Layout {
+        self.info.layout().into()
+    }
+
+    #[must_use]
+    pub fn format(&self) -> Format {
+        self.info.format().into()
+    }
+}
+
+#[allow(clippy::missing_fields_in_debug)]
+impl Debug for Image {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Image")
+            .field("extent", &self.extent())
+            .field("layout", &self.layout())
+            .field("format", &self.format())
+            .field("index", &self.key.index())
+            .field("epoch", &self.key.epoch())
+            .field("offset", &(self.offset.x(), self.offset.y()))
+            .finish()
+    }
+}
+
+/// Frames of the same size laid out in rows within an image, like the frames
+/// of an animation or a strip of icons. Frames are numbered from left to
+/// right, then top to bottom.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct SpriteSheet {
+    image: Image,
+    frame: Extent<Texel>,
+    columns: i16,
+    rows: i16,
+}
+
+impl SpriteSheet {
+    /// Divides an image into frames of `frame` texels. Any space left over at
+    /// the right and bottom edges of the image is not used.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the frame is empty or larger than the image.
+    #[must_use]
+    pub fn new(image: Image, frame: Extent<Texel>) -> Self {
+        let extent = image.extent();
+        assert!(
+            frame.width.0 > 0
+                && frame.height.0 > 0
+                && frame.width <= extent.width
+                && frame.height <= extent.height,
+            "Sprite frame {frame:?} does not fit in the image's extent {extent:?}."
+        );
+
+        Self {
+            image,
+            frame,
+            columns: extent.width.0 / frame.width.0,
+            rows: extent.height.0 / frame.height.0,
+        }
+    }
+
+    #[must_use]
+    pub fn image(&self) -> Image {
+        self.image
+    }
+
+    #[must_use]
+    pub fn frame_extent(&self) -> Extent<Texel> {
+        self.frame
+    }
+
+    #[must_use]
+    #[allow(clippy::cast_sign_loss)]
+    pub fn len(&self) -> usize {
+        self.columns as usize * self.rows as usize
+    }
+
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// A view of one frame of the sheet.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `index` is not less than the number of frames.
+    #[must_use]
+    #[allow(clippy::cast_possible_truncation, clippy::cast_possible_wrap)]
+    pub fn frame(&self, index: usize) -> Image {
+        assert!(
+            index < self.len(),
+            "Sprite frame {index} is out of range ({}).",
+            self.len()
+        );
+
+        let columns = self.columns as usize;
+        let (column, row) = ((index % columns) as i16, (index / columns) as i16);
+
+        self.image.sub_image(Rect::new(
+            (
+                Texel(column * self.frame.width.0),
+                Texel(row * self.frame.height.0),
+            ),
+            self.frame,
+        ))
+    }
+
+    /// Views of every frame, in order.
+    pub fn frames(&self) -> impl Iterator<Item = Image> + '_ {
+        (0..self.len()).map(|index| self.frame(index))
+    }
+}
+
+/// Non-owning reference to pixel data.
+#[derive(Clone, PartialEq, Eq, Hash)]
+pub struct RasterBuf<'a> {
+    info: Info,
+    data: &'a [u8],
+}
+
+impl<'a> RasterBuf<'a> {
+    #[must_use]
+    pub const fn new(info: Info, data: &[u8]) -> RasterBuf {
+        GFX_IMAGE_EXTENT.check(info.extent);
+
+        let row_size = info.row_size();
+        assert!(
+            data.len() % row_size == 0,
+            "Data size does not agree with info."
+        );
+
+        RasterBuf { info, data }
+    }
+
+    pub fn try_new(info: Info, data: &'a [u8]) -> Result<RasterBuf<'a>, Error> {
+        GFX_IMAGE_EXTENT.test(info.extent, Error::SizeLimit)?;
+
+        let row_size = info.row_size();
+        if data.len() % row_size != 0 {
+            return Err(Error::SizeError);
+        }
+
+        Ok(RasterBuf { info, data })
+    }
+
+    #[must_use]
+    pub const fn info(&self) -> Info {
+        self.info
+    }
+
+    #[must_use]
+    pub const fn width(&self) -> Texel {
+        self.info.extent.width
+    }
+
+    #[must_use]
+    pub const fn height(&self) -> Texel {
+        self.info.extent.height
+    }
+
+    #[must_use]
+    pub const fn data(&self) -> &[u8] {
+        self.data
+    }
+
+    #[must_use]
+    pub const fn unwrap(self) -> (Info, &'a [u8]) {
+        (self.info, self.data)
+    }
+
+    #[must_use]
+    pub const fn row_size(&self) -> usize {
+        self.info.row_size()
+    }
+
+    /// Decodes the pixels into linear, premultiplied RGBA. This is the
+    /// inverse of [`Raster::from_linear_rgba`].
+    ///
+    /// Single-channel layouts decode to premultiplied white.
+    pub(crate) fn to_linear_rgba(&self) -> Vec<[f32; 4]> {
+        let unorm = |b: u8| f32::from(b) / 255.0;
+        let color = |b: u8| match self.info.format {
+            Format::Srgb => srgb_to_linear(unorm(b)),
+            Format::Linear | Format::Unkown => unorm(b),
+        };
+
+        self.data
+            .chunks_exact(self.info.layout.bytes_per_pixel())
+            .map(|bytes| match self.info.layout {
+                Layout::Rgba8 | Layout::Rgba8Vector => [
+                    color(bytes[0]),
+                    color(bytes[1]),
+                    color(bytes[2]),
+                    unorm(bytes[3]),
+                ],
+                Layout::Bgra8 => [
+                    color(bytes[2]),
+                    color(bytes[1]),
+                    color(bytes[0]),
+                    unorm(bytes[3]),
+                ],
+                Layout::Alpha8 | Layout::Alpha8Vector => [unorm(bytes[0]); 4],
+                Layout::Rgba16Float => std::array::from_fn(|i| {
+                    f16_to_f32(u16::from_le_bytes([bytes[2 * i], bytes[2 * i + 1]]))
+                }),
+            })
+            .collect()
+    }
+}
+
+impl Debug for RasterBuf<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("PixelBuf")
+            .field("info", &self.info)
+            .field("data", &addr_of!(self.data))
+            .finish()
+    }
+}
+
+/// Owned pixel data.
+#[derive(Clone, PartialEq, Eq, Hash)]
+pub struct Raster {
+    info: Info,
+    data: Vec<u8>,
+}
+
+impl Raster {
+    /// Creates a raster from tightly packed rows of pixels.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the number of bytes does not agree with the info.
+    #[must_use]
+    pub fn new(info: Info, data: Vec<u8>) -> Self {
+        assert_eq!(
+            data.len(),
+            info.row_size() * info.extent.height.0 as usize,
+            "Data size does not agree with info."
+        );
+
+        Self { info, data }
+    }
+
+    /// Encodes linear, premultiplied RGBA pixels into the layout and format
+    /// described by `info`.
+    ///
+    /// Single-channel layouts keep only the alpha channel. Only
+    /// [`Layout::Rgba16Float`] keeps values outside of the range 0 to 1.
+    pub(crate) fn from_linear_rgba(info: Info, pixels: &[[f32; 4]]) -> Self {
+        assert!(
+            info.is_supported(),
+            "Unsupported layout and format combination: {:?} {:?}",
+            info.layout,
+            info.format
+        );
+
+        #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+        let unorm = |value: f32| (value.clamp(0.0, 1.0) * 255.0).round() as u8;
+
+        let color = |value: f32| match info.format {
+            Format::Srgb => unorm(linear_to_srgb(value.clamp(0.0, 1.0))),
+            Format::Linear | Format::Unkown => unorm(value),
+        };
+
+        let mut data = Vec::with_capacity(pixels.len() * info.layout.bytes_per_pixel());
+
+        for &[r, g, b, a] in pixels {
+            match info.layout {
+                Layout::Rgba8 | Layout::Rgba8Vector => {
+                    data.extend_from_slice(&[color(r), color(g), color(b), unorm(a)]);
+                }
+                Layout::Bgra8 => data.extend_from_slice(&[color(b), color(g), color(r), unorm(a)]),
+                Layout::Alpha8 | Layout::Alpha8Vector => data.push(unorm(a)),
+                Layout::Rgba16Float => {
+                    for c in [r, g, b, a] {
+                        data.extend_from_slice(&f32_to_f16(c).to_le_bytes());
+                    }
+                }
+            }
+        }
+
+        Self::new(info, data)
+    }
+
+    #[must_use]
+    pub const fn info(&self) -> Info {
+        self.info
+    }
+
+    #[must_use]
+    pub const fn width(&self) -> Texel {
+        self.info.extent.width
+    }
+
+    #[must_use]
+    pub const fn height(&self) -> Texel {
+        self.info.extent.height
+    }
+
+    #[must_use]
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+
+    #[must_use]
+    pub fn into_data(self) -> Vec<u8> {
+        self.data
+    }
+
+    #[must_use]
+    pub const fn row_size(&self) -> usize {
+        self.info.row_size()
+    }
+
+    #[must_use]
+    pub fn as_buf(&self) -> RasterBuf<'_> {
+        RasterBuf {
+            info: self.info,
+            data: &self.data,
+        }
+    }
+}
+
+impl Debug for Raster {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Raster")
+            .field("info", &self.info)
+            .field("data", &self.data.as_ptr())
+            .finish()
+    }
+}
+
+/// Converts an IEEE 754 half-precision float to single precision.
+pub(crate) fn f16_to_f32(bits: u16) -> f32 {
+    let sign = u32::from(bits >> 15) << 31;
+    let exponent = u32::from((bits >> 10) & 0x1F);
+    let mantissa = u32::from(bits & 0x3FF);
+
+    let bits = match (exponent, mantissa) {
+        (0, 0) => sign,
+        (0, _) => {
+            // subnormal: renormalize the mantissa
+            let shift = mantissa.leading_zeros() - 21;
+            let mantissa = (mantissa << shift) & 0x3FF;
+            sign | ((113 - shift) << 23) | (mantissa << 13)
+        }
+        (0x1F, _) => sign | 0x7F80_0000 | (mantissa << 13),
+        _ => sign | ((exponent + 112) << 23) | (mantissa << 13),
+    };
+
+    f32::from_bits(bits)
+}
+
+/// Converts a single-precision float to half precision, rounding to the
+/// nearest value. Values too large for half precision become infinite.
+#[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+pub(crate) fn f32_to_f16(value: f32) -> u16 {
+    let bits = value.to_bits();
+    let sign = ((bits >> 16) & 0x8000) as u16;
+    let exponent = ((bits >> 23) & 0xFF) as i32 - 127 + 15;
+    let mantissa = bits & 0x7F_FFFF;
+
+    // Rounds away the low `shift` bits of a mantissa, to even on ties.
+    let round = |mantissa: u32, shift: u32| {
+        let half = mantissa >> shift;
+        let rest = mantissa & ((1 << shift) - 1);
+        let tie = 1 << (shift - 1);
+        half + u32::from(rest > tie || (rest == tie && half & 1 == 1))
+    };
+
+    if exponent == 0xFF - 127 + 15 {
+        // infinity or NaN
+        sign | 0x7C00 | if mantissa == 0 { 0 } else { 0x200 }
+    } else if exponent >= 0x1F {
+        sign | 0x7C00
+    } else if exponent > 0 {
+        // a carry out of the mantissa correctly increments the exponent
+        sign | round((exponent as u32) << 23 | mantissa, 13) as u16
+    } else if exponent >= -10 {
+        // subnormal, with the implicit leading bit made explicit
+        sign | round(mantissa | 0x80_0000, (14 - exponent) as u32) as u16
+    } else {
+        sign
+    }
+}
+
+/// Image extents are stored less one, so that 12 bits cover the whole of
+/// [`GFX_IMAGE_EXTENT`]. Use [`width`](Self::width) and
+/// [`height`](Self::height) to read them.
+#[bitfield_struct::bitfield(u32)]
+#[derive(PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub(crate) struct PackedInfo {
+    #[bits(12)] // max width: 4096; could take 1 bit from _empty for 8192
+    width_minus_one: u16,
+    #[bits(12)] // max height: 4096; could take 1 bit from _empty for 8192
+    height_minus_one: u16,
+    #[bits(3)]
+    pub layout: u8,
+    #[bits(3)]
+    pub format: u8,
+    /// The image's rect in the texture cache also holds a mip chain. See
+    /// [`mipmap`](super::mipmap).
+    pub mipmapped: bool,
+    #[bits(1)]
+    _empty: u8,
+}
+
+#[allow(clippy::cast_possible_wrap, clippy::cast_sign_loss)]
+impl PackedInfo {
+    pub fn width(self) -> i16 {
+        self.width_minus_one() as i16 + 1
+    }
+
+    pub fn height(self) -> i16 {
+        self.height_minus_one() as i16 + 1
+    }
+
+    /// # Panics
+    ///
+    /// Panics if the width is not within 1..=4096.
+    pub fn with_width(self, width: i16) -> Self {
+        assert!(width > 0, "Image width {width} is not positive.");
+        self.with_width_minus_one(width as u16 - 1)
+    }
+
+    /// # Panics
+    ///
+    /// Panics if the height is not within 1..=4096.
+    pub fn with_height(self, height: i16) -> Self {
+        assert!(height > 0, "Image height {height} is not positive.");
+        self.with_height_minus_one(height as u16 - 1)
+    }
+}
+
+/// The position of a sub-image within the image that it views.
+#[bitfield_struct::bitfield(u32)]
+#[derive(PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub(crate) struct PackedOffset {
+    #[bits(12)]
+    pub x: u16,
+    #[bits(12)]
+    pub y: u16,
+    pub is_view: bool,
+    #[bits(7)]
+    _empty: u8,
+}
+
+#[bitfield_struct::bitfield(u32)]
+#[derive(PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub(crate) struct PackedKey {
+    #[bits(12)] // max index: 4095
+    pub index: u32,
+    #[bits(20)] // max epoch: 1048576
+    pub epoch: u32,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn half_floats() {
+        for value in [
+            0.0,
+            -0.0,
+            1.0,
+            -2.5,
+            0.333_251_95,
+            65504.0,
+            6.103_515_6e-5,
+            5.960_464_5e-8,
+        ] {
+            assert_eq!(f16_to_f32(f32_to_f16(value)), value);
+        }
+
+        assert_eq!(f32_to_f16(1.0 + 1.0 / 2048.0), 0x3C00); // tie to even
+        assert_eq!(f32_to_f16(1.0 + 3.0 / 2048.0), 0x3C02);
+        assert_eq!(f32_to_f16(1e6), 0x7C00);
+        assert_eq!(f32_to_f16(1e-9), 0);
+        assert!(f16_to_f32(f32_to_f16(f32::NAN)).is_nan());
+    }
+}
@@ -7,5 +7,9 @@ pub const GFX_IMAGE_COUNT: Usize<4096, MAX> = Usize::new("Too many images");
 
 pub const GFX_TEXTURE_COUNT: Usize<128, MAX> = Usize::new("Too many textures");
 
+pub const GFX_GRADIENT_STOP_COUNT: Usize<8, MAX> = Usize::new("Too many gradient stops");
+
+pub const GFX_LAYER_DEPTH: Usize<8, MAX> = Usize::new("Too many nested layers");
+
 pub const GFX_DRAW_PRIM_COUNT: Usize<{ u32::MAX as _ }, MAX> =
     Usize::new("Too many items in draw list");
@@ -0,0 +1,162 @@
+//! Mip chains for images that are drawn smaller than their native size.
+//!
+//! Every level of a mipmapped image is stored in the same rect of the texture
+//! cache as the image itself. The full-size image is on the left, and each
+//! smaller level is stacked below the previous one in a column to its right:
+//!
+//! ```text
+//! +-------+---+
+//! |       | 1 |
+//! |   0   +---+
+//! |       |2|
+//! +-------+-+
+//! ```
+//!
+//! Levels are sampled with their own wrapping, so they never bleed into each
+//! other or into neighboring images.
+
+use crate::{
+    geometry::{Extent, Texel},
+    graphics::image::{Info, Raster, RasterBuf},
+};
+
+/// The number of levels in a full mip chain for an image with the given
+/// extent, down to a single texel.
+pub(crate) fn mip_levels(width: i32, height: i32) -> u32 {
+    (width.max(height).max(1)).ilog2() + 1
+}
+
+/// The texels of a level of a mip chain, as `[x, y, width, height]`, given
+/// those of the full-size level.
+///
+/// Must be kept in sync with `mip_level_region` in `rect_shader.hlsl`.
+pub(crate) fn mip_level_region(region: [i32; 4], level: u32) -> [i32; 4] {
+    let [x, y, width, height] = region;
+
+    if level == 0 {
+        return region;
+    }
+
+    let offset: i32 = (1..level).map(|l| (height >> l).max(1)).sum();
+    [
+        x + width,
+        y + offset,
+        (width >> level).max(1),
+        (height >> level).max(1),
+    ]
+}
+
+/// The extent of the rect needed to hold every level of a mip chain.
+pub(crate) fn mip_chain_extent(extent: Extent<Texel>) -> Extent<Texel> {
+    let (width, height) = (i32::from(extent.width.0), i32::from(extent.height.0));
+    let levels = mip_levels(width, height);
+
+    let [_, y, _, last_height] = mip_level_region([0, 0, width, height], levels - 1);
+    let column_height = if levels > 1 { y + last_height } else { 0 };
+    let column_width = if levels > 1 { (width >> 1).max(1) } else { 0 };
+
+    #[allow(clippy::cast_possible_truncation)]
+    Extent::new(
+        (width + column_width) as i16,
+        height.max(column_height) as i16,
+    )
+}
+
+/// Computes the levels of a mip chain after the first by averaging each 2x2
+/// block of texels in the level above. Colors are averaged in linear space.
+///
+/// When a side has an odd length, the texels at the end of each level at that
+/// side average a 3-texel span so that the last row and column are kept.
+#[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+pub(crate) fn generate_mip_chain(pixels: &RasterBuf) -> Vec<Raster> {
+    let info = pixels.info();
+    let (mut width, mut height) = (
+        i32::from(info.extent.width.0),
+        i32::from(info.extent.height.0),
+    );
+
+    let mut level = pixels.to_linear_rgba();
+    let mut chain = Vec::new();
+
+    // The texels of the level above that a texel covers along one side.
+    let span = |i: i32, next: i32, len: i32| {
+        let end = if i == next - 1 { len } else { 2 * i + 2 };
+        (2 * i).min(len - 1)..end.max(2 * i + 1).min(len)
+    };
+
+    for _ in 1..mip_levels(width, height) {
+        let (next_width, next_height) = ((width >> 1).max(1), (height >> 1).max(1));
+
+        let next: Vec<[f32; 4]> = (0..next_height)
+            .flat_map(|y| (0..next_width).map(move |x| (x, y)))
+            .map(|(x, y)| {
+                let mut sum = [0.0; 4];
+                let mut count = 0.0;
+
+                for ty in span(y, next_height, height) {
+                    for tx in span(x, next_width, width) {
+                        let texel = level[(ty * width + tx) as usize];
+                        (0..4).for_each(|i| sum[i] += texel[i]);
+                        count += 1.0;
+                    }
+                }
+
+                sum.map(|c| c / count)
+            })
+            .collect();
+
+        let next_info = Info {
+            extent: Extent::new(next_width as i16, next_height as i16),
+            ..info
+        };
+        chain.push(Raster::from_linear_rgba(next_info, &next));
+
+        (width, height, level) = (next_width, next_height, next);
+    }
+
+    chain
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::graphics::{Format, Layout};
+
+    #[test]
+    fn chain_layout() {
+        assert_eq!(mip_levels(1, 1), 1);
+        assert_eq!(mip_levels(8, 3), 4);
+
+        let region = [10, 20, 8, 4];
+        assert_eq!(mip_level_region(region, 1), [18, 20, 4, 2]);
+        assert_eq!(mip_level_region(region, 2), [18, 22, 2, 1]);
+        assert_eq!(mip_level_region(region, 3), [18, 23, 1, 1]);
+
+        assert_eq!(mip_chain_extent(Extent::new(8, 4)), Extent::new(12, 4));
+        // wide images need a taller column than the image itself
+        assert_eq!(mip_chain_extent(Extent::new(8, 1)), Extent::new(12, 3));
+        assert_eq!(mip_chain_extent(Extent::new(1, 1)), Extent::new(1, 1));
+    }
+
+    #[test]
+    fn odd_extents() {
+        // 5x3 alpha, with a single texel set in the last row and column
+        let mut data = [0; 15];
+        data[14] = 255;
+
+        let info = Info {
+            extent: Extent::new(5, 3),
+            layout: Layout::Alpha8,
+            format: Format::Linear,
+        };
+
+        let chain = generate_mip_chain(&RasterBuf::new(info, &data));
+        let extents: Vec<_> = chain.iter().map(|level| level.info().extent).collect();
+        assert_eq!(extents, [Extent::new(2, 1), Extent::new(1, 1)]);
+
+        // the corner texel is kept, averaged over a 3x3 span
+        assert_eq!(chain[0].data(), &[0, 28]);
+        assert_eq!(chain[1].data(), &[14]);
+    }
+}
@@ -1,33 +1,59 @@
 mod backend;
 mod color;
 mod draw_list;
-mod image;
+mod gradient;
+mod hdr;
+pub mod image;
 pub(crate) mod limits;
+mod mipmap;
+mod path;
 mod primitives;
+mod rasterizer;
+mod sdf;
+mod text;
 mod texture_atlas;
 
+use std::cell::{Cell, RefCell};
+
+#[cfg(target_os = "windows")]
 use windows::Win32::Foundation::HWND;
 
 use crate::{
     geometry::{Extent, Pixel, Point, Rect, Scale, Texel},
-    graphics::image::PackedKey,
+    graphics::image::{PackedKey, PackedOffset},
+    limits::GFX_IMAGE_EXTENT,
     system::PowerPreference,
     time::{FramesPerSecond, PresentPeriod, PresentTime},
 };
 
 use self::{
-    backend::Device,
-    texture_atlas::{CachedTextureId, TextureCache},
+    backend::{Device, SubmitId},
+    image::codec::{self, FileFormat},
+    mipmap::{generate_mip_chain, mip_chain_extent, mip_level_region},
+    text::{rasterize_glyph, CachedGlyph, GlyphCache},
+    texture_atlas::TextureCache,
 };
 
+#[cfg(target_os = "windows")]
 pub(crate) use self::backend::Swapchain;
 
 pub use self::{
     backend::RenderTarget,
-    color::Color,
-    draw_list::{Canvas, DrawList},
-    image::{Error as ImageError, Format, Image, Info as ImageInfo, Layout, RasterBuf},
-    primitives::RoundRect,
+    color::{Color, Error as ColorError},
+    draw_list::{BlendMode, Canvas, DrawList, Sampler, TextureFilter, WrapMode},
+    gradient::{ColorStop, Fill, Gradient, GradientKind},
+    hdr::{ColorOutput, ColorSpace, DisplayInfo, ToneMapping},
+    image::{
+        Error as ImageError, Format, Image, Info as ImageInfo, Layout, Raster, RasterBuf,
+        SpriteSheet,
+    },
+    path::{FillRule, LineCap, LineJoin, Path, Stroke},
+    primitives::{Border, BorderAlignment, Insets, RoundRect, SliceMode},
+    sdf::SDF_RANGE,
+    text::{
+        Error as FontError, Font, FontId, FontMetrics, LineBox, LineHeight, TextAlign, TextLayout,
+        TextStyle,
+    },
 };
 
 #[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
@@ -35,6 +61,9 @@ pub enum Backend {
     #[default]
     Auto,
     Null,
+    /// Renders on the CPU. This is the default on platforms without a GPU
+    /// backend.
+    Software,
     #[cfg(target_os = "windows")]
     Dx12,
 }
@@ -71,15 +100,26 @@ pub struct FrameInfo {
 
     /// The time that the last present was scheduled to occur.
     pub prev_target_present_time: PresentTime,
+
+    /// The display that the window is shown on.
+    pub display: DisplayInfo,
 }
 
 pub struct Graphics {
     device: Device,
-    textures: TextureCache,
+    textures: RefCell<TextureCache>,
+    glyphs: RefCell<GlyphCache>,
+    /// The most recent submission that may read from the texture cache.
+    last_submit: Cell<SubmitId>,
 }
 
 impl Graphics {
-    pub(crate) fn new(config: &GraphicsConfig) -> Self {
+    /// Creates a graphics context without a window.
+    ///
+    /// Applications created through [`Application`](crate::Application)
+    /// already have one; this is for headless rendering.
+    #[must_use]
+    pub fn new(config: &GraphicsConfig) -> Self {
         let device = Device::new(config);
 
         let textures = TextureCache::new(
@@ -106,7 +146,12 @@ impl Graphics {
 
         device.flush_upload_buffer();
 
-        Self { device, textures }
+        Self {
+            device,
+            textures: RefCell::new(textures),
+            glyphs: RefCell::default(),
+            last_submit: Cell::new(SubmitId(0)),
+        }
     }
 
     #[cfg(target_os = "windows")]
@@ -114,19 +159,123 @@ impl Graphics {
         self.device.create_swapchain(hwnd)
     }
 
-    pub fn create_raster_image(&mut self, info: ImageInfo) -> Result<Image, ImageError> {
-        let (_, texture_id) = self.textures.insert_rect(
-            info.extent,
+    /// Creates a render target that is not attached to a window.
+    ///
+    /// The layout and format describe the pixels returned by
+    /// [`read_pixels`](Self::read_pixels). Drawing always happens at the
+    /// backend's native precision.
+    ///
+    /// # Errors
+    ///
+    /// This function returns [`ImageError::SizeLimit`] if the extent exceeds
+    /// [`GFX_IMAGE_EXTENT`](crate::limits::GFX_IMAGE_EXTENT), or
+    /// [`ImageError::UnsupportedFormat`] if the layout cannot be stored in the
+    /// format (see [`ImageInfo::is_supported`]).
+    pub fn create_render_target(
+        &self,
+        extent: Extent<Texel>,
+        layout: Layout,
+        format: Format,
+    ) -> Result<RenderTarget, ImageError> {
+        GFX_IMAGE_EXTENT.test(extent, ImageError::SizeLimit)?;
+
+        if !(ImageInfo {
+            extent,
+            format,
+            layout,
+        })
+        .is_supported()
+        {
+            return Err(ImageError::UnsupportedFormat);
+        }
+
+        Ok(self.device.create_render_target(extent, layout, format))
+    }
+
+    /// Copies the contents of a render target into system memory.
+    ///
+    /// This blocks until any drawing to the target has completed. Render
+    /// targets that belong to the `Null` backend read back as zeroes.
+    #[must_use]
+    pub fn read_pixels(&self, target: &RenderTarget) -> Raster {
+        self.device.read_pixels(target)
+    }
+
+    /// Captures the contents of a render target and encodes them in an image
+    /// file format. See [`codec::encode`] for how colors are written.
+    ///
+    /// # Errors
+    ///
+    /// This function returns an error if the file format cannot be encoded.
+    pub fn encode_screenshot(
+        &self,
+        target: &RenderTarget,
+        format: FileFormat,
+    ) -> Result<Vec<u8>, codec::Error> {
+        codec::encode(format, &self.read_pixels(target).as_buf())
+    }
+
+    /// Captures the contents of a render target and writes them to an image
+    /// file, choosing the file format from the path's extension.
+    ///
+    /// # Errors
+    ///
+    /// This function returns an error if the extension is not that of a file
+    /// format that can be encoded, or if the file could not be written.
+    pub fn save_screenshot(
+        &self,
+        target: &RenderTarget,
+        path: impl AsRef<std::path::Path>,
+    ) -> Result<(), codec::Error> {
+        codec::save(path, &self.read_pixels(target).as_buf())
+    }
+
+    pub fn create_raster_image(&self, info: ImageInfo) -> Result<Image, ImageError> {
+        self.create_image(info, false)
+    }
+
+    /// Creates an image that is drawn with mipmaps when it is sampled with
+    /// [`TextureFilter::Mipmapped`]. The mip chain is generated each time
+    /// pixels are uploaded for the image.
+    ///
+    /// The levels are stored beside the image in the texture cache, so a
+    /// mipmapped image takes up half again as much space as a plain one.
+    pub fn create_mipmapped_image(&self, info: ImageInfo) -> Result<Image, ImageError> {
+        self.create_image(info, true)
+    }
+
+    fn create_image(&self, info: ImageInfo, mipmapped: bool) -> Result<Image, ImageError> {
+        GFX_IMAGE_EXTENT.test(info.extent, ImageError::SizeLimit)?;
+
+        if !info.is_supported() {
+            return Err(ImageError::UnsupportedFormat);
+        }
+
+        let mut textures = self.textures.borrow_mut();
+        textures.reclaim(
+            |submit| self.device.is_done(submit),
+            |texture| self.device.destroy_texture(texture),
+        );
+
+        let extent = if mipmapped {
+            mip_chain_extent(info.extent)
+        } else {
+            info.extent
+        };
+
+        let (_, texture_id) = textures.insert_rect(
+            extent,
             info.layout,
             info.format,
             |extent, layout, format| self.device.create_texture(extent, layout, format),
-        );
+        )?;
 
         let image = Image {
-            info: info.pack(),
+            info: info.pack().with_mipmapped(mipmapped),
             key: PackedKey::new()
                 .with_index(texture_id.index())
                 .with_epoch(texture_id.epoch()),
+            offset: PackedOffset::new(),
         };
 
         Ok(image)
@@ -134,17 +283,52 @@ impl Graphics {
 
     /// Uploads pixels for an image.
     ///
-    /// The pixel buffer must be the same size as the image.
-    pub fn upload_raster_image(
-        &mut self,
-        image: Image,
-        pixels: &RasterBuf,
-    ) -> Result<(), ImageError> {
-        let cache_id = CachedTextureId::new(image.key.index(), image.key.epoch());
-        let (texture, rect) = self.textures.get_rect(cache_id);
+    /// The pixel buffer must be the same size as the image. Uploading to a
+    /// sub-image replaces only its region of the image, and does not update
+    /// the mip chain of a mipmapped image.
+    ///
+    /// # Errors
+    ///
+    /// This function returns [`ImageError::SizeError`] if the pixel buffer is
+    /// not the same size as the image, [`ImageError::FormatMismatch`] if its
+    /// layout or format differs from the image's, or [`ImageError::Expired`]
+    /// if the image has been deleted.
+    pub fn upload_raster_image(&self, image: Image, pixels: &RasterBuf) -> Result<(), ImageError> {
+        if pixels.info().extent != image.extent() {
+            return Err(ImageError::SizeError);
+        }
+
+        if pixels.info().layout != image.layout() || pixels.info().format != image.format() {
+            return Err(ImageError::FormatMismatch);
+        }
+
+        let (texture, rect) = self.textures.borrow().get_rect(image.cache_id())?;
+        let offset = image.region().map_or(Point::ZERO, |region| region.origin);
+
+        self.device.copy_raster_to_texture(
+            texture,
+            pixels,
+            Point::new(rect.origin.x + offset.x, rect.origin.y + offset.y),
+        );
+
+        if image.is_mipmapped() {
+            let region = [
+                i32::from(rect.origin.x.0),
+                i32::from(rect.origin.y.0),
+                i32::from(image.extent().width.0),
+                i32::from(image.extent().height.0),
+            ];
 
-        self.device
-            .copy_raster_to_texture(texture, pixels, rect.origin);
+            for (level, raster) in (1..).zip(generate_mip_chain(pixels)) {
+                let [x, y, _, _] = mip_level_region(region, level);
+                #[allow(clippy::cast_possible_truncation)]
+                self.device.copy_raster_to_texture(
+                    texture,
+                    &raster.as_buf(),
+                    Point::new(Texel(x as i16), Texel(y as i16)),
+                );
+            }
+        }
 
         Ok(())
     }
@@ -152,16 +336,82 @@ impl Graphics {
     /// Removes an image from circulation.
     ///
     /// The image may continue to be used in the background until any pending
-    /// drawing operations that use this image have completed.
-    pub fn delete_image(&mut self, image: Image) {
-        let _ = image;
-        todo!()
+    /// drawing operations that use this image have completed. Its space is
+    /// reused after that, so draw lists that were built before the image was
+    /// deleted should not be drawn again.
+    ///
+    /// Deleting the default image has no effect, and deleting a sub-image
+    /// deletes the image that it views.
+    ///
+    /// # Errors
+    ///
+    /// This function returns [`ImageError::Expired`] if the image has already
+    /// been deleted.
+    pub fn delete_image(&self, image: Image) -> Result<(), ImageError> {
+        self.textures
+            .borrow_mut()
+            .remove_rect(image.cache_id(), self.last_submit.get())
+    }
+
+    /// Rasterizes the glyphs in a text layout and uploads them to the texture
+    /// cache so that the layout can be drawn. Glyphs that have already been
+    /// prepared are skipped.
+    ///
+    /// This may be called while a canvas is being drawn to, so long as the
+    /// layout is prepared before it is drawn.
+    ///
+    /// # Errors
+    ///
+    /// This function returns [`ImageError::MaxCount`] if the texture cache
+    /// has no room for more glyphs.
+    pub fn prepare_text(&self, layout: &TextLayout) -> Result<(), ImageError> {
+        let mut textures = self.textures.borrow_mut();
+        let mut glyphs = self.glyphs.borrow_mut();
+        textures.reclaim(
+            |submit| self.device.is_done(submit),
+            |texture| self.device.destroy_texture(texture),
+        );
+
+        for glyph in layout.glyphs() {
+            let (key, _) = layout.glyph_key(glyph);
+
+            if glyphs.contains(key) {
+                continue;
+            }
+
+            let cached = if let Some((raster, offset)) = rasterize_glyph(layout.font(glyph), key) {
+                let info = raster.info();
+
+                let (texture, image) = textures.insert_rect(
+                    info.extent,
+                    info.layout,
+                    info.format,
+                    |extent, layout, format| self.device.create_texture(extent, layout, format),
+                )?;
+
+                let (_, rect) = textures.get_rect(image)?;
+                self.device
+                    .copy_raster_to_texture(texture, &raster.as_buf(), rect.origin);
+
+                Some(CachedGlyph {
+                    image,
+                    offset,
+                    extent: info.extent,
+                })
+            } else {
+                None
+            };
+
+            glyphs.insert(key, cached);
+        }
+
+        Ok(())
     }
 
     /// Call to flush staging buffers.
     ///
     /// This does not block.
-    pub fn flush_upload_buffer(&mut self) {
+    pub fn flush_upload_buffer(&self) {
         self.device.flush_upload_buffer();
     }
 
@@ -172,10 +422,239 @@ impl Graphics {
         scale: Scale<Texel, Pixel>,
     ) -> Canvas<'a> {
         let rect = Rect::new(Point::ZERO, target.extent().scale_to(scale));
-        Canvas::new(&self.textures, draw_list, rect)
+        Canvas::new(&self.textures, &self.glyphs, draw_list, rect)
     }
 
     pub fn draw(&self, draw_list: &DrawList, target: &mut RenderTarget) {
-        self.device.draw(draw_list, target);
+        let submit = self.device.draw(draw_list, target);
+        self.last_submit.set(submit);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn render(backend: Backend) -> Raster {
+        let graphics = Graphics::new(&GraphicsConfig {
+            backend,
+            ..Default::default()
+        });
+
+        let mut target = graphics
+            .create_render_target(Extent::new(2, 2), Layout::Rgba8, Format::Srgb)
+            .unwrap();
+
+        let mut draw_list = DrawList::new();
+        {
+            let mut canvas = graphics.create_canvas(&target, &mut draw_list, Scale::default());
+            canvas.clear(Color::BLACK);
+            canvas.draw_rect(&RoundRect::new((1.0, 0.0, 1.0, 2.0)).with_color(Color::RED));
+            canvas.finish();
+        }
+
+        graphics.draw(&draw_list, &mut target);
+        graphics.read_pixels(&target)
+    }
+
+    #[test]
+    fn read_pixels_software() {
+        let raster = render(Backend::Software);
+
+        assert_eq!(raster.info().extent, Extent::new(2, 2));
+        #[rustfmt::skip]
+        assert_eq!(raster.data(), &[
+            0, 0, 0, 255, 255, 0, 0, 255,
+            0, 0, 0, 255, 255, 0, 0, 255,
+        ]);
+    }
+
+    #[test]
+    fn read_pixels_null() {
+        let raster = render(Backend::Null);
+
+        assert_eq!(raster.info().extent, Extent::new(2, 2));
+        assert_eq!(raster.data(), &[0; 16]);
+    }
+
+    #[test]
+    fn delete_image() {
+        let graphics = Graphics::new(&GraphicsConfig {
+            backend: Backend::Software,
+            ..Default::default()
+        });
+
+        let info = ImageInfo {
+            extent: Extent::new(1, 1),
+            layout: Layout::Rgba8,
+            format: Format::Linear,
+        };
+
+        let image = graphics.create_raster_image(info).unwrap();
+        graphics.delete_image(image).unwrap();
+
+        let pixels = RasterBuf::new(info, &[0; 4]);
+        assert!(matches!(
+            graphics.upload_raster_image(image, &pixels),
+            Err(ImageError::Expired)
+        ));
+        assert!(matches!(
+            graphics.delete_image(image),
+            Err(ImageError::Expired)
+        ));
+
+        // the slot is reused, but the old handle stays expired
+        let _ = graphics.create_raster_image(info).unwrap();
+        assert!(graphics.delete_image(image).is_err());
+    }
+
+    #[test]
+    fn max_extent_image() {
+        let graphics = Graphics::new(&GraphicsConfig {
+            backend: Backend::Software,
+            ..Default::default()
+        });
+
+        let info = ImageInfo {
+            extent: Extent::new(4096, 1),
+            layout: Layout::Rgba8,
+            format: Format::Linear,
+        };
+
+        let image = graphics.create_raster_image(info).unwrap();
+        assert_eq!(image.extent(), info.extent);
+
+        // red on the left half, green on the right
+        let pixels: Vec<u8> = [[255, 0, 0, 255], [0, 255, 0, 255]]
+            .iter()
+            .flat_map(|color| color.repeat(2048))
+            .collect();
+        graphics
+            .upload_raster_image(image, &RasterBuf::new(info, &pixels))
+            .unwrap();
+
+        let mut target = graphics
+            .create_render_target(Extent::new(2, 1), Layout::Rgba8, Format::Linear)
+            .unwrap();
+
+        let mut draw_list = DrawList::new();
+        {
+            let mut canvas = graphics.create_canvas(&target, &mut draw_list, Scale::default());
+            canvas.draw_rect(&RoundRect::new((0.0, 0.0, 2.0, 1.0)).with_image(image));
+            canvas.finish();
+        }
+
+        graphics.draw(&draw_list, &mut target);
+        assert_eq!(
+            graphics.read_pixels(&target).data(),
+            &[255, 0, 0, 255, 0, 255, 0, 255]
+        );
+
+        let larger = ImageInfo {
+            extent: Extent::new(4097, 1),
+            ..info
+        };
+        assert!(matches!(
+            graphics.create_raster_image(larger),
+            Err(ImageError::SizeLimit)
+        ));
+    }
+
+    #[test]
+    fn unsupported_format() {
+        let graphics = Graphics::new(&GraphicsConfig {
+            backend: Backend::Software,
+            ..Default::default()
+        });
+
+        let extent = Extent::new(2, 2);
+        for (layout, format) in [
+            (Layout::Alpha8, Format::Srgb),
+            (Layout::Rgba16Float, Format::Srgb),
+            (Layout::Rgba8, Format::Unkown),
+        ] {
+            assert!(matches!(
+                graphics.create_render_target(extent, layout, format),
+                Err(ImageError::UnsupportedFormat)
+            ));
+
+            let info = ImageInfo {
+                extent,
+                format,
+                layout,
+            };
+            assert!(matches!(
+                graphics.create_raster_image(info),
+                Err(ImageError::UnsupportedFormat)
+            ));
+            assert!(matches!(
+                graphics.create_mipmapped_image(info),
+                Err(ImageError::UnsupportedFormat)
+            ));
+        }
+    }
+
+    #[test]
+    fn upload_size() {
+        let graphics = Graphics::new(&GraphicsConfig {
+            backend: Backend::Software,
+            ..Default::default()
+        });
+
+        let info = ImageInfo {
+            extent: Extent::new(2, 2),
+            layout: Layout::Rgba8,
+            format: Format::Linear,
+        };
+
+        let image = graphics.create_raster_image(info).unwrap();
+
+        // a larger raster would write over its neighbors in the atlas
+        let larger = ImageInfo {
+            extent: Extent::new(3, 2),
+            ..info
+        };
+        assert!(matches!(
+            graphics.upload_raster_image(image, &RasterBuf::new(larger, &[0; 24])),
+            Err(ImageError::SizeError)
+        ));
+
+        assert!(graphics
+            .upload_raster_image(image, &RasterBuf::new(info, &[0; 16]))
+            .is_ok());
+    }
+
+    #[test]
+    fn upload_format() {
+        let graphics = Graphics::new(&GraphicsConfig {
+            backend: Backend::Software,
+            ..Default::default()
+        });
+
+        let info = ImageInfo {
+            extent: Extent::new(2, 2),
+            layout: Layout::Rgba8,
+            format: Format::Linear,
+        };
+
+        let image = graphics.create_raster_image(info).unwrap();
+
+        let bgra = ImageInfo {
+            layout: Layout::Bgra8,
+            ..info
+        };
+        assert!(matches!(
+            graphics.upload_raster_image(image, &RasterBuf::new(bgra, &[0; 16])),
+            Err(ImageError::FormatMismatch)
+        ));
+
+        let srgb = ImageInfo {
+            format: Format::Srgb,
+            ..info
+        };
+        assert!(matches!(
+            graphics.upload_raster_image(image, &RasterBuf::new(srgb, &[0; 16])),
+            Err(ImageError::FormatMismatch)
+        ));
     }
 }
@@ -0,0 +1,681 @@
+use std::f32::consts::{FRAC_PI_2, TAU};
+
+use crate::geometry::{Pixel, Point};
+
+/// Decides which areas enclosed by a path are inside it.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum FillRule {
+    /// Inside if the path winds around the point a non-zero number of times.
+    #[default]
+    NonZero,
+    /// Inside if a ray from the point crosses the path an odd number of times.
+    EvenOdd,
+}
+
+impl FillRule {
+    pub(crate) fn is_inside(self, winding: i32) -> bool {
+        match self {
+            Self::NonZero => winding != 0,
+            Self::EvenOdd => winding % 2 != 0,
+        }
+    }
+}
+
+/// The shape drawn where two segments of a stroke meet.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum LineJoin {
+    /// The outer edges are extended until they meet, up to the stroke's miter
+    /// limit. Joins beyond the limit are beveled.
+    #[default]
+    Miter,
+    Round,
+    Bevel,
+}
+
+/// The shape drawn at the ends of open subpaths and dashes.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum LineCap {
+    /// The stroke ends exactly at the end point.
+    #[default]
+    Butt,
+    /// The stroke ends in a half circle centered on the end point.
+    Round,
+    /// The stroke ends in a half square centered on the end point.
+    Square,
+}
+
+/// How the outline of a path is drawn.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Stroke {
+    pub width: f32,
+    pub join: LineJoin,
+    pub cap: LineCap,
+    /// The longest that a miter join may be, as a multiple of the stroke's
+    /// width.
+    pub miter_limit: f32,
+    /// Alternating lengths of dashes and gaps. The stroke is solid if this is
+    /// empty.
+    pub dashes: Vec<f32>,
+    /// How far into the dash pattern the stroke starts.
+    pub dash_offset: f32,
+}
+
+impl Stroke {
+    #[must_use]
+    pub fn new(width: f32) -> Self {
+        Self {
+            width,
+            join: LineJoin::default(),
+            cap: LineCap::default(),
+            miter_limit: 4.0,
+            dashes: Vec::new(),
+            dash_offset: 0.0,
+        }
+    }
+
+    #[must_use]
+    pub fn with_join(mut self, join: LineJoin) -> Self {
+        self.join = join;
+        self
+    }
+
+    #[must_use]
+    pub fn with_cap(mut self, cap: LineCap) -> Self {
+        self.cap = cap;
+        self
+    }
+
+    #[must_use]
+    pub fn with_miter_limit(mut self, limit: f32) -> Self {
+        self.miter_limit = limit;
+        self
+    }
+
+    /// Sets the dash pattern. Like SVG, a pattern with an odd number of
+    /// lengths is repeated to make it even.
+    #[must_use]
+    pub fn with_dashes(mut self, dashes: &[f32], offset: f32) -> Self {
+        self.dashes = dashes.iter().map(|d| d.max(0.0)).collect();
+        if self.dashes.len() % 2 == 1 {
+            self.dashes.extend_from_within(..);
+        }
+
+        self.dash_offset = offset;
+        self
+    }
+}
+
+impl Default for Stroke {
+    fn default() -> Self {
+        Self::new(1.0)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Verb {
+    MoveTo([f32; 2]),
+    LineTo([f32; 2]),
+    QuadTo([f32; 2], [f32; 2]),
+    CubicTo([f32; 2], [f32; 2], [f32; 2]),
+    Close,
+}
+
+/// A shape made of lines and curves.
+///
+/// A path is made of subpaths, each of which starts with a
+/// [`move_to`](Self::move_to). Drawing commands that are not preceded by a
+/// `move_to` start at the origin.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Path {
+    verbs: Vec<Verb>,
+    start: [f32; 2],
+    current: [f32; 2],
+}
+
+impl Path {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Starts a new subpath at `point`.
+    #[must_use]
+    pub fn move_to(mut self, point: impl Into<Point<Pixel>>) -> Self {
+        let point = to_array(point.into());
+        self.verbs.push(Verb::MoveTo(point));
+        self.start = point;
+        self.current = point;
+        self
+    }
+
+    #[must_use]
+    pub fn line_to(mut self, point: impl Into<Point<Pixel>>) -> Self {
+        let point = to_array(point.into());
+        self.verbs.push(Verb::LineTo(point));
+        self.current = point;
+        self
+    }
+
+    /// Draws a quadratic bezier curve to `point`.
+    #[must_use]
+    pub fn quad_to(
+        mut self,
+        control: impl Into<Point<Pixel>>,
+        point: impl Into<Point<Pixel>>,
+    ) -> Self {
+        let point = to_array(point.into());
+        self.verbs
+            .push(Verb::QuadTo(to_array(control.into()), point));
+        self.current = point;
+        self
+    }
+
+    /// Draws a cubic bezier curve to `point`.
+    #[must_use]
+    pub fn cubic_to(
+        mut self,
+        control1: impl Into<Point<Pixel>>,
+        control2: impl Into<Point<Pixel>>,
+        point: impl Into<Point<Pixel>>,
+    ) -> Self {
+        let point = to_array(point.into());
+        self.verbs.push(Verb::CubicTo(
+            to_array(control1.into()),
+            to_array(control2.into()),
+            point,
+        ));
+        self.current = point;
+        self
+    }
+
+    /// Draws a circular arc of `radius` that is tangent to the line from the
+    /// current point to `corner` and the line from `corner` to `point`,
+    /// connecting it to the current point with a straight line.
+    ///
+    /// The arc ends where it touches the second line, rather than at `point`.
+    /// This rounds the corner of a polyline, as in the HTML canvas `arcTo`.
+    #[must_use]
+    pub fn arc_to(
+        self,
+        corner: impl Into<Point<Pixel>>,
+        point: impl Into<Point<Pixel>>,
+        radius: f32,
+    ) -> Self {
+        let p0 = self.current;
+        let p1 = to_array(corner.into());
+        let p2 = to_array(point.into());
+
+        let v1 = normalize(sub(p0, p1));
+        let v2 = normalize(sub(p2, p1));
+
+        let (Some(v1), Some(v2)) = (v1, v2) else {
+            return self.line_to(to_point(p1));
+        };
+
+        let cos = dot(v1, v2).clamp(-1.0, 1.0);
+        if radius <= 0.0 || cross(v1, v2).abs() < 1e-6 {
+            return self.line_to(to_point(p1));
+        }
+
+        // The angle between the two lines at the corner.
+        let angle = cos.acos();
+        let tangent = radius / (angle / 2.0).tan();
+        let start = add(p1, scale(v1, tangent));
+        let end = add(p1, scale(v2, tangent));
+
+        let bisector = normalize(add(v1, v2)).unwrap();
+        let center = add(p1, scale(bisector, radius / (angle / 2.0).sin()));
+
+        let start_angle = angle_of(sub(start, center));
+        let sweep = (std::f32::consts::PI - angle).copysign(-cross(v1, v2));
+
+        self.line_to(to_point(start))
+            .arc(center, radius, start_angle, sweep, end)
+    }
+
+    /// Closes the current subpath with a straight line to its start.
+    #[must_use]
+    pub fn close(mut self) -> Self {
+        self.verbs.push(Verb::Close);
+        self.current = self.start;
+        self
+    }
+
+    /// Appends cubic curves that approximate an arc of a circle, splitting
+    /// it into quarter turns or less.
+    fn arc(mut self, center: [f32; 2], radius: f32, start: f32, sweep: f32, end: [f32; 2]) -> Self {
+        let count = (sweep.abs() / FRAC_PI_2).ceil().max(1.0);
+        let step = sweep / count;
+        let k = 4.0 / 3.0 * (step / 4.0).tan() * radius;
+
+        let mut angle = start;
+        for i in 0..count as usize {
+            let next = angle + step;
+            let (s0, c0) = angle.sin_cos();
+            let (s1, c1) = next.sin_cos();
+
+            let from = [center[0] + radius * c0, center[1] + radius * s0];
+            let to = if i + 1 == count as usize {
+                end
+            } else {
+                [center[0] + radius * c1, center[1] + radius * s1]
+            };
+
+            self.verbs.push(Verb::CubicTo(
+                [from[0] - k * s0, from[1] + k * c0],
+                [to[0] + k * s1, to[1] - k * c1],
+                to,
+            ));
+
+            angle = next;
+        }
+
+        self.current = end;
+        self
+    }
+
+    /// Approximates the path with polylines, each of which deviates from the
+    /// path by no more than `tolerance`.
+    pub(crate) fn flatten(&self, tolerance: f32) -> Vec<Polyline> {
+        let mut polylines = Vec::new();
+        let mut current = Polyline::default();
+        let mut last = [0.0, 0.0];
+
+        // Subpaths that are only a `move_to` are not drawn.
+        let mut drawn = false;
+
+        let mut finish = |current: &mut Polyline, drawn: bool| {
+            let mut polyline = std::mem::take(current);
+            polyline.points.dedup();
+
+            if polyline.closed
+                && polyline.points.len() > 1
+                && polyline.points.first() == polyline.points.last()
+            {
+                polyline.points.pop();
+            }
+
+            if drawn {
+                polylines.push(polyline);
+            }
+        };
+
+        for verb in &self.verbs {
+            if current.points.is_empty() {
+                current.points.push(last);
+            }
+
+            match *verb {
+                Verb::MoveTo(p) => {
+                    finish(&mut current, drawn);
+                    current.points.push(p);
+                    drawn = false;
+                }
+                Verb::LineTo(p) => current.points.push(p),
+                Verb::QuadTo(c, p) => {
+                    let dd = length(add(sub(last, scale(c, 2.0)), p));
+                    let count = segment_count(0.25 * dd, tolerance);
+
+                    for i in 1..=count {
+                        let t = i as f32 / count as f32;
+                        let mt = 1.0 - t;
+                        current.points.push(add(
+                            add(scale(last, mt * mt), scale(c, 2.0 * mt * t)),
+                            scale(p, t * t),
+                        ));
+                    }
+                }
+                Verb::CubicTo(c1, c2, p) => {
+                    let dd = length(add(sub(last, scale(c1, 2.0)), c2))
+                        .max(length(add(sub(c1, scale(c2, 2.0)), p)));
+                    let count = segment_count(0.75 * dd, tolerance);
+
+                    for i in 1..=count {
+                        let t = i as f32 / count as f32;
+                        let mt = 1.0 - t;
+                        current.points.push(add(
+                            add(scale(last, mt * mt * mt), scale(c1, 3.0 * mt * mt * t)),
+                            add(scale(c2, 3.0 * mt * t * t), scale(p, t * t * t)),
+                        ));
+                    }
+                }
+                Verb::Close => {
+                    // The next subpath starts where this one started.
+                    let start = current.points[0];
+                    current.closed = true;
+                    finish(&mut current, true);
+                    current.points.push(start);
+                    drawn = false;
+                }
+            }
+
+            if !matches!(verb, Verb::MoveTo(_) | Verb::Close) {
+                drawn = true;
+            }
+
+            last = *current.points.last().unwrap();
+        }
+
+        finish(&mut current, drawn);
+
+        polylines
+    }
+}
+
+/// A sequence of connected points, in the order that they are drawn.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub(crate) struct Polyline {
+    pub points: Vec<[f32; 2]>,
+    /// Whether the last point connects back to the first.
+    pub closed: bool,
+}
+
+/// Converts the outline of a polyline into polygons that cover the stroke
+/// when filled with [`FillRule::NonZero`].
+///
+/// Every polygon winds in the same direction so that overlaps between them do
+/// not cancel out.
+pub(crate) fn stroke_polyline(
+    polyline: &Polyline,
+    stroke: &Stroke,
+    tolerance: f32,
+    out: &mut Vec<Vec<[f32; 2]>>,
+) {
+    if stroke.width <= 0.0 || polyline.points.is_empty() {
+        return;
+    }
+
+    let dash_length: f32 = stroke.dashes.iter().sum();
+
+    if dash_length > 0.0 {
+        for dash in split_dashes(polyline, &stroke.dashes, stroke.dash_offset) {
+            stroke_solid(&dash, stroke, tolerance, out);
+        }
+    } else {
+        stroke_solid(polyline, stroke, tolerance, out);
+    }
+}
+
+fn stroke_solid(
+    polyline: &Polyline,
+    stroke: &Stroke,
+    tolerance: f32,
+    out: &mut Vec<Vec<[f32; 2]>>,
+) {
+    let half = stroke.width / 2.0;
+    let points = &polyline.points;
+
+    // A single point draws a dot if the caps extend past the end points.
+    if points.len() == 1 {
+        let p = points[0];
+        match stroke.cap {
+            LineCap::Butt => {}
+            LineCap::Round => push_polygon(out, circle(p, half, tolerance)),
+            LineCap::Square => push_polygon(
+                out,
+                vec![
+                    [p[0] - half, p[1] - half],
+                    [p[0] + half, p[1] - half],
+                    [p[0] + half, p[1] + half],
+                    [p[0] - half, p[1] + half],
+                ],
+            ),
+        }
+        return;
+    }
+
+    let segments = if polyline.closed {
+        points.len()
+    } else {
+        points.len() - 1
+    };
+
+    for i in 0..segments {
+        let a = points[i];
+        let b = points[(i + 1) % points.len()];
+        let n = scale(normal(a, b), half);
+
+        push_polygon(out, vec![add(a, n), add(b, n), sub(b, n), sub(a, n)]);
+    }
+
+    let joins = if polyline.closed {
+        0..points.len()
+    } else {
+        1..points.len() - 1
+    };
+
+    for i in joins {
+        let prev = points[(i + points.len() - 1) % points.len()];
+        let p = points[i];
+        let next = points[(i + 1) % points.len()];
+        join(prev, p, next, stroke, tolerance, out);
+    }
+
+    if !polyline.closed {
+        cap(points[1], points[0], stroke, tolerance, out);
+        cap(
+            points[points.len() - 2],
+            points[points.len() - 1],
+            stroke,
+            tolerance,
+            out,
+        );
+    }
+}
+
+/// Fills the gap on the outside of the corner at `p`.
+fn join(
+    prev: [f32; 2],
+    p: [f32; 2],
+    next: [f32; 2],
+    stroke: &Stroke,
+    tolerance: f32,
+    out: &mut Vec<Vec<[f32; 2]>>,
+) {
+    let half = stroke.width / 2.0;
+    let n0 = normal(prev, p);
+    let n1 = normal(p, next);
+
+    let turn = cross(sub(p, prev), sub(next, p));
+    if turn == 0.0 && dot(n0, n1) > 0.0 {
+        return;
+    }
+
+    // The outside of the corner is opposite the direction of the turn.
+    let side = if turn > 0.0 { -1.0 } else { 1.0 };
+    let a = add(p, scale(n0, side * half));
+    let b = add(p, scale(n1, side * half));
+
+    match stroke.join {
+        LineJoin::Round => push_polygon(out, circle(p, half, tolerance)),
+        LineJoin::Bevel => push_polygon(out, vec![p, a, b]),
+        LineJoin::Miter => {
+            // The miter is offset from `p` by `half` along both normals.
+            let cos = dot(n0, n1);
+            let miter = scale(add(n0, n1), side * half / (1.0 + cos).max(f32::EPSILON));
+
+            if length(miter) <= stroke.miter_limit * half {
+                push_polygon(out, vec![p, a, add(p, miter), b]);
+            } else {
+                push_polygon(out, vec![p, a, b]);
+            }
+        }
+    }
+}
+
+/// Draws the cap at `end`, for a segment that arrives from `from`.
+fn cap(
+    from: [f32; 2],
+    end: [f32; 2],
+    stroke: &Stroke,
+    tolerance: f32,
+    out: &mut Vec<Vec<[f32; 2]>>,
+) {
+    let half = stroke.width / 2.0;
+
+    match stroke.cap {
+        LineCap::Butt => {}
+        LineCap::Round => push_polygon(out, circle(end, half, tolerance)),
+        LineCap::Square => {
+            let n = scale(normal(from, end), half);
+            let d = scale(normalize(sub(end, from)).unwrap_or([1.0, 0.0]), half);
+            let far = add(end, d);
+            push_polygon(
+                out,
+                vec![add(end, n), add(far, n), sub(far, n), sub(end, n)],
+            );
+        }
+    }
+}
+
+/// Splits a polyline into the open polylines that are drawn by a dash
+/// pattern.
+fn split_dashes(polyline: &Polyline, dashes: &[f32], offset: f32) -> Vec<Polyline> {
+    let total: f32 = dashes.iter().sum();
+
+    // Find where in the pattern the offset falls.
+    let mut index = 0;
+    let mut remaining = dashes[0];
+    let mut skip = offset.rem_euclid(total);
+    while skip > 0.0 {
+        if skip < remaining {
+            remaining -= skip;
+            break;
+        }
+
+        skip -= remaining;
+        index = (index + 1) % dashes.len();
+        remaining = dashes[index];
+    }
+
+    let mut points = polyline.points.clone();
+    if polyline.closed {
+        points.push(points[0]);
+    }
+
+    let mut result = Vec::new();
+    let mut current = Polyline::default();
+    if index % 2 == 0 {
+        current.points.push(points[0]);
+    }
+
+    for pair in points.windows(2) {
+        let (mut a, b) = (pair[0], pair[1]);
+        let mut segment = length(sub(b, a));
+
+        while segment > 0.0 {
+            let advance = remaining.min(segment);
+            let t = advance / segment;
+            a = add(a, scale(sub(b, a), t));
+            segment -= advance;
+            remaining -= advance;
+
+            if index % 2 == 0 {
+                current.points.push(a);
+            }
+
+            if remaining <= 0.0 {
+                if index % 2 == 0 {
+                    result.push(std::mem::take(&mut current));
+                } else {
+                    current.points.push(a);
+                }
+
+                index = (index + 1) % dashes.len();
+                remaining = dashes[index];
+            }
+        }
+    }
+
+    if current.points.len() > 1 {
+        result.push(current);
+    }
+
+    for dash in &mut result {
+        dash.points.dedup();
+    }
+
+    result
+}
+
+/// A polygon that approximates a circle to within `tolerance`.
+fn circle(center: [f32; 2], radius: f32, tolerance: f32) -> Vec<[f32; 2]> {
+    let step = 2.0 * (1.0 - (tolerance / radius).min(1.0)).acos();
+    let count = ((TAU / step).ceil() as usize).clamp(8, 256);
+
+    (0..count)
+        .map(|i| {
+            let (sin, cos) = (TAU * i as f32 / count as f32).sin_cos();
+            [center[0] + radius * cos, center[1] + radius * sin]
+        })
+        .collect()
+}
+
+/// Adds a polygon to `out`, reversing it if needed so that every polygon winds
+/// the same way.
+fn push_polygon(out: &mut Vec<Vec<[f32; 2]>>, mut polygon: Vec<[f32; 2]>) {
+    let mut area = 0.0;
+    for i in 0..polygon.len() {
+        area += cross(polygon[i], polygon[(i + 1) % polygon.len()]);
+    }
+
+    if area < 0.0 {
+        polygon.reverse();
+    }
+
+    out.push(polygon);
+}
+
+/// The number of line segments needed to approximate a curve to within
+/// `tolerance`, per Wang's formula. `bound` is `d(d - 1) / 8` times the
+/// largest second difference of a degree `d` curve's control points.
+fn segment_count(bound: f32, tolerance: f32) -> usize {
+    ((bound / tolerance).sqrt().ceil() as usize).clamp(1, 256)
+}
+
+fn to_array(point: Point<Pixel>) -> [f32; 2] {
+    [point.x.0, point.y.0]
+}
+
+fn to_point(point: [f32; 2]) -> Point<Pixel> {
+    Point::new(point[0], point[1])
+}
+
+/// The unit normal to the left of the direction from `a` to `b`.
+fn normal(a: [f32; 2], b: [f32; 2]) -> [f32; 2] {
+    let d = normalize(sub(b, a)).unwrap_or([1.0, 0.0]);
+    [-d[1], d[0]]
+}
+
+fn add(a: [f32; 2], b: [f32; 2]) -> [f32; 2] {
+    [a[0] + b[0], a[1] + b[1]]
+}
+
+fn sub(a: [f32; 2], b: [f32; 2]) -> [f32; 2] {
+    [a[0] - b[0], a[1] - b[1]]
+}
+
+fn scale(a: [f32; 2], s: f32) -> [f32; 2] {
+    [a[0] * s, a[1] * s]
+}
+
+fn dot(a: [f32; 2], b: [f32; 2]) -> f32 {
+    a[0] * b[0] + a[1] * b[1]
+}
+
+fn cross(a: [f32; 2], b: [f32; 2]) -> f32 {
+    a[0] * b[1] - a[1] * b[0]
+}
+
+fn length(a: [f32; 2]) -> f32 {
+    a[0].hypot(a[1])
+}
+
+fn angle_of(a: [f32; 2]) -> f32 {
+    a[1].atan2(a[0])
+}
+
+fn normalize(a: [f32; 2]) -> Option<[f32; 2]> {
+    let len = length(a);
+    (len > 0.0).then(|| scale(a, 1.0 / len))
+}
@@ -1,11 +1,95 @@
 use crate::geometry::{Pixel, Rect};
 
-use super::{color::Color, image::Image};
+use super::{color::Color, draw_list::Sampler, gradient::Gradient, image::Image};
+
+/// Where a border is drawn relative to the edge of its rect.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum BorderAlignment {
+    /// The border is drawn entirely within the rect.
+    #[default]
+    Inside,
+    /// The border straddles the edge of the rect.
+    Center,
+    /// The border is drawn entirely outside the rect.
+    Outside,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Border {
+    pub width: f32,
+    pub color: Color,
+    pub alignment: BorderAlignment,
+}
+
+impl Default for Border {
+    fn default() -> Self {
+        Self {
+            width: 0.0,
+            color: Color::BLACK,
+            alignment: BorderAlignment::Inside,
+        }
+    }
+}
+
+/// The widths of the fixed border around the center of a nine-slice image,
+/// in texels.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Insets {
+    pub left: f32,
+    pub top: f32,
+    pub right: f32,
+    pub bottom: f32,
+}
+
+impl Insets {
+    #[must_use]
+    pub fn new(left: f32, top: f32, right: f32, bottom: f32) -> Self {
+        Self {
+            left,
+            top,
+            right,
+            bottom,
+        }
+    }
+
+    /// The same inset on every side.
+    #[must_use]
+    pub fn uniform(inset: f32) -> Self {
+        Self::new(inset, inset, inset, inset)
+    }
+}
+
+/// How the edges and center of a nine-slice image fill the space between its
+/// corners.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum SliceMode {
+    /// The slices are scaled to fit.
+    #[default]
+    Stretch,
+    /// The slices are repeated at their native size, starting from the
+    /// top-left. The last repetition in each direction is cut short.
+    Tile,
+}
 
 pub struct RoundRect {
     pub rect: Rect<Pixel>,
+    /// The corner radii in the order top-left, top-right, bottom-right,
+    /// bottom-left.
+    ///
+    /// Radii are clamped to half of the shorter side of the rect.
+    pub radii: [f32; 4],
     pub color: Color,
+    /// Replaces `color` as the fill if set.
+    pub gradient: Option<Gradient>,
     pub image: Image,
+    /// Where the image is drawn, in the same coordinates as `rect`. The rect
+    /// is filled with the image if this is `None`. Outside of this area, the
+    /// image is extended according to the sampler's wrap modes.
+    pub image_rect: Option<Rect<Pixel>>,
+    pub sampler: Sampler,
+    /// The border around the rect. The fill is not drawn underneath the
+    /// border.
+    pub border: Border,
 }
 
 impl RoundRect {
@@ -15,20 +99,75 @@ impl RoundRect {
 
         Self {
             rect,
+            radii: [0.0; 4],
             color: Color::WHITE,
+            gradient: None,
             image: Image::default(),
+            image_rect: None,
+            sampler: Sampler::default(),
+            border: Border::default(),
         }
     }
 
+    /// Sets the same radius for all four corners.
+    #[must_use]
+    pub fn with_radius(mut self, radius: f32) -> Self {
+        self.radii = [radius; 4];
+        self
+    }
+
+    /// Sets the corner radii in the order top-left, top-right, bottom-right,
+    /// bottom-left.
+    #[must_use]
+    pub fn with_radii(mut self, radii: [f32; 4]) -> Self {
+        self.radii = radii;
+        self
+    }
+
     #[must_use]
     pub fn with_color(mut self, color: Color) -> Self {
         self.color = color;
         self
     }
 
+    /// Fills the rect with a gradient instead of a solid color. The gradient
+    /// is multiplied by the image, if there is one.
+    #[must_use]
+    pub fn with_gradient(mut self, gradient: Gradient) -> Self {
+        self.gradient = Some(gradient);
+        self
+    }
+
     #[must_use]
     pub fn with_image(mut self, image: Image) -> Self {
         self.image = image;
         self
     }
+
+    /// Draws one copy of the image at `rect` instead of stretching it over
+    /// the whole rect. Use a sampler that repeats to tile the image.
+    #[must_use]
+    pub fn with_image_rect(mut self, rect: impl Into<Rect<Pixel>>) -> Self {
+        self.image_rect = Some(rect.into());
+        self
+    }
+
+    #[must_use]
+    pub fn with_sampler(mut self, sampler: Sampler) -> Self {
+        self.sampler = sampler;
+        self
+    }
+
+    #[must_use]
+    pub fn with_border(mut self, width: f32, color: Color) -> Self {
+        self.border.width = width;
+        self.border.color = color;
+        self
+    }
+
+    #[must_use]
+    pub fn with_border_alignment(mut self, alignment: BorderAlignment) -> Self {
+        self.border.alignment = alignment;
+        self
+    }
 }
@@ -0,0 +1,252 @@
+//! Scanline rasterization of polygons into coverage.
+//!
+//! Paths are drawn as spans of pixels with uniform coverage so that they can
+//! be drawn with the same rect pipeline as every other primitive.
+
+use super::path::FillRule;
+
+/// The number of sub-scanlines sampled per row of pixels.
+const SAMPLES: usize = 16;
+
+/// A rect of pixels that share the same coverage, in the render target's
+/// coordinates.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) struct Span {
+    pub x: i32,
+    pub y: i32,
+    pub width: i32,
+    pub height: i32,
+    /// The fraction of each pixel that is covered, quantized to 1/255.
+    pub coverage: f32,
+}
+
+/// A polygon edge, directed from `from` to `to`.
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct Edge {
+    pub from: [f32; 2],
+    pub to: [f32; 2],
+}
+
+impl Edge {
+    /// The edges of a closed polygon.
+    pub fn polygon(points: &[[f32; 2]]) -> impl Iterator<Item = Self> + '_ {
+        (0..points.len()).map(|i| Self {
+            from: points[i],
+            to: points[(i + 1) % points.len()],
+        })
+    }
+}
+
+/// Computes the coverage of the area enclosed by `edges` within `bounds`,
+/// given as `[left, top, right, bottom]` in whole pixels.
+///
+/// Coverage is exact horizontally, and sampled at [`SAMPLES`] sub-scanlines
+/// vertically. Rows of runs with the same extent and coverage are merged into
+/// a single span.
+#[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+pub(crate) fn rasterize(edges: &[Edge], rule: FillRule, bounds: [i32; 4]) -> Vec<Span> {
+    let mut spans: Vec<Span> = Vec::new();
+
+    // Horizontal edges are never crossed by a scanline.
+    let mut edges: Vec<Edge> = edges
+        .iter()
+        .filter(|edge| edge.from[1] != edge.to[1])
+        .copied()
+        .collect();
+
+    if edges.is_empty() {
+        return spans;
+    }
+
+    let (mut min, mut max) = ([f32::MAX; 2], [f32::MIN; 2]);
+    for edge in &edges {
+        for p in [edge.from, edge.to] {
+            min = [min[0].min(p[0]), min[1].min(p[1])];
+            max = [max[0].max(p[0]), max[1].max(p[1])];
+        }
+    }
+
+    let left = (min[0].floor() as i32).max(bounds[0]);
+    let right = (max[0].ceil() as i32).min(bounds[2]);
+    let top = (min[1].floor() as i32).max(bounds[1]);
+    let bottom = (max[1].ceil() as i32).min(bounds[3]);
+
+    if left >= right || top >= bottom {
+        return spans;
+    }
+
+    edges.sort_by(|a, b| a.from[1].min(a.to[1]).total_cmp(&b.from[1].min(b.to[1])));
+
+    let mut row = vec![0.0; (right - left) as usize];
+    let mut active: Vec<Edge> = Vec::new();
+    let mut crossings: Vec<(f32, i32)> = Vec::new();
+    let mut next_edge = 0;
+
+    // The spans from the previous row, which may be extended by this one.
+    let mut previous: Vec<usize> = Vec::new();
+    let mut current: Vec<usize> = Vec::new();
+
+    for py in top..bottom {
+        let (row_top, row_bottom) = (py as f32, (py + 1) as f32);
+
+        while next_edge < edges.len()
+            && edges[next_edge].from[1].min(edges[next_edge].to[1]) < row_bottom
+        {
+            active.push(edges[next_edge]);
+            next_edge += 1;
+        }
+
+        active.retain(|edge| edge.from[1].max(edge.to[1]) > row_top);
+
+        row.fill(0.0);
+
+        for sample in 0..SAMPLES {
+            let sy = row_top + (sample as f32 + 0.5) / SAMPLES as f32;
+
+            crossings.clear();
+            for edge in &active {
+                let ([x0, y0], [x1, y1]) = (edge.from, edge.to);
+                let (low, high) = (y0.min(y1), y0.max(y1));
+
+                if low <= sy && sy < high {
+                    let x = x0 + (sy - y0) * (x1 - x0) / (y1 - y0);
+                    crossings.push((x, if y1 > y0 { 1 } else { -1 }));
+                }
+            }
+
+            crossings.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+            let mut winding = 0;
+            for pair in crossings.windows(2) {
+                winding += pair[0].1;
+
+                if rule.is_inside(winding) {
+                    accumulate(&mut row, pair[0].0 - left as f32, pair[1].0 - left as f32);
+                }
+            }
+        }
+
+        std::mem::swap(&mut previous, &mut current);
+        current.clear();
+
+        let mut candidates = previous.iter().copied().peekable();
+        let mut x = 0;
+        while x < row.len() {
+            let level = quantize(row[x]);
+            let start = x;
+            while x < row.len() && quantize(row[x]) == level {
+                x += 1;
+            }
+
+            if level == 0 {
+                continue;
+            }
+
+            let span = Span {
+                x: left + start as i32,
+                y: py,
+                width: (x - start) as i32,
+                height: 1,
+                coverage: f32::from(level) / 255.0,
+            };
+
+            // Previous spans are ordered by x, as are the runs in this row.
+            while candidates.peek().is_some_and(|&i| spans[i].x < span.x) {
+                candidates.next();
+            }
+
+            let extended = candidates.peek().copied().filter(|&i| {
+                let above = &spans[i];
+                above.x == span.x && above.width == span.width && above.coverage == span.coverage
+            });
+
+            if let Some(i) = extended {
+                spans[i].height += 1;
+                current.push(i);
+                candidates.next();
+            } else {
+                current.push(spans.len());
+                spans.push(span);
+            }
+        }
+    }
+
+    spans
+}
+
+/// Adds the coverage of one sub-scanline from `x0` to `x1` to each pixel in
+/// the row.
+#[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+fn accumulate(row: &mut [f32], x0: f32, x1: f32) {
+    let x0 = x0.clamp(0.0, row.len() as f32);
+    let x1 = x1.clamp(0.0, row.len() as f32);
+
+    if x0 >= x1 {
+        return;
+    }
+
+    let weight = 1.0 / SAMPLES as f32;
+    let first = x0.floor() as usize;
+    let last = (x1.ceil() as usize).min(row.len());
+
+    for (i, pixel) in row.iter_mut().enumerate().take(last).skip(first) {
+        let overlap = x1.min(i as f32 + 1.0) - x0.max(i as f32);
+        *pixel += overlap * weight;
+    }
+}
+
+#[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+fn quantize(coverage: f32) -> u8 {
+    (coverage.clamp(0.0, 1.0) * 255.0).round() as u8
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn square(x: f32, y: f32, size: f32) -> Vec<[f32; 2]> {
+        vec![[x, y], [x + size, y], [x + size, y + size], [x, y + size]]
+    }
+
+    #[test]
+    fn rect_is_one_span() {
+        let edges: Vec<_> = Edge::polygon(&square(2.0, 2.0, 4.0)).collect();
+        let spans = rasterize(&edges, FillRule::NonZero, [0, 0, 8, 8]);
+
+        assert_eq!(
+            spans,
+            [Span {
+                x: 2,
+                y: 2,
+                width: 4,
+                height: 4,
+                coverage: 1.0,
+            }]
+        );
+    }
+
+    #[test]
+    fn fill_rules() {
+        // Two overlapping squares wound the same way.
+        let mut edges: Vec<_> = Edge::polygon(&square(0.0, 0.0, 4.0)).collect();
+        edges.extend(Edge::polygon(&square(2.0, 0.0, 4.0)));
+
+        let nonzero = rasterize(&edges, FillRule::NonZero, [0, 0, 8, 8]);
+        assert_eq!(nonzero.len(), 1);
+        assert_eq!(nonzero[0].width, 6);
+
+        let evenodd = rasterize(&edges, FillRule::EvenOdd, [0, 0, 8, 8]);
+        let widths: Vec<_> = evenodd.iter().map(|s| (s.x, s.width)).collect();
+        assert_eq!(widths, [(0, 2), (4, 2)]);
+    }
+
+    #[test]
+    fn partial_coverage() {
+        let edges: Vec<_> = Edge::polygon(&square(0.5, 0.0, 1.0)).collect();
+        let spans = rasterize(&edges, FillRule::NonZero, [0, 0, 2, 1]);
+
+        assert_eq!(spans.len(), 1);
+        assert_eq!((spans[0].x, spans[0].width), (0, 2));
+        assert_eq!(spans[0].coverage, 128.0 / 255.0);
+    }
+}
@@ -0,0 +1,476 @@
+//! Signed distance fields for images that stay sharp at any scale.
+//!
+//! [`Layout::Alpha8Vector`] images store a single signed distance per texel,
+//! and [`Layout::Rgba8Vector`] images store a multi-channel signed distance
+//! field (MSDF) in their color channels, which also keeps sharp corners. The
+//! alpha channel of an MSDF holds the true signed distance.
+//!
+//! Distances are measured in texels and encoded as `0.5 + distance / (2 *
+//! SDF_RANGE)`, so that values above one half are inside the shape. Vector
+//! images are drawn as a mask, tinted by the color of the rect they fill.
+
+use crate::{
+    geometry::{Extent, Texel},
+    graphics::{
+        draw_list::PATH_TOLERANCE,
+        image::{Format, Info, Layout, Raster, RasterBuf},
+        path::{FillRule, Path},
+    },
+};
+
+/// The distance in texels from the edge of a shape at which a vector image's
+/// values saturate. Shapes should have at least this much space around them
+/// within the image.
+///
+/// Must be kept in sync with `SDF_RANGE` in `rect_shader.hlsl`.
+pub const SDF_RANGE: f32 = 4.0;
+
+/// Edges of a contour that turn by more than this angle (in radians) are
+/// treated as corners when assigning channels to the edges of an MSDF.
+const CORNER_ANGLE: f32 = 0.5;
+
+/// The channels of an MSDF that an edge contributes to. Adjacent edges that
+/// meet at a corner share exactly one channel.
+const WHITE: u8 = 0b111;
+const PALETTE: [u8; 3] = [0b011, 0b110, 0b101];
+
+impl Raster {
+    /// Computes an [`Layout::Alpha8Vector`] signed distance field from the
+    /// coverage in an [`Layout::Alpha8`] raster. The field has the same
+    /// extent as the raster, and anything beyond its edges is outside.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the raster is not `Alpha8`.
+    #[must_use]
+    #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+    pub fn sdf_from_alpha(alpha: &RasterBuf) -> Self {
+        assert_eq!(
+            alpha.info().layout,
+            Layout::Alpha8,
+            "Signed distance fields can only be computed from coverage."
+        );
+
+        let width = usize::try_from(alpha.width().0).unwrap();
+        let height = usize::try_from(alpha.height().0).unwrap();
+        let coverage = |x: usize, y: usize| f32::from(alpha.data()[y * width + x]) / 255.0;
+
+        // The fields are padded by a texel on every side so that the edges of
+        // the raster are treated as outside.
+        let (padded_width, padded_height) = (width + 2, height + 2);
+        let mut to_inside = vec![f32::INFINITY; padded_width * padded_height];
+        let mut to_outside = vec![0.0; padded_width * padded_height];
+
+        for y in 0..height {
+            for x in 0..width {
+                if coverage(x, y) >= 0.5 {
+                    let i = (y + 1) * padded_width + x + 1;
+                    to_inside[i] = 0.0;
+                    to_outside[i] = f32::INFINITY;
+                }
+            }
+        }
+
+        distance_transform(&mut to_inside, padded_width, padded_height);
+        distance_transform(&mut to_outside, padded_width, padded_height);
+
+        let mut data = Vec::with_capacity(width * height);
+        for y in 0..height {
+            for x in 0..width {
+                let value = coverage(x, y);
+                let i = (y + 1) * padded_width + x + 1;
+
+                // Partially covered texels are on the edge, and their coverage
+                // is a better estimate of the distance than the transform's.
+                let distance = if value > 0.0 && value < 1.0 {
+                    value - 0.5
+                } else if value >= 0.5 {
+                    to_outside[i].sqrt() - 0.5
+                } else {
+                    0.5 - to_inside[i].sqrt()
+                };
+
+                data.push(encode(distance));
+            }
+        }
+
+        let info = Info {
+            extent: alpha.info().extent,
+            format: Format::Linear,
+            layout: Layout::Alpha8Vector,
+        };
+
+        Self::new(info, data)
+    }
+
+    /// Computes an [`Layout::Rgba8Vector`] multi-channel signed distance
+    /// field of the area enclosed by `path`, with one texel per unit of the
+    /// path's coordinates. Open subpaths are closed with a straight line.
+    #[must_use]
+    #[allow(clippy::cast_precision_loss)]
+    pub fn msdf_from_path(path: &Path, rule: FillRule, extent: Extent<Texel>) -> Self {
+        let contours: Vec<Vec<[f32; 2]>> = path
+            .flatten(PATH_TOLERANCE)
+            .into_iter()
+            .map(|polyline| polyline.points)
+            .filter(|points| points.len() > 1)
+            .collect();
+
+        let segments = color_segments(&contours, rule);
+
+        let width = usize::try_from(extent.width.0).unwrap();
+        let height = usize::try_from(extent.height.0).unwrap();
+        let mut data = Vec::with_capacity(width * height * 4);
+
+        for y in 0..height {
+            for x in 0..width {
+                let p = [x as f32 + 0.5, y as f32 + 0.5];
+
+                let mut nearest = [None::<Nearest>; 3];
+                let mut true_distance = f32::INFINITY;
+
+                for segment in &segments {
+                    let candidate = segment.nearest(p);
+                    true_distance = true_distance.min(candidate.distance);
+
+                    for (channel, nearest) in nearest.iter_mut().enumerate() {
+                        if segment.channels & (1 << channel) != 0
+                            && nearest.is_none_or(|n| candidate.is_closer_than(&n))
+                        {
+                            *nearest = Some(candidate);
+                        }
+                    }
+                }
+
+                if !rule.is_inside(winding(&contours, p)) {
+                    true_distance = -true_distance;
+                }
+
+                let mut channels = nearest.map(|n| n.map_or(-f32::INFINITY, |n| n.pseudo_distance));
+
+                // Where the channels disagree with the shape, they would draw
+                // a spurious artifact away from the edges.
+                if (median(channels) >= 0.0) != (true_distance >= 0.0) {
+                    channels = [true_distance; 3];
+                }
+
+                data.extend(channels.map(encode));
+                data.push(encode(true_distance));
+            }
+        }
+
+        let info = Info {
+            extent,
+            format: Format::Linear,
+            layout: Layout::Rgba8Vector,
+        };
+
+        Self::new(info, data)
+    }
+}
+
+/// The median of the three color channels of an MSDF, which is the distance
+/// to the edge of the shape.
+pub(crate) fn median([r, g, b]: [f32; 3]) -> f32 {
+    r.min(g).max(r.max(g).min(b))
+}
+
+#[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+fn encode(distance: f32) -> u8 {
+    let value = 0.5 + distance / (2.0 * SDF_RANGE);
+    (value.clamp(0.0, 1.0) * 255.0).round() as u8
+}
+
+/// Replaces every value in the grid with the squared distance to the nearest
+/// zero value, using the algorithm from Felzenszwalb and Huttenlocher's
+/// "Distance Transforms of Sampled Functions".
+fn distance_transform(grid: &mut [f32], width: usize, height: usize) {
+    let mut column = vec![0.0; height];
+
+    for x in 0..width {
+        for y in 0..height {
+            column[y] = grid[y * width + x];
+        }
+
+        distance_transform_1d(&mut column);
+
+        for y in 0..height {
+            grid[y * width + x] = column[y];
+        }
+    }
+
+    for row in grid.chunks_exact_mut(width) {
+        distance_transform_1d(row);
+    }
+}
+
+#[allow(clippy::cast_precision_loss)]
+fn distance_transform_1d(values: &mut [f32]) {
+    let input = values.to_vec();
+
+    // The parabolas of the lower envelope, and where each begins.
+    let mut parabolas = Vec::with_capacity(input.len());
+    let mut starts = Vec::with_capacity(input.len() + 1);
+
+    for (q, &value) in input.iter().enumerate() {
+        if value.is_infinite() {
+            continue;
+        }
+
+        let intersection = |p: usize| {
+            let (q, p) = (q as f32, p as f32);
+            ((value + q * q) - (input[p as usize] + p * p)) / (2.0 * (q - p))
+        };
+
+        while let Some(&p) = parabolas.last() {
+            if intersection(p) <= *starts.last().unwrap() {
+                parabolas.pop();
+                starts.pop();
+            } else {
+                break;
+            }
+        }
+
+        starts.push(
+            parabolas
+                .last()
+                .map_or(f32::NEG_INFINITY, |&p| intersection(p)),
+        );
+        parabolas.push(q);
+    }
+
+    if parabolas.is_empty() {
+        return;
+    }
+
+    let mut k = 0;
+    for (q, value) in values.iter_mut().enumerate() {
+        while k + 1 < parabolas.len() && starts[k + 1] < q as f32 {
+            k += 1;
+        }
+
+        let p = parabolas[k];
+        let offset = q as f32 - p as f32;
+        *value = offset * offset + input[p];
+    }
+}
+
+/// The winding number of the closed contours around `p`.
+fn winding(contours: &[Vec<[f32; 2]>], p: [f32; 2]) -> i32 {
+    let mut winding = 0;
+
+    for points in contours {
+        for (i, &from) in points.iter().enumerate() {
+            let to = points[(i + 1) % points.len()];
+            let side = (to[0] - from[0]) * (p[1] - from[1]) - (p[0] - from[0]) * (to[1] - from[1]);
+
+            if from[1] <= p[1] && to[1] > p[1] && side > 0.0 {
+                winding += 1;
+            } else if from[1] > p[1] && to[1] <= p[1] && side < 0.0 {
+                winding -= 1;
+            }
+        }
+    }
+
+    winding
+}
+
+/// An edge of the shape and the channels that it contributes to.
+struct Segment {
+    from: [f32; 2],
+    to: [f32; 2],
+    channels: u8,
+    /// 1 if the area to the left of the segment is inside the shape, -1 if
+    /// it is to the right.
+    sign: f32,
+}
+
+#[derive(Clone, Copy)]
+struct Nearest {
+    /// The unsigned distance to the closest point on the segment.
+    distance: f32,
+    /// The signed distance to the line through the segment if the closest
+    /// point is one of its ends, or to the segment otherwise.
+    pseudo_distance: f32,
+    /// How far from perpendicular the direction to the closest point is,
+    /// used to pick between segments that share a closest point.
+    obliqueness: f32,
+}
+
+impl Nearest {
+    fn is_closer_than(&self, other: &Self) -> bool {
+        const EPSILON: f32 = 1e-4;
+
+        if (self.distance - other.distance).abs() <= EPSILON {
+            self.obliqueness < other.obliqueness
+        } else {
+            self.distance < other.distance
+        }
+    }
+}
+
+impl Segment {
+    fn nearest(&self, p: [f32; 2]) -> Nearest {
+        let d = [self.to[0] - self.from[0], self.to[1] - self.from[1]];
+        let length = d[0].hypot(d[1]);
+        let direction = [d[0] / length, d[1] / length];
+
+        let offset = [p[0] - self.from[0], p[1] - self.from[1]];
+        let along = offset[0] * direction[0] + offset[1] * direction[1];
+        let across = (direction[0] * offset[1] - direction[1] * offset[0]) * self.sign;
+
+        let t = along.clamp(0.0, length);
+        let closest = [p[0] - direction[0] * t, p[1] - direction[1] * t];
+        let closest = [closest[0] - self.from[0], closest[1] - self.from[1]];
+        let distance = closest[0].hypot(closest[1]);
+
+        let obliqueness = if along < 0.0 || along > length {
+            (along - t).abs() / distance.max(f32::EPSILON)
+        } else {
+            0.0
+        };
+
+        Nearest {
+            distance,
+            pseudo_distance: across,
+            obliqueness,
+        }
+    }
+}
+
+/// Splits the contours into segments, assigning channels so that the
+/// segments on either side of a corner share only one.
+///
+/// Segments with the same fill on both sides, such as those inside another
+/// contour, are not part of the shape's edge and are left out.
+fn color_segments(contours: &[Vec<[f32; 2]>], rule: FillRule) -> Vec<Segment> {
+    let mut segments = Vec::new();
+
+    for points in contours {
+        let n = points.len();
+        let direction = |i: usize| {
+            let (from, to) = (points[i % n], points[(i + 1) % n]);
+            (to[1] - from[1]).atan2(to[0] - from[0])
+        };
+
+        // A corner at `i` lies between segment `i - 1` and segment `i`.
+        let corners: Vec<usize> = (0..n)
+            .filter(|&i| {
+                let turn = direction(i) - direction(i + n - 1);
+                let turn = (turn + std::f32::consts::PI).rem_euclid(std::f32::consts::TAU)
+                    - std::f32::consts::PI;
+                turn.abs() > CORNER_ANGLE
+            })
+            .collect();
+
+        let mut channels = vec![WHITE; n];
+
+        match corners.len() {
+            0 => {}
+            // A teardrop is split into three parts so that its corner is kept.
+            1 if n >= 3 => {
+                for (k, channel) in channels.iter_mut().enumerate() {
+                    *channel = PALETTE[k * 3 / n];
+                }
+                channels.rotate_right(corners[0]);
+            }
+            1 => {}
+            count => {
+                for (run, &start) in corners.iter().enumerate() {
+                    let end = corners.get(run + 1).copied().unwrap_or(corners[0] + n);
+                    // The last run meets the first, so they must differ.
+                    let color = if run == count - 1 && count % 3 == 1 {
+                        PALETTE[1]
+                    } else {
+                        PALETTE[run % 3]
+                    };
+
+                    for i in start..end {
+                        channels[i % n] = color;
+                    }
+                }
+            }
+        }
+
+        for (i, &channel) in channels.iter().enumerate() {
+            let (from, to) = (points[i], points[(i + 1) % n]);
+            let d = [to[0] - from[0], to[1] - from[1]];
+            let length = d[0].hypot(d[1]);
+
+            if length == 0.0 {
+                continue;
+            }
+
+            let mid = [(from[0] + to[0]) / 2.0, (from[1] + to[1]) / 2.0];
+            let normal = [-d[1] / length * 1e-3, d[0] / length * 1e-3];
+
+            let left = rule.is_inside(winding(contours, [mid[0] + normal[0], mid[1] + normal[1]]));
+            let right = rule.is_inside(winding(contours, [mid[0] - normal[0], mid[1] - normal[1]]));
+
+            if left != right {
+                segments.push(Segment {
+                    from,
+                    to,
+                    channels: channel,
+                    sign: if left { 1.0 } else { -1.0 },
+                });
+            }
+        }
+    }
+
+    segments
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn decode(value: u8) -> f32 {
+        (f32::from(value) / 255.0 - 0.5) * 2.0 * SDF_RANGE
+    }
+
+    #[test]
+    fn alpha_distances() {
+        let info = Info {
+            extent: Extent::new(9, 9),
+            format: Format::Linear,
+            layout: Layout::Alpha8,
+        };
+
+        let row = [0, 0, 0, 128, 255, 255, 255, 255, 255];
+        let coverage = row.repeat(9);
+        let sdf = Raster::sdf_from_alpha(&RasterBuf::new(info, &coverage));
+
+        // the middle row is far enough from the top and bottom edges
+        let distances: Vec<f32> = sdf.data()[36..45].iter().map(|&v| decode(v)).collect();
+
+        // the edge of the raster is outside
+        assert!((distances[8] - 0.5).abs() < 0.05);
+        assert!((distances[6] - 2.5).abs() < 0.05);
+        assert!(distances[3].abs() < 0.05);
+        assert!((distances[0] + 2.5).abs() < 0.05);
+    }
+
+    #[test]
+    fn msdf_corners() {
+        let square = Path::new()
+            .move_to((4.0, 4.0))
+            .line_to((12.0, 4.0))
+            .line_to((12.0, 12.0))
+            .line_to((4.0, 12.0))
+            .close();
+
+        let msdf = Raster::msdf_from_path(&square, FillRule::NonZero, Extent::new(16, 16));
+        let distance = |x: usize, y: usize| {
+            let texel = &msdf.data()[(y * 16 + x) * 4..][..4];
+            median([decode(texel[0]), decode(texel[1]), decode(texel[2])])
+        };
+
+        assert!((distance(8, 8) - 3.5).abs() < 0.05);
+        assert!((distance(8, 1) + 2.5).abs() < 0.05);
+        // the corner is sharp: the texel just outside it along the diagonal
+        // is outside by less than a texel
+        assert!(distance(12, 12) < 0.0);
+        assert!(distance(11, 11) > 0.0);
+    }
+}
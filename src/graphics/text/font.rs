@@ -0,0 +1,162 @@
+use std::sync::{
+    atomic::{AtomicU64, Ordering},
+    Arc,
+};
+
+use ttf_parser::{Face, FaceParsingError, GlyphId, OutlineBuilder};
+
+use crate::graphics::path::Path;
+
+static NEXT_FONT_ID: AtomicU64 = AtomicU64::new(0);
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error("The font data could not be parsed: {0}")]
+    Malformed(#[from] FaceParsingError),
+}
+
+/// Identifies a loaded font. Every call to [`Font::from_bytes`] produces a
+/// different id, even for the same data.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct FontId(pub(crate) u64);
+
+/// Vertical metrics for a font at a particular size, in pixels.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct FontMetrics {
+    /// The distance from the baseline to the top of the tallest glyphs.
+    pub ascent: f32,
+    /// The distance from the baseline to the bottom of the lowest glyphs.
+    /// This is positive for fonts that descend below the baseline.
+    pub descent: f32,
+    /// The recommended space between the descent of one line and the ascent
+    /// of the next.
+    pub line_gap: f32,
+}
+
+/// A TrueType or OpenType font.
+///
+/// Fonts are cheap to clone, and share their data.
+#[derive(Clone)]
+pub struct Font {
+    id: FontId,
+    data: Arc<[u8]>,
+    index: u32,
+    units_per_em: f32,
+}
+
+impl Font {
+    /// Loads a font from the contents of a font file. `index` selects a face
+    /// from a font collection, and should be 0 otherwise.
+    ///
+    /// # Errors
+    ///
+    /// This function returns an error if the data is not a font, or if the
+    /// collection has no face at `index`.
+    pub fn from_bytes(data: impl Into<Arc<[u8]>>, index: u32) -> Result<Self, Error> {
+        let data = data.into();
+        let face = Face::parse(&data, index)?;
+        let units_per_em = f32::from(face.units_per_em());
+
+        Ok(Self {
+            id: FontId(NEXT_FONT_ID.fetch_add(1, Ordering::Relaxed)),
+            data,
+            index,
+            units_per_em,
+        })
+    }
+
+    #[must_use]
+    pub fn id(&self) -> FontId {
+        self.id
+    }
+
+    #[must_use]
+    pub fn metrics(&self, size: f32) -> FontMetrics {
+        let face = self.face();
+        let scale = size / self.units_per_em;
+
+        FontMetrics {
+            ascent: f32::from(face.ascender()) * scale,
+            descent: -f32::from(face.descender()) * scale,
+            line_gap: f32::from(face.line_gap()) * scale,
+        }
+    }
+
+    /// The font's data, parsed. This was validated when the font was loaded,
+    /// so it cannot fail.
+    pub(crate) fn face(&self) -> Face<'_> {
+        Face::parse(&self.data, self.index).unwrap()
+    }
+
+    /// The number of pixels per font unit at `size`.
+    pub(crate) fn scale(&self, size: f32) -> f32 {
+        size / self.units_per_em
+    }
+
+    /// The outline of a glyph at `size`, with the glyph's origin on the
+    /// baseline at `origin` and y pointing down.
+    ///
+    /// Returns `None` if the glyph has no outline, like a space.
+    pub(crate) fn outline(&self, glyph: u16, size: f32, origin: [f32; 2]) -> Option<Path> {
+        let mut builder = PathBuilder {
+            path: Path::new(),
+            scale: self.scale(size),
+            origin,
+        };
+
+        self.face().outline_glyph(GlyphId(glyph), &mut builder)?;
+        Some(builder.path)
+    }
+}
+
+impl std::fmt::Debug for Font {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Font")
+            .field("id", &self.id)
+            .field("index", &self.index)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Converts glyph outlines from font units with y pointing up.
+struct PathBuilder {
+    path: Path,
+    scale: f32,
+    origin: [f32; 2],
+}
+
+impl PathBuilder {
+    fn point(&self, x: f32, y: f32) -> (f32, f32) {
+        (
+            self.origin[0] + x * self.scale,
+            self.origin[1] - y * self.scale,
+        )
+    }
+}
+
+impl OutlineBuilder for PathBuilder {
+    fn move_to(&mut self, x: f32, y: f32) {
+        let point = self.point(x, y);
+        self.path = std::mem::take(&mut self.path).move_to(point);
+    }
+
+    fn line_to(&mut self, x: f32, y: f32) {
+        let point = self.point(x, y);
+        self.path = std::mem::take(&mut self.path).line_to(point);
+    }
+
+    fn quad_to(&mut self, x1: f32, y1: f32, x: f32, y: f32) {
+        let (control, point) = (self.point(x1, y1), self.point(x, y));
+        self.path = std::mem::take(&mut self.path).quad_to(control, point);
+    }
+
+    fn curve_to(&mut self, x1: f32, y1: f32, x2: f32, y2: f32, x: f32, y: f32) {
+        let (control1, control2) = (self.point(x1, y1), self.point(x2, y2));
+        let point = self.point(x, y);
+        self.path = std::mem::take(&mut self.path).cubic_to(control1, control2, point);
+    }
+
+    fn close(&mut self) {
+        self.path = std::mem::take(&mut self.path).close();
+    }
+}
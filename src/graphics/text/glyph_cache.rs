@@ -0,0 +1,168 @@
+use std::collections::HashMap;
+
+use crate::{
+    geometry::{Extent, Texel},
+    graphics::{
+        draw_list::PATH_TOLERANCE,
+        image::{Format, Info as ImageInfo, Layout, Raster},
+        path::FillRule,
+        rasterizer::{rasterize, Edge},
+        texture_atlas::CachedTextureId,
+    },
+};
+
+use super::font::{Font, FontId};
+
+/// Glyphs are rasterized at this many horizontal offsets within a pixel so
+/// that text can be positioned more precisely than whole pixels.
+const SUBPIXEL_STEPS: f32 = 4.0;
+
+/// Identifies a glyph rasterized at a particular size and subpixel offset.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub(crate) struct GlyphKey {
+    font: FontId,
+    glyph: u16,
+    /// The bits of the size in pixels, so that the key can be hashed.
+    size: u32,
+    subpixel: u8,
+}
+
+impl GlyphKey {
+    /// The key for a glyph with its origin at `x`, and the whole pixel that
+    /// its bitmap is positioned relative to.
+    #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+    pub fn new(font: FontId, glyph: u16, size: f32, x: f32) -> (Self, i32) {
+        let steps = (x * SUBPIXEL_STEPS).round();
+        let pixel = (steps / SUBPIXEL_STEPS).floor();
+        let subpixel = (steps - pixel * SUBPIXEL_STEPS) as u8;
+
+        let key = Self {
+            font,
+            glyph,
+            size: size.to_bits(),
+            subpixel,
+        };
+
+        (key, pixel as i32)
+    }
+
+    fn size(self) -> f32 {
+        f32::from_bits(self.size)
+    }
+
+    fn offset(self) -> f32 {
+        f32::from(self.subpixel) / SUBPIXEL_STEPS
+    }
+}
+
+/// A glyph's bitmap in the texture cache.
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct CachedGlyph {
+    pub image: CachedTextureId,
+    /// The position of the bitmap's top-left corner relative to the glyph's
+    /// origin, in whole pixels.
+    pub offset: [i32; 2],
+    pub extent: Extent<Texel>,
+}
+
+/// Glyph bitmaps that have been uploaded to the texture cache.
+///
+/// Glyphs stay cached for the life of the graphics context.
+#[derive(Default)]
+pub(crate) struct GlyphCache {
+    /// Glyphs without an outline map to `None`.
+    glyphs: HashMap<GlyphKey, Option<CachedGlyph>>,
+}
+
+impl GlyphCache {
+    pub fn contains(&self, key: GlyphKey) -> bool {
+        self.glyphs.contains_key(&key)
+    }
+
+    /// Looks up a glyph that has been rasterized. Returns `None` if the glyph
+    /// has not been cached, or has nothing to draw.
+    pub fn get(&self, key: GlyphKey) -> Option<&CachedGlyph> {
+        self.glyphs.get(&key).and_then(Option::as_ref)
+    }
+
+    pub fn insert(&mut self, key: GlyphKey, glyph: Option<CachedGlyph>) {
+        self.glyphs.insert(key, glyph);
+    }
+}
+
+/// Rasterizes a glyph into an `Alpha8` bitmap of its coverage, along with the
+/// position of the bitmap relative to the glyph's origin.
+///
+/// Returns `None` if the glyph has no outline, or covers no pixels.
+#[allow(
+    clippy::cast_possible_truncation,
+    clippy::cast_possible_wrap,
+    clippy::cast_sign_loss
+)]
+pub(crate) fn rasterize_glyph(font: &Font, key: GlyphKey) -> Option<(Raster, [i32; 2])> {
+    let path = font.outline(key.glyph, key.size(), [key.offset(), 0.0])?;
+
+    let edges: Vec<Edge> = path
+        .flatten(PATH_TOLERANCE)
+        .iter()
+        .flat_map(|polyline| Edge::polygon(&polyline.points))
+        .collect();
+
+    let (mut min, mut max) = ([f32::MAX; 2], [f32::MIN; 2]);
+    for edge in &edges {
+        min = [min[0].min(edge.from[0]), min[1].min(edge.from[1])];
+        max = [max[0].max(edge.from[0]), max[1].max(edge.from[1])];
+    }
+
+    let bounds = [
+        min[0].floor() as i32,
+        min[1].floor() as i32,
+        max[0].ceil() as i32,
+        max[1].ceil() as i32,
+    ];
+
+    let (width, height) = (bounds[2] - bounds[0], bounds[3] - bounds[1]);
+    if width <= 0 || height <= 0 {
+        return None;
+    }
+
+    let mut coverage = vec![0; (width * height) as usize];
+    for span in rasterize(&edges, FillRule::NonZero, bounds) {
+        let value = (span.coverage * 255.0).round() as u8;
+
+        for y in span.y - bounds[1]..span.y - bounds[1] + span.height {
+            let row = (y * width) as usize;
+            let start = row + (span.x - bounds[0]) as usize;
+            coverage[start..start + span.width as usize].fill(value);
+        }
+    }
+
+    let info = ImageInfo {
+        extent: Extent::new(width as i16, height as i16),
+        layout: Layout::Alpha8,
+        format: Format::Linear,
+    };
+
+    Some((Raster::new(info, coverage), [bounds[0], bounds[1]]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn subpixel_positions() {
+        let font = FontId(0);
+        let key = |x| {
+            let (key, pixel) = GlyphKey::new(font, 1, 12.0, x);
+            (pixel, key.subpixel)
+        };
+
+        assert_eq!(key(0.0), (0, 0));
+        assert_eq!(key(2.3), (2, 1));
+        assert_eq!(key(2.5), (2, 2));
+        // offsets that round up to the next pixel start at its left edge
+        assert_eq!(key(2.9), (3, 0));
+        assert_eq!(key(-0.25), (-1, 3));
+    }
+}
@@ -0,0 +1,696 @@
+use std::ops::Range;
+
+use rustybuzz::{Direction, UnicodeBuffer};
+use ttf_parser::Face;
+use unicode_bidi::{bidi_class, BidiClass, BidiInfo, Level};
+use unicode_linebreak::{linebreaks, BreakOpportunity};
+
+use crate::geometry::{Extent, Pixel, Point, Rect};
+
+use super::{
+    font::{Font, FontMetrics},
+    glyph_cache::GlyphKey,
+};
+
+/// How lines are positioned horizontally within a layout.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum TextAlign {
+    /// Left for left-to-right paragraphs and right for right-to-left ones.
+    #[default]
+    Start,
+    /// Right for left-to-right paragraphs and left for right-to-left ones.
+    End,
+    Left,
+    Center,
+    Right,
+}
+
+/// The distance between the tops of consecutive lines.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum LineHeight {
+    /// The ascent, descent and line gap of the line's fonts.
+    #[default]
+    Normal,
+    /// A multiple of the font size.
+    Relative(f32),
+    /// A height in pixels.
+    Absolute(f32),
+}
+
+/// Describes how text is shaped and arranged.
+#[derive(Clone, Debug)]
+pub struct TextStyle {
+    /// The fonts to draw with, in order of preference. Characters are drawn
+    /// with the first font that has a glyph for them.
+    pub fonts: Vec<Font>,
+    /// The font size in pixels.
+    pub size: f32,
+    pub align: TextAlign,
+    pub line_height: LineHeight,
+}
+
+impl TextStyle {
+    #[must_use]
+    pub fn new(font: &Font, size: f32) -> Self {
+        Self {
+            fonts: vec![font.clone()],
+            size,
+            align: TextAlign::default(),
+            line_height: LineHeight::default(),
+        }
+    }
+
+    /// Adds a font to use for characters that the fonts before it do not
+    /// have.
+    #[must_use]
+    pub fn with_fallback(mut self, font: &Font) -> Self {
+        self.fonts.push(font.clone());
+        self
+    }
+
+    #[must_use]
+    pub fn with_align(mut self, align: TextAlign) -> Self {
+        self.align = align;
+        self
+    }
+
+    #[must_use]
+    pub fn with_line_height(mut self, line_height: LineHeight) -> Self {
+        self.line_height = line_height;
+        self
+    }
+}
+
+/// A line of text within a layout.
+#[derive(Clone, Debug, PartialEq)]
+pub struct LineBox {
+    /// The line's glyphs, not counting trailing whitespace, and the full
+    /// height of the line.
+    pub rect: Rect<Pixel>,
+    /// The distance from the top of the layout to the line's baseline.
+    pub baseline: f32,
+    /// The bytes of the text on the line, including any trailing whitespace
+    /// and line break.
+    pub range: Range<usize>,
+}
+
+/// A glyph placed within a layout.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) struct PositionedGlyph {
+    /// An index into the layout's fonts.
+    pub font: u16,
+    pub glyph: u16,
+    /// The glyph's origin on the baseline, relative to the top-left corner of
+    /// the layout.
+    pub position: Point<Pixel>,
+}
+
+/// The horizontal extent of a cluster of glyphs that are drawn for a range of
+/// bytes, used for hit testing.
+#[derive(Clone, Debug, PartialEq)]
+struct ClusterBox {
+    range: Range<usize>,
+    left: f32,
+    right: f32,
+    rtl: bool,
+}
+
+impl ClusterBox {
+    /// The edge at which a caret before the cluster is drawn.
+    fn leading(&self) -> f32 {
+        if self.rtl {
+            self.right
+        } else {
+            self.left
+        }
+    }
+
+    /// The edge at which a caret after the cluster is drawn.
+    fn trailing(&self) -> f32 {
+        if self.rtl {
+            self.left
+        } else {
+            self.right
+        }
+    }
+}
+
+/// A shaped glyph, before it is placed on a line.
+#[derive(Clone, Copy, Debug)]
+struct ShapedGlyph {
+    font: u16,
+    glyph: u16,
+    /// The index of the first byte of the text that the glyph was shaped from.
+    cluster: usize,
+    advance: f32,
+    offset: [f32; 2],
+}
+
+/// Text that has been shaped and broken into lines for drawing.
+///
+/// Lines are broken at Unicode line break opportunities to fit within a max
+/// width, and each line is reordered for display with the Unicode
+/// bidirectional algorithm. Text is shaped once before it is broken into
+/// lines, so shaping does not change across line breaks.
+///
+/// Glyphs must be prepared with [`Graphics::prepare_text`] before the layout
+/// can be drawn.
+///
+/// [`Graphics::prepare_text`]: crate::graphics::Graphics::prepare_text
+#[derive(Clone, Debug)]
+pub struct TextLayout {
+    fonts: Vec<Font>,
+    glyphs: Vec<PositionedGlyph>,
+    size: f32,
+    extent: Extent<Pixel>,
+    lines: Vec<LineBox>,
+    /// The clusters on each line, from left to right.
+    clusters: Vec<Vec<ClusterBox>>,
+}
+
+impl TextLayout {
+    /// Lays out `text`, breaking lines that would be wider than `max_width`.
+    ///
+    /// Lines are aligned within `max_width`, or within the widest line if
+    /// there is no max width. Words that are wider than `max_width` on their
+    /// own overflow it.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `style` has no fonts.
+    #[must_use]
+    pub fn new(text: &str, style: &TextStyle, max_width: Option<f32>) -> Self {
+        assert!(!style.fonts.is_empty(), "TextStyle must have a font");
+
+        let bidi = BidiInfo::new(text, None);
+        let shaped = shape(text, style, &bidi);
+        let ranges = break_lines(text, &shaped, max_width);
+
+        let metrics: Vec<FontMetrics> = style
+            .fonts
+            .iter()
+            .map(|font| font.metrics(style.size))
+            .collect();
+
+        let mut glyphs = Vec::with_capacity(shaped.len());
+        let mut lines = Vec::with_capacity(ranges.len());
+        let mut clusters = Vec::with_capacity(ranges.len());
+        let mut top = 0.0;
+
+        for range in ranges {
+            let content = range.start..trim_line_break(text, range.clone());
+            let para = bidi
+                .paragraphs
+                .iter()
+                .find(|para| para.range.contains(&range.start))
+                .or(bidi.paragraphs.last());
+
+            let mut line_clusters = Vec::new();
+            let mut line_glyphs = Vec::new();
+            let mut pen = 0.0;
+
+            if let Some(para) = para.filter(|_| !content.is_empty()) {
+                let content = content.start..content.end.min(para.range.end);
+                let (levels, runs) = bidi.visual_runs(para, content);
+
+                for run in runs {
+                    let rtl = levels[run.start].is_rtl();
+                    let run_glyphs = glyphs_in(&shaped, run.clone());
+
+                    let mut place = |index: usize| {
+                        let glyph = &shaped[index];
+                        let next = shaped[index + 1..]
+                            .iter()
+                            .map(|g| g.cluster)
+                            .find(|&cluster| cluster != glyph.cluster)
+                            .map_or(run.end, |cluster| cluster.min(run.end));
+
+                        line_glyphs.push((pen, *glyph));
+
+                        match line_clusters.last_mut() {
+                            Some(ClusterBox { range, right, .. })
+                                if range.start == glyph.cluster =>
+                            {
+                                *right = pen + glyph.advance;
+                            }
+                            _ => line_clusters.push(ClusterBox {
+                                range: glyph.cluster..next,
+                                left: pen,
+                                right: pen + glyph.advance,
+                                rtl,
+                            }),
+                        }
+
+                        pen += glyph.advance;
+                    };
+
+                    if rtl {
+                        run_glyphs.rev().for_each(&mut place);
+                    } else {
+                        run_glyphs.for_each(&mut place);
+                    }
+                }
+            }
+
+            let rtl = para.is_some_and(|para| para.level.is_rtl());
+
+            // Trailing whitespace hangs past the end of the line, and is not
+            // counted when aligning it.
+            let visible = range.start..range.start + text[content.clone()].trim_end().len();
+            let hanging = pen - advance(&shaped, visible);
+            let width = pen - hanging;
+
+            let fonts_used = line_glyphs
+                .iter()
+                .map(|(_, glyph)| usize::from(glyph.font))
+                .chain(line_glyphs.is_empty().then_some(0));
+
+            let (mut ascent, mut descent, mut line_gap) = (0.0f32, 0.0f32, 0.0f32);
+            for font in fonts_used {
+                ascent = ascent.max(metrics[font].ascent);
+                descent = descent.max(metrics[font].descent);
+                line_gap = line_gap.max(metrics[font].line_gap);
+            }
+
+            let height = match style.line_height {
+                LineHeight::Normal => ascent + descent + line_gap,
+                LineHeight::Relative(factor) => factor * style.size,
+                LineHeight::Absolute(height) => height,
+            };
+
+            let baseline = top + (height - ascent - descent) / 2.0 + ascent;
+            // The start of the visible glyphs, before alignment.
+            let start = if rtl { hanging } else { 0.0 };
+
+            lines.push((
+                LineBox {
+                    rect: Rect::new((0.0, top), (width, height)),
+                    baseline,
+                    range,
+                },
+                glyphs.len()..glyphs.len() + line_glyphs.len(),
+                rtl,
+            ));
+
+            glyphs.extend(line_glyphs.into_iter().map(|(x, glyph)| PositionedGlyph {
+                font: glyph.font,
+                glyph: glyph.glyph,
+                position: Point::new(x + glyph.offset[0] - start, baseline - glyph.offset[1]),
+            }));
+
+            for cluster in &mut line_clusters {
+                cluster.left -= start;
+                cluster.right -= start;
+            }
+
+            clusters.push(line_clusters);
+            top += height;
+        }
+
+        let widest = lines
+            .iter()
+            .map(|(line, ..)| line.rect.extent.width.0)
+            .fold(0.0, f32::max);
+        let available = max_width.unwrap_or(widest);
+
+        // Lines and their glyphs were laid out at x = 0, so they only need to
+        // be moved into place.
+        let lines = lines
+            .into_iter()
+            .zip(&mut clusters)
+            .map(|((mut line, line_glyphs, rtl), clusters)| {
+                let width = line.rect.extent.width.0;
+                let left = match (style.align, rtl) {
+                    (TextAlign::Left, _) | (TextAlign::Start, false) | (TextAlign::End, true) => {
+                        0.0
+                    }
+                    (TextAlign::Right, _) | (TextAlign::Start, true) | (TextAlign::End, false) => {
+                        available - width
+                    }
+                    (TextAlign::Center, _) => (available - width) / 2.0,
+                };
+
+                line.rect.origin.x = Pixel(left);
+
+                for cluster in clusters.iter_mut() {
+                    cluster.left += left;
+                    cluster.right += left;
+                }
+
+                for glyph in &mut glyphs[line_glyphs] {
+                    glyph.position.x.0 += left;
+                }
+
+                line
+            })
+            .collect();
+
+        Self {
+            fonts: style.fonts.clone(),
+            glyphs,
+            size: style.size,
+            extent: Extent::new(available, top),
+            lines,
+            clusters,
+        }
+    }
+
+    /// The size of the box that lines are aligned in.
+    #[must_use]
+    pub fn extent(&self) -> Extent<Pixel> {
+        self.extent
+    }
+
+    /// The distance from the top of the layout to the first line's baseline.
+    #[must_use]
+    pub fn baseline(&self) -> f32 {
+        self.lines[0].baseline
+    }
+
+    /// The font size in pixels.
+    #[must_use]
+    pub fn size(&self) -> f32 {
+        self.size
+    }
+
+    /// The lines of the layout, from top to bottom. There is always at least
+    /// one line, even if the text is empty.
+    #[must_use]
+    pub fn lines(&self) -> &[LineBox] {
+        &self.lines
+    }
+
+    /// Finds the caret position closest to `point`, as a byte index into the
+    /// text.
+    ///
+    /// Points above or below the layout hit the first or last line, and points
+    /// beside a line hit its nearest end.
+    #[must_use]
+    pub fn hit_test(&self, point: impl Into<Point<Pixel>>) -> usize {
+        let point = point.into();
+
+        let line_index = self
+            .lines
+            .iter()
+            .position(|line| point.y.0 < line.rect.origin.y.0 + line.rect.extent.height.0)
+            .unwrap_or(self.lines.len() - 1);
+
+        let clusters = &self.clusters[line_index];
+        let (Some(first), Some(last)) = (clusters.first(), clusters.last()) else {
+            return self.lines[line_index].range.start;
+        };
+
+        let x = point.x.0;
+        let edge = |cluster: &ClusterBox, left_side: bool| {
+            if left_side != cluster.rtl {
+                cluster.range.start
+            } else {
+                cluster.range.end
+            }
+        };
+
+        if x < first.left {
+            return edge(first, true);
+        }
+
+        clusters
+            .iter()
+            .find(|cluster| x < cluster.right)
+            .map_or_else(
+                || edge(last, false),
+                |cluster| edge(cluster, x < (cluster.left + cluster.right) / 2.0),
+            )
+    }
+
+    /// The position of the caret before the byte at `index`, as a rect with
+    /// no width that spans the height of its line.
+    ///
+    /// Indices within a cluster of glyphs, like a ligature, are moved to the
+    /// start of the cluster. Indices past the end of the text are moved to the
+    /// end.
+    #[must_use]
+    pub fn caret_rect(&self, index: usize) -> Rect<Pixel> {
+        let line_index = self
+            .lines
+            .iter()
+            .rposition(|line| line.range.start <= index)
+            .unwrap_or(0);
+
+        let line = &self.lines[line_index];
+        let clusters = &self.clusters[line_index];
+
+        let x = clusters
+            .iter()
+            .find(|cluster| cluster.range.contains(&index))
+            .map(ClusterBox::leading)
+            .or_else(|| {
+                // The caret is after the last cluster in logical order.
+                clusters
+                    .iter()
+                    .max_by_key(|cluster| cluster.range.end)
+                    .filter(|cluster| cluster.range.end <= index)
+                    .map(ClusterBox::trailing)
+            })
+            .unwrap_or(line.rect.origin.x.0);
+
+        Rect::new((x, line.rect.origin.y.0), (0.0, line.rect.extent.height.0))
+    }
+
+    pub(crate) fn glyphs(&self) -> &[PositionedGlyph] {
+        &self.glyphs
+    }
+
+    pub(crate) fn font(&self, glyph: &PositionedGlyph) -> &Font {
+        &self.fonts[usize::from(glyph.font)]
+    }
+
+    /// The key for a glyph's bitmap, and the whole pixel that the bitmap is
+    /// positioned relative to.
+    pub(crate) fn glyph_key(&self, glyph: &PositionedGlyph) -> (GlyphKey, i32) {
+        let font = self.font(glyph);
+        GlyphKey::new(font.id(), glyph.glyph, self.size, glyph.position.x.0)
+    }
+}
+
+/// Shapes runs of text that share a font and direction, and returns the
+/// glyphs in logical order.
+#[allow(clippy::cast_possible_truncation, clippy::cast_precision_loss)]
+fn shape(text: &str, style: &TextStyle, bidi: &BidiInfo) -> Vec<ShapedGlyph> {
+    let faces: Vec<Face> = style.fonts.iter().map(Font::face).collect();
+
+    let mut runs: Vec<(Range<usize>, usize, Level)> = Vec::new();
+    let mut previous = 0;
+
+    for (index, c) in text.char_indices() {
+        let font = choose_font(&faces, c, previous);
+        let level = bidi.levels[index];
+        let end = index + c.len_utf8();
+
+        match runs.last_mut() {
+            Some((range, run_font, run_level)) if *run_font == font && *run_level == level => {
+                range.end = end;
+            }
+            _ => runs.push((index..end, font, level)),
+        }
+
+        previous = font;
+    }
+
+    let mut glyphs = Vec::with_capacity(text.len());
+
+    for (range, font, level) in runs {
+        let face = rustybuzz::Face::from_face(faces[font].clone());
+        let scale = style.fonts[font].scale(style.size);
+
+        let mut buffer = UnicodeBuffer::new();
+        buffer.push_str(&text[range.clone()]);
+        buffer.set_direction(if level.is_rtl() {
+            Direction::RightToLeft
+        } else {
+            Direction::LeftToRight
+        });
+        buffer.guess_segment_properties();
+
+        let output = rustybuzz::shape(&face, &[], buffer);
+        let start = glyphs.len();
+
+        for (info, position) in output.glyph_infos().iter().zip(output.glyph_positions()) {
+            glyphs.push(ShapedGlyph {
+                font: font as u16,
+                glyph: info.glyph_id as u16,
+                cluster: range.start + info.cluster as usize,
+                advance: position.x_advance as f32 * scale,
+                offset: [
+                    position.x_offset as f32 * scale,
+                    position.y_offset as f32 * scale,
+                ],
+            });
+        }
+
+        // Right-to-left runs are shaped in visual order.
+        if level.is_rtl() {
+            glyphs[start..].reverse();
+        }
+    }
+
+    glyphs
+}
+
+/// Picks the first font that has a glyph for `c`. Whitespace, control
+/// characters and combining marks stay in the previous character's font where
+/// possible so that they do not split runs.
+fn choose_font(faces: &[Face], c: char, previous: usize) -> usize {
+    let inherits = c.is_whitespace() || c.is_control() || bidi_class(c) == BidiClass::NSM;
+
+    if inherits && (c.is_control() || faces[previous].glyph_index(c).is_some()) {
+        return previous;
+    }
+
+    faces
+        .iter()
+        .position(|face| face.glyph_index(c).is_some())
+        .unwrap_or(0)
+}
+
+/// Breaks text into lines at line break opportunities, keeping lines within
+/// `max_width` where possible. Mandatory breaks, like newlines, always end a
+/// line.
+fn break_lines(text: &str, shaped: &[ShapedGlyph], max_width: Option<f32>) -> Vec<Range<usize>> {
+    let mut lines = Vec::new();
+    let mut start = 0;
+    // The last break opportunity on the current line.
+    let mut end = 0;
+
+    for (index, opportunity) in linebreaks(text) {
+        if let Some(max_width) = max_width {
+            let visible = start + text[start..index].trim_end().len();
+
+            if end > start && advance(shaped, start..visible) > max_width {
+                lines.push(start..end);
+                start = end;
+            }
+        }
+
+        end = index;
+
+        if opportunity == BreakOpportunity::Mandatory {
+            lines.push(start..end);
+            start = end;
+        }
+    }
+
+    // Text that ends with a line break, or is empty, ends with an empty line.
+    if lines
+        .last()
+        .is_none_or(|line| line.end > trim_line_break(text, line.clone()))
+    {
+        lines.push(text.len()..text.len());
+    }
+
+    lines
+}
+
+/// The end of a line, not counting a line break at its end.
+fn trim_line_break(text: &str, line: Range<usize>) -> usize {
+    let content = text[line.clone()].trim_end_matches([
+        '\n', '\r', '\u{b}', '\u{c}', '\u{85}', '\u{2028}', '\u{2029}',
+    ]);
+    line.start + content.len()
+}
+
+/// The indices of the glyphs shaped from the bytes in `range`.
+fn glyphs_in(
+    shaped: &[ShapedGlyph],
+    range: Range<usize>,
+) -> impl DoubleEndedIterator<Item = usize> {
+    let start = shaped.partition_point(|glyph| glyph.cluster < range.start);
+    let end = shaped.partition_point(|glyph| glyph.cluster < range.end);
+    start..end
+}
+
+/// The total advance of the glyphs shaped from the bytes in `range`.
+fn advance(shaped: &[ShapedGlyph], range: Range<usize>) -> f32 {
+    glyphs_in(shaped, range).map(|i| shaped[i].advance).sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn style() -> TextStyle {
+        let latin = Font::from_bytes(
+            include_bytes!("../../../tests/fonts/latin.ttf").as_slice(),
+            0,
+        )
+        .unwrap();
+        let hebrew = Font::from_bytes(
+            include_bytes!("../../../tests/fonts/hebrew.ttf").as_slice(),
+            0,
+        )
+        .unwrap();
+
+        TextStyle::new(&latin, 10.0).with_fallback(&hebrew)
+    }
+
+    fn ranges(layout: &TextLayout) -> Vec<Range<usize>> {
+        layout
+            .lines()
+            .iter()
+            .map(|line| line.range.clone())
+            .collect()
+    }
+
+    #[test]
+    fn line_breaks() {
+        let style = style();
+
+        let layout = TextLayout::new("a\n\nb\n", &style, None);
+        assert_eq!(ranges(&layout), [0..2, 2..3, 3..5, 5..5]);
+        // empty text still has a line, for the caret
+        let empty = TextLayout::new("", &style, None);
+        assert_eq!(empty.lines().len(), 1);
+        assert!(empty.lines()[0].range.is_empty());
+
+        let one_line = TextLayout::new("aaa bbb ccc", &style, None);
+        let width = one_line.lines()[0].rect.extent.width.0;
+
+        let wrapped = TextLayout::new("aaa bbb ccc", &style, Some(width * 0.7));
+        assert_eq!(ranges(&wrapped), [0..8, 8..11]);
+        // trailing whitespace is not part of the line's width
+        assert!(wrapped.lines()[0].rect.extent.width.0 < width * 0.7);
+
+        // words that are too wide overflow
+        let narrow = TextLayout::new("aaa bbb", &style, Some(1.0));
+        assert_eq!(ranges(&narrow), [0..4, 4..7]);
+    }
+
+    #[test]
+    fn bidi_hit_testing() {
+        // alef bet, then "ab"
+        let text = "\u{5d0}\u{5d1} ab";
+        let layout = TextLayout::new(text, &style(), None);
+        let caret = |index| layout.caret_rect(index).origin.x.0;
+
+        // the paragraph is right-to-left, so the Hebrew is on the right and
+        // reads from right to left
+        assert!(caret(0) > caret(2));
+        assert!(caret(2) > caret(4));
+        // "ab" is left-to-right, and to the left of the space
+        assert!(caret(4) > caret(6));
+        assert!(caret(5) < caret(6));
+        assert_eq!(caret(5), 0.0);
+
+        for index in [0, 2, 5, 6] {
+            let rect = layout.caret_rect(index);
+            let inside = |dx: f32| (rect.origin.x.0 + dx, 5.0);
+
+            // points just inside the cluster after the caret hit the caret
+            let dx = if index < 4 { -0.5 } else { 0.5 };
+            assert_eq!(layout.hit_test(inside(dx)), index);
+        }
+
+        let line = &layout.lines()[0];
+        assert_eq!(layout.hit_test((-10.0, 100.0)), 5);
+        assert_eq!(layout.hit_test((line.rect.extent.width.0 + 10.0, 0.0)), 0);
+    }
+}
@@ -0,0 +1,16 @@
+//! Fonts, text layout, and the glyph bitmaps used to draw text.
+//!
+//! Glyphs are rasterized on the CPU into `Alpha8` pages of the texture cache
+//! and drawn as textured rects, so text goes through the same pipeline as
+//! images.
+
+mod font;
+mod glyph_cache;
+mod layout;
+
+pub use self::{
+    font::{Error, Font, FontId, FontMetrics},
+    layout::{LineBox, LineHeight, TextAlign, TextLayout, TextStyle},
+};
+
+pub(crate) use self::glyph_cache::{rasterize_glyph, CachedGlyph, GlyphCache};
@@ -1,21 +1,32 @@
-use arrayvec::ArrayVec;
-
 use crate::{
     core::static_slot_map::{new_key_type, SlotMap},
-    geometry::{Extent, Point, Rect, Scale, Texel, UV},
-    graphics::{Format, Layout},
-    limits::GFX_IMAGE_COUNT,
+    geometry::{Extent, Point, Rect, Texel, UV},
+    graphics::{Format, ImageError, Layout},
+    limits::{GFX_IMAGE_COUNT, GFX_TEXTURE_COUNT},
 };
 
-use super::backend::TextureId;
+use super::backend::{SubmitId, TextureId};
 
 new_key_type!(CachedTextureId);
 
 const ATLAS_EXTENT: Texel = Texel(1024);
 
+/// Empty space left to the right of and below every packed rect so that
+/// linear filtering does not blend neighboring images together.
+const ATLAS_PADDING: i16 = 1;
+
+/// Shelf heights are rounded up to a multiple of this to make it more likely
+/// that shelves can be shared by images of similar heights.
+const SHELF_ALIGNMENT: i16 = 4;
+
 pub struct TextureCache {
-    textures: ArrayVec<AtlasMap, 16>,
+    /// Atlases indexed by [`CachedTexture::atlas`]. Destroyed textures leave
+    /// an empty slot that is filled by the next new texture.
+    textures: Vec<Option<AtlasMap>>,
     cache: Box<SlotMap<{ GFX_IMAGE_COUNT.get() }, CachedTexture, CachedTextureId>>,
+    /// Rects that have been removed, but may still be read by the GPU until
+    /// the submission completes.
+    pending: Vec<(CachedTexture, SubmitId)>,
 }
 
 impl TextureCache {
@@ -26,8 +37,9 @@ impl TextureCache {
         alloc_new: impl FnMut(Extent<Texel>, Layout, Format) -> TextureId,
     ) -> Self {
         let mut this = Self {
-            textures: ArrayVec::new(),
+            textures: Vec::new(),
             cache: Box::new(SlotMap::new()),
+            pending: Vec::new(),
         };
 
         let _ = this.insert_rect(extent, layout, format, alloc_new);
@@ -40,45 +52,170 @@ impl TextureCache {
         (id, self.cache.get(id).unwrap().clone().texture)
     }
 
+    /// Finds space for a rect of the given extent in a texture with a
+    /// matching layout and format, allocating a new texture if necessary.
+    ///
+    /// Rects that do not fit in an atlas are given a texture of their own.
+    /// Fails with [`ImageError::MaxCount`] if a new texture is needed but
+    /// [`GFX_TEXTURE_COUNT`] textures are already in use.
     pub fn insert_rect(
         &mut self,
         extent: Extent<Texel>,
         layout: Layout,
         format: Format,
         mut alloc_new: impl FnMut(Extent<Texel>, Layout, Format) -> TextureId,
-    ) -> (TextureId, CachedTextureId) {
-        // todo: actually calculate the required extent
-        let texture = alloc_new(Extent::new(ATLAS_EXTENT, ATLAS_EXTENT), layout, format);
+    ) -> Result<(TextureId, CachedTextureId), ImageError> {
+        if !self.cache.has_capacity(1) {
+            return Err(ImageError::MaxCount);
+        }
 
-        self.textures.push(AtlasMap {
-            texture,
-            used: true,
-        });
+        let existing = self
+            .textures
+            .iter_mut()
+            .enumerate()
+            .filter_map(|(index, atlas)| Some((index, atlas.as_mut()?)))
+            .filter(|(_, atlas)| atlas.layout == layout && atlas.format == format)
+            .find_map(|(index, atlas)| atlas.alloc(extent).map(|origin| (index, origin)));
+
+        let (index, origin) = if let Some(existing) = existing {
+            existing
+        } else {
+            if self.textures.iter().flatten().count() >= GFX_TEXTURE_COUNT.get() {
+                return Err(ImageError::MaxCount);
+            }
+
+            let (atlas_extent, allocator) = if is_dedicated(extent) {
+                (extent, Allocator::Dedicated)
+            } else {
+                let atlas_extent = Extent::new(ATLAS_EXTENT, ATLAS_EXTENT);
+                (
+                    atlas_extent,
+                    Allocator::Shelves(ShelfPacker::new(atlas_extent)),
+                )
+            };
+
+            let mut atlas = AtlasMap {
+                texture: alloc_new(atlas_extent, layout, format),
+                layout,
+                format,
+                extent: atlas_extent,
+                allocator,
+            };
+
+            let origin = match atlas.allocator {
+                Allocator::Shelves(_) => atlas.alloc(extent).unwrap(),
+                Allocator::Dedicated => Point::ZERO,
+            };
+
+            let index = if let Some(index) = self.textures.iter().position(Option::is_none) {
+                self.textures[index] = Some(atlas);
+                index
+            } else {
+                self.textures.push(Some(atlas));
+                self.textures.len() - 1
+            };
+
+            (index, origin)
+        };
+
+        let texture = self.textures[index].as_ref().unwrap().texture;
 
         let cached_id = self
             .cache
             .insert(CachedTexture {
                 texture,
-                rect: Rect::new(Point::new(0, 0), extent),
+                rect: Rect::new(origin, extent),
+                atlas: index,
             })
-            .unwrap();
+            .unwrap_or_else(|_| unreachable!());
 
-        (texture, cached_id)
+        Ok((texture, cached_id))
     }
 
-    pub fn remove_rect(&mut self, image: CachedTextureId) {
-        todo!()
+    /// Removes a rect from the cache.
+    ///
+    /// The rect's space is not reused until `after` has completed and
+    /// [`reclaim`](Self::reclaim) is called. The default rect is never
+    /// removed.
+    pub fn remove_rect(
+        &mut self,
+        image: CachedTextureId,
+        after: SubmitId,
+    ) -> Result<(), ImageError> {
+        if image == self.default().0 {
+            return Ok(());
+        }
+
+        let cached = self.cache.remove(image).ok_or(ImageError::Expired)?;
+        self.pending.push((cached, after));
+        Ok(())
     }
 
-    pub fn get_rect(&self, image: CachedTextureId) -> (TextureId, Rect<Texel>) {
-        let cached = self.cache.get(image).unwrap();
-        (cached.texture, cached.rect)
+    /// Makes the space used by removed rects available again once the GPU
+    /// is done with them.
+    ///
+    /// Dedicated textures are passed to `destroy` instead, since they would
+    /// otherwise only be reused by a rect of a fitting extent.
+    pub fn reclaim(
+        &mut self,
+        is_done: impl Fn(SubmitId) -> bool,
+        mut destroy: impl FnMut(TextureId),
+    ) {
+        for (cached, submit) in std::mem::take(&mut self.pending) {
+            if is_done(submit) {
+                let slot = &mut self.textures[cached.atlas];
+                let atlas = slot.as_mut().unwrap();
+
+                match &mut atlas.allocator {
+                    Allocator::Shelves(packer) => {
+                        packer.free(Rect::new(cached.rect.origin, padded(cached.rect.extent)));
+                    }
+                    Allocator::Dedicated => {
+                        destroy(atlas.texture);
+                        *slot = None;
+                    }
+                }
+            } else {
+                self.pending.push((cached, submit));
+            }
+        }
+    }
+
+    pub fn get_rect(&self, image: CachedTextureId) -> Result<(TextureId, Rect<Texel>), ImageError> {
+        let cached = self.cache.get(image).ok_or(ImageError::Expired)?;
+        Ok((cached.texture, cached.rect))
     }
 
-    pub fn get_uv_rect(&self, image: CachedTextureId) -> (TextureId, Rect<UV>) {
-        let cached = self.cache.get(image).unwrap();
-        let scale = Scale::new(1.0 / f32::from(ATLAS_EXTENT.0));
-        (cached.texture, cached.rect.scale_to(scale))
+    pub fn get_uv_rect(&self, image: CachedTextureId) -> Result<(TextureId, Rect<UV>), ImageError> {
+        self.get_uv_region(image, None)
+    }
+
+    /// The UV coordinates of a region of a cached rect, given relative to the
+    /// rect's origin, or of the whole rect if `region` is `None`.
+    pub fn get_uv_region(
+        &self,
+        image: CachedTextureId,
+        region: Option<Rect<Texel>>,
+    ) -> Result<(TextureId, Rect<UV>), ImageError> {
+        let cached = self.cache.get(image).ok_or(ImageError::Expired)?;
+        let region = region.unwrap_or(Rect::from_extent(cached.rect.extent));
+        let atlas = self.textures[cached.atlas].as_ref().unwrap();
+
+        let width = f32::from(atlas.extent.width);
+        let height = f32::from(atlas.extent.height);
+
+        let [x, y, w, h] = region.to_xywh().map(f32::from);
+        let (x, y) = (
+            x + f32::from(cached.rect.origin.x),
+            y + f32::from(cached.rect.origin.y),
+        );
+
+        let uv = Rect::new(
+            Point::new(x / width, y / height),
+            Extent::new(w / width, h / height),
+        );
+
+        Ok((cached.texture, uv))
     }
 }
 
@@ -86,9 +223,374 @@ impl TextureCache {
 pub struct CachedTexture {
     pub texture: TextureId,
     pub rect: Rect<Texel>,
+    atlas: usize,
+}
+
+/// Rects that are too large for an atlas, once padded, get a texture of their
+/// own.
+fn is_dedicated(extent: Extent<Texel>) -> bool {
+    extent.width.0 + ATLAS_PADDING > ATLAS_EXTENT.0
+        || extent.height.0 + ATLAS_PADDING > ATLAS_EXTENT.0
+}
+
+fn padded(extent: Extent<Texel>) -> Extent<Texel> {
+    Extent::new(extent.width + ATLAS_PADDING, extent.height + ATLAS_PADDING)
 }
 
 struct AtlasMap {
     texture: TextureId,
-    used: bool,
+    layout: Layout,
+    format: Format,
+    extent: Extent<Texel>,
+    allocator: Allocator,
+}
+
+enum Allocator {
+    Shelves(ShelfPacker),
+    /// The texture holds a single rect, and is destroyed along with it.
+    Dedicated,
+}
+
+impl AtlasMap {
+    fn alloc(&mut self, extent: Extent<Texel>) -> Option<Point<Texel>> {
+        match &mut self.allocator {
+            Allocator::Shelves(packer) => packer.alloc(padded(extent)),
+            Allocator::Dedicated => None,
+        }
+    }
+}
+
+/// Packs rects into horizontal shelves that span the width of the atlas.
+///
+/// Each shelf tracks its unused horizontal spans, so that space can be handed
+/// out to any rect that is no taller than the shelf.
+struct ShelfPacker {
+    extent: Extent<Texel>,
+    shelves: Vec<Shelf>,
+    /// The top of the space that has not yet been assigned to a shelf.
+    next_y: i16,
+}
+
+struct Shelf {
+    y: i16,
+    height: i16,
+    /// Unused spans as `(x, width)`, sorted by `x`.
+    free: Vec<(i16, i16)>,
+}
+
+impl ShelfPacker {
+    fn new(extent: Extent<Texel>) -> Self {
+        Self {
+            extent,
+            shelves: Vec::new(),
+            next_y: 0,
+        }
+    }
+
+    fn alloc(&mut self, extent: Extent<Texel>) -> Option<Point<Texel>> {
+        let (width, height) = (extent.width.0, extent.height.0);
+
+        if width > self.extent.width.0 || height > self.extent.height.0 {
+            return None;
+        }
+
+        // Best fit: the shortest shelf that the rect fits in.
+        let best = self
+            .shelves
+            .iter()
+            .enumerate()
+            .filter(|(_, shelf)| shelf.height >= height)
+            .filter(|(_, shelf)| shelf.free.iter().any(|&(_, w)| w >= width))
+            .min_by_key(|(_, shelf)| shelf.height)
+            .map(|(index, shelf)| (index, shelf.height));
+
+        let remaining = self.extent.height.0 - self.next_y;
+        let can_open_shelf = remaining >= height;
+
+        // Don't waste a tall shelf on a short rect if a new one fits.
+        let index = match best {
+            Some((index, best_height)) if !can_open_shelf || best_height <= height * 3 / 2 => index,
+            _ if can_open_shelf => {
+                let aligned = (height + SHELF_ALIGNMENT - 1) / SHELF_ALIGNMENT * SHELF_ALIGNMENT;
+                let shelf_height = aligned.min(remaining);
+
+                self.shelves.push(Shelf {
+                    y: self.next_y,
+                    height: shelf_height,
+                    free: vec![(0, self.extent.width.0)],
+                });
+
+                self.next_y += shelf_height;
+                self.shelves.len() - 1
+            }
+            _ => return None,
+        };
+
+        let shelf = &mut self.shelves[index];
+
+        let span = shelf.free.iter().position(|&(_, w)| w >= width).unwrap();
+        let (x, span_width) = shelf.free[span];
+
+        if span_width == width {
+            shelf.free.remove(span);
+        } else {
+            shelf.free[span] = (x + width, span_width - width);
+        }
+
+        Some(Point::new(x, shelf.y))
+    }
+
+    fn free(&mut self, rect: Rect<Texel>) {
+        let (x, width) = (rect.origin.x.0, rect.extent.width.0);
+
+        let shelf = self
+            .shelves
+            .iter_mut()
+            .find(|shelf| shelf.y == rect.origin.y.0)
+            .expect("rect does not belong to a shelf");
+
+        let index = shelf.free.partition_point(|&(free_x, _)| free_x < x);
+        shelf.free.insert(index, (x, width));
+
+        // Merge with the spans on either side, if they are adjacent.
+        if let Some(&(next_x, next_width)) = shelf.free.get(index + 1) {
+            if x + width == next_x {
+                shelf.free[index].1 += next_width;
+                shelf.free.remove(index + 1);
+            }
+        }
+
+        if let Some(prev) = index.checked_sub(1) {
+            let (prev_x, prev_width) = shelf.free[prev];
+            if prev_x + prev_width == x {
+                shelf.free[prev].1 += shelf.free[index].1;
+                shelf.free.remove(index);
+            }
+        }
+
+        // Give empty shelves at the bottom of the atlas back to the unassigned
+        // space so that they can be resized.
+        while let Some(last) = self.shelves.last() {
+            if last.free.as_slice() != [(0, self.extent.width.0)] {
+                break;
+            }
+
+            self.next_y = last.y;
+            self.shelves.pop();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cache() -> (
+        TextureCache,
+        impl FnMut(Extent<Texel>, Layout, Format) -> TextureId,
+    ) {
+        let mut count = 0;
+        let mut alloc = move |_, _, _| {
+            count += 1;
+            TextureId::new(count - 1, 0)
+        };
+
+        let cache = TextureCache::new(Extent::new(1, 1), Layout::Rgba8, Format::Linear, &mut alloc);
+        (cache, alloc)
+    }
+
+    fn overlaps(a: Rect<Texel>, b: Rect<Texel>) -> bool {
+        let [ax, ay, aw, ah] = a.to_xywh().map(|v| v.0);
+        let [bx, by, bw, bh] = b.to_xywh().map(|v| v.0);
+        ax < bx + bw && bx < ax + aw && ay < by + bh && by < ay + ah
+    }
+
+    #[test]
+    fn pack_many() {
+        let (mut cache, mut alloc) = cache();
+
+        let mut rects = vec![];
+        for i in 0..500 {
+            let extent = Extent::new(8 + i % 24, 8 + (i * 7) % 24);
+            let (texture, id) = cache
+                .insert_rect(extent, Layout::Rgba8, Format::Linear, &mut alloc)
+                .unwrap();
+
+            // everything fits on the first page along with the default texture
+            assert_eq!(texture, TextureId::new(0, 0));
+            rects.push(cache.get_rect(id).unwrap().1);
+        }
+
+        for (i, a) in rects.iter().enumerate() {
+            assert!(a.origin.x.0 + a.extent.width.0 <= ATLAS_EXTENT.0);
+            assert!(a.origin.y.0 + a.extent.height.0 <= ATLAS_EXTENT.0);
+
+            for b in &rects[i + 1..] {
+                assert!(!overlaps(*a, *b), "{a:?} overlaps {b:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn spill_to_new_page() {
+        let (mut cache, mut alloc) = cache();
+
+        let extent = Extent::new(500, 500);
+        let mut textures = vec![];
+        for _ in 0..5 {
+            let (texture, _) = cache
+                .insert_rect(extent, Layout::Rgba8, Format::Linear, &mut alloc)
+                .unwrap();
+            textures.push(texture.index());
+        }
+
+        // 2 rows of 2, below the shelf holding the default texture
+        assert_eq!(textures, [0, 0, 0, 0, 1]);
+    }
+
+    #[test]
+    fn separate_pages_per_format() {
+        let (mut cache, mut alloc) = cache();
+
+        let extent = Extent::new(16, 16);
+        let (a, _) = cache
+            .insert_rect(extent, Layout::Rgba8, Format::Srgb, &mut alloc)
+            .unwrap();
+        let (b, _) = cache
+            .insert_rect(extent, Layout::Alpha8, Format::Linear, &mut alloc)
+            .unwrap();
+        let (c, _) = cache
+            .insert_rect(extent, Layout::Rgba8, Format::Srgb, &mut alloc)
+            .unwrap();
+
+        assert_ne!(a, b);
+        assert_eq!(a, c);
+    }
+
+    #[test]
+    fn dedicated_texture() {
+        let (mut cache, mut alloc) = cache();
+
+        let extent = Extent::new(2048, 100);
+        let (texture, id) = cache
+            .insert_rect(extent, Layout::Rgba8, Format::Linear, &mut alloc)
+            .unwrap();
+
+        assert_ne!(texture, cache.default().1);
+        assert_eq!(
+            cache.get_rect(id).unwrap().1,
+            Rect::new(Point::ZERO, extent)
+        );
+
+        let (_, uv) = cache.get_uv_rect(id).unwrap();
+        assert_eq!(uv, Rect::new(Point::ZERO, Extent::new(1.0, 1.0)));
+
+        // dedicated textures are never shared
+        let (other, _) = cache
+            .insert_rect(Extent::new(4, 4), Layout::Rgba8, Format::Linear, &mut alloc)
+            .unwrap();
+        assert_ne!(other, texture);
+    }
+
+    #[test]
+    fn destroy_dedicated_texture() {
+        let (mut cache, mut alloc) = cache();
+
+        let extent = Extent::new(2048, 100);
+        let (texture, id) = cache
+            .insert_rect(extent, Layout::Rgba8, Format::Linear, &mut alloc)
+            .unwrap();
+        cache.remove_rect(id, SubmitId(1)).unwrap();
+
+        let mut destroyed = vec![];
+        cache.reclaim(|_| false, |texture| destroyed.push(texture));
+        assert!(destroyed.is_empty());
+
+        cache.reclaim(|_| true, |texture| destroyed.push(texture));
+        assert_eq!(destroyed, [texture]);
+
+        // a rect of a different extent takes the destroyed texture's place
+        let (other, id) = cache
+            .insert_rect(
+                Extent::new(100, 2048),
+                Layout::Rgba8,
+                Format::Linear,
+                &mut alloc,
+            )
+            .unwrap();
+        assert_ne!(other, texture);
+        assert_eq!(cache.textures.len(), 2);
+
+        let (_, uv) = cache.get_uv_rect(id).unwrap();
+        assert_eq!(uv, Rect::new(Point::ZERO, Extent::new(1.0, 1.0)));
+    }
+
+    #[test]
+    fn max_texture_count() {
+        let (mut cache, mut alloc) = cache();
+
+        let extent = Extent::new(2048, 100);
+        let mut ids = vec![];
+        for _ in 1..GFX_TEXTURE_COUNT.get() {
+            let (_, id) = cache
+                .insert_rect(extent, Layout::Rgba8, Format::Linear, &mut alloc)
+                .unwrap();
+            ids.push(id);
+        }
+
+        let mut allocated = false;
+        let result = cache.insert_rect(extent, Layout::Rgba8, Format::Linear, |e, l, f| {
+            allocated = true;
+            alloc(e, l, f)
+        });
+        assert!(matches!(result, Err(ImageError::MaxCount)));
+        assert!(!allocated);
+
+        // rects that fit in an existing atlas don't need a new texture
+        assert!(cache
+            .insert_rect(Extent::new(4, 4), Layout::Rgba8, Format::Linear, &mut alloc)
+            .is_ok());
+
+        cache.remove_rect(ids[0], SubmitId(1)).unwrap();
+        cache.reclaim(|_| true, |_| {});
+        assert!(cache
+            .insert_rect(extent, Layout::Rgba8, Format::Linear, &mut alloc)
+            .is_ok());
+    }
+
+    #[test]
+    fn reuse_after_remove() {
+        let (mut cache, mut alloc) = cache();
+
+        let extent = Extent::new(100, 100);
+        let (_, a) = cache
+            .insert_rect(extent, Layout::Rgba8, Format::Linear, &mut alloc)
+            .unwrap();
+        let (_, a_rect) = cache.get_rect(a).unwrap();
+
+        cache.remove_rect(a, SubmitId(1)).unwrap();
+        assert!(matches!(cache.get_rect(a), Err(ImageError::Expired)));
+        assert!(matches!(
+            cache.remove_rect(a, SubmitId(1)),
+            Err(ImageError::Expired)
+        ));
+
+        // the GPU may still be reading from the old rect
+        cache.reclaim(|_| false, |_| {});
+        let (_, b) = cache
+            .insert_rect(extent, Layout::Rgba8, Format::Linear, &mut alloc)
+            .unwrap();
+        assert_ne!(cache.get_rect(b).unwrap().1, a_rect);
+
+        cache.reclaim(|_| true, |_| {});
+        let (_, c) = cache
+            .insert_rect(extent, Layout::Rgba8, Format::Linear, &mut alloc)
+            .unwrap();
+        assert_eq!(cache.get_rect(c).unwrap().1, a_rect);
+
+        // the default rect is never removed
+        let (default, _) = cache.default();
+        cache.remove_rect(default, SubmitId(1)).unwrap();
+        assert!(cache.get_rect(default).is_ok());
+    }
 }
@@ -8,8 +8,10 @@ mod string;
 pub mod system;
 pub mod time;
 
+#[cfg(target_os = "windows")]
 mod application;
 mod core;
 
-pub use application::{AppContext, Application, Config, EventHandler};
+#[cfg(target_os = "windows")]
+pub use application::{AppContext, Application, Config, EventHandler, RedrawError};
 pub use string::HashedStr;
@@ -1,5 +1,5 @@
 use crate::{
-    graphics::{Image, RasterBuf},
+    graphics::{image::codec, Image, ImageError, RasterBuf},
     HashedStr,
 };
 
@@ -16,6 +16,12 @@ pub enum Error {
 
     #[error("The resource could not be loaded.")]
     Io(std::io::Error),
+
+    #[error("The image could not be decoded.")]
+    Decode(#[from] codec::Error),
+
+    #[error("The image could not be created.")]
+    Image(#[from] ImageError),
 }
 
 #[derive(Debug)]
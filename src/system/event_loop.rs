@@ -101,6 +101,14 @@ pub trait EventHandler<WindowData> {
         position: Point<Wixel>,
     );
 
+    /// The display settings have changed, such as the resolution or whether
+    /// HDR is enabled.
+    fn display_changed(
+        &mut self,
+        event_loop: &ActiveEventLoop<WindowData>,
+        window: Window<WindowData>,
+    );
+
     fn wake_requested(
         &mut self,
         event_loop: &ActiveEventLoop<WindowData>,
@@ -1,3 +1,4 @@
+#[cfg(target_os = "windows")]
 pub(crate) mod event_loop;
 
 mod input;
@@ -8,7 +9,9 @@ pub use power::*;
 
 pub(crate) mod time;
 
+#[cfg(target_os = "windows")]
 mod window;
+#[cfg(target_os = "windows")]
 pub use window::*;
 
 #[cfg(target_os = "windows")]
@@ -1,5 +1,6 @@
 use std::ops::{Add, AddAssign, Sub, SubAssign};
 
+#[cfg(target_os = "windows")]
 use super::platform_impl;
 
 pub(crate) const NANOSECONDS_PER_SECOND: i64 = 1_000_000_000;
@@ -9,6 +10,7 @@ pub(crate) const NANOSECONDS_PER_SECOND_F64: f64 = 1_000_000_000.0;
 pub struct Nanoseconds(pub i64);
 
 impl Nanoseconds {
+    #[cfg(target_os = "windows")]
     pub fn now() -> Self {
         Self(platform_impl::time::now_nanoseconds())
     }
@@ -23,9 +23,9 @@ use windows::{
                 LoadCursorW, PeekMessageW, PostQuitMessage, RegisterClassExW, TranslateMessage,
                 CREATESTRUCTW, CS_HREDRAW, CS_VREDRAW, CW_USEDEFAULT, GWLP_USERDATA, HICON,
                 IDC_ARROW, MSG, PM_NOREMOVE, SW_NORMAL, WM_CLOSE, WM_CREATE, WM_DESTROY,
-                WM_DPICHANGED, WM_ENTERSIZEMOVE, WM_EXITSIZEMOVE, WM_GETMINMAXINFO, WM_LBUTTONDOWN,
-                WM_MBUTTONDBLCLK, WM_MOUSEHWHEEL, WM_MOUSEMOVE, WM_MOUSEWHEEL, WM_PAINT,
-                WM_SHOWWINDOW, WM_TIMER, WM_WINDOWPOSCHANGED, WNDCLASSEXW,
+                WM_DISPLAYCHANGE, WM_DPICHANGED, WM_ENTERSIZEMOVE, WM_EXITSIZEMOVE,
+                WM_GETMINMAXINFO, WM_LBUTTONDOWN, WM_MBUTTONDBLCLK, WM_MOUSEHWHEEL, WM_MOUSEMOVE,
+                WM_MOUSEWHEEL, WM_PAINT, WM_SHOWWINDOW, WM_TIMER, WM_WINDOWPOSCHANGED, WNDCLASSEXW,
                 WS_EX_NOREDIRECTIONBITMAP, WS_OVERLAPPEDWINDOW,
             },
         },
@@ -338,6 +338,7 @@ unsafe extern "system" fn unsafe_wndproc<WindowData, H: api::EventHandler<Window
                 let dpi = u16::try_from(wparam.0).expect("WM_DPICHANGED exceeded u16::MAX");
                 context.dpi_changed(dpi, cast_lparam_as_ref(lparam));
             }
+            WM_DISPLAYCHANGE => context.display_changed(),
             WM_GETMINMAXINFO => context.get_min_max_info(cast_lparam_as_mut(lparam)),
             WM_WINDOWPOSCHANGED => context.pos_changed(cast_lparam_as_ref(lparam)),
             UM_DEFER_PAINT => context.paint_defer(),
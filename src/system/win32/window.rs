@@ -286,6 +286,10 @@ impl<'a, WindowData, H: EventHandler<WindowData>> HandlerContext<'a, WindowData,
         mmi.ptMaxTrackSize.y = new_max.height;
     }
 
+    pub fn display_changed(&mut self) {
+        self.event(EventHandler::display_changed);
+    }
+
     pub fn pos_changed(&mut self, pos: &WINDOWPOS) {
         let x = i16::try_from(pos.x).unwrap();
         let y = i16::try_from(pos.y).unwrap();
@@ -4,6 +4,7 @@ use super::platform_impl;
 
 use crate::{
     geometry::{Extent, Pixel, Point, Scale, Wixel},
+    graphics::{ColorSpace, ToneMapping},
     limits,
     time::FramesPerSecond,
 };
@@ -38,6 +39,12 @@ pub struct WindowAttributes {
     pub position: Option<Point<Wixel>>,
     pub is_visible: bool,
     pub is_resizable: bool,
+    /// The color space that the window's contents are encoded in. HDR color
+    /// spaces let colors brighter than SDR white be shown on HDR displays.
+    pub color_space: ColorSpace,
+    /// How colors brighter than the display can show are brought into its
+    /// range.
+    pub tone_mapping: ToneMapping,
 }
 
 impl WindowAttributes {
@@ -82,6 +89,18 @@ impl WindowAttributes {
         self.is_resizable = is_resizable;
         self
     }
+
+    #[must_use]
+    pub fn with_color_space(mut self, color_space: ColorSpace) -> Self {
+        self.color_space = color_space;
+        self
+    }
+
+    #[must_use]
+    pub fn with_tone_mapping(mut self, tone_mapping: ToneMapping) -> Self {
+        self.tone_mapping = tone_mapping;
+        self
+    }
 }
 
 impl Default for WindowAttributes {
@@ -94,6 +113,8 @@ impl Default for WindowAttributes {
             position: None,
             is_visible: true,
             is_resizable: true,
+            color_space: ColorSpace::default(),
+            tone_mapping: ToneMapping::default(),
         }
     }
 }
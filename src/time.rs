@@ -22,11 +22,13 @@ pub struct PresentTime(Nanoseconds);
 
 impl PresentTime {
     #[must_use]
+    #[cfg(target_os = "windows")]
     pub fn now() -> Self {
         Self(Nanoseconds::now())
     }
 
     #[must_use]
+    #[cfg(target_os = "windows")]
     pub(crate) fn from_qpc_time(ticks: i64) -> Self {
         Self(Nanoseconds::from_qpc_time(ticks))
     }
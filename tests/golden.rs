@@ -0,0 +1,885 @@
+mod support;
+
+use plinth::{
+    geometry::{Affine, Extent, Rect, Texel},
+    graphics::{
+        BlendMode, BorderAlignment, Color, FillRule, Format, Gradient, ImageInfo, Insets, Layout,
+        LineCap, LineJoin, Path, Raster, RasterBuf, RoundRect, Sampler, SliceMode, SpriteSheet,
+        Stroke, TextAlign, TextLayout, TextStyle, TextureFilter, WrapMode,
+    },
+};
+
+use support::{load_font, GoldenTest};
+
+#[rustfmt::skip]
+const RGB_STRIP: [u8; 12] = [
+    255, 0, 0, 255,
+    0, 255, 0, 255,
+    0, 0, 255, 255,
+];
+
+#[rustfmt::skip]
+const CHECKER: [u8; 16] = [
+    255, 255, 255, 255,   0,   0,   0, 255,
+      0,   0,   0, 255, 255, 255, 255, 255,
+];
+
+#[test]
+fn solid_rects() {
+    GoldenTest::new("solid_rects", (32, 32)).check(|canvas| {
+        canvas.clear(Color::BLACK);
+        canvas.draw_rect(&RoundRect::new((2.0, 2.0, 12.0, 12.0)).with_color(Color::RED));
+        canvas.draw_rect(&RoundRect::new((18.0, 2.0, 12.0, 12.0)).with_color(Color::GREEN));
+        canvas.draw_rect(&RoundRect::new((2.0, 18.0, 12.0, 12.0)).with_color(Color::BLUE));
+        // fractional edges are anti-aliased
+        canvas.draw_rect(&RoundRect::new((18.4, 18.6, 11.3, 10.9)).with_color(Color::WHITE));
+    });
+}
+
+#[test]
+fn rounded_rects() {
+    GoldenTest::new("rounded_rects", (48, 32)).check(|canvas| {
+        canvas.clear(Color::BLACK);
+        canvas.draw_rect(
+            &RoundRect::new((2.0, 2.0, 20.0, 12.0))
+                .with_radius(4.0)
+                .with_color(Color::RED),
+        );
+        canvas.draw_rect(
+            &RoundRect::new((26.0, 2.0, 20.0, 12.0))
+                .with_radii([0.0, 6.0, 2.0, 10.0])
+                .with_color(Color::GREEN),
+        );
+        // radii larger than the rect are clamped, making a circle
+        canvas.draw_rect(
+            &RoundRect::new((2.5, 16.5, 13.0, 13.0))
+                .with_radius(100.0)
+                .with_color(Color::BLUE),
+        );
+        canvas.draw_rect(
+            &RoundRect::new((20.0, 18.0, 24.0, 10.0))
+                .with_radius(5.0)
+                .with_color(Color::WHITE),
+        );
+    });
+}
+
+#[test]
+fn borders() {
+    let half_white = Color {
+        r: 0.5,
+        g: 0.5,
+        b: 0.5,
+        a: 0.5,
+    };
+
+    GoldenTest::new("borders", (64, 32)).check(|canvas| {
+        canvas.clear(Color::BLACK);
+
+        for (i, alignment) in [
+            BorderAlignment::Inside,
+            BorderAlignment::Center,
+            BorderAlignment::Outside,
+        ]
+        .into_iter()
+        .enumerate()
+        {
+            let x = 4.0 + 20.0 * i as f32;
+            canvas.draw_rect(
+                &RoundRect::new((x, 4.0, 16.0, 10.0))
+                    .with_color(Color::BLUE)
+                    .with_border(2.0, Color::RED)
+                    .with_border_alignment(alignment),
+            );
+            canvas.draw_rect(
+                &RoundRect::new((x, 18.0, 16.0, 10.0))
+                    .with_radius(4.0)
+                    .with_color(Color::BLUE)
+                    .with_border(2.0, half_white)
+                    .with_border_alignment(alignment),
+            );
+        }
+    });
+}
+
+#[test]
+fn gradients() {
+    let clear = Color {
+        r: 0.0,
+        g: 0.0,
+        b: 0.0,
+        a: 0.0,
+    };
+
+    GoldenTest::new("gradients", (64, 32)).check(|canvas| {
+        canvas.clear(Color::BLACK);
+        canvas.draw_rect(
+            &RoundRect::new((2.0, 2.0, 60.0, 8.0)).with_gradient(
+                Gradient::linear((2.0, 0.0), (62.0, 0.0))
+                    .with_stop(0.0, Color::RED)
+                    .with_stop(0.5, Color::GREEN)
+                    .with_stop(1.0, Color::BLUE),
+            ),
+        );
+        // hard edge from repeated offsets
+        canvas.draw_rect(
+            &RoundRect::new((2.0, 12.0, 60.0, 2.0)).with_gradient(
+                Gradient::linear((2.0, 0.0), (62.0, 0.0))
+                    .with_stop(0.5, Color::WHITE)
+                    .with_stop(0.5, Color::BLUE),
+            ),
+        );
+        canvas.draw_rect(
+            &RoundRect::new((2.0, 16.0, 14.0, 14.0))
+                .with_radius(7.0)
+                .with_gradient(
+                    Gradient::radial((9.0, 23.0), 7.0)
+                        .with_stop(0.0, Color::WHITE)
+                        .with_stop(1.0, clear),
+                ),
+        );
+        canvas.draw_rect(
+            &RoundRect::new((20.0, 16.0, 14.0, 14.0)).with_gradient(
+                Gradient::conic((27.0, 23.0), 0.0)
+                    .with_stop(0.0, Color::RED)
+                    .with_stop(1.0, Color::BLUE),
+            ),
+        );
+        // gradients are drawn inside the border
+        canvas.draw_rect(
+            &RoundRect::new((38.0, 16.0, 24.0, 14.0))
+                .with_radius(4.0)
+                .with_border(2.0, Color::WHITE)
+                .with_gradient(
+                    Gradient::linear((0.0, 16.0), (0.0, 30.0))
+                        .with_stop(0.0, Color::GREEN)
+                        .with_stop(1.0, Color::BLUE),
+                ),
+        );
+    });
+}
+
+#[test]
+fn shadows() {
+    let shadow = Color {
+        r: 0.0,
+        g: 0.0,
+        b: 0.0,
+        a: 0.6,
+    };
+
+    GoldenTest::new("shadows", (64, 32)).check(|canvas| {
+        canvas.clear(Color::WHITE);
+
+        canvas.draw_shadow(
+            (6.0, 6.0, 20.0, 16.0),
+            [4.0; 4],
+            6.0,
+            0.0,
+            (0.0, 3.0),
+            shadow,
+        );
+        canvas.draw_rect(
+            &RoundRect::new((6.0, 6.0, 20.0, 16.0))
+                .with_radius(4.0)
+                .with_color(Color::BLUE),
+        );
+
+        // spread grows the shadow before blurring
+        canvas.draw_shadow(
+            (38.0, 8.0, 20.0, 16.0),
+            [0.0; 4],
+            4.0,
+            2.0,
+            (0.0, 0.0),
+            shadow,
+        );
+        canvas.draw_rect(&RoundRect::new((38.0, 8.0, 20.0, 16.0)).with_color(Color::BLUE));
+
+        // no blur is a hard edged rect
+        canvas.draw_shadow(
+            (30.0, 28.0, 4.0, 2.0),
+            [0.0; 4],
+            0.0,
+            0.0,
+            (0.0, 0.0),
+            Color::RED,
+        );
+    });
+}
+
+#[test]
+fn clips() {
+    GoldenTest::new("clips", (64, 32)).check(|canvas| {
+        canvas.clear(Color::WHITE);
+
+        // nested clips intersect, and popping restores the outer clip
+        canvas.push_clip((4.0, 4.0, 24.0, 24.0));
+        canvas.push_clip((12.0, 0.0, 32.0, 12.0));
+        canvas.draw_rect(&RoundRect::new((0.0, 0.0, 64.0, 32.0)).with_color(Color::RED));
+        canvas.pop_clip();
+        canvas.draw_rect(&RoundRect::new((0.0, 20.0, 64.0, 12.0)).with_color(Color::BLUE));
+        canvas.pop_clip();
+
+        // rounded clips have anti-aliased edges
+        canvas.push_round_clip((36.0, 4.0, 24.0, 24.0), [12.0, 0.0, 12.0, 0.0]);
+        canvas.draw_rect(&RoundRect::new((32.0, 0.0, 32.0, 32.0)).with_color(Color::GREEN));
+        canvas.pop_clip();
+    });
+}
+
+#[test]
+fn transforms() {
+    GoldenTest::new("transforms", (64, 32)).check(|canvas| {
+        canvas.clear(Color::WHITE);
+
+        // rotate about the center of the rect
+        canvas.push_transform(
+            &Affine::translation(-6.0, -6.0)
+                .then(&Affine::rotation(std::f32::consts::FRAC_PI_4))
+                .then(&Affine::translation(16.0, 16.0)),
+        );
+        canvas.draw_rect(&RoundRect::new((0.0, 0.0, 12.0, 12.0)).with_color(Color::RED));
+        canvas.pop_transform();
+
+        // nested transforms apply in the coordinate space of their parent
+        canvas.push_transform(&Affine::translation(32.0, 4.0));
+        canvas.push_transform(&Affine::scale(2.0, 2.0));
+        canvas.draw_rect(
+            &RoundRect::new((0.0, 0.0, 6.0, 6.0))
+                .with_radius(2.0)
+                .with_color(Color::BLUE),
+        );
+        canvas.pop_transform();
+
+        // clips are transformed too
+        canvas.push_clip((16.0, 18.0, 12.0, 6.0));
+        canvas.draw_rect(&RoundRect::new((0.0, 0.0, 64.0, 32.0)).with_color(Color::GREEN));
+        canvas.pop_clip();
+        canvas.pop_transform();
+
+        canvas.push_transform(&Affine::skew(-0.5, 0.0));
+        canvas.draw_rect(&RoundRect::new((40.0, 24.0, 8.0, 6.0)).with_color(Color::BLUE));
+        canvas.pop_transform();
+    });
+}
+
+#[test]
+fn paths() {
+    GoldenTest::new("paths", (64, 32)).check(|canvas| {
+        canvas.clear(Color::WHITE);
+
+        // a self-intersecting star leaves a hole with the even-odd rule
+        let star = |x: f32| {
+            Path::new()
+                .move_to((x + 8.0, 1.0))
+                .line_to((x + 13.0, 15.0))
+                .line_to((x + 1.0, 6.0))
+                .line_to((x + 15.0, 6.0))
+                .line_to((x + 3.0, 15.0))
+                .close()
+        };
+
+        canvas.fill_path(&star(0.0), FillRule::NonZero, Color::RED);
+        canvas.fill_path(&star(16.0), FillRule::EvenOdd, Color::RED);
+
+        // curves
+        let blob = Path::new()
+            .move_to((34.0, 14.0))
+            .quad_to((40.0, -2.0), (46.0, 14.0))
+            .cubic_to((46.0, 4.0), (62.0, 4.0), (62.0, 14.0))
+            .close();
+        canvas.fill_path(&blob, FillRule::NonZero, Color::BLUE);
+
+        let zigzag = Path::new()
+            .move_to((4.0, 28.0))
+            .line_to((10.0, 20.0))
+            .line_to((16.0, 28.0));
+
+        canvas.stroke_path(&zigzag, &Stroke::new(3.0), Color::BLUE);
+        canvas.stroke_path(
+            &zigzag.clone().line_to((16.0, 20.0)),
+            &Stroke::new(2.0).with_join(LineJoin::Round),
+            Color::RED,
+        );
+
+        let rounded = Path::new()
+            .move_to((20.0, 28.0))
+            .arc_to((20.0, 20.0), (28.0, 20.0), 4.0)
+            .line_to((30.0, 20.0));
+
+        canvas.stroke_path(
+            &rounded,
+            &Stroke::new(2.0)
+                .with_cap(LineCap::Round)
+                .with_join(LineJoin::Bevel),
+            Color::GREEN,
+        );
+
+        let dashed = Path::new().move_to((36.0, 24.0)).line_to((60.0, 24.0));
+        canvas.stroke_path(
+            &dashed,
+            &Stroke::new(3.0).with_dashes(&[4.0, 2.0], 0.0),
+            Color::BLACK,
+        );
+    });
+}
+
+#[test]
+fn gradient_paths() {
+    GoldenTest::new("gradient_paths", (64, 32)).check(|canvas| {
+        canvas.clear(Color::WHITE);
+
+        let triangle = Path::new()
+            .move_to((2.0, 30.0))
+            .line_to((16.0, 2.0))
+            .line_to((30.0, 30.0))
+            .close();
+
+        canvas.fill_path(
+            &triangle,
+            FillRule::NonZero,
+            Gradient::linear((0.0, 2.0), (0.0, 30.0))
+                .with_stop(0.0, Color::RED)
+                .with_stop(1.0, Color::BLUE),
+        );
+
+        // the gradient moves with the path
+        canvas.push_transform(&Affine::translation(32.0, 0.0));
+        canvas.stroke_path(
+            &Path::new()
+                .move_to((4.0, 16.0))
+                .quad_to((14.0, -4.0), (26.0, 16.0))
+                .quad_to((14.0, 36.0), (4.0, 16.0)),
+            &Stroke::new(4.0),
+            Gradient::radial((15.0, 16.0), 12.0)
+                .with_stop(0.0, Color::GREEN)
+                .with_stop(1.0, Color::BLACK),
+        );
+        canvas.pop_transform();
+    });
+}
+
+#[test]
+fn blended_rects() {
+    let half_red = Color {
+        r: 0.5,
+        g: 0.0,
+        b: 0.0,
+        a: 0.5,
+    };
+
+    let half_blue = Color {
+        r: 0.0,
+        g: 0.0,
+        b: 0.5,
+        a: 0.5,
+    };
+
+    GoldenTest::new("blended_rects", (32, 32)).check(|canvas| {
+        canvas.clear(Color::WHITE);
+        canvas.draw_rect(&RoundRect::new((4.0, 4.0, 16.0, 16.0)).with_color(half_red));
+        canvas.draw_rect(&RoundRect::new((12.0, 12.0, 16.0, 16.0)).with_color(half_blue));
+    });
+}
+
+#[test]
+fn image_uv() {
+    let mut test = GoldenTest::new("image_uv", (32, 32));
+
+    let strip = test.create_image(
+        ImageInfo {
+            extent: Extent::new(3, 1),
+            layout: Layout::Rgba8,
+            format: Format::Linear,
+        },
+        &RGB_STRIP,
+    );
+
+    let checker = test.create_image(
+        ImageInfo {
+            extent: Extent::new(2, 2),
+            layout: Layout::Rgba8,
+            format: Format::Srgb,
+        },
+        &CHECKER,
+    );
+
+    test.check(|canvas| {
+        canvas.clear(Color::BLACK);
+        canvas.draw_rect(&RoundRect::new((1.0, 1.0, 30.0, 8.0)).with_image(strip));
+        canvas.draw_rect(&RoundRect::new((1.0, 12.0, 16.0, 16.0)).with_image(checker));
+        canvas.draw_rect(
+            &RoundRect::new((20.0, 12.0, 8.0, 16.0))
+                .with_image(checker)
+                .with_color(Color::RED),
+        );
+    });
+}
+
+#[test]
+fn sprite_sheet() {
+    let mut test = GoldenTest::new("sprite_sheet", (64, 32));
+
+    // four 2x2 frames: red, green, blue, and white, each with a black
+    // top-left texel
+    let colors = [[255, 0, 0], [0, 255, 0], [0, 0, 255], [255, 255, 255]];
+    let pixels: Vec<u8> = (0..16)
+        .flat_map(|i| {
+            let (x, y) = (i % 8, i / 8);
+            let [r, g, b] = if x % 2 == 0 && y == 0 {
+                [0, 0, 0]
+            } else {
+                colors[x / 2]
+            };
+            [r, g, b, 255]
+        })
+        .collect();
+
+    let info = ImageInfo {
+        extent: Extent::new(8, 2),
+        layout: Layout::Rgba8,
+        format: Format::Linear,
+    };
+    let image = test.create_image(info, &pixels);
+
+    let sheet = SpriteSheet::new(image, Extent::new(2, 2));
+    assert_eq!(sheet.len(), 4);
+
+    // the last frame is replaced with yellow
+    let yellow = [255, 255, 0, 255].repeat(4);
+    let frame_info = ImageInfo {
+        extent: Extent::new(2, 2),
+        ..info
+    };
+    test.graphics
+        .upload_raster_image(sheet.frame(3), &RasterBuf::new(frame_info, &yellow))
+        .unwrap();
+    test.graphics.flush_upload_buffer();
+
+    // the right half of the green and blue frames
+    let nested = image
+        .sub_image(Rect::new((Texel(2), Texel(0)), (Texel(4), Texel(2))))
+        .sub_image(Rect::new((Texel(1), Texel(0)), (Texel(2), Texel(2))));
+
+    test.check(|canvas| {
+        canvas.clear(Color::BLACK);
+
+        for (i, frame) in sheet.frames().enumerate() {
+            #[allow(clippy::cast_precision_loss)]
+            let x = 2.0 + 16.0 * i as f32;
+            canvas.draw_rect(&RoundRect::new((x, 2.0, 12.0, 12.0)).with_image(frame));
+        }
+
+        canvas.draw_rect(&RoundRect::new((2.0, 18.0, 24.0, 12.0)).with_image(nested));
+    });
+}
+
+#[test]
+fn nine_slice() {
+    let mut test = GoldenTest::new("nine_slice", (96, 48));
+
+    // red corners, green edges and a blue center, two texels each, with
+    // alternating dark texels so that tiling is visible
+    let pixels: Vec<u8> = (0..36)
+        .flat_map(|i| {
+            let (x, y) = (i % 6, i / 6);
+            let (edge_x, edge_y) = (!(2..4).contains(&x), !(2..4).contains(&y));
+            let shade = if (x + y) % 2 == 0 { 255 } else { 128 };
+
+            match (edge_x, edge_y) {
+                (true, true) => [255, 0, 0, 255],
+                (true, false) | (false, true) => [0, shade, 0, 255],
+                (false, false) => [0, 0, shade, 255],
+            }
+        })
+        .collect();
+
+    let frame = test.create_image(
+        ImageInfo {
+            extent: Extent::new(6, 6),
+            layout: Layout::Rgba8,
+            format: Format::Linear,
+        },
+        &pixels,
+    );
+
+    test.check(|canvas| {
+        canvas.clear(Color::BLACK);
+        let insets = Insets::uniform(2.0);
+        canvas.draw_nine_slice(frame, insets, (2.0, 2.0, 40.0, 27.0), SliceMode::Stretch);
+        canvas.draw_nine_slice(frame, insets, (48.0, 2.0, 41.0, 27.0), SliceMode::Tile);
+        // too small for the corners, which are scaled down
+        canvas.draw_nine_slice(frame, insets, (2.0, 36.0, 2.0, 2.0), SliceMode::Stretch);
+        canvas.draw_nine_slice(frame, insets, (8.0, 36.0, 10.0, 4.0), SliceMode::Tile);
+    });
+}
+
+#[test]
+fn vector_images() {
+    let mut test = GoldenTest::new("vector_images", (96, 64));
+
+    let star = Path::new()
+        .move_to((8.0, 2.0))
+        .line_to((10.0, 6.5))
+        .line_to((14.0, 7.0))
+        .line_to((11.0, 10.0))
+        .line_to((12.0, 14.0))
+        .line_to((8.0, 12.0))
+        .line_to((4.0, 14.0))
+        .line_to((5.0, 10.0))
+        .line_to((2.0, 7.0))
+        .line_to((6.0, 6.5))
+        .close();
+
+    let msdf = Raster::msdf_from_path(&star, FillRule::NonZero, Extent::new(16, 16));
+    let star = test.create_image(msdf.info(), msdf.data());
+
+    // a ring, drawn with anti-aliased edges
+    let info = ImageInfo {
+        extent: Extent::new(16, 16),
+        layout: Layout::Alpha8,
+        format: Format::Linear,
+    };
+    let coverage: Vec<u8> = (0..256)
+        .map(|i| {
+            let (x, y) = ((i % 16) as f32 + 0.5 - 8.0, (i / 16) as f32 + 0.5 - 8.0);
+            let distance = (x.hypot(y) - 4.5).abs() - 1.5;
+            ((0.5 - distance).clamp(0.0, 1.0) * 255.0).round() as u8
+        })
+        .collect();
+
+    let sdf = Raster::sdf_from_alpha(&RasterBuf::new(info, &coverage));
+    let ring = test.create_image(sdf.info(), sdf.data());
+
+    test.check(|canvas| {
+        canvas.clear(Color::BLACK);
+        canvas.draw_rect(&RoundRect::new((0.0, 0.0, 16.0, 16.0)).with_image(star));
+        canvas.draw_rect(
+            &RoundRect::new((16.0, 0.0, 48.0, 48.0))
+                .with_image(star)
+                .with_color(Color::RED),
+        );
+        canvas.draw_rect(&RoundRect::new((0.0, 48.0, 16.0, 16.0)).with_image(ring));
+        canvas.draw_rect(
+            &RoundRect::new((64.0, 0.0, 32.0, 32.0))
+                .with_image(ring)
+                .with_color(Color::BLUE),
+        );
+        canvas.draw_rect(&RoundRect::new((64.0, 40.0, 8.0, 8.0)).with_image(ring));
+    });
+}
+
+#[test]
+fn wrap_modes() {
+    let mut test = GoldenTest::new("wrap_modes", (96, 64));
+
+    let image = test.create_image(
+        ImageInfo {
+            extent: Extent::new(3, 1),
+            layout: Layout::Rgba8,
+            format: Format::Linear,
+        },
+        &RGB_STRIP,
+    );
+    // the blue texel, which must not pick up its neighbors when repeated
+    let blue = image.sub_image(Rect::new((Texel(2), Texel(0)), (Texel(1), Texel(1))));
+
+    let modes = [WrapMode::Clamp, WrapMode::Repeat, WrapMode::Mirror];
+
+    test.check(|canvas| {
+        canvas.clear(Color::BLACK);
+
+        for (i, mode) in modes.into_iter().enumerate() {
+            #[allow(clippy::cast_precision_loss)]
+            let x = 2.0 + 32.0 * i as f32;
+
+            for (row, filter) in [TextureFilter::Point, TextureFilter::Linear]
+                .into_iter()
+                .enumerate()
+            {
+                #[allow(clippy::cast_precision_loss)]
+                let y = 2.0 + 20.0 * row as f32;
+
+                canvas.draw_rect(
+                    &RoundRect::new((x, y, 28.0, 16.0))
+                        .with_image(image)
+                        .with_image_rect((x + 10.0, y + 4.0, 6.0, 8.0))
+                        .with_sampler(Sampler::new(filter, mode)),
+                );
+            }
+
+            canvas.draw_rect(
+                &RoundRect::new((x, 42.0, 28.0, 16.0))
+                    .with_image(blue)
+                    .with_image_rect((x, 42.0, 5.0, 5.0))
+                    .with_sampler(Sampler::new(TextureFilter::Linear, mode)),
+            );
+        }
+    });
+}
+
+#[test]
+fn mipmaps() {
+    let mut test = GoldenTest::new("mipmaps", (96, 48));
+
+    // one-texel checks, which alias badly when drawn smaller
+    let checks: Vec<u8> = (0..32 * 32)
+        .flat_map(|i| {
+            let v = if (i % 32 + i / 32) % 2 == 0 { 255 } else { 0 };
+            [v, v, v, 255]
+        })
+        .collect();
+    let info = ImageInfo {
+        extent: Extent::new(32, 32),
+        layout: Layout::Rgba8,
+        format: Format::Linear,
+    };
+    let image = test.create_mipmapped_image(info, &checks);
+
+    test.check(|canvas| {
+        canvas.clear(Color::BLACK);
+
+        for (row, filter) in [TextureFilter::Linear, TextureFilter::Mipmapped]
+            .into_iter()
+            .enumerate()
+        {
+            #[allow(clippy::cast_precision_loss)]
+            let y = 2.0 + 24.0 * row as f32;
+            let sampler = Sampler::new(filter, WrapMode::Clamp);
+
+            for (x, size) in [(2.0, 20.0), (26.0, 13.0), (42.0, 7.0), (52.0, 3.0)] {
+                canvas.draw_rect(
+                    &RoundRect::new((x, y, size, size))
+                        .with_image(image)
+                        .with_sampler(sampler),
+                );
+            }
+        }
+
+        // sub-images of a mipmapped image are drawn without its mipmaps
+        canvas.draw_rect(
+            &RoundRect::new((60.0, 2.0, 7.0, 7.0))
+                .with_image(
+                    image.sub_image(Rect::new((Texel(0), Texel(0)), (Texel(16), Texel(16)))),
+                )
+                .with_sampler(Sampler::new(TextureFilter::Mipmapped, WrapMode::Clamp)),
+        );
+    });
+}
+
+#[test]
+fn layers() {
+    let orange = Color {
+        r: 0.9,
+        g: 0.4,
+        b: 0.1,
+        a: 1.0,
+    };
+    let gray = Color {
+        r: 0.75,
+        g: 0.75,
+        b: 0.75,
+        a: 1.0,
+    };
+
+    GoldenTest::new("layers", (96, 48)).check(|canvas| {
+        canvas.clear(Color::BLACK);
+
+        // a backdrop that is half blue and half orange in every cell
+        for y in [2.0, 26.0] {
+            canvas.draw_rect(
+                &RoundRect::new((2.0, y, 92.0, 10.0)).with_gradient(
+                    Gradient::linear((2.0, 0.0), (94.0, 0.0))
+                        .with_stop(0.0, Color::BLUE)
+                        .with_stop(1.0, orange),
+                ),
+            );
+            canvas.draw_rect(&RoundRect::new((2.0, y + 10.0, 92.0, 10.0)).with_color(orange));
+        }
+
+        // children that overlap are faded together
+        canvas.push_layer(0.5, BlendMode::SourceOver);
+        canvas.draw_rect(&RoundRect::new((4.0, 4.0, 12.0, 16.0)).with_color(Color::RED));
+        canvas.draw_rect(&RoundRect::new((10.0, 6.0, 10.0, 12.0)).with_color(Color::GREEN));
+        canvas.pop_layer();
+
+        for (i, mode) in [
+            BlendMode::Multiply,
+            BlendMode::Screen,
+            BlendMode::Overlay,
+            BlendMode::Difference,
+        ]
+        .into_iter()
+        .enumerate()
+        {
+            #[allow(clippy::cast_precision_loss)]
+            let x = 26.0 + 18.0 * i as f32;
+
+            canvas.push_layer(1.0, mode);
+            canvas.draw_rect(&RoundRect::new((x, 4.0, 14.0, 16.0)).with_color(gray));
+            canvas.pop_layer();
+        }
+
+        // punches a rounded hole through everything beneath the layer
+        canvas.push_layer(1.0, BlendMode::DestinationOut);
+        canvas.draw_rect(
+            &RoundRect::new((4.0, 28.0, 16.0, 16.0))
+                .with_radius(8.0)
+                .with_color(Color::WHITE),
+        );
+        canvas.pop_layer();
+
+        // a nested layer that is masked by what the outer layer drew
+        canvas.push_layer(1.0, BlendMode::SourceOver);
+        canvas.draw_rect(
+            &RoundRect::new((26.0, 28.0, 16.0, 16.0))
+                .with_radius(8.0)
+                .with_color(Color::WHITE),
+        );
+        canvas.push_layer(1.0, BlendMode::SourceIn);
+        for i in 0..4 {
+            #[allow(clippy::cast_precision_loss)]
+            let y = 28.0 + 4.0 * i as f32;
+            canvas.draw_rect(&RoundRect::new((22.0, y, 24.0, 2.0)).with_color(Color::RED));
+        }
+        canvas.pop_layer();
+        canvas.pop_layer();
+
+        // only the area inside the clip is composited
+        canvas.push_round_clip((48.0, 28.0, 20.0, 16.0), [6.0; 4]);
+        canvas.push_layer(0.75, BlendMode::Xor);
+        canvas.draw_rect(&RoundRect::new((44.0, 32.0, 28.0, 8.0)).with_color(Color::GREEN));
+        canvas.pop_layer();
+        canvas.pop_clip();
+
+        canvas.push_clip((74.0, 30.0, 16.0, 12.0));
+        canvas.push_layer(1.0, BlendMode::Clear);
+        canvas.pop_layer();
+        canvas.pop_clip();
+    });
+}
+
+#[test]
+fn backdrop_blur() {
+    GoldenTest::new("backdrop_blur", (96, 48)).check(|canvas| {
+        canvas.clear(Color::WHITE);
+
+        // stripes of alternating colors that the blurs smear together
+        for i in 0..12 {
+            #[allow(clippy::cast_precision_loss)]
+            let x = 8.0 * i as f32;
+            let color = if i % 2 == 0 { Color::BLUE } else { Color::RED };
+            canvas.draw_rect(&RoundRect::new((x, 0.0, 4.0, 48.0)).with_color(color));
+        }
+
+        // frosted glass: a blur with a translucent tint over it
+        canvas.draw_backdrop_blur((4.0, 4.0, 40.0, 18.0), [6.0; 4], 3.0);
+        canvas.draw_rect(
+            &RoundRect::new((4.0, 4.0, 40.0, 18.0))
+                .with_radius(6.0)
+                .with_color(Color {
+                    r: 0.2,
+                    g: 0.2,
+                    b: 0.2,
+                    a: 0.2,
+                }),
+        );
+
+        // a light blur that is cut off by the clip
+        canvas.push_clip((52.0, 4.0, 20.0, 18.0));
+        canvas.draw_backdrop_blur((52.0, 4.0, 40.0, 18.0), [0.0; 4], 1.0);
+        canvas.pop_clip();
+
+        // the radii and the blur are scaled with the transform
+        canvas.push_transform(&Affine::scale(2.0, 2.0));
+        canvas.draw_backdrop_blur((2.0, 13.0, 20.0, 9.0), [4.5; 4], 1.0);
+        canvas.pop_transform();
+
+        // inside a layer, only what was drawn into the layer is blurred
+        canvas.push_layer(1.0, BlendMode::SourceOver);
+        canvas.draw_rect(&RoundRect::new((62.0, 32.0, 16.0, 8.0)).with_color(Color::GREEN));
+        canvas.draw_backdrop_blur((52.0, 26.0, 40.0, 20.0), [4.0; 4], 2.0);
+        canvas.pop_layer();
+    });
+}
+
+#[test]
+fn text() {
+    let mut test = GoldenTest::new("text", (96, 40));
+    let font = load_font("latin.ttf");
+
+    let small = TextLayout::new("Hello, world!", &TextStyle::new(&font, 12.0), None);
+    let large = TextLayout::new("Ag&%", &TextStyle::new(&font, 20.0), None);
+    test.prepare_text(&small);
+    test.prepare_text(&large);
+
+    test.check(|canvas| {
+        canvas.clear(Color::WHITE);
+        canvas.draw_text(&small, (2.0, 2.0), Color::BLACK);
+        canvas.draw_text(&large, (2.5, 16.0), Color::RED);
+    });
+}
+
+#[test]
+fn gradient_text() {
+    let mut test = GoldenTest::new("gradient_text", (80, 24));
+    let font = load_font("latin.ttf");
+
+    let layout = TextLayout::new("Gradient", &TextStyle::new(&font, 16.0), None);
+    test.prepare_text(&layout);
+
+    test.check(|canvas| {
+        canvas.clear(Color::WHITE);
+        canvas.draw_text(
+            &layout,
+            (2.0, 2.0),
+            Gradient::linear((2.0, 0.0), (70.0, 0.0))
+                .with_stop(0.0, Color::RED)
+                .with_stop(1.0, Color::BLUE),
+        );
+    });
+}
+
+#[test]
+fn text_layout() {
+    let mut test = GoldenTest::new("text_layout", (96, 96));
+    let latin = load_font("latin.ttf");
+    let hebrew = load_font("hebrew.ttf");
+
+    let style = TextStyle::new(&latin, 11.0).with_fallback(&hebrew);
+
+    let wrapped = TextLayout::new(
+        "The quick brown fox jumps",
+        &style.clone().with_align(TextAlign::Center),
+        Some(80.0),
+    );
+    // "shalom" between two Latin words, which stays in logical order
+    let mixed = TextLayout::new("one \u{5e9}\u{5dc}\u{5d5}\u{5dd} two", &style, None);
+    let rtl = TextLayout::new("\u{5d0}\u{5d1}\u{5d2} abc", &style, Some(80.0));
+
+    for layout in [&wrapped, &mixed, &rtl] {
+        test.prepare_text(layout);
+    }
+
+    test.check(|canvas| {
+        canvas.clear(Color::WHITE);
+
+        for line in wrapped.lines() {
+            let rect = line.rect;
+            canvas.draw_rect(
+                &RoundRect::new((
+                    8.0 + rect.origin.x.0,
+                    4.0 + rect.origin.y.0,
+                    rect.extent.width.0,
+                    rect.extent.height.0,
+                ))
+                .with_color(Color::BLUE),
+            );
+        }
+
+        canvas.draw_text(&wrapped, (8.0, 4.0), Color::WHITE);
+        canvas.draw_text(&mixed, (8.0, 56.0), Color::BLACK);
+        canvas.draw_text(&rtl, (8.0, 74.0), Color::RED);
+    });
+}
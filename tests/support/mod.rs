@@ -0,0 +1,208 @@
+//! Golden-image testing for canvas output.
+//!
+//! Scenes are rendered with the software backend into an offscreen target and
+//! compared against reference images in `tests/golden/`. Run the tests with
+//! `PLINTH_BLESS=1` to write new reference images instead of comparing.
+
+#![allow(dead_code)]
+
+use std::path::{Path, PathBuf};
+
+use plinth::{
+    geometry::{Extent, Scale, Texel},
+    graphics::{
+        image::codec, Backend, Canvas, DrawList, Font, Format, Graphics, GraphicsConfig, Image,
+        ImageInfo, Layout, Raster, RasterBuf, TextLayout,
+    },
+};
+
+const BLESS_VAR: &str = "PLINTH_BLESS";
+
+pub struct GoldenTest {
+    pub graphics: Graphics,
+    name: &'static str,
+    extent: Extent<Texel>,
+    tolerance: u8,
+}
+
+impl GoldenTest {
+    pub fn new(name: &'static str, extent: impl Into<Extent<Texel>>) -> Self {
+        let graphics = Graphics::new(&GraphicsConfig {
+            backend: Backend::Software,
+            debug_mode: false,
+            ..Default::default()
+        });
+
+        Self {
+            graphics,
+            name,
+            extent: extent.into(),
+            tolerance: 1,
+        }
+    }
+
+    /// Sets the largest per-channel difference that is still considered a
+    /// match.
+    #[must_use]
+    pub fn with_tolerance(mut self, tolerance: u8) -> Self {
+        self.tolerance = tolerance;
+        self
+    }
+
+    /// Creates an image and uploads `pixels` to it.
+    pub fn create_image(&mut self, info: ImageInfo, pixels: &[u8]) -> Image {
+        let image = self.graphics.create_raster_image(info).unwrap();
+        self.graphics
+            .upload_raster_image(image, &RasterBuf::new(info, pixels))
+            .unwrap();
+        self.graphics.flush_upload_buffer();
+        image
+    }
+
+    /// Creates a mipmapped image and uploads its pixels.
+    pub fn create_mipmapped_image(&mut self, info: ImageInfo, pixels: &[u8]) -> Image {
+        let image = self.graphics.create_mipmapped_image(info).unwrap();
+        self.graphics
+            .upload_raster_image(image, &RasterBuf::new(info, pixels))
+            .unwrap();
+        self.graphics.flush_upload_buffer();
+        image
+    }
+
+    /// Prepares a text layout for drawing.
+    pub fn prepare_text(&mut self, layout: &TextLayout) {
+        self.graphics.prepare_text(layout).unwrap();
+        self.graphics.flush_upload_buffer();
+    }
+
+    /// Renders a scene and compares it with the reference image.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the reference image is missing or differs from the rendered
+    /// scene by more than the tolerance.
+    pub fn check(&self, draw: impl FnOnce(&mut Canvas)) {
+        let actual = self.render(draw);
+        let reference_path = reference_path(self.name);
+
+        if std::env::var_os(BLESS_VAR).is_some() {
+            write_png(&reference_path, &actual);
+            return;
+        }
+
+        assert!(
+            reference_path.exists(),
+            "Missing reference image {}. Run with {BLESS_VAR}=1 to create it.",
+            reference_path.display()
+        );
+
+        let expected = read_png(&reference_path);
+
+        assert_eq!(
+            expected.info().extent,
+            actual.info().extent,
+            "Reference image {} has a different size.",
+            reference_path.display()
+        );
+
+        let (diff, mismatches) = diff(&expected, &actual, self.tolerance);
+
+        if mismatches > 0 {
+            let out_dir = Path::new(env!("CARGO_TARGET_TMPDIR")).join("golden");
+            std::fs::create_dir_all(&out_dir).unwrap();
+
+            let actual_path = out_dir.join(format!("{}.actual.png", self.name));
+            let diff_path = out_dir.join(format!("{}.diff.png", self.name));
+
+            write_png(&actual_path, &actual);
+            write_png(&diff_path, &diff);
+
+            panic!(
+                "{mismatches} pixels differ from {} by more than {}.\nactual: {}\ndiff: {}",
+                reference_path.display(),
+                self.tolerance,
+                actual_path.display(),
+                diff_path.display(),
+            );
+        }
+    }
+
+    fn render(&self, draw: impl FnOnce(&mut Canvas)) -> Raster {
+        let mut target = self
+            .graphics
+            .create_render_target(self.extent, Layout::Rgba8, Format::Srgb)
+            .unwrap();
+
+        let mut draw_list = DrawList::new();
+
+        {
+            let mut canvas = self
+                .graphics
+                .create_canvas(&target, &mut draw_list, Scale::default());
+            draw(&mut canvas);
+            canvas.finish();
+        }
+
+        self.graphics.draw(&draw_list, &mut target);
+        self.graphics.read_pixels(&target)
+    }
+}
+
+/// Loads one of the fonts in `tests/fonts/`.
+pub fn load_font(name: &str) -> Font {
+    let path = Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests")
+        .join("fonts")
+        .join(name);
+
+    Font::from_bytes(std::fs::read(path).unwrap(), 0).unwrap()
+}
+
+fn reference_path(name: &str) -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests")
+        .join("golden")
+        .join(format!("{name}.png"))
+}
+
+/// Compares two rasters channel by channel.
+///
+/// The diff image shows matching pixels as a faded grayscale copy of the
+/// expected image and mismatched pixels in solid red.
+fn diff(expected: &Raster, actual: &Raster, tolerance: u8) -> (Raster, usize) {
+    let mut mismatches = 0;
+    let mut data = Vec::with_capacity(expected.data().len());
+
+    for (e, a) in expected
+        .data()
+        .chunks_exact(4)
+        .zip(actual.data().chunks_exact(4))
+    {
+        if e.iter().zip(a).any(|(e, a)| e.abs_diff(*a) > tolerance) {
+            mismatches += 1;
+            data.extend_from_slice(&[255, 0, 0, 255]);
+        } else {
+            let luma = (u16::from(e[0]) + u16::from(e[1]) + u16::from(e[2])) / 3;
+            let faded = (128 + luma / 2) as u8;
+            data.extend_from_slice(&[faded, faded, faded, 255]);
+        }
+    }
+
+    (Raster::new(expected.info(), data), mismatches)
+}
+
+fn read_png(path: &Path) -> Raster {
+    let raster = codec::decode(&std::fs::read(path).unwrap()).unwrap();
+
+    assert_eq!(
+        raster.info().format,
+        Format::Srgb,
+        "Reference images must be sRGB."
+    );
+
+    raster
+}
+
+fn write_png(path: &Path, raster: &Raster) {
+    codec::save(path, &raster.as_buf()).unwrap();
+}